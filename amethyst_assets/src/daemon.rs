@@ -1,7 +1,12 @@
 use std::{
     net::{AddrParseError, SocketAddr},
     path::PathBuf,
-    thread::JoinHandle,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
 };
 
 use amethyst_error::Error;
@@ -9,7 +14,14 @@ use distill::daemon::AssetDaemon as AtelierAssetDaemon;
 use structopt::StructOpt;
 use tokio::sync::oneshot::Sender;
 
-use crate::{prefab::PrefabImporter, simple_importer::get_source_importers};
+use crate::{
+    import_defaults::ImportDefaults, prefab::PrefabImporter, simple_importer::get_source_importers,
+    SourceFileImporter,
+};
+
+/// Time between two checks of the modification times of the folder import defaults and `.meta`
+/// files.
+const IMPORT_DEFAULTS_INTERVAL: Duration = Duration::from_secs(1);
 
 /// Parameters to the asset daemon.
 ///
@@ -84,6 +96,8 @@ struct InitializedDaemon {
 struct StartedDaemon {
     shutdown: Option<Sender<bool>>,
     join_handle: Option<JoinHandle<()>>,
+    import_defaults_stop: Arc<AtomicBool>,
+    import_defaults_handle: Option<JoinHandle<()>>,
 }
 
 impl AssetDaemon {
@@ -119,6 +133,28 @@ impl AssetDaemon {
 
 impl InitializedDaemon {
     fn start_on_new_thread(&self) -> AssetDaemonState {
+        let mut import_defaults = ImportDefaults::new(
+            self.opt.asset_dirs.clone(),
+            inventory::iter::<SourceFileImporter>
+                .into_iter()
+                .map(|s| (s.extension.trim_start_matches('.'), s.defaults)),
+            self.opt.db_dir.join("import_defaults.ron"),
+        );
+        // Defaults changed while the daemon was stopped are applied before it imports anything.
+        import_defaults.apply();
+        let import_defaults_stop = Arc::new(AtomicBool::new(false));
+        let stop = import_defaults_stop.clone();
+        let import_defaults_handle = thread::Builder::new()
+            .name("import defaults".to_string())
+            .spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    thread::sleep(IMPORT_DEFAULTS_INTERVAL);
+                    import_defaults.apply();
+                }
+            })
+            .map_err(|e| log::error!("Could not watch the import defaults: {}", e))
+            .ok();
+
         let (join_handle, shutdown) = AtelierAssetDaemon::default()
             .with_importers_boxed(get_source_importers())
             .with_importer("prefab", PrefabImporter::default())
@@ -129,12 +165,20 @@ impl InitializedDaemon {
         AssetDaemonState::Started(StartedDaemon {
             shutdown: Some(shutdown),
             join_handle: Some(join_handle),
+            import_defaults_stop,
+            import_defaults_handle,
         })
     }
 }
 
 impl StartedDaemon {
     fn stop_and_join(&mut self) -> AssetDaemonState {
+        self.import_defaults_stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.import_defaults_handle.take() {
+            if handle.join().is_err() {
+                log::error!("Failure joining the import defaults thread");
+            }
+        }
         self.shutdown
             .take()
             .ok_or_else(|| Error::from_string("Shutdown Sender not present"))
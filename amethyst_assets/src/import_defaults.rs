@@ -0,0 +1,658 @@
+//! Folder-level defaults of importer options.
+//!
+//! A file called `<extension>.import_defaults`, such as `png.import_defaults`, sets the importer
+//! options of the source files with that extension in its folder and all folders below it. It
+//! contains the fields of the importer options to change, in RON:
+//!
+//! ```text
+//! (
+//!     generate_mips: true,
+//! )
+//! ```
+//!
+//! The asset daemon writes them into the `importer_options` of the `.meta` file of each of these
+//! assets, which makes it import the asset again. Defaults in a folder replace those of the
+//! folders above it. Assets whose options were changed in their own `.meta` file keep them, the
+//! others follow changes to the defaults, including their removal.
+
+use std::fmt;
+
+use amethyst_error::{format_err, Error};
+use ron::ser::PrettyConfig;
+use serde::{
+    de::{
+        self, DeserializeOwned, DeserializeSeed, IgnoredAny, IntoDeserializer, MapAccess, Visitor,
+    },
+    forward_to_deserialize_any, Serialize,
+};
+
+/// Extension of the files holding the defaults of a folder.
+pub const IMPORT_DEFAULTS_EXTENSION: &str = "import_defaults";
+
+/// Merges the folder defaults `defaults` into the importer options `options` of a `.meta` file,
+/// all in RON. `applied` are the options last written by the asset daemon for that asset. Returns
+/// the options to write back, or `None` to keep them.
+pub type ImportDefaultsFn =
+    fn(options: &str, defaults: &str, applied: Option<&str>) -> Result<Option<String>, Error>;
+
+/// Implements [`ImportDefaultsFn`] for the importer options `T`.
+///
+/// The top level fields of `defaults` replace those of `T::default()`. The result is only
+/// returned if `options` are the default options or the `applied` ones, since anything else was
+/// set for the asset on purpose.
+///
+/// # Errors
+///
+/// Fails if `options` or the merged defaults are not valid options of type `T`.
+pub fn merge_import_defaults<T>(
+    options: &str,
+    defaults: &str,
+    applied: Option<&str>,
+) -> Result<Option<String>, Error>
+where
+    T: Default + Serialize + DeserializeOwned,
+{
+    let to_ron = |value: &T| ron::ser::to_string(value).map_err(Error::new);
+    let importer_defaults = to_ron(&T::default())?;
+    let current = ron::de::from_str::<T>(options)
+        .map_err(|e| format_err!("Invalid importer options: {}", e))?;
+    let current = to_ron(&current)?;
+    let applied = applied
+        .and_then(|applied| ron::de::from_str::<T>(applied).ok())
+        .map(|applied| to_ron(&applied))
+        .transpose()?;
+    if current != importer_defaults && applied.as_ref() != Some(&current) {
+        return Ok(None);
+    }
+
+    let merged = overlay_fields::<T>(&importer_defaults, defaults)
+        .map_err(|e| format_err!("Invalid import defaults: {}", e))?;
+    if to_ron(&merged)? == current {
+        return Ok(None);
+    }
+    ron::ser::to_string_pretty(&merged, PrettyConfig::default())
+        .map(Some)
+        .map_err(Error::new)
+}
+
+/// Deserializes the RON struct `overlay` as `T`, taking the top level fields it doesn't set from
+/// the RON struct `base`.
+///
+/// `ron::Value` can't hold enum variants, so both structs are read by ron itself and their fields
+/// handed to the `Deserialize` implementation of `T` one by one.
+fn overlay_fields<T: DeserializeOwned>(base: &str, overlay: &str) -> Result<T, ron::Error> {
+    T::deserialize(Overlay { base, overlay })
+}
+
+struct Overlay<'a> {
+    base: &'a str,
+    overlay: &'a str,
+}
+
+impl<'de> de::Deserializer<'de> for Overlay<'de> {
+    type Error = ron::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ron::Error> {
+        self.deserialize_struct("", &[], visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ron::Error> {
+        let mut overlay = ron::de::Deserializer::from_str(self.overlay)?;
+        let value = de::Deserializer::deserialize_struct(
+            &mut overlay,
+            name,
+            fields,
+            OverlayVisitor {
+                base: self.base,
+                name,
+                fields,
+                visitor,
+            },
+        )?;
+        overlay.end()?;
+        Ok(value)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier
+        ignored_any
+    }
+}
+
+/// Reads the fields of `base` once the ones of the overlay are available.
+struct OverlayVisitor<'de, V> {
+    base: &'de str,
+    name: &'static str,
+    fields: &'static [&'static str],
+    visitor: V,
+}
+
+impl<'de, V: Visitor<'de>> Visitor<'de> for OverlayVisitor<'de, V> {
+    type Value = V::Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.visitor.expecting(formatter)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, overlay: A) -> Result<V::Value, A::Error> {
+        let mut base = ron::de::Deserializer::from_str(self.base).map_err(de::Error::custom)?;
+        let value = de::Deserializer::deserialize_struct(
+            &mut base,
+            self.name,
+            self.fields,
+            BaseVisitor {
+                overlay,
+                visitor: self.visitor,
+            },
+        )
+        .map_err(de::Error::custom)?;
+        base.end().map_err(de::Error::custom)?;
+        Ok(value)
+    }
+}
+
+/// Passes the fields of both structs to the visitor of the options.
+struct BaseVisitor<A, V> {
+    overlay: A,
+    visitor: V,
+}
+
+impl<'de, A: MapAccess<'de>, V: Visitor<'de>> Visitor<'de> for BaseVisitor<A, V> {
+    type Value = V::Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.visitor.expecting(formatter)
+    }
+
+    fn visit_map<B: MapAccess<'de>>(self, base: B) -> Result<V::Value, B::Error> {
+        self.visitor.visit_map(OverlayMap {
+            overlay: Some(self.overlay),
+            base,
+            overlaid: Vec::new(),
+        })
+    }
+}
+
+/// The fields of the overlay, followed by the fields of the base it doesn't set.
+struct OverlayMap<A, B> {
+    /// `None` once all fields of the overlay were read.
+    overlay: Option<A>,
+    base: B,
+    overlaid: Vec<String>,
+}
+
+impl<'de, A: MapAccess<'de>, B: MapAccess<'de>> MapAccess<'de> for OverlayMap<A, B> {
+    type Error = B::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, B::Error> {
+        if let Some(overlay) = &mut self.overlay {
+            if let Some(key) = overlay
+                .next_key_seed(FieldName)
+                .map_err(de::Error::custom)?
+            {
+                self.overlaid.push(key.clone());
+                return seed.deserialize(key.into_deserializer()).map(Some);
+            }
+            self.overlay = None;
+        }
+        while let Some(key) = self.base.next_key_seed(FieldName)? {
+            if self.overlaid.contains(&key) {
+                self.base.next_value::<IgnoredAny>()?;
+            } else {
+                return seed.deserialize(key.into_deserializer()).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<S::Value, B::Error> {
+        match &mut self.overlay {
+            Some(overlay) => overlay.next_value_seed(seed).map_err(de::Error::custom),
+            None => self.base.next_value_seed(seed),
+        }
+    }
+}
+
+/// Reads a field name as a `String`, which ron only reads as an identifier.
+struct FieldName;
+
+impl<'de> DeserializeSeed<'de> for FieldName {
+    type Value = String;
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<String, D::Error> {
+        deserializer.deserialize_identifier(self)
+    }
+}
+
+impl<'de> Visitor<'de> for FieldName {
+    type Value = String;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a field name")
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<String, E> {
+        Ok(name.to_string())
+    }
+}
+
+#[cfg(feature = "asset-daemon")]
+pub(crate) use self::daemon::ImportDefaults;
+
+#[cfg(feature = "asset-daemon")]
+mod daemon {
+    use std::{
+        collections::HashMap,
+        fs,
+        ops::Range,
+        path::{Path, PathBuf},
+        time::SystemTime,
+    };
+
+    use serde::de::{self, IgnoredAny};
+
+    use super::{FieldName, ImportDefaultsFn, IMPORT_DEFAULTS_EXTENSION};
+
+    /// Writes the folder defaults into the `.meta` files of the asset directories.
+    pub(crate) struct ImportDefaults {
+        asset_dirs: Vec<PathBuf>,
+        importers: HashMap<String, ImportDefaultsFn>,
+        /// File keeping `applied` between runs of the daemon.
+        record: PathBuf,
+        /// Importer options last written to each `.meta` file.
+        applied: HashMap<PathBuf, String>,
+        /// Modification times of each `.meta` file and its defaults when they were last applied.
+        checked: HashMap<PathBuf, Checked>,
+    }
+
+    /// Modification times of a `.meta` file and of the defaults file it follows.
+    #[derive(Clone, PartialEq)]
+    struct Checked {
+        meta: Option<SystemTime>,
+        defaults: Option<(PathBuf, Option<SystemTime>)>,
+    }
+
+    impl ImportDefaults {
+        pub(crate) fn new(
+            asset_dirs: Vec<PathBuf>,
+            importers: impl IntoIterator<Item = (&'static str, ImportDefaultsFn)>,
+            record: PathBuf,
+        ) -> Self {
+            let applied = fs::read_to_string(&record)
+                .ok()
+                .and_then(|text| ron::de::from_str(&text).ok())
+                .unwrap_or_default();
+            ImportDefaults {
+                asset_dirs,
+                importers: importers
+                    .into_iter()
+                    .map(|(extension, merge)| (extension.to_lowercase(), merge))
+                    .collect(),
+                record,
+                applied,
+                checked: HashMap::new(),
+            }
+        }
+
+        /// Updates the `.meta` files with defaults in their folder or above, and the ones that
+        /// lost them. Only the `.meta` files that were modified since the last call, or whose
+        /// defaults were, are read.
+        pub(crate) fn apply(&mut self) {
+            let mut changed = false;
+            for dir in self.asset_dirs.clone() {
+                changed |= self.apply_dir(&dir, &HashMap::new());
+            }
+            if changed {
+                let result = ron::ser::to_string(&self.applied)
+                    .map_err(|e| e.to_string())
+                    .and_then(|text| {
+                        if let Some(dir) = self.record.parent() {
+                            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+                        }
+                        fs::write(&self.record, text).map_err(|e| e.to_string())
+                    });
+                if let Err(e) = result {
+                    log::warn!("Could not write {}: {}", self.record.display(), e);
+                }
+            }
+        }
+
+        fn apply_dir(
+            &mut self,
+            dir: &Path,
+            inherited: &HashMap<String, (PathBuf, Option<SystemTime>)>,
+        ) -> bool {
+            let entries: Vec<PathBuf> = match fs::read_dir(dir) {
+                Ok(entries) => entries.filter_map(|e| e.ok().map(|e| e.path())).collect(),
+                Err(e) => {
+                    log::warn!("Could not read asset directory {}: {}", dir.display(), e);
+                    return false;
+                }
+            };
+
+            let mut defaults = inherited.clone();
+            for path in &entries {
+                if extension(path).as_deref() == Some(IMPORT_DEFAULTS_EXTENSION) {
+                    if let Some(extension) = path.file_stem().and_then(|stem| stem.to_str()) {
+                        defaults.insert(extension.to_lowercase(), (path.clone(), modified(path)));
+                    }
+                }
+            }
+
+            let mut changed = false;
+            for path in &entries {
+                if path.is_dir() {
+                    changed |= self.apply_dir(path, &defaults);
+                } else if extension(path).as_deref() == Some("meta") {
+                    changed |= self.apply_meta(path, &defaults);
+                }
+            }
+            changed
+        }
+
+        fn apply_meta(
+            &mut self,
+            meta: &Path,
+            defaults: &HashMap<String, (PathBuf, Option<SystemTime>)>,
+        ) -> bool {
+            let source = meta.with_extension("");
+            let extension = match extension(&source) {
+                Some(extension) if source.is_file() => extension,
+                _ => return false,
+            };
+            let merge = match self.importers.get(&extension) {
+                Some(merge) => *merge,
+                None => return false,
+            };
+            let defaults = defaults.get(&extension);
+            if defaults.is_none() && !self.applied.contains_key(meta) {
+                return false;
+            }
+            let checked = Checked {
+                meta: modified(meta),
+                defaults: defaults.cloned(),
+            };
+            if self.checked.get(meta) == Some(&checked) {
+                return false;
+            }
+
+            let applied = self.applied.get(meta).map(String::as_str);
+            let defaults = defaults.map(|(path, _)| path.as_path());
+            let changed = match update_meta(meta, defaults, merge, applied) {
+                Ok(Some(options)) => {
+                    log::debug!("Applied import defaults to {}", meta.display());
+                    self.applied.insert(meta.to_path_buf(), options);
+                    true
+                }
+                Ok(None) => false,
+                Err(e) => {
+                    log::warn!(
+                        "Could not apply import defaults to {}: {}",
+                        meta.display(),
+                        e
+                    );
+                    false
+                }
+            };
+            let checked = Checked {
+                meta: modified(meta),
+                ..checked
+            };
+            self.checked.insert(meta.to_path_buf(), checked);
+            changed
+        }
+    }
+
+    fn extension(path: &Path) -> Option<String> {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_lowercase)
+    }
+
+    fn modified(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    /// Writes the `defaults`, or the importer defaults if there are none, into the importer
+    /// options of `meta`. Returns the written options.
+    fn update_meta(
+        meta: &Path,
+        defaults: Option<&Path>,
+        merge: ImportDefaultsFn,
+        applied: Option<&str>,
+    ) -> Result<Option<String>, String> {
+        let text = fs::read_to_string(meta).map_err(|e| e.to_string())?;
+        let span = field_span(&text, "importer_options")?
+            .ok_or_else(|| "No importer_options".to_string())?;
+        let defaults = match defaults {
+            Some(path) => {
+                fs::read_to_string(path)
+                    .map_err(|e| format!("Could not read {}: {}", path.display(), e))?
+            }
+            None => "()".to_string(),
+        };
+        let options = match merge(&text[span.clone()], &defaults, applied) {
+            Ok(Some(options)) => options,
+            Ok(None) => return Ok(None),
+            Err(e) => return Err(e.to_string()),
+        };
+
+        let mut updated = String::with_capacity(text.len() + options.len());
+        updated.push_str(&text[..span.start]);
+        updated.push_str(&options);
+        updated.push_str(&text[span.end..]);
+        fs::write(meta, updated).map_err(|e| e.to_string())?;
+        Ok(Some(options))
+    }
+
+    /// Returns the span of the value of the top level field `name` of the RON struct in `text`,
+    /// so that the rest of the `.meta` file is kept as the daemon wrote it. Names, values and
+    /// comments are read by ron.
+    pub(super) fn field_span(text: &str, name: &str) -> Result<Option<Range<usize>>, String> {
+        let mut pos = parse_at(text, 0, |_| Ok(()))?.1;
+        if !text[pos..].starts_with('(') {
+            return Err("Expected a struct".to_string());
+        }
+        pos += 1;
+        loop {
+            pos = parse_at(text, pos, |_| Ok(()))?.1;
+            if text[pos..].starts_with(')') {
+                return Ok(None);
+            }
+            let (field, end) = parse_at(text, pos, |de| {
+                de::DeserializeSeed::deserialize(FieldName, de)
+            })?;
+            pos = parse_at(text, end, |_| Ok(()))?.1;
+            if !text[pos..].starts_with(':') {
+                return Err(format!("Expected `:` after `{}`", field));
+            }
+            let start = parse_at(text, pos + 1, |_| Ok(()))?.1;
+            let end = parse_at(text, start, |de| {
+                de::Deserializer::deserialize_ignored_any(de, IgnoredAny)
+            })?
+            .1;
+            if field == name {
+                return Ok(Some(start..end));
+            }
+            pos = parse_at(text, end, |_| Ok(()))?.1;
+            if text[pos..].starts_with(',') {
+                pos += 1;
+            } else if !text[pos..].starts_with(')') {
+                return Err(format!("Expected `,` after the value of `{}`", field));
+            }
+        }
+    }
+
+    /// Runs `parse` at `pos` in `text`, after the whitespace and comments there. Returns its
+    /// result and the offset of the text it left.
+    fn parse_at<T>(
+        text: &str,
+        pos: usize,
+        parse: impl FnOnce(&mut ron::Deserializer<'_>) -> ron::Result<T>,
+    ) -> Result<(T, usize), String> {
+        let mut de = ron::Deserializer::from_str(&text[pos..]).map_err(|e| e.to_string())?;
+        let value = parse(&mut de).map_err(|e| format!("{} at {}", e, pos))?;
+        Ok((value, text.len() - de.remainder().len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    #[serde(default)]
+    struct Options {
+        mips: bool,
+        filter: Filter,
+        name: String,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Filter {
+        Nearest,
+        Linear,
+    }
+
+    impl Default for Filter {
+        fn default() -> Self {
+            Filter::Nearest
+        }
+    }
+
+    #[cfg(feature = "asset-daemon")]
+    #[test]
+    fn finds_fields_of_meta_files() {
+        use super::daemon::field_span;
+
+        let meta = r#"(
+            version: 1, // comment with )
+            importer_options: Options(name: "a, b)", filter: Linear),
+            importer_state: (id: Some("x")),
+        )"#;
+        let span = field_span(meta, "importer_options").unwrap().unwrap();
+        assert_eq!(&meta[span], r#"Options(name: "a, b)", filter: Linear)"#);
+        assert_eq!(field_span(meta, "assets").unwrap(), None);
+        assert!(field_span("[1, 2]", "version").is_err());
+    }
+
+    #[test]
+    fn defaults_apply_to_untouched_options_only() {
+        let defaults = "(mips: true, /* linear */ filter: Linear)";
+        let merged = merge_import_defaults::<Options>("(mips: false)", defaults, None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            ron::de::from_str::<Options>(&merged).unwrap(),
+            Options {
+                mips: true,
+                filter: Filter::Linear,
+                name: String::new(),
+            }
+        );
+        assert_eq!(
+            merge_import_defaults::<Options>(&merged, defaults, Some(&merged)).unwrap(),
+            None
+        );
+
+        let changed = merge_import_defaults::<Options>(&merged, "(mips: true)", Some(&merged))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            ron::de::from_str::<Options>(&changed).unwrap(),
+            Options {
+                mips: true,
+                ..Options::default()
+            }
+        );
+
+        assert_eq!(
+            merge_import_defaults::<Options>(r#"(name: "custom")"#, defaults, Some(&merged))
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            merge_import_defaults::<Options>("()", "(mips: false)", None).unwrap(),
+            None
+        );
+        assert!(merge_import_defaults::<Options>("()", "(mips: 3)", None).is_err());
+    }
+
+    #[cfg(feature = "asset-daemon")]
+    #[test]
+    fn daemon_writes_defaults_into_meta_files() {
+        use std::fs;
+
+        use super::daemon::field_span;
+
+        let root = std::env::temp_dir().join(format!("import_defaults_{}", std::process::id()));
+        let textures = root.join("assets/textures");
+        fs::create_dir_all(textures.join("ui")).unwrap();
+        let meta = |path: &str| {
+            format!(
+                "(\n    version: 1,\n    importer_options: {},\n    importer_state: (id: None),\n)",
+                path
+            )
+        };
+        fs::write(root.join("assets/png.import_defaults"), "(mips: true)").unwrap();
+        fs::write(textures.join("ui/png.import_defaults"), "(filter: Linear)").unwrap();
+        for (file, options) in &[
+            ("a.png", "()"),
+            ("b.png", r#"(name: "b")"#),
+            ("ui/c.png", "()"),
+            ("c.txt", "()"),
+        ] {
+            fs::write(textures.join(file), "").unwrap();
+            fs::write(textures.join(format!("{}.meta", file)), meta(options)).unwrap();
+        }
+
+        let options = |file: &str| {
+            let text = fs::read_to_string(textures.join(format!("{}.meta", file))).unwrap();
+            assert!(text.ends_with("importer_state: (id: None),\n)"));
+            let span = field_span(&text, "importer_options").unwrap().unwrap();
+            ron::de::from_str::<Options>(&text[span]).unwrap()
+        };
+        let new_defaults = || {
+            ImportDefaults::new(
+                vec![root.join("assets")],
+                vec![("png", merge_import_defaults::<Options> as ImportDefaultsFn)],
+                root.join("applied.ron"),
+            )
+        };
+
+        let mut import_defaults = new_defaults();
+        import_defaults.apply();
+        assert!(options("a.png").mips);
+        assert!(!options("b.png").mips);
+        assert_eq!(options("ui/c.png").filter, Filter::Linear);
+        assert!(!options("ui/c.png").mips);
+        assert_eq!(
+            fs::read_to_string(textures.join("c.txt.meta")).unwrap(),
+            meta("()")
+        );
+
+        fs::write(textures.join("ui/png.import_defaults"), "(mips: true)").unwrap();
+        import_defaults.apply();
+        assert_eq!(options("ui/c.png").filter, Filter::Nearest);
+        assert!(options("ui/c.png").mips);
+        fs::write(textures.join("ui/png.import_defaults"), "(filter: Linear)").unwrap();
+
+        fs::remove_file(root.join("assets/png.import_defaults")).unwrap();
+        new_defaults().apply();
+        assert_eq!(options("a.png"), Options::default());
+        assert_eq!(options("ui/c.png").filter, Filter::Linear);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -24,6 +24,7 @@ mod cache;
 mod daemon;
 /// asset loading specific errors
 pub mod error;
+pub mod import_defaults;
 #[cfg(feature = "json")]
 mod json;
 mod loader;
@@ -58,6 +59,7 @@ pub use crate::{
     asset::{Asset, Format, FormatValue, ProcessableAsset, SerializableFormat},
     bundle::LoaderBundle,
     cache::Cache,
+    import_defaults::{merge_import_defaults, ImportDefaultsFn},
     loader::{create_asset_type, AssetUuid, DefaultLoader, LoadStatus, Loader},
     processor::{AssetProcessorSystem, ProcessingQueue, ProcessingState},
     progress::{Completion, Progress, ProgressCounter, Tracker},
@@ -75,6 +75,29 @@ pub trait Loader: Send + Sync {
     /// * `T`: Asset `TypeUuid`.
     fn load<T: TypeUuid>(&self, path: &str) -> Handle<T>;
 
+    /// Loads the asset at `path` like [`Loader::load`], and reports whether it could be processed
+    /// to `progress`.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `A`: Asset type.
+    /// * `P`: Progress, usually a `&mut ProgressCounter`.
+    fn load_with_progress<A, P>(
+        &self,
+        path: &str,
+        mut progress: P,
+        processing_queue: &ProcessingQueue<A::Data>,
+    ) -> Handle<A>
+    where
+        A: Asset + TypeUuid,
+        P: Progress,
+    {
+        let handle = self.load::<A>(path);
+        progress.add_assets(1);
+        processing_queue.track(handle.load_handle(), Box::new(progress.create_tracker()));
+        handle
+    }
+
     /// Returns a weak handle to the asset of the given UUID, if any.
     ///
     /// # Parameters
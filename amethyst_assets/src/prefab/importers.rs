@@ -11,6 +11,9 @@ use type_uuid::TypeUuid;
 
 use crate::{prefab, prefab::Prefab};
 
+/// Importer options of `.prefab` files, stored in the `.meta` file of each prefab.
+///
+/// Prefabs have no options yet.
 #[derive(Default, Deserialize, Serialize, TypeUuid, Clone, Copy)]
 #[uuid = "80583980-24d4-4034-8394-ea749b43f55d"]
 pub struct PrefabImporterOptions {}
@@ -57,7 +60,8 @@ impl Importer for PrefabImporter {
         // STEP 2: Deserialize the prefab into a legion world
 
         // Create a deserializer
-        let mut de = ron::de::Deserializer::from_bytes(bytes.as_slice()).unwrap();
+        let mut de = ron::de::Deserializer::from_bytes(bytes.as_slice())
+            .map_err(distill_importer::Error::RonDe)?;
 
         // Create the component registry
         let registered_components = {
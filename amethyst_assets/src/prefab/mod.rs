@@ -8,6 +8,14 @@ pub use assets::Prefab;
 
 pub(crate) mod system;
 
+mod spawn;
+pub use spawn::{spawn_prefab, spawn_prefab_batch, PrefabEntity, PrefabOverrides, SpawnedPrefab};
+
+mod outline;
+
+mod save;
+pub use save::{PrefabEdits, PrefabSaver};
+
 mod component_registry;
 pub use component_registry::{ComponentRegistry, ComponentRegistryBuilder};
 pub use legion_prefab::{self, register_component_type, ComponentRegistration};
@@ -15,6 +23,9 @@ pub use serde_diff::{self, SerdeDiff};
 
 mod processor;
 
+mod validate;
+pub use validate::{validate_prefabs, PrefabDiagnostic, PrefabDiagnosticKind};
+
 // register core components
 register_component_type!(amethyst_core::transform::Transform);
 register_component_type!(amethyst_core::transform::TransformValues);
@@ -0,0 +1,62 @@
+//! The shape of a `.prefab` file.
+//!
+//! The ids are borrowed from the file, their addresses tell where they are in it.
+
+use serde::{de::IgnoredAny, Deserialize};
+
+#[derive(Deserialize)]
+#[serde(rename = "Prefab")]
+pub(super) struct PrefabOutline<'a> {
+    pub(super) id: &'a str,
+    #[serde(borrow)]
+    pub(super) objects: Vec<ObjectOutline<'a>>,
+}
+
+#[derive(Deserialize)]
+pub(super) enum ObjectOutline<'a> {
+    Entity(#[serde(borrow)] EntityOutline<'a>),
+    PrefabRef(#[serde(borrow)] PrefabRefOutline<'a>),
+}
+
+#[derive(Deserialize)]
+pub(super) struct EntityOutline<'a> {
+    pub(super) id: &'a str,
+    #[serde(borrow)]
+    pub(super) components: Vec<ComponentOutline<'a>>,
+}
+
+#[derive(Deserialize)]
+pub(super) struct ComponentOutline<'a> {
+    #[serde(rename = "type")]
+    pub(super) type_id: &'a str,
+    #[serde(rename = "data")]
+    _data: IgnoredAny,
+}
+
+#[derive(Deserialize)]
+pub(super) struct PrefabRefOutline<'a> {
+    pub(super) prefab_id: &'a str,
+    #[serde(borrow)]
+    pub(super) entity_overrides: Vec<EntityOverrideOutline<'a>>,
+}
+
+#[derive(Deserialize)]
+pub(super) struct EntityOverrideOutline<'a> {
+    pub(super) entity_id: &'a str,
+    #[serde(borrow)]
+    pub(super) component_overrides: Vec<ComponentOverrideOutline<'a>>,
+}
+
+#[derive(Deserialize)]
+pub(super) struct ComponentOverrideOutline<'a> {
+    pub(super) component_type: &'a str,
+    #[serde(rename = "diff")]
+    _diff: IgnoredAny,
+}
+
+/// Byte offset of `part` in `text`, which it has to be borrowed from.
+pub(super) fn offset_in(text: &str, part: &str) -> usize {
+    let offset = part.as_ptr() as usize - text.as_ptr() as usize;
+    debug_assert!(offset + part.len() <= text.len());
+    offset
+}
@@ -2,6 +2,7 @@ use amethyst_core::{
     dispatcher::System,
     ecs::{systems::ParallelRunnable, SystemBuilder},
 };
+use amethyst_error::{format_err, Error};
 use distill::core::AssetUuid;
 use fnv::{FnvHashMap, FnvHashSet};
 use prefab_format::PrefabUuid;
@@ -21,7 +22,7 @@ impl Prefab {
         prefab: &Prefab,
         storage: &AssetStorage<Prefab>,
         component_registry: &ComponentRegistry,
-    ) -> legion_prefab::CookedPrefab {
+    ) -> Result<legion_prefab::CookedPrefab, Error> {
         // This will allow us to look up prefab references by AssetUuid
         let mut prefab_lookup = FnvHashMap::default();
 
@@ -34,15 +35,30 @@ impl Prefab {
         while let Some((cur_prefab, children)) = dependency_stack.last_mut() {
             if let Some(child_handle) = children.next() {
                 log::debug!("Checking for child prefab {:?}", child_handle);
-                if let Some(child_prefab) = storage.get(child_handle) {
-                    if prefab_lookup.contains_key(&child_prefab.raw.prefab_id()) {
-                        continue;
-                    }
-
-                    dependency_stack.push((child_prefab, child_prefab.dependencies.iter()));
-                } else {
-                    log::error!("Prefab dependency is not yet loaded!");
+                let child_prefab = storage.get(child_handle).ok_or_else(|| {
+                    format_err!(
+                        "Prefab {} depends on a prefab which is not loaded yet",
+                        display_id(cur_prefab.raw.prefab_id())
+                    )
+                })?;
+                let child_id = child_prefab.raw.prefab_id();
+                if prefab_lookup.contains_key(&child_id) {
+                    // Already cooked through another path (diamond dependency).
+                    continue;
+                }
+                if let Some(position) = dependency_stack
+                    .iter()
+                    .position(|(p, _)| p.raw.prefab_id() == child_id)
+                {
+                    let mut cycle: Vec<PrefabUuid> = dependency_stack[position..]
+                        .iter()
+                        .map(|(p, _)| p.raw.prefab_id())
+                        .collect();
+                    cycle.push(child_id);
+                    return Err(cycle_error(&cycle));
                 }
+
+                dependency_stack.push((child_prefab, child_prefab.dependencies.iter()));
             } else {
                 // No more dependencies, add cur_prefab to prefab_cook_order and
                 // pop the stack.
@@ -55,12 +71,85 @@ impl Prefab {
         log::debug!("prefab_cook_order: {:x?}", prefab_cook_order);
         log::debug!("prefab_lookup: {:x?}", prefab_lookup.keys());
 
-        legion_prefab::cook_prefab(
+        Ok(legion_prefab::cook_prefab(
             component_registry.components(),
             component_registry.components_by_uuid(),
             prefab_cook_order.as_slice(),
             &prefab_lookup,
-        )
+        ))
+    }
+
+    /// Returns `true` if every prefab this one depends on, directly or through other prefabs, is
+    /// committed to the storage.
+    fn dependencies_committed(&self, storage: &AssetStorage<Prefab>) -> bool {
+        let mut visited = FnvHashSet::default();
+        let mut stack: Vec<&Prefab> = vec![self];
+        while let Some(prefab) = stack.pop() {
+            for handle in &prefab.dependencies {
+                if !storage.contains(handle.load_handle()) {
+                    return false;
+                }
+                if let Some(child) = storage.get(handle) {
+                    if visited.insert(child.raw.prefab_id()) {
+                        stack.push(child);
+                    }
+                }
+            }
+        }
+        true
+    }
+}
+
+fn display_id(id: PrefabUuid) -> uuid::Uuid {
+    uuid::Uuid::from_bytes(id)
+}
+
+fn cycle_error(cycle: &[PrefabUuid]) -> Error {
+    let path: Vec<String> = cycle.iter().map(|id| display_id(*id).to_string()).collect();
+    format_err!("Cyclic prefab dependency: {}", path.join(" -> "))
+}
+
+/// References between prefabs seen by the processor, including prefabs which are still waiting
+/// for their dependencies. Cycles would otherwise leave all prefabs involved loading forever.
+#[derive(Default)]
+pub(crate) struct PrefabGraph {
+    refs: FnvHashMap<PrefabUuid, Vec<PrefabUuid>>,
+}
+
+impl PrefabGraph {
+    fn insert(&mut self, id: PrefabUuid, refs: Vec<PrefabUuid>) {
+        self.refs.insert(id, refs);
+    }
+
+    /// Returns the path of a cycle going through `id`, if there is one.
+    fn find_cycle(&self, id: PrefabUuid) -> Option<Vec<PrefabUuid>> {
+        let mut visited = FnvHashSet::default();
+        let mut path = vec![id];
+        if self.visit(id, id, &mut visited, &mut path) {
+            Some(path)
+        } else {
+            None
+        }
+    }
+
+    fn visit(
+        &self,
+        target: PrefabUuid,
+        current: PrefabUuid,
+        visited: &mut FnvHashSet<PrefabUuid>,
+        path: &mut Vec<PrefabUuid>,
+    ) -> bool {
+        for child in self.refs.get(&current).into_iter().flatten() {
+            path.push(*child);
+            if *child == target {
+                return true;
+            }
+            if visited.insert(*child) && self.visit(target, *child, visited, path) {
+                return true;
+            }
+            path.pop();
+        }
+        false
     }
 }
 
@@ -69,6 +158,7 @@ struct PrefabProcessorSystem;
 
 impl System for PrefabProcessorSystem {
     fn build(self) -> Box<dyn ParallelRunnable> {
+        let mut graph = PrefabGraph::default();
         Box::new(
             SystemBuilder::new("PrefabProcessorSystem")
                 .read_resource::<ComponentRegistry>()
@@ -82,6 +172,7 @@ impl System for PrefabProcessorSystem {
                             processing_queue,
                             storage,
                             loader,
+                            &mut graph,
                         );
                     },
                 ),
@@ -94,9 +185,10 @@ fn prefab_asset_processor(
     processing_queue: &mut ProcessingQueue<Prefab>,
     storage: &mut AssetStorage<Prefab>,
     loader: &mut DefaultLoader,
+    graph: &mut PrefabGraph,
 ) -> Vec<crate::Handle<Prefab>> {
-    // Re-cook prefabs with changed dependencies.
-    // FIXME: deal with cyclic and diamond dependencies correctly
+    // Re-cook prefabs with changed dependencies. Every depender is re-cooked at most once per
+    // change, which also stops the propagation from going around in circles.
     let mut visited = FnvHashSet::default();
 
     while let Some(dependee) = processing_queue.changed.pop() {
@@ -110,14 +202,20 @@ fn prefab_asset_processor(
                     .get_asset_with_version(weak_handle)
                     .map(move |(prefab, _)| (weak_handle, prefab))
             })
-            .map(|(weak_handle, prefab)| {
-                if visited.insert(weak_handle.load_handle()) {
-                    processing_queue.changed.push(weak_handle.load_handle());
+            .filter(|(weak_handle, _)| visited.insert(weak_handle.load_handle()))
+            .filter_map(|(weak_handle, prefab)| {
+                processing_queue.changed.push(weak_handle.load_handle());
+                match Prefab::cook_prefab(prefab, storage, component_registry) {
+                    Ok(cooked) => Some((weak_handle.clone(), cooked)),
+                    Err(e) => {
+                        log::error!(
+                            "Failed to re-cook prefab {}: {}",
+                            display_id(prefab.raw.prefab_id()),
+                            e
+                        );
+                        None
+                    }
                 }
-                (
-                    weak_handle.clone(),
-                    Prefab::cook_prefab(prefab, storage, component_registry),
-                )
             })
             .collect();
 
@@ -132,7 +230,16 @@ fn prefab_asset_processor(
     let mut loading = Vec::new();
 
     processing_queue.process(storage, |mut prefab, storage, handle| {
-        log::debug!("Processing Prefab {:x?}", AssetUuid(prefab.raw.prefab_id()));
+        let id = prefab.raw.prefab_id();
+        log::debug!("Processing Prefab {:x?}", AssetUuid(id));
+
+        graph.insert(
+            id,
+            prefab.raw.prefab_meta.prefab_refs.keys().copied().collect(),
+        );
+        if let Some(cycle) = graph.find_cycle(id) {
+            return Err(cycle_error(&cycle));
+        }
 
         prefab.dependencies = prefab
             .raw
@@ -146,22 +253,17 @@ fn prefab_asset_processor(
             })
             .collect();
 
-        Ok(
-            if prefab
-                .dependencies
-                .iter()
-                .all(|handle| storage.contains(handle.load_handle()))
-            {
-                prefab.cooked = Some(Prefab::cook_prefab(&prefab, storage, component_registry));
-                prefab.version += storage
-                    .get_for_load_handle(*handle)
-                    .map_or(1, |Prefab { version, .. }| *version + 1);
-
-                ProcessingState::Loaded(prefab)
-            } else {
-                ProcessingState::Loading(prefab)
-            },
-        )
+        // Cooking is deferred until the whole dependency tree is committed.
+        Ok(if prefab.dependencies_committed(storage) {
+            prefab.cooked = Some(Prefab::cook_prefab(&prefab, storage, component_registry)?);
+            prefab.version += storage
+                .get_for_load_handle(*handle)
+                .map_or(1, |Prefab { version, .. }| *version + 1);
+
+            ProcessingState::Loaded(prefab)
+        } else {
+            ProcessingState::Loading(prefab)
+        })
     });
     storage.process_custom_drop(|_| {});
     loading
@@ -180,7 +282,7 @@ mod tests {
     use crate::{
         prefab::{ComponentRegistryBuilder, Prefab},
         processor::LoadNotifier,
-        Handle,
+        Completion, Handle, ProgressCounter,
     };
 
     struct Fixture {
@@ -227,6 +329,7 @@ mod tests {
             mut prefab_storage,
             component_registry,
         } = Fixture::setup();
+        let mut graph = PrefabGraph::default();
 
         let raw_prefab = Prefab::default();
 
@@ -238,6 +341,7 @@ mod tests {
             &mut processing_queue,
             &mut prefab_storage,
             &mut loader,
+            &mut graph,
         );
 
         let asset = prefab_storage
@@ -255,6 +359,7 @@ mod tests {
             mut prefab_storage,
             component_registry,
         } = Fixture::setup();
+        let mut graph = PrefabGraph::default();
 
         let mut prefab_root = Prefab::default();
         let prefab_child = Prefab::default();
@@ -275,6 +380,7 @@ mod tests {
             &mut processing_queue,
             &mut prefab_storage,
             &mut loader,
+            &mut graph,
         );
 
         let child_handle = children_handles.get(0).unwrap().load_handle();
@@ -292,6 +398,7 @@ mod tests {
             &mut processing_queue,
             &mut prefab_storage,
             &mut loader,
+            &mut graph,
         );
 
         prefab_storage.commit_asset(child_handle, 0);
@@ -301,6 +408,7 @@ mod tests {
             &mut processing_queue,
             &mut prefab_storage,
             &mut loader,
+            &mut graph,
         );
 
         let asset = prefab_storage
@@ -308,4 +416,63 @@ mod tests {
             .expect("prefab is not in storage");
         assert!(asset.cooked.is_some());
     }
+
+    #[test]
+    fn graph_finds_cycles() {
+        let (a, b, c, d) = ([1; 16], [2; 16], [3; 16], [4; 16]);
+        let mut graph = PrefabGraph::default();
+
+        // Diamond: a -> b -> d, a -> c -> d
+        graph.insert(a, vec![b, c]);
+        graph.insert(b, vec![d]);
+        graph.insert(c, vec![d]);
+        assert_eq!(graph.find_cycle(a), None);
+
+        // d -> a closes a cycle through b (and c).
+        graph.insert(d, vec![a]);
+        assert_eq!(graph.find_cycle(d), Some(vec![d, a, b, d]));
+        assert!(graph.find_cycle(c).is_some());
+    }
+
+    #[serial]
+    #[test]
+    fn cyclic_prefab_is_reported_to_progress() {
+        let Fixture {
+            mut loader,
+            mut processing_queue,
+            mut prefab_storage,
+            component_registry,
+        } = Fixture::setup();
+        let mut graph = PrefabGraph::default();
+
+        let mut prefab = Prefab::default();
+        let id = prefab.raw.prefab_id();
+        // A prefab referencing itself.
+        prefab.raw.prefab_meta.prefab_refs.insert(
+            id,
+            PrefabRef {
+                overrides: HashMap::new(),
+            },
+        );
+
+        let mut progress = ProgressCounter::new();
+        let prefab_handle: Handle<Prefab> =
+            loader.load_from_data(prefab, &mut progress, &processing_queue);
+
+        prefab_asset_processor(
+            &component_registry,
+            &mut processing_queue,
+            &mut prefab_storage,
+            &mut loader,
+            &mut graph,
+        );
+
+        assert!(prefab_storage.get(&prefab_handle).is_none());
+        assert_eq!(progress.complete(), Completion::Failed);
+        let errors = progress.errors();
+        assert!(errors[0]
+            .error
+            .to_string()
+            .contains("Cyclic prefab dependency"));
+    }
 }
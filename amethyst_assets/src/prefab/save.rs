@@ -0,0 +1,471 @@
+use std::{any::type_name, fmt::Write as _, fs, path::Path};
+
+use amethyst_core::ecs::{storage::Component, Entity, Resources, World};
+use amethyst_error::{format_err, Error};
+use prefab_format::{EntityUuid, PrefabUuid};
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+use serde_diff::{Diff, SerdeDiff};
+use type_uuid::TypeUuid;
+
+use crate::{
+    prefab::{
+        outline::{offset_in, ObjectOutline, PrefabOutline},
+        system::PrefabInstance,
+        Prefab,
+    },
+    AssetStorage, Handle,
+};
+
+type CaptureFn = fn(&World, Entity, &World, Entity) -> Result<Option<ComponentEdit>, Error>;
+
+#[derive(Clone, Debug)]
+struct SavedComponent {
+    name: &'static str,
+    capture: CaptureFn,
+}
+
+/// Captures the live state of a spawned prefab instance so it can be written back to disk.
+///
+/// Only registered component types are saved. They must be stored in the world with the same
+/// type as in the prefab, so components with a spawn mapping in the `ComponentRegistry` have to
+/// be registered with their prefab type and are compared on the prefab side only.
+///
+/// # Example
+///
+/// ```no_run
+/// use amethyst::{
+///     assets::prefab::PrefabSaver,
+///     core::{ecs::*, Transform},
+/// };
+///
+/// # fn save(world: &World, resources: &Resources, root: Entity) -> amethyst::Result<()> {
+/// let edits = PrefabSaver::new()
+///     .with_component::<Transform>()
+///     .capture(world, resources, root)?;
+/// if !edits.is_empty() {
+///     edits.write_overrides("assets/level_1_tweaked.prefab")?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PrefabSaver {
+    components: Vec<SavedComponent>,
+}
+
+impl PrefabSaver {
+    /// Creates a saver without any component types.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Saves components of type `T`.
+    #[must_use]
+    pub fn with_component<T>(mut self) -> Self
+    where
+        T: Component + TypeUuid + Serialize + SerdeDiff,
+    {
+        self.components.push(SavedComponent {
+            name: type_name::<T>(),
+            capture: capture_component::<T>,
+        });
+        self
+    }
+
+    /// Compares the prefab instance whose root is `root` with the prefab it was spawned from.
+    ///
+    /// `root` is the entity holding the `Handle<Prefab>`, either added by hand or returned by
+    /// `spawn_prefab`. Fails if the instance has not been spawned yet or the prefab is not loaded.
+    /// Entities which have been deleted since spawning are left out.
+    pub fn capture(
+        &self,
+        world: &World,
+        resources: &Resources,
+        root: Entity,
+    ) -> Result<PrefabEdits, Error> {
+        let prefab_storage = resources.get::<AssetStorage<Prefab>>().ok_or_else(|| {
+            format_err!("AssetStorage<Prefab> can not be retrieved from ECS Resources")
+        })?;
+        let entry = world
+            .entry_ref(root)
+            .map_err(|_| format_err!("Entity {:?} does not exist", root))?;
+        let handle = entry
+            .get_component::<Handle<Prefab>>()
+            .map_err(|_| format_err!("Entity {:?} is not the root of a prefab", root))?;
+        let instance = entry
+            .get_component::<PrefabInstance>()
+            .map_err(|_| format_err!("Prefab of entity {:?} has not been spawned yet", root))?;
+        let prefab = prefab_storage
+            .get(handle)
+            .ok_or_else(|| format_err!("Prefab is not loaded yet"))?;
+        let cooked = prefab
+            .cooked
+            .as_ref()
+            .ok_or_else(|| format_err!("Prefab is not loaded yet"))?;
+
+        let mut entities = Vec::new();
+        for (id, cooked_entity) in &cooked.entities {
+            let live_entity = match instance.entity_map.get(cooked_entity) {
+                Some(entity) if world.contains(*entity) => *entity,
+                _ => continue,
+            };
+            let mut components = Vec::new();
+            for component in &self.components {
+                let edit = (component.capture)(&cooked.world, *cooked_entity, world, live_entity)
+                    .map_err(|e| {
+                    format_err!("Could not save component `{}`: {}", component.name, e)
+                })?;
+                components.extend(edit);
+            }
+            entities.push(EntityEdits {
+                id: *id,
+                components,
+            });
+        }
+        entities.sort_by_key(|entity| entity.id);
+
+        Ok(PrefabEdits {
+            source: prefab.raw.prefab_id(),
+            entities,
+        })
+    }
+}
+
+fn capture_component<T>(
+    prefab_world: &World,
+    prefab_entity: Entity,
+    world: &World,
+    entity: Entity,
+) -> Result<Option<ComponentEdit>, Error>
+where
+    T: Component + TypeUuid + Serialize + SerdeDiff,
+{
+    let live_entry = match world.entry_ref(entity) {
+        Ok(entry) => entry,
+        Err(_) => return Ok(None),
+    };
+    let live = match live_entry.get_component::<T>() {
+        Ok(live) => live,
+        Err(_) => return Ok(None),
+    };
+    let data = ron::ser::to_string(live).map_err(Error::new)?;
+
+    let prefab_entry = prefab_world.entry_ref(prefab_entity).ok();
+    let change = match prefab_entry
+        .as_ref()
+        .and_then(|entry| entry.get_component::<T>().ok())
+    {
+        Some(cooked) if ron::ser::to_string(cooked).map_err(Error::new)? == data => {
+            ComponentChange::Unchanged
+        }
+        Some(cooked) => {
+            ComponentChange::Changed(
+                ron::ser::to_string(&Diff::serializable(cooked, live)).map_err(Error::new)?,
+            )
+        }
+        None => ComponentChange::Added,
+    };
+
+    Ok(Some(ComponentEdit {
+        name: type_name::<T>(),
+        uuid: T::UUID,
+        data,
+        change,
+    }))
+}
+
+#[derive(Clone, Debug)]
+enum ComponentChange {
+    Unchanged,
+    /// serde-diff patch from the prefab to the live component, in RON.
+    Changed(String),
+    /// Not in the prefab, added to the live entity.
+    Added,
+}
+
+#[derive(Clone, Debug)]
+struct ComponentEdit {
+    name: &'static str,
+    uuid: type_uuid::Bytes,
+    /// The live component in RON.
+    data: String,
+    change: ComponentChange,
+}
+
+#[derive(Clone, Debug)]
+struct EntityEdits {
+    id: EntityUuid,
+    components: Vec<ComponentEdit>,
+}
+
+/// Live state of a prefab instance captured by [`PrefabSaver::capture`].
+#[derive(Clone, Debug)]
+pub struct PrefabEdits {
+    source: PrefabUuid,
+    entities: Vec<EntityEdits>,
+}
+
+impl PrefabEdits {
+    /// Returns `true` if no saved component differs from the prefab.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.changes().next().is_none()
+    }
+
+    /// Returns the id of each changed entity with the type name of each changed or added
+    /// component.
+    pub fn changes(&self) -> impl Iterator<Item = (EntityUuid, &'static str)> + '_ {
+        self.entities.iter().flat_map(|entity| {
+            entity
+                .components
+                .iter()
+                .filter(|c| !matches!(c.change, ComponentChange::Unchanged))
+                .map(move |c| (entity.id, c.name))
+        })
+    }
+
+    /// Returns a `.prefab` file with a new id containing every entity of the instance with its
+    /// saved components as they are now.
+    ///
+    /// The entities of prefabs referenced by the source prefab are included as well, so the
+    /// result does not depend on any other prefab.
+    #[must_use]
+    pub fn to_prefab(&self) -> String {
+        self.prefab_with_id(*uuid::Uuid::new_v4().as_bytes())
+    }
+
+    /// Returns `source`, the text of the source prefab's file, with the data of every changed
+    /// component replaced by its live state.
+    ///
+    /// Everything else in the file is kept as it is, including components which are not saved,
+    /// referenced prefabs and comments. Fails if a change can't be written into an entity of the
+    /// file itself, such as a change to an entity of a referenced prefab or an added component;
+    /// use [`PrefabEdits::to_overrides`] or [`PrefabEdits::to_prefab`] for those.
+    pub fn to_source_prefab(&self, source: &str) -> Result<String, Error> {
+        let outline: PrefabOutline<'_> = ron::de::from_str(source)
+            .map_err(|e| format_err!("Could not read the source prefab: {}", e))?;
+        if parse_id(outline.id) != Some(self.source) {
+            return Err(format_err!(
+                "The file is not the source prefab {}",
+                display_id(self.source)
+            ));
+        }
+
+        let mut replacements = Vec::new();
+        for entity in &self.entities {
+            for component in &entity.components {
+                let refuse = |reason: &str| {
+                    format_err!(
+                        "Component `{}` of entity {} {}, save the edits with \
+                         `PrefabEdits::to_overrides` instead",
+                        component.name,
+                        display_id(entity.id),
+                        reason
+                    )
+                };
+                match component.change {
+                    ComponentChange::Unchanged => continue,
+                    ComponentChange::Changed(_) => {}
+                    ComponentChange::Added => {
+                        return Err(format_err!(
+                            "Component `{}` was added to entity {}, save the edits with \
+                             `PrefabEdits::to_prefab` instead",
+                            component.name,
+                            display_id(entity.id)
+                        ))
+                    }
+                }
+                let type_id = outline
+                    .objects
+                    .iter()
+                    .find_map(|object| {
+                        match object {
+                            ObjectOutline::Entity(e) if parse_id(e.id) == Some(entity.id) => {
+                                Some(e)
+                            }
+                            _ => None,
+                        }
+                    })
+                    .ok_or_else(|| refuse("belongs to a referenced prefab"))?
+                    .components
+                    .iter()
+                    .find(|c| parse_id(c.type_id) == Some(component.uuid))
+                    .ok_or_else(|| refuse("is not in the source prefab"))?
+                    .type_id;
+                let span = data_span(source, type_id)
+                    .ok_or_else(|| refuse("could not be found in the source prefab"))?;
+                replacements.push((span, &component.data));
+            }
+        }
+
+        replacements.sort_by_key(|((start, _), _)| *start);
+        let mut out = String::with_capacity(source.len());
+        let mut copied = 0;
+        for ((start, end), data) in replacements {
+            out.push_str(&source[copied..start]);
+            out.push_str(data);
+            copied = end;
+        }
+        out.push_str(&source[copied..]);
+        Ok(out)
+    }
+
+    /// Returns a `.prefab` file with a new id which references the source prefab and overrides
+    /// the changed components.
+    ///
+    /// Changes made by later edits of the source prefab are kept for all unchanged fields.
+    /// Components added at runtime can't be expressed as overrides and are left out with a
+    /// warning, use [`PrefabEdits::to_prefab`] to keep them.
+    #[must_use]
+    pub fn to_overrides(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "Prefab(");
+        let _ = writeln!(out, "    id: \"{}\",", uuid::Uuid::new_v4());
+        let _ = writeln!(out, "    objects: [");
+        let _ = writeln!(out, "        PrefabRef((");
+        let _ = writeln!(
+            out,
+            "            prefab_id: \"{}\",",
+            display_id(self.source)
+        );
+        let _ = writeln!(out, "            entity_overrides: [");
+        for entity in &self.entities {
+            let mut overrides = String::new();
+            for component in &entity.components {
+                match &component.change {
+                    ComponentChange::Unchanged => {}
+                    ComponentChange::Changed(diff) => {
+                        let _ = writeln!(overrides, "                        (");
+                        let _ = writeln!(
+                            overrides,
+                            "                            component_type: \"{}\",",
+                            display_id(component.uuid)
+                        );
+                        let _ = writeln!(
+                            overrides,
+                            "                            diff: {},",
+                            ron::ser::to_string(diff).unwrap_or_default()
+                        );
+                        let _ = writeln!(overrides, "                        ),");
+                    }
+                    ComponentChange::Added => {
+                        log::warn!(
+                            "Component `{}` added to entity {} can't be saved as an override",
+                            component.name,
+                            display_id(entity.id)
+                        )
+                    }
+                }
+            }
+            if overrides.is_empty() {
+                continue;
+            }
+            let _ = writeln!(out, "                (");
+            let _ = writeln!(
+                out,
+                "                    entity_id: \"{}\",",
+                display_id(entity.id)
+            );
+            let _ = writeln!(out, "                    component_overrides: [");
+            out.push_str(&overrides);
+            let _ = writeln!(out, "                    ],");
+            let _ = writeln!(out, "                ),");
+        }
+        let _ = writeln!(out, "            ],");
+        let _ = writeln!(out, "        )),");
+        let _ = writeln!(out, "    ],");
+        let _ = writeln!(out, ")");
+        out
+    }
+
+    /// Writes [`PrefabEdits::to_prefab`] to `path`.
+    pub fn write_prefab<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        write(path.as_ref(), &self.to_prefab())
+    }
+
+    /// Applies [`PrefabEdits::to_source_prefab`] to the file at `path`, which should be the file
+    /// the source prefab was loaded from. The asset daemon picks up the change and reloads every
+    /// instance.
+    pub fn write_source_prefab<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .map_err(|e| format_err!("Could not read prefab from {}: {}", path.display(), e))?;
+        write(path, &self.to_source_prefab(&source)?)
+    }
+
+    /// Writes [`PrefabEdits::to_overrides`] to `path`.
+    pub fn write_overrides<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        write(path.as_ref(), &self.to_overrides())
+    }
+
+    fn prefab_with_id(&self, id: PrefabUuid) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "Prefab(");
+        let _ = writeln!(out, "    id: \"{}\",", display_id(id));
+        let _ = writeln!(out, "    objects: [");
+        for entity in &self.entities {
+            let _ = writeln!(out, "        Entity((");
+            let _ = writeln!(out, "            id: \"{}\",", display_id(entity.id));
+            let _ = writeln!(out, "            components: [");
+            for component in &entity.components {
+                let _ = writeln!(out, "                (");
+                let _ = writeln!(
+                    out,
+                    "                    type: \"{}\",",
+                    display_id(component.uuid)
+                );
+                let _ = writeln!(out, "                    data: {},", component.data);
+                let _ = writeln!(out, "                ),");
+            }
+            let _ = writeln!(out, "            ],");
+            let _ = writeln!(out, "        )),");
+        }
+        let _ = writeln!(out, "    ],");
+        let _ = writeln!(out, ")");
+        out
+    }
+}
+
+fn display_id(id: [u8; 16]) -> uuid::Uuid {
+    uuid::Uuid::from_bytes(id)
+}
+
+fn parse_id(id: &str) -> Option<[u8; 16]> {
+    uuid::Uuid::parse_str(id).ok().map(|id| *id.as_bytes())
+}
+
+/// Byte range of the data of the component with the `type_id` borrowed from `text`.
+///
+/// The data has to follow the type, as in files written by [`PrefabEdits`].
+fn data_span(text: &str, type_id: &str) -> Option<(usize, usize)> {
+    let start = offset_in(text, type_id);
+    let mut position = start + type_id.len();
+    if !text[..start].ends_with('"') || !text[position..].starts_with('"') {
+        return None;
+    }
+    for token in &["\"", ",", "data", ":"] {
+        position = skip_whitespace(text, position)?;
+        if !text[position..].starts_with(token) {
+            return None;
+        }
+        position += token.len();
+    }
+    let start = skip_whitespace(text, position)?;
+
+    let mut de = ron::de::Deserializer::from_str(&text[start..]).ok()?;
+    IgnoredAny::deserialize(&mut de).ok()?;
+    let end = text.len() - de.remainder().len();
+    Some((start, start + text[start..end].trim_end().len()))
+}
+
+/// Skips whitespace and comments starting at `position`.
+fn skip_whitespace(text: &str, position: usize) -> Option<usize> {
+    let de = ron::de::Deserializer::from_str(&text[position..]).ok()?;
+    Some(text.len() - de.remainder().len())
+}
+
+fn write(path: &Path, contents: &str) -> Result<(), Error> {
+    fs::write(path, contents)
+        .map_err(|e| format_err!("Could not write prefab to {}: {}", path.display(), e))
+}
@@ -0,0 +1,267 @@
+use std::{any::type_name, collections::HashMap};
+
+use amethyst_core::ecs::{
+    query, storage::Component, world::EntityHasher, Entity, IntoQuery, Resources, World,
+};
+use amethyst_error::{format_err, Error};
+use prefab_format::EntityUuid;
+use serde_diff::{Apply, Diff, SerdeDiff};
+
+use crate::{
+    prefab::{system::PrefabInstance, ComponentRegistry, Prefab},
+    AssetStorage, Handle,
+};
+
+/// Entity of a prefab targeted by an override.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrefabEntity {
+    /// The root entity of the prefab, returned as [`SpawnedPrefab::root`].
+    Root,
+    /// The entity with this id in the `.prefab` file.
+    Id(EntityUuid),
+}
+
+#[derive(Clone, Debug)]
+struct ComponentPatch {
+    entity: PrefabEntity,
+    component: &'static str,
+    /// serde-diff patch in RON, or the error serializing it.
+    patch: Result<String, String>,
+    apply: fn(&mut World, Entity, &str) -> Result<(), Error>,
+}
+
+/// Changes to the components of a single prefab instance, applied right after it is spawned.
+///
+/// Overrides are serde-diff patches, the same as the `component_overrides` of a `PrefabRef` in a
+/// `.prefab` file. They apply to the spawned components, so to the target type of any spawn
+/// mapping registered with the `ComponentRegistry`.
+///
+/// # Example
+///
+/// ```
+/// use amethyst::{
+///     assets::prefab::{PrefabEntity, PrefabOverrides},
+///     core::Transform,
+/// };
+///
+/// let mut moved = Transform::default();
+/// moved.set_translation_xyz(10.0, 0.0, 0.0);
+///
+/// let overrides =
+///     PrefabOverrides::new().with_diff(PrefabEntity::Root, &Transform::default(), &moved);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PrefabOverrides {
+    patches: Vec<ComponentPatch>,
+}
+
+impl PrefabOverrides {
+    /// Creates an empty set of overrides.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies `patch`, a serde-diff patch in RON like the `diff` of a component override in a
+    /// `.prefab` file, to the `T` component of `entity`.
+    #[must_use]
+    pub fn with_patch<T, P>(self, entity: PrefabEntity, patch: P) -> Self
+    where
+        T: Component + SerdeDiff,
+        P: Into<String>,
+    {
+        self.push::<T>(entity, Ok(patch.into()))
+    }
+
+    /// Applies the changes from `old` to `new` to the `T` component of `entity`. Fields which are
+    /// the same in `old` and `new` keep the value from the prefab.
+    #[must_use]
+    pub fn with_diff<T>(self, entity: PrefabEntity, old: &T, new: &T) -> Self
+    where
+        T: Component + SerdeDiff,
+    {
+        let patch = ron::ser::to_string(&Diff::serializable(old, new)).map_err(|e| e.to_string());
+        self.push::<T>(entity, patch)
+    }
+
+    /// Returns `true` if there are no overrides.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.patches.is_empty()
+    }
+
+    /// Applies the overrides to the instance with the root entity `root`, looking up the other
+    /// entities by their id with `entity`.
+    pub(crate) fn apply<F>(&self, world: &mut World, root: Entity, entity: F) -> Result<(), Error>
+    where
+        F: Fn(&EntityUuid) -> Option<Entity>,
+    {
+        for patch in &self.patches {
+            let target = match patch.entity {
+                PrefabEntity::Root => Some(root),
+                PrefabEntity::Id(id) => entity(&id),
+            }
+            .ok_or_else(|| format_err!("Prefab has no entity {:?}", patch.entity))?;
+            let text = patch.patch.as_ref().map_err(|e| {
+                format_err!(
+                    "Could not serialize override of `{}`: {}",
+                    patch.component,
+                    e
+                )
+            })?;
+            (patch.apply)(world, target, text)?;
+        }
+        Ok(())
+    }
+
+    fn push<T: Component + SerdeDiff>(
+        mut self,
+        entity: PrefabEntity,
+        patch: Result<String, String>,
+    ) -> Self {
+        self.patches.push(ComponentPatch {
+            entity,
+            component: type_name::<T>(),
+            patch,
+            apply: apply_patch::<T>,
+        });
+        self
+    }
+}
+
+fn apply_patch<T: Component + SerdeDiff>(
+    world: &mut World,
+    entity: Entity,
+    patch: &str,
+) -> Result<(), Error> {
+    let mut entry = world
+        .entry(entity)
+        .ok_or_else(|| format_err!("Entity {:?} does not exist", entity))?;
+    let component = entry.get_component_mut::<T>().map_err(|_| {
+        format_err!(
+            "Entity {:?} has no `{}` component to override",
+            entity,
+            type_name::<T>()
+        )
+    })?;
+    let mut deserializer = ron::de::Deserializer::from_str(patch).map_err(Error::new)?;
+    Apply::apply(&mut deserializer, component).map_err(Error::new)
+}
+
+/// Entities of a prefab instance created by [`spawn_prefab`].
+#[derive(Clone, Debug)]
+pub struct SpawnedPrefab {
+    /// The root entity, holding the `Handle<Prefab>` of the instance.
+    pub root: Entity,
+    /// Maps the entities of the cooked prefab to the spawned entities.
+    pub entity_map: HashMap<Entity, Entity, EntityHasher>,
+    ids: HashMap<EntityUuid, Entity>,
+}
+
+impl SpawnedPrefab {
+    /// Returns the spawned entity for the entity with id `id` in the `.prefab` file.
+    #[must_use]
+    pub fn entity(&self, id: &EntityUuid) -> Option<Entity> {
+        self.ids.get(id).copied()
+    }
+
+    /// Returns all spawned entities.
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.entity_map.values().copied()
+    }
+}
+
+/// Spawns an instance of a loaded prefab and applies `overrides` to it.
+///
+/// Unlike adding a `Handle<Prefab>` to an entity, the entities exist as soon as this returns.
+/// The root entity gets the `Handle<Prefab>`, so the instance is updated when the prefab is
+/// reloaded like any other, and `overrides` are applied again afterwards. Fails if the prefab is
+/// not loaded yet.
+pub fn spawn_prefab(
+    world: &mut World,
+    resources: &Resources,
+    handle: &Handle<Prefab>,
+    overrides: &PrefabOverrides,
+) -> Result<SpawnedPrefab, Error> {
+    let mut spawned = spawn_prefab_batch(world, resources, handle, Some(overrides))?;
+    Ok(spawned.remove(0))
+}
+
+/// Spawns one instance of a loaded prefab for each of `overrides`, for example
+/// `std::iter::repeat(&PrefabOverrides::new()).take(100)` for a hundred identical bullets.
+///
+/// Resources are looked up and the prefab is resolved once for the whole batch. If an override
+/// fails to apply, all instances spawned by this call are removed again and the error is
+/// returned.
+pub fn spawn_prefab_batch<'a, I>(
+    world: &mut World,
+    resources: &Resources,
+    handle: &Handle<Prefab>,
+    overrides: I,
+) -> Result<Vec<SpawnedPrefab>, Error>
+where
+    I: IntoIterator<Item = &'a PrefabOverrides>,
+{
+    let component_registry = resources
+        .get::<ComponentRegistry>()
+        .ok_or_else(|| format_err!("ComponentRegistry can not be retrieved from ECS Resources"))?;
+    let prefab_storage = resources.get::<AssetStorage<Prefab>>().ok_or_else(|| {
+        format_err!("AssetStorage<Prefab> can not be retrieved from ECS Resources")
+    })?;
+
+    let (cooked_prefab, version) = match prefab_storage.get(handle) {
+        Some(Prefab {
+            cooked: Some(cooked_prefab),
+            version,
+            ..
+        }) => (cooked_prefab, *version),
+        _ => return Err(format_err!("Prefab is not loaded yet")),
+    };
+    let prefab_root = <(Entity,)>::query()
+        .iter(&cooked_prefab.world)
+        .next()
+        .map(|(entity,)| *entity)
+        .ok_or_else(|| format_err!("Prefab has no entities"))?;
+
+    let no_mapping = HashMap::default();
+    let mut spawned = Vec::new();
+    for overrides in overrides {
+        let entity_map = world.clone_from(
+            &cooked_prefab.world,
+            &query::any(),
+            &mut component_registry.spawn_clone_impl(resources, &no_mapping),
+        );
+        let instance = SpawnedPrefab {
+            root: entity_map[&prefab_root],
+            ids: cooked_prefab
+                .entities
+                .iter()
+                .filter_map(|(id, entity)| entity_map.get(entity).map(|spawned| (*id, *spawned)))
+                .collect(),
+            entity_map,
+        };
+
+        if let Err(err) = overrides.apply(world, instance.root, |id| instance.entity(id)) {
+            for entity in spawned
+                .iter()
+                .chain(std::iter::once(&instance))
+                .flat_map(SpawnedPrefab::entities)
+            {
+                world.remove(entity);
+            }
+            return Err(err);
+        }
+
+        if let Some(mut entry) = world.entry(instance.root) {
+            entry.add_component(handle.clone());
+            entry.add_component(PrefabInstance {
+                version,
+                entity_map: instance.entity_map.clone(),
+                overrides: overrides.clone(),
+            });
+        }
+        spawned.push(instance);
+    }
+
+    Ok(spawned)
+}
@@ -5,13 +5,17 @@ use amethyst_core::ecs::{
 };
 
 use crate::{
-    prefab::{ComponentRegistry, Prefab},
+    prefab::{ComponentRegistry, Prefab, PrefabOverrides},
     AssetStorage, Handle,
 };
 
-struct PrefabInstance {
-    version: u32,
-    entity_map: HashMap<Entity, Entity, EntityHasher>,
+/// Tracks the entities spawned for a prefab, on the entity holding the `Handle<Prefab>`.
+pub(crate) struct PrefabInstance {
+    pub(crate) version: u32,
+    /// Maps entities of the cooked prefab to the spawned entities.
+    pub(crate) entity_map: HashMap<Entity, Entity, EntityHasher>,
+    /// Overrides given to `spawn_prefab`, applied again whenever the prefab is reloaded.
+    pub(crate) overrides: PrefabOverrides,
 }
 
 /// Attaches prefabs to entities that have Handle<Prefab>
@@ -28,6 +32,7 @@ pub fn prefab_spawning_tick(world: &mut World, resources: &mut Resources) {
         &legion_prefab::CookedPrefab,
         u32,
         HashMap<Entity, Entity, EntityHasher>,
+        PrefabOverrides,
     )> = Vec::new();
 
     let mut entity_query = <(Entity,)>::query();
@@ -53,13 +58,23 @@ pub fn prefab_spawning_tick(world: &mut World, resources: &mut Resources) {
                             entity_map.insert(*root_entity, *entity);
                         }
                     }
-                    prefabs.push((*entity, cooked_prefab, *prefab_version, entity_map));
+                    let overrides = instance
+                        .as_ref()
+                        .map(|instance| instance.overrides.clone())
+                        .unwrap_or_default();
+                    prefabs.push((
+                        *entity,
+                        cooked_prefab,
+                        *prefab_version,
+                        entity_map,
+                        overrides,
+                    ));
                 }
             }
         },
     );
 
-    for (entity, prefab, version, prev_entity_map) in prefabs {
+    for (entity, prefab, version, prev_entity_map, overrides) in prefabs {
         let entity_map = world.clone_from(
             &prefab.world,
             &query::any(),
@@ -78,12 +93,29 @@ pub fn prefab_spawning_tick(world: &mut World, resources: &mut Resources) {
             }
         }
 
+        // Cloning the prefab again replaced the overridden components.
+        let overridden = overrides.apply(world, entity, |id| {
+            prefab
+                .entities
+                .get(id)
+                .and_then(|prefab_entity| entity_map.get(prefab_entity))
+                .copied()
+        });
+        if let Err(err) = overridden {
+            log::error!(
+                "Could not apply the overrides of the prefab of {:?}: {}",
+                entity,
+                err
+            );
+        }
+
         log::debug!("Spawn for {:?}", entity);
 
         if let Some(mut entry) = world.entry(entity) {
             entry.add_component(PrefabInstance {
                 version,
                 entity_map,
+                overrides,
             });
         } else {
             log::error!("Could not update entity");
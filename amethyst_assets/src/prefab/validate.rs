@@ -0,0 +1,585 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use fnv::{FnvHashMap, FnvHashSet};
+use legion_prefab::ComponentRegistration;
+use prefab_format::ComponentTypeUuid;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::prefab::{
+    outline::{offset_in, ObjectOutline, PrefabOutline},
+    ComponentRegistry,
+};
+
+/// A problem found in a `.prefab` file by [`validate_prefabs`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrefabDiagnostic {
+    /// File containing the problem.
+    pub path: PathBuf,
+    /// Line of the problem, starting at 1, if it could be determined.
+    pub line: Option<usize>,
+    /// What is wrong.
+    pub kind: PrefabDiagnosticKind,
+}
+
+impl fmt::Display for PrefabDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.path.display(), line, self.kind),
+            None => write!(f, "{}: {}", self.path.display(), self.kind),
+        }
+    }
+}
+
+/// The kinds of problems reported by [`validate_prefabs`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum PrefabDiagnosticKind {
+    /// The file could not be read.
+    Io(String),
+    /// The file is not valid RON or does not have the shape of a prefab.
+    Syntax(String),
+    /// A component type UUID which is not in the `ComponentRegistry`.
+    UnknownComponent(Uuid),
+    /// The data of a registered component could not be deserialized.
+    InvalidComponent {
+        /// Type UUID of the component.
+        component: Uuid,
+        /// Deserialization error.
+        message: String,
+    },
+    /// A referenced prefab does not exist in the validated directories.
+    MissingPrefab(Uuid),
+    /// An override targets an entity which does not exist in the referenced prefab.
+    MissingEntity {
+        /// The referenced prefab.
+        prefab: Uuid,
+        /// The entity which is not in it.
+        entity: Uuid,
+    },
+    /// Another file uses the same prefab id.
+    DuplicatePrefabId(PathBuf),
+    /// Prefabs referencing each other in a cycle, starting and ending with this file's prefab.
+    Cycle(Vec<Uuid>),
+}
+
+impl fmt::Display for PrefabDiagnosticKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrefabDiagnosticKind::Io(e) => write!(f, "could not read file: {}", e),
+            PrefabDiagnosticKind::Syntax(e) => write!(f, "syntax error: {}", e),
+            PrefabDiagnosticKind::UnknownComponent(uuid) => {
+                write!(f, "unknown component type {}", uuid)
+            }
+            PrefabDiagnosticKind::InvalidComponent { component, message } => {
+                write!(f, "invalid data for component {}: {}", component, message)
+            }
+            PrefabDiagnosticKind::MissingPrefab(uuid) => {
+                write!(f, "referenced prefab {} does not exist", uuid)
+            }
+            PrefabDiagnosticKind::MissingEntity { prefab, entity } => {
+                write!(f, "prefab {} has no entity {}", prefab, entity)
+            }
+            PrefabDiagnosticKind::DuplicatePrefabId(other) => {
+                write!(f, "prefab id is also used by {}", other.display())
+            }
+            PrefabDiagnosticKind::Cycle(cycle) => {
+                let path: Vec<String> = cycle.iter().map(ToString::to_string).collect();
+                write!(f, "cyclic prefab references: {}", path.join(" -> "))
+            }
+        }
+    }
+}
+
+/// Validates all `.prefab` files in `dirs` and their subdirectories against `registry`.
+///
+/// Reports syntax errors, unknown component type UUIDs, component data which does not
+/// deserialize, references to prefabs or entities which don't exist, duplicate prefab ids and
+/// reference cycles.
+pub fn validate_prefabs<P: AsRef<Path>>(
+    dirs: &[P],
+    registry: &ComponentRegistry,
+) -> Vec<PrefabDiagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut paths = Vec::new();
+    for dir in dirs {
+        collect_prefab_files(dir.as_ref(), &mut paths, &mut diagnostics);
+    }
+    paths.sort();
+
+    let mut sources = Vec::new();
+    for path in paths {
+        match fs::read_to_string(&path) {
+            Ok(text) => sources.push((path, text)),
+            Err(e) => {
+                diagnostics.push(PrefabDiagnostic {
+                    path,
+                    line: None,
+                    kind: PrefabDiagnosticKind::Io(e.to_string()),
+                })
+            }
+        }
+    }
+
+    diagnostics.extend(validate_sources(&sources, registry));
+    diagnostics
+}
+
+fn collect_prefab_files(
+    dir: &Path,
+    paths: &mut Vec<PathBuf>,
+    diagnostics: &mut Vec<PrefabDiagnostic>,
+) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            diagnostics.push(PrefabDiagnostic {
+                path: dir.to_path_buf(),
+                line: None,
+                kind: PrefabDiagnosticKind::Io(e.to_string()),
+            });
+            return;
+        }
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_prefab_files(&path, paths, diagnostics);
+        } else if path.extension().map_or(false, |ext| ext == "prefab") {
+            paths.push(path);
+        }
+    }
+}
+
+/// What the validator needs to know about a prefab file, with line numbers.
+#[derive(Debug)]
+struct Outline {
+    prefab_id: Uuid,
+    entities: Vec<Uuid>,
+    references: Vec<Reference>,
+    /// Byte offsets of the ids in the file in the order they appear, with the component type
+    /// for the ids of components and component overrides.
+    ids: Vec<(usize, Option<Uuid>)>,
+}
+
+#[derive(Debug)]
+struct Reference {
+    prefab: Uuid,
+    line: usize,
+    entities: Vec<(Uuid, usize)>,
+}
+
+/// An error in a prefab file, with its position.
+#[derive(Debug)]
+struct SourceError {
+    offset: usize,
+    line: usize,
+    message: String,
+}
+
+fn validate_sources(
+    sources: &[(PathBuf, String)],
+    registry: &ComponentRegistry,
+) -> Vec<PrefabDiagnostic> {
+    let registered: HashMap<ComponentTypeUuid, ComponentRegistration> = registry
+        .components_by_uuid()
+        .iter()
+        .map(|(uuid, registration)| (*uuid, registration.clone()))
+        .collect();
+
+    let mut diagnostics = Vec::new();
+    let mut outlines = Vec::new();
+
+    for (path, text) in sources {
+        let mut diagnose = |line, kind| {
+            diagnostics.push(PrefabDiagnostic {
+                path: path.clone(),
+                line: Some(line),
+                kind,
+            });
+        };
+
+        let outline = match outline(text) {
+            Ok(outline) => outline,
+            Err(e) => {
+                diagnose(e.line, PrefabDiagnosticKind::Syntax(e.message));
+                continue;
+            }
+        };
+
+        for (offset, component) in &outline.ids {
+            if let Some(component) = component {
+                if !registered.contains_key(component.as_bytes()) {
+                    diagnose(
+                        line_at(text, *offset),
+                        PrefabDiagnosticKind::UnknownComponent(*component),
+                    );
+                }
+            }
+        }
+
+        if let Err(e) = deserialize(text, &registered) {
+            // Blame the component the deserializer was reading, unknown ones are reported above.
+            let reading = outline
+                .ids
+                .iter()
+                .rev()
+                .find(|(offset, _)| *offset <= e.offset);
+            match reading {
+                Some((_, Some(component))) if !registered.contains_key(component.as_bytes()) => {}
+                Some((_, Some(component))) => {
+                    diagnose(
+                        e.line,
+                        PrefabDiagnosticKind::InvalidComponent {
+                            component: *component,
+                            message: e.message,
+                        },
+                    )
+                }
+                _ => diagnose(e.line, PrefabDiagnosticKind::Syntax(e.message)),
+            }
+        }
+
+        outlines.push((path, outline));
+    }
+
+    check_references(&outlines, &mut diagnostics);
+    diagnostics
+}
+
+/// Reads the ids and references of a prefab file.
+fn outline(text: &str) -> Result<Outline, SourceError> {
+    let prefab = parse(text, |de| {
+        let prefab = PrefabOutline::deserialize(&mut *de)?;
+        de.end()?;
+        Ok(prefab)
+    })?;
+
+    let uuid = |id: &str| {
+        let offset = offset_in(text, id);
+        Uuid::parse_str(id).map(|uuid| (uuid, offset)).map_err(|e| {
+            SourceError {
+                offset,
+                line: line_at(text, offset),
+                message: format!("invalid UUID `{}`: {}", id, e),
+            }
+        })
+    };
+
+    let (prefab_id, offset) = uuid(prefab.id)?;
+    let mut outline = Outline {
+        prefab_id,
+        entities: Vec::new(),
+        references: Vec::new(),
+        ids: vec![(offset, None)],
+    };
+    for object in &prefab.objects {
+        match object {
+            ObjectOutline::Entity(entity) => {
+                let (id, offset) = uuid(entity.id)?;
+                outline.entities.push(id);
+                outline.ids.push((offset, None));
+                for component in &entity.components {
+                    let (component, offset) = uuid(component.type_id)?;
+                    outline.ids.push((offset, Some(component)));
+                }
+            }
+            ObjectOutline::PrefabRef(reference) => {
+                let (prefab, offset) = uuid(reference.prefab_id)?;
+                outline.ids.push((offset, None));
+                let mut entities = Vec::new();
+                for entity in &reference.entity_overrides {
+                    let (id, offset) = uuid(entity.entity_id)?;
+                    entities.push((id, line_at(text, offset)));
+                    outline.ids.push((offset, None));
+                    for component in &entity.component_overrides {
+                        let (component, offset) = uuid(component.component_type)?;
+                        outline.ids.push((offset, Some(component)));
+                    }
+                }
+                outline.references.push(Reference {
+                    prefab,
+                    line: line_at(text, offset),
+                    entities,
+                });
+            }
+        }
+    }
+
+    Ok(outline)
+}
+
+/// Deserializes a prefab the same way the `PrefabImporter` does.
+fn deserialize(
+    text: &str,
+    registered: &HashMap<ComponentTypeUuid, ComponentRegistration>,
+) -> Result<(), SourceError> {
+    parse(text, |de| {
+        let prefab_serde_context = legion_prefab::PrefabSerdeContext {
+            registered_components: registered,
+        };
+        let prefab_deser = legion_prefab::PrefabFormatDeserializer::new(prefab_serde_context);
+        prefab_format::deserialize(de, &prefab_deser)
+    })
+}
+
+/// Runs `f` on a RON deserializer of `text`, locating its errors.
+fn parse<'a, T>(
+    text: &'a str,
+    f: impl FnOnce(&mut ron::de::Deserializer<'a>) -> Result<T, ron::Error>,
+) -> Result<T, SourceError> {
+    let mut de = ron::de::Deserializer::from_str(text).map_err(|e| {
+        SourceError {
+            offset: 0,
+            line: e.position.line.max(1),
+            message: e.code.to_string(),
+        }
+    })?;
+    f(&mut de).map_err(|e| {
+        // Errors raised by serde itself, such as missing fields, don't carry a position, but
+        // the deserializer stops right where they happened.
+        let offset = text.len() - de.remainder().len();
+        let line = if e.position.line > 0 {
+            e.position.line
+        } else {
+            line_at(text, offset)
+        };
+        SourceError {
+            offset,
+            line,
+            message: e.code.to_string(),
+        }
+    })
+}
+
+/// Line of a byte offset, starting at 1.
+fn line_at(text: &str, offset: usize) -> usize {
+    text[..offset].matches('\n').count() + 1
+}
+
+fn check_references(outlines: &[(&PathBuf, Outline)], diagnostics: &mut Vec<PrefabDiagnostic>) {
+    let mut by_id: FnvHashMap<Uuid, (&PathBuf, &Outline)> = FnvHashMap::default();
+    for (path, outline) in outlines {
+        if let Some((other, _)) = by_id.get(&outline.prefab_id) {
+            diagnostics.push(PrefabDiagnostic {
+                path: (*path).clone(),
+                line: None,
+                kind: PrefabDiagnosticKind::DuplicatePrefabId((*other).clone()),
+            });
+        } else {
+            by_id.insert(outline.prefab_id, (*path, outline));
+        }
+    }
+
+    for (path, outline) in outlines {
+        for reference in &outline.references {
+            let mut diagnose = |line, kind| {
+                diagnostics.push(PrefabDiagnostic {
+                    path: (*path).clone(),
+                    line: Some(line),
+                    kind,
+                });
+            };
+            match by_id.get(&reference.prefab) {
+                None => {
+                    diagnose(
+                        reference.line,
+                        PrefabDiagnosticKind::MissingPrefab(reference.prefab),
+                    )
+                }
+                Some((_, target)) => {
+                    for (entity, line) in &reference.entities {
+                        if !target.entities.contains(entity) {
+                            diagnose(
+                                *line,
+                                PrefabDiagnosticKind::MissingEntity {
+                                    prefab: reference.prefab,
+                                    entity: *entity,
+                                },
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Report every cycle once, on the file of the first prefab in it.
+    let mut reported = FnvHashSet::default();
+    let mut ids: Vec<&Uuid> = by_id.keys().collect();
+    ids.sort();
+    for id in ids {
+        let mut path = vec![*id];
+        if find_cycle(*id, *id, &by_id, &mut FnvHashSet::default(), &mut path) {
+            let mut members = path.clone();
+            members.sort();
+            members.dedup();
+            if reported.insert(members) {
+                let (file, outline) = by_id[id];
+                let line = outline
+                    .references
+                    .iter()
+                    .find(|r| r.prefab == path[1])
+                    .map(|r| r.line);
+                diagnostics.push(PrefabDiagnostic {
+                    path: file.clone(),
+                    line,
+                    kind: PrefabDiagnosticKind::Cycle(path),
+                });
+            }
+        }
+    }
+}
+
+fn find_cycle(
+    target: Uuid,
+    current: Uuid,
+    by_id: &FnvHashMap<Uuid, (&PathBuf, &Outline)>,
+    visited: &mut FnvHashSet<Uuid>,
+    path: &mut Vec<Uuid>,
+) -> bool {
+    let references = match by_id.get(&current) {
+        Some((_, outline)) => &outline.references,
+        None => return false,
+    };
+    for reference in references {
+        path.push(reference.prefab);
+        if reference.prefab == target {
+            return true;
+        }
+        if visited.insert(reference.prefab)
+            && find_cycle(target, reference.prefab, by_id, visited, path)
+        {
+            return true;
+        }
+        path.pop();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prefab::ComponentRegistryBuilder;
+
+    const BROKEN_REFERENCES: &str = r#"Prefab(
+    id: "a1e04f08-8bdd-403a-baa2-1ba83113f186",
+    objects: [
+        PrefabRef((
+            prefab_id: "8b10e4fb-5a25-40b0-9ddd-a28bc842f834",
+            entity_overrides: [],
+        )),
+        Entity((
+            id: "b6cc74e0-1c11-4876-8949-1408736a9613",
+            components: [
+                (
+                    // Not registered.
+                    type: "f5780013-bae4-49f0-ac0e-a108ff52fec0",
+                    data: (x: 0, y: 0),
+                ),
+            ],
+        )),
+    ],
+)"#;
+
+    fn validate(sources: &[(&str, &str)]) -> Vec<PrefabDiagnostic> {
+        let registry = ComponentRegistryBuilder::default().build();
+        let sources: Vec<(PathBuf, String)> = sources
+            .iter()
+            .map(|(path, text)| (PathBuf::from(path), (*text).to_string()))
+            .collect();
+        validate_sources(&sources, &registry)
+    }
+
+    fn uuid(s: &str) -> Uuid {
+        Uuid::parse_str(s).unwrap()
+    }
+
+    #[test]
+    fn outline_finds_ids_and_lines() {
+        let outline = outline(BROKEN_REFERENCES).unwrap();
+
+        assert_eq!(
+            outline.prefab_id,
+            uuid("a1e04f08-8bdd-403a-baa2-1ba83113f186")
+        );
+        assert_eq!(
+            outline.entities,
+            vec![uuid("b6cc74e0-1c11-4876-8949-1408736a9613")]
+        );
+        assert_eq!(outline.references[0].line, 5);
+        let (offset, component) = outline.ids[3];
+        assert_eq!(
+            component,
+            Some(uuid("f5780013-bae4-49f0-ac0e-a108ff52fec0"))
+        );
+        assert_eq!(line_at(BROKEN_REFERENCES, offset), 13);
+    }
+
+    #[test]
+    fn outline_reports_invalid_uuids_with_line() {
+        let e = outline("Prefab(\n    id: \"not a uuid\",\n    objects: [],\n)").unwrap_err();
+
+        assert_eq!(e.line, 2);
+        assert!(e.message.contains("not a uuid"));
+    }
+
+    #[test]
+    fn reports_unknown_components_and_missing_prefabs() {
+        let diagnostics = validate(&[("a.prefab", BROKEN_REFERENCES)]);
+
+        assert!(diagnostics.contains(&PrefabDiagnostic {
+            path: "a.prefab".into(),
+            line: Some(13),
+            kind: PrefabDiagnosticKind::UnknownComponent(uuid(
+                "f5780013-bae4-49f0-ac0e-a108ff52fec0"
+            )),
+        }));
+        assert!(diagnostics.contains(&PrefabDiagnostic {
+            path: "a.prefab".into(),
+            line: Some(5),
+            kind: PrefabDiagnosticKind::MissingPrefab(uuid("8b10e4fb-5a25-40b0-9ddd-a28bc842f834")),
+        }));
+    }
+
+    #[test]
+    fn reports_syntax_errors_with_line() {
+        let diagnostics = validate(&[(
+            "a.prefab",
+            "Prefab(\n    id: \"a1e04f08-8bdd-403a-baa2-1ba83113f186\",\n    objects: [\n        Entity((\n    ],\n)",
+        )]);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, Some(5));
+        assert!(matches!(
+            diagnostics[0].kind,
+            PrefabDiagnosticKind::Syntax(_)
+        ));
+    }
+
+    #[test]
+    fn reports_cycles_once() {
+        let a = r#"Prefab(id: "00000000-0000-0000-0000-00000000000a", objects: [
+            PrefabRef((prefab_id: "00000000-0000-0000-0000-00000000000b", entity_overrides: [])),
+        ])"#;
+        let b = r#"Prefab(id: "00000000-0000-0000-0000-00000000000b", objects: [
+            PrefabRef((prefab_id: "00000000-0000-0000-0000-00000000000a", entity_overrides: [])),
+        ])"#;
+
+        let diagnostics = validate(&[("a.prefab", a), ("b.prefab", b)]);
+
+        let a_id = uuid("00000000-0000-0000-0000-00000000000a");
+        let b_id = uuid("00000000-0000-0000-0000-00000000000b");
+        assert_eq!(
+            diagnostics,
+            vec![PrefabDiagnostic {
+                path: "a.prefab".into(),
+                line: Some(2),
+                kind: PrefabDiagnosticKind::Cycle(vec![a_id, b_id, a_id]),
+            }]
+        );
+    }
+}
@@ -71,6 +71,14 @@ impl LoadNotifier {
         }
     }
 
+    pub fn has_tracker(&self) -> bool {
+        self.tracker.is_some()
+    }
+
+    pub fn set_tracker(&mut self, tracker: Box<dyn Tracker>) {
+        self.tracker = Some(tracker);
+    }
+
     /// Signals that this load operation has completed successfully.
     pub fn complete(self) {
         if let Some(asset_load_op) = self.asset_load_op {
@@ -120,6 +128,8 @@ pub struct ProcessingQueue<T> {
     pub(crate) processed: Arc<SegQueue<Processed<T>>>,
     requeue: Mutex<Vec<Processed<T>>>,
     pub(crate) changed: SegQueue<LoadHandle>,
+    /// Trackers waiting for assets requested through `Loader::load_with_progress`.
+    trackers: Mutex<Vec<(LoadHandle, Box<dyn Tracker>)>>,
 }
 
 impl<T> Default for ProcessingQueue<T> {
@@ -128,6 +138,7 @@ impl<T> Default for ProcessingQueue<T> {
             processed: Arc::new(SegQueue::new()),
             requeue: Mutex::new(Vec::new()),
             changed: SegQueue::new(),
+            trackers: Mutex::new(Vec::new()),
         }
     }
 }
@@ -171,6 +182,14 @@ impl<T> ProcessingQueue<T> {
         self.changed.push(handle);
     }
 
+    /// Reports the outcome of processing the asset of `handle` to `tracker`.
+    pub(crate) fn track(&self, handle: LoadHandle, tracker: Box<dyn Tracker>) {
+        self.trackers
+            .lock()
+            .expect("The mutex of `trackers` in `ProcessingQueue` was poisoned")
+            .push((handle, tracker));
+    }
+
     pub(crate) fn enqueue_from_data(
         &self,
         handle: LoadHandle,
@@ -196,14 +215,27 @@ impl<T> ProcessingQueue<T> {
             .requeue
             .get_mut()
             .expect("The mutex of `requeue` in `AssetStorage` was poisoned");
+        let trackers = self
+            .trackers
+            .get_mut()
+            .expect("The mutex of `trackers` in `ProcessingQueue` was poisoned");
         while let Some(Processed {
             data,
             handle,
-            load_notifier,
+            mut load_notifier,
             version,
             commit,
         }) = self.processed.pop()
         {
+            if !load_notifier.has_tracker() {
+                if let Some(index) = trackers
+                    .iter()
+                    .position(|(tracked, _)| storage.resolve(*tracked) == Some(handle))
+                {
+                    load_notifier.set_tracker(trackers.swap_remove(index).1);
+                }
+            }
+
             let f = &mut f;
 
             let asset = match data.and_then(|d| f(d, storage, &handle)) {
@@ -233,6 +265,18 @@ impl<T> ProcessingQueue<T> {
             }
         }
 
+        // Assets which were already loaded when they were requested won't be processed again.
+        for (tracked, tracker) in std::mem::take(trackers) {
+            let loaded = storage.resolve(tracked).map_or(false, |handle| {
+                storage.contains(handle) && requeue.iter().all(|p| p.handle != handle)
+            });
+            if loaded {
+                tracker.success();
+            } else {
+                trackers.push((tracked, tracker));
+            }
+        }
+
         for p in requeue.drain(..) {
             self.processed.push(p);
         }
@@ -6,7 +6,7 @@ use distill::importer::{
 use serde::{Deserialize, Serialize};
 use type_uuid::TypeUuid;
 
-use crate::{AssetUuid, Format};
+use crate::{import_defaults::ImportDefaultsFn, AssetUuid, Format};
 
 /// A simple state for Importer to retain the same UUID between imports
 /// for all single-asset source files
@@ -85,6 +85,10 @@ pub struct SourceFileImporter {
     pub extension: &'static str,
     /// closure that creates Importer for given Format
     pub instantiator: fn() -> Box<dyn BoxedImporter>,
+    /// Merges folder defaults into the importer options, see [`merge_import_defaults`].
+    ///
+    /// [`merge_import_defaults`]: crate::merge_import_defaults
+    pub defaults: ImportDefaultsFn,
 }
 inventory::collect!(SourceFileImporter);
 
@@ -100,7 +104,9 @@ pub fn get_source_importers(
 /// Associates the given file extension with a `Format` implementation
 ///
 /// The `AssetDaemon` will automatically re-import the asset when a file of that format is created
-/// or modified.
+/// or modified. Its options can be set for a whole folder, see [`import_defaults`].
+///
+/// [`import_defaults`]: crate::import_defaults
 ///
 /// # Parameters
 ///
@@ -143,6 +149,7 @@ macro_rules! register_importer {
             $crate::SourceFileImporter {
                 extension: $ext,
                 instantiator: || Box::new($crate::SimpleImporter::from(<$format as Default>::default())),
+                defaults: $crate::merge_import_defaults::<$format>,
             }
         }
     };
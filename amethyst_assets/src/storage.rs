@@ -87,25 +87,22 @@ impl<A> AssetStorage<A> {
 
     /// returns true when asset is loaded for this handle
     pub fn contains(&self, load_handle: LoadHandle) -> bool {
-        let load_handle = if load_handle.is_indirect() {
-            if let Some(handle) = self.indirection_table.resolve(load_handle) {
-                handle
-            } else {
-                return false;
-            }
+        self.resolve(load_handle)
+            .map_or(false, |load_handle| self.assets.contains_key(&load_handle))
+    }
+
+    /// Resolves an indirect handle, such as one returned by `Loader::load`, to the handle the
+    /// asset is stored under.
+    pub(crate) fn resolve(&self, load_handle: LoadHandle) -> Option<LoadHandle> {
+        if load_handle.is_indirect() {
+            self.indirection_table.resolve(load_handle)
         } else {
-            load_handle
-        };
-        self.assets.contains_key(&load_handle)
+            Some(load_handle)
+        }
     }
 
     fn get_asset_state(&self, load_handle: LoadHandle) -> Option<&AssetState<A>> {
-        let load_handle = if load_handle.is_indirect() {
-            self.indirection_table.resolve(load_handle)?
-        } else {
-            load_handle
-        };
-        self.assets.get(&load_handle)
+        self.assets.get(&self.resolve(load_handle)?)
     }
 
     /// Returns the asset for the given load handle, or `None` if has not completed loading.
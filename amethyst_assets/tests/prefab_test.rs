@@ -1,7 +1,14 @@
-use std::time::{Duration, Instant};
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
 use amethyst_assets::{
-    prefab::{register_component_type, Prefab},
+    prefab::{
+        register_component_type, spawn_prefab, spawn_prefab_batch, Prefab, PrefabEntity,
+        PrefabOverrides, PrefabSaver,
+    },
     AssetHandle, AssetStorage, DefaultLoader, Handle, LoadStatus, Loader,
 };
 use amethyst_core::ecs::{world::ComponentError, Dispatcher, Entity, IntoQuery, Resources, World};
@@ -133,6 +140,239 @@ fn a_prefab_with_dependencies_is_applied_to_an_entity() {
     });
 }
 
+#[test]
+#[serial]
+fn prefabs_are_spawned_with_overrides() {
+    common::run_test(|dispatcher, world, resources| {
+        let prefab_handle: Handle<Prefab> = {
+            let loader = resources.get::<DefaultLoader>().expect("Missing loader");
+            loader.load("test_provided_component.prefab")
+        };
+
+        execute_dispatcher_until_loaded(dispatcher, world, resources, prefab_handle.clone());
+
+        let second_entity = *uuid::Uuid::parse_str("82e18eda-a3d1-43a5-b169-073a915a0f44")
+            .unwrap()
+            .as_bytes();
+        let prefab_position = Position2D { x: 100, y: 100 };
+        let overrides: Vec<PrefabOverrides> = (0..3)
+            .map(|x| {
+                PrefabOverrides::new().with_diff(
+                    PrefabEntity::Id(second_entity),
+                    &prefab_position,
+                    &Position2D { x, y: 100 },
+                )
+            })
+            .collect();
+
+        let spawned = spawn_prefab_batch(world, resources, &prefab_handle, &overrides)
+            .expect("Could not spawn prefabs");
+
+        assert_eq!(spawned.len(), 3);
+        for (x, instance) in spawned.iter().enumerate() {
+            assert_eq!(instance.entities().count(), 2);
+
+            let entity = instance.entity(&second_entity).unwrap();
+            let entry = world.entry(entity).unwrap();
+            assert_eq!(
+                *entry.get_component::<Position2D>().unwrap(),
+                Position2D {
+                    x: x as i32,
+                    y: 100
+                }
+            );
+
+            let root = world.entry(instance.root).unwrap();
+            assert_eq!(
+                *root.get_component::<Position2D>().unwrap(),
+                prefab_position
+            );
+            assert!(root.get_component::<Handle<Prefab>>().is_ok());
+        }
+    });
+}
+
+#[test]
+#[serial]
+fn a_failed_spawn_batch_leaves_no_entities() {
+    common::run_test(|dispatcher, world, resources| {
+        let prefab_handle: Handle<Prefab> = {
+            let loader = resources.get::<DefaultLoader>().expect("Missing loader");
+            loader.load("test_provided_component.prefab")
+        };
+
+        execute_dispatcher_until_loaded(dispatcher, world, resources, prefab_handle.clone());
+
+        let entity_count = |world: &World| <Entity>::query().iter(world).count();
+        let before = entity_count(world);
+        let overrides = vec![
+            PrefabOverrides::new(),
+            PrefabOverrides::new().with_diff(
+                PrefabEntity::Id([0; 16]),
+                &Position2D { x: 100, y: 100 },
+                &Position2D { x: 0, y: 0 },
+            ),
+        ];
+
+        assert!(spawn_prefab_batch(world, resources, &prefab_handle, &overrides).is_err());
+        assert_eq!(entity_count(world), before);
+    });
+}
+
+#[test]
+#[serial]
+fn live_prefab_edits_are_saved_as_overrides() {
+    common::run_test(|dispatcher, world, resources| {
+        let prefab_handle: Handle<Prefab> = {
+            let loader = resources.get::<DefaultLoader>().expect("Missing loader");
+            loader.load("test_provided_component.prefab")
+        };
+
+        execute_dispatcher_until_loaded(dispatcher, world, resources, prefab_handle.clone());
+
+        let second_entity = *uuid::Uuid::parse_str("82e18eda-a3d1-43a5-b169-073a915a0f44")
+            .unwrap()
+            .as_bytes();
+        let spawned = spawn_prefab(world, resources, &prefab_handle, &PrefabOverrides::new())
+            .expect("Could not spawn prefab");
+        let saver = PrefabSaver::new().with_component::<Position2D>();
+
+        let edits = saver
+            .capture(world, resources, spawned.root)
+            .expect("Could not capture prefab");
+        assert!(edits.is_empty());
+
+        world
+            .entry(spawned.entity(&second_entity).unwrap())
+            .unwrap()
+            .get_component_mut::<Position2D>()
+            .unwrap()
+            .x = 42;
+
+        let edits = saver
+            .capture(world, resources, spawned.root)
+            .expect("Could not capture prefab");
+        let changes: Vec<_> = edits.changes().collect();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].0, second_entity);
+
+        let overrides = edits.to_overrides();
+        assert!(overrides.contains("82e18eda-a3d1-43a5-b169-073a915a0f44"));
+        assert!(overrides.contains("f5780013-bae4-49f0-ac0e-a108ff52fec0"));
+        assert!(overrides.contains("Value(42)"));
+        assert!(edits.to_prefab().contains("(x:42,y:100)"));
+
+        let source = fs::read_to_string("tests/assets/test_provided_component.prefab")
+            .expect("Could not read source prefab");
+        let saved = edits
+            .to_source_prefab(&source)
+            .expect("Could not save source prefab");
+        assert_eq!(saved.matches("data: (x:42,y:100),").count(), 1);
+        assert!(saved.contains("// Component AssetTypeId"));
+    });
+}
+
+#[test]
+#[serial]
+fn saved_prefab_edits_load_back() {
+    common::run_test(|dispatcher, world, resources| {
+        let prefab_handle: Handle<Prefab> = {
+            let loader = resources.get::<DefaultLoader>().expect("Missing loader");
+            loader.load("test_provided_component.prefab")
+        };
+
+        execute_dispatcher_until_loaded(dispatcher, world, resources, prefab_handle.clone());
+
+        let second_entity = *uuid::Uuid::parse_str("82e18eda-a3d1-43a5-b169-073a915a0f44")
+            .unwrap()
+            .as_bytes();
+        let spawned = spawn_prefab(world, resources, &prefab_handle, &PrefabOverrides::new())
+            .expect("Could not spawn prefab");
+        world
+            .entry(spawned.entity(&second_entity).unwrap())
+            .unwrap()
+            .get_component_mut::<Position2D>()
+            .unwrap()
+            .x = 42;
+
+        let edits = PrefabSaver::new()
+            .with_component::<Position2D>()
+            .capture(world, resources, spawned.root)
+            .expect("Could not capture prefab");
+        let _saved = SavedFiles(vec![
+            PathBuf::from("tests/assets/saved_edits.prefab"),
+            PathBuf::from("tests/assets/saved_overrides.prefab"),
+        ]);
+        edits
+            .write_prefab("tests/assets/saved_edits.prefab")
+            .expect("Could not write prefab");
+        edits
+            .write_overrides("tests/assets/saved_overrides.prefab")
+            .expect("Could not write overrides");
+
+        for path in &["saved_edits.prefab", "saved_overrides.prefab"] {
+            let saved_handle: Handle<Prefab> = {
+                let loader = resources.get::<DefaultLoader>().expect("Missing loader");
+                loader.load(path)
+            };
+            execute_dispatcher_until_imported(dispatcher, world, resources, &saved_handle);
+
+            let instance = spawn_prefab(world, resources, &saved_handle, &PrefabOverrides::new())
+                .expect("Could not spawn saved prefab");
+            let mut positions: Vec<(i32, i32)> = instance
+                .entities()
+                .filter_map(|entity| {
+                    let entry = world.entry(entity)?;
+                    let position = entry.get_component::<Position2D>().ok()?;
+                    Some((position.x, position.y))
+                })
+                .collect();
+            positions.sort_unstable();
+            assert_eq!(positions, vec![(42, 100), (100, 100)], "{}", path);
+        }
+    });
+}
+
+/// Removes files written by a test, together with the metadata the asset daemon adds.
+struct SavedFiles(Vec<PathBuf>);
+
+impl Drop for SavedFiles {
+    fn drop(&mut self) {
+        for path in &self.0 {
+            let _ = fs::remove_file(path);
+            let _ = fs::remove_file(path.with_extension("prefab.meta"));
+        }
+    }
+}
+
+/// Waits for an asset written by the test, which the asset daemon may not have imported yet.
+fn execute_dispatcher_until_imported(
+    dispatcher: &mut Dispatcher,
+    world: &mut World,
+    resources: &mut Resources,
+    prefab_handle: &Handle<Prefab>,
+) {
+    let timeout = Instant::now() + Duration::from_secs(20);
+    loop {
+        assert!(
+            Instant::now() < timeout,
+            "Timed out waiting for prefab to be imported"
+        );
+        {
+            let loader = resources
+                .get_mut::<DefaultLoader>()
+                .expect("Missing loader");
+            match loader.get_load_status_handle(prefab_handle.load_handle()) {
+                LoadStatus::Loaded => break,
+                LoadStatus::Error(_) => panic!("Could not load saved prefab"),
+                _ => (),
+            }
+        }
+        dispatcher.execute(world, resources);
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
 fn execute_dispatcher_until_prefab_is_applied(
     dispatcher: &mut Dispatcher,
     world: &mut World,
@@ -0,0 +1,256 @@
+//! Default configuration files with documentation comments.
+
+use std::{collections::HashMap, fs, path::Path};
+
+use serde::Serialize;
+
+use crate::{merge::join, ConfigError, ConfigFormat};
+
+/// Documentation of the fields of a configuration type, written as comments by
+/// [`default_config_string`].
+///
+/// Usually derived with `#[derive(ConfigDocs)]` from `amethyst_derive`, which uses the doc
+/// comments of the type and its fields. Fields marked with `#[config_docs(nested)]` include the
+/// documentation of their own type.
+pub trait ConfigDocs {
+    /// Documentation of the whole configuration, written at the top of the file.
+    #[must_use]
+    fn type_docs() -> &'static str {
+        ""
+    }
+
+    /// Documentation of each field with its path, such as `window.title`.
+    fn field_docs() -> Vec<(String, &'static str)>;
+}
+
+/// Returns the default value of `T` in `format`, with the documentation of each field as a
+/// comment above it.
+///
+/// JSON has no comments, so JSON files are written without documentation. Binary files can't be
+/// documented and fail with [`ConfigError::Serializer`].
+pub fn default_config_string<T>(format: ConfigFormat) -> Result<String, ConfigError>
+where
+    T: Default + Serialize + ConfigDocs,
+{
+    let config = T::default();
+    let (text, comment) = match format {
+        ConfigFormat::Ron => {
+            (
+                ron::ser::to_string_pretty(&config, ron::ser::PrettyConfig::default())
+                    .map_err(ConfigError::Serializer)?,
+                "//",
+            )
+        }
+        #[cfg(feature = "json")]
+        ConfigFormat::Json => return Ok(serde_json::to_string_pretty(&config)?),
+        #[cfg(feature = "binary")]
+        ConfigFormat::Binary => {
+            return Err(ConfigError::Serializer(serde::ser::Error::custom(
+                "binary configuration files can't be documented",
+            )))
+        }
+        #[cfg(feature = "toml")]
+        ConfigFormat::Toml => {
+            (
+                toml::to_string_pretty(&toml::Value::try_from(&config)?)?,
+                "#",
+            )
+        }
+        #[cfg(feature = "yaml")]
+        ConfigFormat::Yaml => (serde_yaml::to_string(&config)?, "#"),
+    };
+
+    let docs: HashMap<String, &str> = T::field_docs().into_iter().collect();
+    let mut out = String::new();
+    write_comment(&mut out, "", comment, T::type_docs());
+
+    // Fields of the structs enclosing the current line, with their indentation.
+    let mut parents: Vec<(usize, String)> = Vec::new();
+    // Path of the current TOML table.
+    let mut table = String::new();
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        let indent = &line[..line.len() - trimmed.len()];
+
+        let path = if comment == "#" && trimmed.starts_with('[') {
+            table = trimmed.trim_matches(|c| c == '[' || c == ']').to_string();
+            Some(table.clone())
+        } else if let Some((key, value)) = split_key(trimmed) {
+            while parents
+                .last()
+                .map_or(false, |(parent, _)| *parent >= indent.len())
+            {
+                parents.pop();
+            }
+            let parent = parents.last().map_or(&table, |(_, path)| path);
+            let path = join(parent, key);
+            if value.is_empty() || value.ends_with(['(', '[', '{']) {
+                parents.push((indent.len(), path.clone()));
+            }
+            Some(path)
+        } else {
+            None
+        };
+
+        if let Some(docs) = path.and_then(|path| docs.get(&path)) {
+            write_comment(&mut out, indent, comment, docs);
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Writes the documented default value of `T` to `path`, the format is picked from the
+/// extension.
+///
+/// This is meant to be run from a command line flag of the game, so players and modders get an
+/// up to date description of every setting.
+///
+/// # Example
+///
+/// ```no_run
+/// use amethyst_config::{write_default_config, ConfigDocs};
+/// use serde::Serialize;
+///
+/// /// Audio settings.
+/// #[derive(Default, Serialize)]
+/// struct AudioConfig {
+///     /// Volume of all sounds, between 0 and 1.
+///     volume: f32,
+/// }
+///
+/// // Usually `#[derive(ConfigDocs)]`.
+/// impl ConfigDocs for AudioConfig {
+///     fn type_docs() -> &'static str {
+///         "Audio settings."
+///     }
+///
+///     fn field_docs() -> Vec<(String, &'static str)> {
+///         vec![("volume".into(), "Volume of all sounds, between 0 and 1.")]
+///     }
+/// }
+///
+/// if std::env::args().any(|arg| arg == "--write-default-config") {
+///     write_default_config::<AudioConfig, _>("config/audio.ron")
+///         .expect("Failed to write default configuration");
+///     return;
+/// }
+/// ```
+pub fn write_default_config<T, P>(path: P) -> Result<(), ConfigError>
+where
+    T: Default + Serialize + ConfigDocs,
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let content = default_config_string::<T>(ConfigFormat::from_path(path)?)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, content)?;
+    Ok(())
+}
+
+/// Splits a `key: value` or `key = value` line, where the key is a plain identifier.
+fn split_key(line: &str) -> Option<(&str, &str)> {
+    let end = line
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+        .unwrap_or(line.len());
+    let (key, rest) = line.split_at(end);
+    let value = rest
+        .strip_prefix(':')
+        .or_else(|| rest.strip_prefix(" ="))?
+        .trim();
+    if key.is_empty() {
+        None
+    } else {
+        Some((key, value))
+    }
+}
+
+fn write_comment(out: &mut String, indent: &str, comment: &str, docs: &str) {
+    for line in docs.lines() {
+        out.push_str(indent);
+        out.push_str(comment);
+        if !line.is_empty() {
+            out.push(' ');
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::Config;
+
+    #[derive(Debug, Default, PartialEq, Deserialize, Serialize)]
+    struct Window {
+        title: String,
+        fullscreen: bool,
+    }
+
+    #[derive(Debug, Default, PartialEq, Deserialize, Serialize)]
+    struct Game {
+        volume: f32,
+        window: Window,
+    }
+
+    impl ConfigDocs for Game {
+        fn type_docs() -> &'static str {
+            "Settings of the game."
+        }
+
+        fn field_docs() -> Vec<(String, &'static str)> {
+            vec![
+                ("volume".into(), "Volume of all sounds."),
+                ("window".into(), "The main window."),
+                (
+                    "window.fullscreen".into(),
+                    "Covers the whole screen.\n\nSlow on some systems.",
+                ),
+            ]
+        }
+    }
+
+    #[test]
+    fn default_ron_file_is_documented() {
+        let text = default_config_string::<Game>(ConfigFormat::Ron).unwrap();
+        assert_eq!(
+            text,
+            "// Settings of the game.
+(
+    // Volume of all sounds.
+    volume: 0,
+    // The main window.
+    window: (
+        title: \"\",
+        // Covers the whole screen.
+        //
+        // Slow on some systems.
+        fullscreen: false,
+    ),
+)
+"
+        );
+        assert_eq!(
+            Game::load_bytes_format(ConfigFormat::Ron, text.as_bytes()).unwrap(),
+            Game::default()
+        );
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn default_toml_file_is_documented() {
+        let text = default_config_string::<Game>(ConfigFormat::Toml).unwrap();
+        assert!(text.contains("# The main window.\n[window]\n"));
+        assert!(text.contains("# Slow on some systems.\nfullscreen = false\n"));
+        assert_eq!(
+            Game::load_bytes_format(ConfigFormat::Toml, text.as_bytes()).unwrap(),
+            Game::default()
+        );
+    }
+}
@@ -0,0 +1,632 @@
+//! Configuration merged from defaults, files, environment variables and the command line.
+
+use std::{
+    cell::RefCell,
+    env, fmt, fs,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    merge::{join, Filtered, Merge, Node},
+    read_file,
+    value::{from_value, to_value, ConfigValue},
+    ConfigError, Validate, ValidationErrors,
+};
+
+/// Where the value of a configuration field comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigLayer {
+    /// `Default::default()` of the configuration type.
+    Default,
+    /// A configuration file added with [`LayeredConfigBuilder::with_file`].
+    File(PathBuf),
+    /// The configuration file of the user.
+    UserFile(PathBuf),
+    /// The environment variable with this name.
+    Env(String),
+    /// The `--set key.path=value` command line argument with this value.
+    Arg(String),
+}
+
+impl fmt::Display for ConfigLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigLayer::Default => write!(f, "default"),
+            ConfigLayer::File(path) => write!(f, "file {}", path.display()),
+            ConfigLayer::UserFile(path) => write!(f, "user file {}", path.display()),
+            ConfigLayer::Env(name) => write!(f, "environment variable {}", name),
+            ConfigLayer::Arg(arg) => write!(f, "command line --set {}", arg),
+        }
+    }
+}
+
+/// Loads a configuration from several layers, each one overriding single fields of the ones
+/// before it:
+///
+/// 1. `Default::default()`,
+/// 2. the files added with [`with_file`], in order,
+/// 3. the user file in the platform configuration directory,
+/// 4. environment variables starting with a prefix, `PREFIX_WINDOW__TITLE` sets `window.title`,
+/// 5. `--set window.title=value` command line arguments.
+///
+/// Files only need to contain the fields they change, nested structs are merged field by field.
+/// Other values, such as lists, maps and enums, are replaced as a whole. Values from environment
+/// variables and the command line are written in RON, strings don't need quotes.
+///
+/// # Example
+///
+/// ```no_run
+/// use amethyst_config::LayeredConfigBuilder;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Clone, Default, Deserialize, Serialize)]
+/// struct GameConfig {
+///     volume: f32,
+///     fullscreen: bool,
+/// }
+///
+/// let config = LayeredConfigBuilder::<GameConfig>::new()
+///     .with_file("config/game.ron")
+///     .with_user_file("my_game", "game.ron")
+///     .with_env_prefix("MY_GAME")
+///     .with_args(std::env::args())
+///     .load()
+///     .expect("Failed to load configuration");
+/// println!("{}", config.report());
+///
+/// // Saves the volume to the user file, leaving the other layers alone.
+/// let mut changed = config.config().clone();
+/// changed.volume = 0.5;
+/// config
+///     .write_user(&changed)
+///     .expect("Failed to save configuration");
+/// ```
+///
+/// [`with_file`]: LayeredConfigBuilder::with_file
+#[derive(Debug)]
+pub struct LayeredConfigBuilder<T> {
+    files: Vec<PathBuf>,
+    user_file: Option<PathBuf>,
+    env_prefix: Option<String>,
+    args: Vec<String>,
+    marker: PhantomData<T>,
+}
+
+impl<T> LayeredConfigBuilder<T>
+where
+    T: Default + Serialize + for<'de> Deserialize<'de>,
+{
+    /// Creates a builder for a configuration with only the default layer.
+    #[must_use]
+    pub fn new() -> Self {
+        LayeredConfigBuilder {
+            files: Vec::new(),
+            user_file: None,
+            env_prefix: None,
+            args: Vec::new(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Adds a configuration file, which must exist. The format is picked from the extension.
+    #[must_use]
+    pub fn with_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.files.push(path.into());
+        self
+    }
+
+    /// Sets the user file to `file_name` in the `app_name` directory of the platform
+    /// configuration directory, `$XDG_CONFIG_HOME` or `~/.config` on Linux,
+    /// `~/Library/Application Support` on macOS and `%APPDATA%` on Windows.
+    ///
+    /// The user file is optional and is the only file written by
+    /// [`LayeredConfig::write_user`]. It is ignored if the platform has no configuration
+    /// directory.
+    #[must_use]
+    pub fn with_user_file(self, app_name: &str, file_name: &str) -> Self {
+        match user_config_dir() {
+            Some(dir) => self.with_user_file_at(dir.join(app_name).join(file_name)),
+            None => self,
+        }
+    }
+
+    /// Sets the path of the user file.
+    #[must_use]
+    pub fn with_user_file_at<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.user_file = Some(path.into());
+        self
+    }
+
+    /// Reads environment variables starting with `prefix` followed by an underscore. The rest of
+    /// the name is the lowercase field path with `__` between fields.
+    #[must_use]
+    pub fn with_env_prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.env_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Reads `--set key.path=value` and `--set=key.path=value` from the command line arguments.
+    /// Other arguments are ignored.
+    #[must_use]
+    pub fn with_args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut args = args.into_iter().map(Into::into);
+        while let Some(arg) = args.next() {
+            if arg == "--set" {
+                if let Some(value) = args.next() {
+                    self.args.push(value);
+                }
+            } else if let Some(value) = arg.strip_prefix("--set=") {
+                self.args.push(value.to_string());
+            }
+        }
+        self
+    }
+
+    /// Loads and merges all layers.
+    pub fn load(&self) -> Result<LayeredConfig<T>, ConfigError> {
+        let mut state = LoadState::new(T::default())?;
+
+        for path in &self.files {
+            state.file(path, &ConfigLayer::File(path.clone()))?;
+        }
+        let below_user = state.tree.clone();
+        if let Some(path) = &self.user_file {
+            if path.exists() {
+                state.file(path, &ConfigLayer::UserFile(path.clone()))?;
+            }
+        }
+        let user = state.tree.clone();
+
+        if let Some(prefix) = &self.env_prefix {
+            let prefix = format!("{}_", prefix);
+            let mut vars: Vec<_> = env::vars()
+                .filter(|(name, _)| name.starts_with(&prefix))
+                .collect();
+            vars.sort();
+            for (name, value) in vars {
+                let path: Vec<_> = name[prefix.len()..]
+                    .split("__")
+                    .map(str::to_lowercase)
+                    .collect();
+                state.assign(&path, value, ConfigLayer::Env(name))?;
+            }
+        }
+        for arg in &self.args {
+            let layer = ConfigLayer::Arg(arg.clone());
+            let (path, value) = arg.split_at(arg.find('=').ok_or_else(|| {
+                ConfigError::Override(layer.to_string(), "expected key.path=value".to_string())
+            })?);
+            let path: Vec<_> = path.split('.').map(str::to_string).collect();
+            state.assign(&path, value[1..].to_string(), layer)?;
+        }
+
+        Ok(LayeredConfig {
+            config: state.config,
+            tree: state.tree,
+            below_user,
+            user,
+            user_file: self.user_file.clone(),
+            records: state.records,
+        })
+    }
+}
+
+impl<T> LayeredConfigBuilder<T>
+where
+    T: Default + Serialize + for<'de> Deserialize<'de> + Validate,
+{
+    /// Loads and merges all layers, then checks the result with [`Validate::validate`].
+    ///
+    /// Fails with [`ConfigError::Validation`] listing every invalid value with the layer it
+    /// comes from.
+    pub fn load_validated(&self) -> Result<LayeredConfig<T>, ConfigError> {
+        let config = self.load()?;
+        ValidationErrors::run(&config.config, |field| Some(config.origin(field).clone()))?;
+        Ok(config)
+    }
+}
+
+struct LoadState<T> {
+    config: T,
+    tree: ConfigValue,
+    records: Vec<(String, ConfigLayer)>,
+}
+
+impl<T> LoadState<T>
+where
+    T: Serialize + for<'de> Deserialize<'de>,
+{
+    fn new(config: T) -> Result<Self, ConfigError> {
+        Ok(LoadState {
+            tree: to_value(&config)?,
+            config,
+            records: Vec::new(),
+        })
+    }
+
+    fn merge<'de, D: serde::Deserializer<'de>>(
+        &mut self,
+        de: D,
+        layer: &ConfigLayer,
+    ) -> Result<(), D::Error> {
+        let set = RefCell::new(Vec::new());
+        self.config = T::deserialize(Merge {
+            de,
+            base: Some(&self.tree),
+            path: String::new(),
+            set: &set,
+        })?;
+        self.records.extend(
+            set.into_inner()
+                .into_iter()
+                .map(|path| (path, layer.clone())),
+        );
+        Ok(())
+    }
+
+    fn file(&mut self, path: &Path, layer: &ConfigLayer) -> Result<(), ConfigError> {
+        let content = read_file(path)?;
+        match path.extension().and_then(std::ffi::OsStr::to_str) {
+            Some("ron") => {
+                ron::de::Deserializer::from_bytes(&content)
+                    .and_then(|mut de| {
+                        self.merge(&mut de, layer)?;
+                        de.end()
+                    })
+                    .map_err(|e| ConfigError::FileParser(e, path.to_path_buf()))?
+            }
+            #[cfg(feature = "json")]
+            Some("json") => {
+                let mut de = serde_json::de::Deserializer::from_slice(&content);
+                self.merge(&mut de, layer)?;
+                de.end()?;
+            }
+            #[cfg(feature = "toml")]
+            Some("toml") => {
+                let content = std::str::from_utf8(&content).map_err(|e| {
+                    ConfigError::File(std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+                })?;
+                self.merge(&mut toml::Deserializer::new(content), layer)?;
+            }
+            #[cfg(feature = "yaml")]
+            Some("yaml" | "yml") => {
+                self.merge(serde_yaml::Deserializer::from_slice(&content), layer)?;
+            }
+            _ => return Err(ConfigError::Extension(path.to_path_buf())),
+        }
+        self.tree = to_value(&self.config)?;
+        Ok(())
+    }
+
+    fn assign(
+        &mut self,
+        path: &[String],
+        value: String,
+        layer: ConfigLayer,
+    ) -> Result<(), ConfigError> {
+        let node = Node::new(path, value);
+        self.merge(&node, &layer)
+            .map_err(|e| ConfigError::Override(layer.to_string(), e.to_string()))?;
+        // Only the assigned value counts as set, not the structs leading to it.
+        self.records.retain(|(_, recorded)| recorded != &layer);
+        self.records.push((path.join("."), layer));
+        self.tree = to_value(&self.config)?;
+        Ok(())
+    }
+}
+
+/// A configuration loaded by [`LayeredConfigBuilder`], with the layer each value comes from.
+#[derive(Debug)]
+pub struct LayeredConfig<T> {
+    config: T,
+    tree: ConfigValue,
+    below_user: ConfigValue,
+    user: ConfigValue,
+    user_file: Option<PathBuf>,
+    /// Paths set by each layer, in the order the layers were applied.
+    records: Vec<(String, ConfigLayer)>,
+}
+
+impl<T> LayeredConfig<T>
+where
+    T: Serialize + for<'de> Deserialize<'de>,
+{
+    /// Returns the merged configuration.
+    #[must_use]
+    pub fn config(&self) -> &T {
+        &self.config
+    }
+
+    /// Returns the merged configuration.
+    #[must_use]
+    pub fn into_inner(self) -> T {
+        self.config
+    }
+
+    /// Returns the path of the user file, if any.
+    #[must_use]
+    pub fn user_file(&self) -> Option<&Path> {
+        self.user_file.as_deref()
+    }
+
+    /// Returns the layer which set the value at `path`, such as `window.title`.
+    #[must_use]
+    pub fn origin(&self, path: &str) -> &ConfigLayer {
+        self.records
+            .iter()
+            .rev()
+            .find(|(set, _)| contains(set, path) || contains(path, set))
+            .map_or(&ConfigLayer::Default, |(_, layer)| layer)
+    }
+
+    /// Returns every value of the configuration with its path and the layer it comes from.
+    #[must_use]
+    pub fn provenance(&self) -> Vec<(String, &ConfigValue, &ConfigLayer)> {
+        let mut leaves = Vec::new();
+        collect_leaves(&self.tree, String::new(), &mut leaves);
+        leaves
+            .into_iter()
+            .map(|(path, value)| {
+                let layer = self.origin(&path);
+                (path, value, layer)
+            })
+            .collect()
+    }
+
+    /// Describes where each value comes from, one `path = value (layer)` line per value.
+    #[must_use]
+    pub fn report(&self) -> String {
+        self.provenance()
+            .into_iter()
+            .map(|(path, value, layer)| format!("{} = {} ({})\n", path, value, layer))
+            .collect()
+    }
+
+    /// Saves `config` to the user file.
+    ///
+    /// Only the fields differing from the layers below the user file are written. Values set by
+    /// environment variables or the command line are not saved unless they have been changed.
+    pub fn write_user(&self, config: &T) -> Result<(), ConfigError> {
+        let path = self
+            .user_file
+            .as_ref()
+            .ok_or_else(|| ConfigError::Override("user file".to_string(), "not set".to_string()))?;
+
+        let mut tree = to_value(config)?;
+        for (set, layer) in &self.records {
+            if let ConfigLayer::Env(_) | ConfigLayer::Arg(_) = layer {
+                if tree.get_path(set) == self.tree.get_path(set) {
+                    if let (Some(value), Some(user)) =
+                        (tree.get_path_mut(set), self.user.get_path(set))
+                    {
+                        *value = user.clone();
+                    }
+                }
+            }
+        }
+
+        let mut leaves = Vec::new();
+        collect_leaves(&tree, String::new(), &mut leaves);
+        let keep: Vec<String> = leaves
+            .into_iter()
+            .filter(|(leaf, value)| self.below_user.get_path(leaf) != Some(value))
+            .map(|(leaf, _)| leaf)
+            .collect();
+        let config: T = from_value(tree)?;
+        let filtered = Filtered {
+            value: &config,
+            path: String::new(),
+            keep: &keep,
+        };
+
+        let content = match path.extension().and_then(std::ffi::OsStr::to_str) {
+            Some("ron") => {
+                ron::ser::to_string_pretty(&filtered, ron::ser::PrettyConfig::default())
+                    .map_err(ConfigError::Serializer)?
+            }
+            #[cfg(feature = "json")]
+            Some("json") => serde_json::to_string_pretty(&filtered)?,
+            #[cfg(feature = "toml")]
+            Some("toml") => toml::to_string_pretty(&toml::Value::try_from(&filtered)?)?,
+            #[cfg(feature = "yaml")]
+            Some("yaml" | "yml") => serde_yaml::to_string(&filtered)?,
+            _ => return Err(ConfigError::Extension(path.clone())),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, content)?;
+        Ok(())
+    }
+}
+
+/// Returns `true` if `path` is `parent` or a field inside it.
+fn contains(parent: &str, path: &str) -> bool {
+    path.starts_with(parent)
+        && (path.len() == parent.len()
+            || parent.is_empty()
+            || path[parent.len()..].starts_with('.'))
+}
+
+fn collect_leaves<'a>(
+    value: &'a ConfigValue,
+    path: String,
+    leaves: &mut Vec<(String, &'a ConfigValue)>,
+) {
+    match value {
+        ConfigValue::Map(map) if !map.is_empty() => {
+            for (key, value) in map {
+                collect_leaves(value, join(&path, key), leaves);
+            }
+        }
+        _ => leaves.push((path, value)),
+    }
+}
+
+fn user_config_dir() -> Option<PathBuf> {
+    if cfg!(target_os = "windows") {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Debug, Default, PartialEq, Deserialize, Serialize)]
+    struct Window {
+        title: String,
+        size: (u32, u32),
+        fullscreen: bool,
+    }
+
+    #[derive(Debug, Default, PartialEq, Deserialize, Serialize)]
+    struct Game {
+        window: Window,
+        volume: f32,
+        max_fps: Option<u32>,
+    }
+
+    impl Validate for Game {
+        fn validate(&self, errors: &mut ValidationErrors) {
+            errors.range("volume", &self.volume, 0.0..=1.0);
+            errors.check(!self.window.title.is_empty(), "window.title", "is empty");
+        }
+    }
+
+    fn base() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/layered_base.ron")
+    }
+
+    #[test]
+    fn layers_are_merged_field_by_field() {
+        let user = env::temp_dir().join("amethyst_config_layered_merge.ron");
+        fs::write(&user, "(window: (fullscreen: true))").unwrap();
+        env::set_var("LAYERED_MERGE_TEST_VOLUME", "0.25");
+
+        let config = LayeredConfigBuilder::<Game>::new()
+            .with_file(base())
+            .with_user_file_at(&user)
+            .with_env_prefix("LAYERED_MERGE_TEST")
+            .with_args(vec![
+                "game",
+                "--set",
+                "window.title=Tweaked game",
+                "--set=max_fps=60",
+            ])
+            .load()
+            .unwrap();
+
+        assert_eq!(
+            *config.config(),
+            Game {
+                window: Window {
+                    title: "Tweaked game".to_string(),
+                    size: (1280, 720),
+                    fullscreen: true,
+                },
+                volume: 0.25,
+                max_fps: Some(60),
+            }
+        );
+        assert_eq!(*config.origin("window.size"), ConfigLayer::File(base()));
+        assert_eq!(
+            *config.origin("window.fullscreen"),
+            ConfigLayer::UserFile(user)
+        );
+        assert_eq!(
+            *config.origin("volume"),
+            ConfigLayer::Env("LAYERED_MERGE_TEST_VOLUME".to_string())
+        );
+        assert_eq!(
+            *config.origin("window.title"),
+            ConfigLayer::Arg("window.title=Tweaked game".to_string())
+        );
+        assert!(config
+            .report()
+            .contains("max_fps = 60 (command line --set max_fps=60)"));
+    }
+
+    #[test]
+    fn only_user_changes_are_written() {
+        let user = env::temp_dir().join("amethyst_config_layered_write.ron");
+        let _ = fs::remove_file(&user);
+
+        let config = LayeredConfigBuilder::<Game>::new()
+            .with_file(base())
+            .with_user_file_at(&user)
+            .with_args(vec!["--set", "volume=0.1"])
+            .load()
+            .unwrap();
+        let mut changed = config.into_inner();
+        changed.window.fullscreen = true;
+
+        let config = LayeredConfigBuilder::<Game>::new()
+            .with_file(base())
+            .with_user_file_at(&user)
+            .with_args(vec!["--set", "volume=0.1"])
+            .load()
+            .unwrap();
+        config.write_user(&changed).unwrap();
+
+        let written: ron::Value = ron::de::from_str(&fs::read_to_string(&user).unwrap()).unwrap();
+        let expected: ron::Value = ron::de::from_str("(window: (fullscreen: true))").unwrap();
+        assert_eq!(written, expected);
+    }
+
+    #[test]
+    fn invalid_override_names_its_source() {
+        let result = LayeredConfigBuilder::<Game>::new()
+            .with_args(vec!["--set", "volume=loud"])
+            .load();
+
+        match result {
+            Err(ConfigError::Override(source, _)) => {
+                assert_eq!(source, "command line --set volume=loud");
+            }
+            _ => panic!("{:?}", result.map(|_| ())),
+        }
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_layers_are_validated_with_their_origin() {
+        let user = env::temp_dir().join("amethyst_config_layered_validated.yaml");
+        fs::write(&user, "window:\n  title: \"\"\n").unwrap();
+
+        let errors = match LayeredConfigBuilder::<Game>::new()
+            .with_file(base())
+            .with_user_file_at(&user)
+            .with_args(vec!["--set", "volume=2.5"])
+            .load_validated()
+        {
+            Err(ConfigError::Validation(errors)) => errors,
+            other => panic!("Expected validation errors, got {:?}", other),
+        };
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            errors[0].layer,
+            Some(ConfigLayer::Arg("volume=2.5".to_string()))
+        );
+        assert_eq!(errors[1].field, "window.title");
+        assert_eq!(errors[1].file(), Some(user.as_path()));
+    }
+}
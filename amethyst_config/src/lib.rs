@@ -15,6 +15,9 @@
 #![warn(clippy::pedantic)]
 #![allow(clippy::new_without_default)]
 
+#[cfg(feature = "toml")]
+extern crate dep_toml as toml;
+
 use std::{
     error::Error,
     fmt, io,
@@ -27,6 +30,21 @@ use ron::{self, error::Error as RonError};
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "json")]
 use serde_json::error::Error as SerJsonError;
+#[cfg(feature = "yaml")]
+use serde_yaml::Error as YamlError;
+
+pub use crate::{
+    docs::{default_config_string, write_default_config, ConfigDocs},
+    layered::{ConfigLayer, LayeredConfig, LayeredConfigBuilder},
+    validate::{Validate, ValidationError, ValidationErrors},
+    value::ConfigValue,
+};
+
+mod docs;
+mod layered;
+mod merge;
+mod validate;
+mod value;
 
 /// Error related to anything that manages/creates configurations as well as
 /// "workspace"-related things.
@@ -42,16 +60,33 @@ pub enum ConfigError {
     Serializer(ron::Error),
     /// Related to the path of the file.
     Extension(PathBuf),
+    /// An invalid environment variable or command line override of a layered configuration,
+    /// with the layer it comes from.
+    Override(String, String),
+    /// A configuration which could not be converted to or from a [`ConfigValue`] to merge its
+    /// layers.
+    Value(String),
     /// Forward to serde json's errors
     #[cfg(feature = "json")]
     SerdeJsonError(SerJsonError),
     /// Forward to bincode's errors
     #[cfg(feature = "binary")]
     BincodeError(BincodeError),
+    /// Forward to the errors of the toml deserializer
+    #[cfg(feature = "toml")]
+    TomlDeError(toml::de::Error),
+    /// Forward to the errors of the toml serializer
+    #[cfg(feature = "toml")]
+    TomlSerError(toml::ser::Error),
+    /// Forward to serde yaml's errors
+    #[cfg(feature = "yaml")]
+    YamlError(YamlError),
+    /// All the errors found by [`Validate::validate`].
+    Validation(Vec<ValidationError>),
 }
 
 /// Config file format for serde
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ConfigFormat {
     /// Rusty Object Notation files (.ron), default
     Ron,
@@ -61,6 +96,39 @@ pub enum ConfigFormat {
     /// Binary files (.bin), encoded with bincode, requires enabling `binary` feature
     #[cfg(feature = "binary")]
     Binary,
+    /// Tom's Obvious, Minimal Language files (.toml), requires enabling `toml` feature
+    #[cfg(feature = "toml")]
+    Toml,
+    /// YAML Ain't Markup Language files (.yaml or .yml), requires enabling `yaml` feature
+    #[cfg(feature = "yaml")]
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// Returns the format of a file with the given extension.
+    #[must_use]
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "ron" => Some(ConfigFormat::Ron),
+            #[cfg(feature = "json")]
+            "json" => Some(ConfigFormat::Json),
+            #[cfg(feature = "binary")]
+            "bin" => Some(ConfigFormat::Binary),
+            #[cfg(feature = "toml")]
+            "toml" => Some(ConfigFormat::Toml),
+            #[cfg(feature = "yaml")]
+            "yaml" | "yml" => Some(ConfigFormat::Yaml),
+            _ => None,
+        }
+    }
+
+    /// Returns the format of the file at `path`, picked from its extension.
+    pub fn from_path(path: &Path) -> Result<Self, ConfigError> {
+        path.extension()
+            .and_then(std::ffi::OsStr::to_str)
+            .and_then(ConfigFormat::from_extension)
+            .ok_or_else(|| ConfigError::Extension(path.to_path_buf()))
+    }
 }
 
 impl fmt::Display for ConfigError {
@@ -82,14 +150,35 @@ impl fmt::Display for ConfigError {
                     found,
                 )
             }
+            ConfigError::Override(ref layer, ref msg) => write!(f, "{}: {}", layer, msg),
+            ConfigError::Value(ref msg) => write!(f, "{}", msg),
             #[cfg(feature = "json")]
             ConfigError::SerdeJsonError(ref msg) => write!(f, "{}", msg),
             #[cfg(feature = "binary")]
             ConfigError::BincodeError(ref msg) => write!(f, "{}", msg),
+            #[cfg(feature = "toml")]
+            ConfigError::TomlDeError(ref msg) => write!(f, "{}", msg),
+            #[cfg(feature = "toml")]
+            ConfigError::TomlSerError(ref msg) => write!(f, "{}", msg),
+            #[cfg(feature = "yaml")]
+            ConfigError::YamlError(ref msg) => write!(f, "{}", msg),
+            ConfigError::Validation(ref errors) => {
+                write!(f, "Invalid configuration:")?;
+                for error in errors {
+                    write!(f, "\n  {}", error)?;
+                }
+                Ok(())
+            }
         }
     }
 }
 
+impl From<value::Error> for ConfigError {
+    fn from(e: value::Error) -> Self {
+        ConfigError::Value(e.0)
+    }
+}
+
 impl From<RonError> for ConfigError {
     fn from(e: RonError) -> Self {
         ConfigError::Parser(e)
@@ -110,6 +199,27 @@ impl From<BincodeError> for ConfigError {
     }
 }
 
+#[cfg(feature = "toml")]
+impl From<toml::de::Error> for ConfigError {
+    fn from(e: toml::de::Error) -> Self {
+        ConfigError::TomlDeError(e)
+    }
+}
+
+#[cfg(feature = "toml")]
+impl From<toml::ser::Error> for ConfigError {
+    fn from(e: toml::ser::Error) -> Self {
+        ConfigError::TomlSerError(e)
+    }
+}
+
+#[cfg(feature = "yaml")]
+impl From<YamlError> for ConfigError {
+    fn from(e: YamlError) -> Self {
+        ConfigError::YamlError(e)
+    }
+}
+
 impl From<io::Error> for ConfigError {
     fn from(e: io::Error) -> ConfigError {
         ConfigError::File(e)
@@ -123,10 +233,19 @@ impl Error for ConfigError {
             ConfigError::FileParser(_, _) | ConfigError::Parser(_) => "Project parser error",
             ConfigError::Serializer(_) => "Project serializer error",
             ConfigError::Extension(_) => "Invalid extension or directory for a file",
+            ConfigError::Override(_, _) => "Invalid configuration override",
+            ConfigError::Value(_) => "Configuration value conversion error",
             #[cfg(feature = "json")]
             ConfigError::SerdeJsonError(_) => "Serialization or deserialization error (serde_json)",
             #[cfg(feature = "binary")]
             ConfigError::BincodeError(_) => "Serialization or deserialization error (bincode)",
+            #[cfg(feature = "toml")]
+            ConfigError::TomlDeError(_) => "Deserialization error (toml)",
+            #[cfg(feature = "toml")]
+            ConfigError::TomlSerError(_) => "Serialization error (toml)",
+            #[cfg(feature = "yaml")]
+            ConfigError::YamlError(_) => "Serialization or deserialization error (serde_yaml)",
+            ConfigError::Validation(_) => "Invalid configuration values",
         }
     }
 
@@ -167,6 +286,18 @@ where
     fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), ConfigError> {
         self.write_format(ConfigFormat::Ron, path)
     }
+    /// Loads a configuration structure from a file and checks it with [`Validate::validate`].
+    ///
+    /// Fails with [`ConfigError::Validation`] listing every invalid value.
+    fn load_validated<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError>
+    where
+        Self: Validate,
+    {
+        let path = path.as_ref();
+        let config = Self::load(path)?;
+        ValidationErrors::run(&config, |_| Some(ConfigLayer::File(path.to_path_buf())))?;
+        Ok(config)
+    }
 }
 
 impl<T> Config for T
@@ -174,39 +305,11 @@ where
     T: for<'a> Deserialize<'a> + Serialize,
 {
     fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
-        use std::{fs::File, io::Read};
-
-        use encoding_rs_io::DecodeReaderBytes;
-
         let path = path.as_ref();
+        let content = read_file(path)?;
 
-        let content = {
-            let file = File::open(path)?;
-
-            // Convert UTF-8-BOM & UTF-16-BOM to regular UTF-8. Else bytes are passed through
-            let mut decoder = DecodeReaderBytes::new(file);
-
-            let mut buffer = Vec::new();
-            decoder.read_to_end(&mut buffer)?;
-
-            buffer
-        };
-
-        path.extension()
-            .and_then(std::ffi::OsStr::to_str)
-            .map_or_else(
-                || Err(ConfigError::Extension(path.to_path_buf())),
-                |extension| {
-                    match extension {
-                        "ron" => Self::load_bytes_format(ConfigFormat::Ron, &content),
-                        #[cfg(feature = "json")]
-                        "json" => Self::load_bytes_format(ConfigFormat::Json, &content),
-                        #[cfg(feature = "binary")]
-                        "bin" => Self::load_bytes_format(ConfigFormat::Binary, &content),
-                        _ => Err(ConfigError::Extension(path.to_path_buf())),
-                    }
-                },
-            )
+        ConfigFormat::from_path(path)
+            .and_then(|format| Self::load_bytes_format(format, &content))
             .map_err(|err| {
                 // Enrich parsing error with a path to the file being parsed.
                 match err {
@@ -218,7 +321,8 @@ where
 
     fn load_bytes_format(format: ConfigFormat, bytes: &[u8]) -> Result<Self, ConfigError> {
         match format {
-            ConfigFormat::Ron => {
+            ConfigFormat::Ron =>
+            {
                 #[allow(clippy::shadow_unrelated)]
                 ron::de::Deserializer::from_bytes(bytes)
                     .and_then(|mut de| {
@@ -240,6 +344,16 @@ where
                 let des: T = bincode::deserialize(bytes)?;
                 Ok(des)
             }
+            #[cfg(feature = "toml")]
+            ConfigFormat::Toml => {
+                let des: T = toml::from_slice(bytes)?;
+                Ok(des)
+            }
+            #[cfg(feature = "yaml")]
+            ConfigFormat::Yaml => {
+                let des: T = serde_yaml::from_slice(bytes)?;
+                Ok(des)
+            }
         }
     }
 
@@ -262,12 +376,40 @@ where
             }
             #[cfg(feature = "binary")]
             ConfigFormat::Binary => File::create(path)?.write_all(&bincode::serialize(self)?)?,
+            #[cfg(feature = "toml")]
+            ConfigFormat::Toml => {
+                // Going through a `toml::Value` puts nested tables after the plain values.
+                let str = toml::to_string_pretty(&toml::Value::try_from(self)?)?;
+                File::create(path)?.write_all(str.as_bytes())?;
+            }
+            #[cfg(feature = "yaml")]
+            ConfigFormat::Yaml => {
+                let str = serde_yaml::to_string(self)?;
+                File::create(path)?.write_all(str.as_bytes())?;
+            }
         };
 
         Ok(())
     }
 }
 
+/// Reads a configuration file, converting UTF-8-BOM & UTF-16-BOM to regular UTF-8.
+pub(crate) fn read_file(path: &Path) -> Result<Vec<u8>, ConfigError> {
+    use std::{fs::File, io::Read};
+
+    use encoding_rs_io::DecodeReaderBytes;
+
+    let file = File::open(path)?;
+
+    // Else bytes are passed through
+    let mut decoder = DecodeReaderBytes::new(file);
+
+    let mut buffer = Vec::new();
+    decoder.read_to_end(&mut buffer)?;
+
+    Ok(buffer)
+}
+
 #[cfg(test)]
 mod test {
     use std::path::Path;
@@ -0,0 +1,657 @@
+//! Serde adapters used to merge configuration layers field by field.
+//!
+//! A layer is deserialized through [`Merge`], which walks the layer together with the merged
+//! value of the layers below it, serialized as a [`ConfigValue`]. Struct fields missing from
+//! the layer are taken from that value, everything else comes from the layer. The paths of the
+//! values set by the layer are recorded for the provenance report.
+
+use std::{cell::RefCell, collections::BTreeMap, fmt};
+
+use serde::{
+    de::{self, DeserializeSeed, Deserializer, IntoDeserializer, MapAccess, SeqAccess, Visitor},
+    ser::{self, Serialize, Serializer},
+};
+
+use crate::value::{ConfigValue, ValueDeserializer};
+
+pub(crate) fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+/// Deserializer reading a layer on top of `base`.
+pub(crate) struct Merge<'b, D> {
+    pub(crate) de: D,
+    pub(crate) base: Option<&'b ConfigValue>,
+    pub(crate) path: String,
+    pub(crate) set: &'b RefCell<Vec<String>>,
+}
+
+impl<'b, D> Merge<'b, D> {
+    fn record(&self) {
+        self.set.borrow_mut().push(self.path.clone());
+    }
+
+    fn with<E>(self, de: E) -> Merge<'b, E> {
+        Merge {
+            de,
+            base: self.base,
+            path: self.path,
+            set: self.set,
+        }
+    }
+}
+
+macro_rules! forward_recorded {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(
+                self,
+                $($arg: $ty,)*
+                visitor: V,
+            ) -> Result<V::Value, Self::Error> {
+                self.record();
+                self.de.$method($($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+impl<'de, 'b, D: Deserializer<'de>> Deserializer<'de> for Merge<'b, D> {
+    type Error = D::Error;
+
+    forward_recorded! {
+        deserialize_any();
+        deserialize_bool();
+        deserialize_i8();
+        deserialize_i16();
+        deserialize_i32();
+        deserialize_i64();
+        deserialize_u8();
+        deserialize_u16();
+        deserialize_u32();
+        deserialize_u64();
+        deserialize_f32();
+        deserialize_f64();
+        deserialize_char();
+        deserialize_str();
+        deserialize_string();
+        deserialize_bytes();
+        deserialize_byte_buf();
+        deserialize_unit();
+        deserialize_unit_struct(name: &'static str);
+        deserialize_seq();
+        deserialize_tuple(len: usize);
+        deserialize_tuple_struct(name: &'static str, len: usize);
+        deserialize_map();
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]);
+        deserialize_identifier();
+        deserialize_ignored_any();
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let de = self.de;
+        let merge = Merge {
+            de: visitor,
+            base: self.base,
+            path: self.path,
+            set: self.set,
+        };
+        de.deserialize_option(Wrap(merge))
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let de = self.de;
+        let merge = Merge {
+            de: visitor,
+            base: self.base,
+            path: self.path,
+            set: self.set,
+        };
+        de.deserialize_newtype_struct(name, Wrap(merge))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let de = self.de;
+        let merge = Merge {
+            de: visitor,
+            base: self.base,
+            path: self.path,
+            set: self.set,
+        };
+        de.deserialize_struct(name, fields, Wrap(merge))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.de.is_human_readable()
+    }
+}
+
+/// Visitor passing the merge state on to nested values.
+struct Wrap<'b, V>(Merge<'b, V>);
+
+impl<'de, 'b, V: Visitor<'de>> Visitor<'de> for Wrap<'b, V> {
+    type Value = V::Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.de.expecting(formatter)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        self.0.record();
+        self.0.de.visit_none()
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        self.0.record();
+        self.0.de.visit_unit()
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        let visitor = self.0.de;
+        let mut merge = Merge {
+            de: (),
+            base: self.0.base,
+            path: self.0.path,
+            set: self.0.set,
+        };
+        merge.base = merge.base.filter(|base| !base.is_unit());
+        visitor.visit_some(merge.with(deserializer))
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        let visitor = self.0.de;
+        let merge = Merge {
+            de: (),
+            base: self.0.base,
+            path: self.0.path,
+            set: self.0.set,
+        };
+        visitor.visit_newtype_struct(merge.with(deserializer))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+        self.0.record();
+        self.0.de.visit_seq(seq)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        let visitor = self.0.de;
+        visitor.visit_map(MergeMap {
+            map,
+            base: self.0.base.and_then(ConfigValue::as_map),
+            path: self.0.path,
+            set: self.0.set,
+            seen: Vec::new(),
+            current: None,
+            rest: None,
+            rest_value: None,
+        })
+    }
+}
+
+/// Fields of a struct in the layer, followed by the fields only present in the base.
+struct MergeMap<'b, A> {
+    map: A,
+    base: Option<&'b [(String, ConfigValue)]>,
+    path: String,
+    set: &'b RefCell<Vec<String>>,
+    seen: Vec<String>,
+    /// Path and base value of the field read from the layer.
+    current: Option<(String, Option<&'b ConfigValue>)>,
+    rest: Option<std::vec::IntoIter<&'b (String, ConfigValue)>>,
+    rest_value: Option<&'b ConfigValue>,
+}
+
+impl<'de, 'b, A: MapAccess<'de>> MapAccess<'de> for MergeMap<'b, A> {
+    type Error = A::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        if self.rest.is_none() {
+            if let Some(key) = self.map.next_key_seed(KeySeed)? {
+                self.current = Some((
+                    join(&self.path, &key),
+                    self.base.and_then(|base| {
+                        base.iter()
+                            .find(|(name, _)| name == &key)
+                            .map(|(_, value)| value)
+                    }),
+                ));
+                self.seen.push(key.clone());
+                return seed.deserialize(key.into_deserializer()).map(Some);
+            }
+
+            let read = &self.seen;
+            let rest: Vec<_> = self
+                .base
+                .into_iter()
+                .flatten()
+                .filter(|(key, _)| !read.contains(key))
+                .collect();
+            self.rest = Some(rest.into_iter());
+        }
+
+        match self.rest.as_mut().and_then(Iterator::next) {
+            Some((key, value)) => {
+                self.rest_value = Some(value);
+                seed.deserialize(key.as_str().into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<S: DeserializeSeed<'de>>(
+        &mut self,
+        seed: S,
+    ) -> Result<S::Value, Self::Error> {
+        if let Some(value) = self.rest_value.take() {
+            return seed
+                .deserialize(ValueDeserializer(value.clone()))
+                .map_err(de::Error::custom);
+        }
+        let (path, base) = self
+            .current
+            .take()
+            .ok_or_else(|| <A::Error as de::Error>::custom("value requested before key"))?;
+        self.map.next_value_seed(MergeSeed(Merge {
+            de: seed,
+            base,
+            path,
+            set: self.set,
+        }))
+    }
+}
+
+struct MergeSeed<'b, S>(Merge<'b, S>);
+
+impl<'de, 'b, S: DeserializeSeed<'de>> DeserializeSeed<'de> for MergeSeed<'b, S> {
+    type Value = S::Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<S::Value, D::Error> {
+        let seed = self.0.de;
+        let merge = Merge {
+            de: (),
+            base: self.0.base,
+            path: self.0.path,
+            set: self.0.set,
+        };
+        seed.deserialize(merge.with(deserializer))
+    }
+}
+
+/// Reads a struct field name as a string. Field names are identifiers, which not every format
+/// can deserialize as a `String`.
+struct KeySeed;
+
+impl<'de> DeserializeSeed<'de> for KeySeed {
+    type Value = String;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<String, D::Error> {
+        deserializer.deserialize_identifier(self)
+    }
+}
+
+impl<'de> Visitor<'de> for KeySeed {
+    type Value = String;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a field name")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<String, E> {
+        Ok(value.to_string())
+    }
+
+    fn visit_string<E: de::Error>(self, value: String) -> Result<String, E> {
+        Ok(value)
+    }
+}
+
+/// A single `key.path=value` override, from an environment variable or the command line.
+#[derive(Debug)]
+pub(crate) enum Node {
+    /// The value, in RON. Strings don't need quotes.
+    Leaf(String),
+    Branch(BTreeMap<String, Node>),
+}
+
+impl Node {
+    pub(crate) fn new(path: &[String], value: String) -> Self {
+        path.iter().rev().fold(Node::Leaf(value), |node, key| {
+            let mut map = BTreeMap::new();
+            map.insert(key.clone(), node);
+            Node::Branch(map)
+        })
+    }
+}
+
+fn parse_ron<'de, T>(
+    raw: &'de str,
+    parse: impl FnOnce(&mut ron::Deserializer<'de>) -> ron::Result<T>,
+) -> ron::Result<T> {
+    let mut de = ron::Deserializer::from_str(raw)?;
+    let value = parse(&mut de)?;
+    de.end()?;
+    Ok(value)
+}
+
+macro_rules! forward_to_ron {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(
+                self,
+                $($arg: $ty,)*
+                visitor: V,
+            ) -> ron::Result<V::Value> {
+                match self {
+                    Node::Leaf(raw) => parse_ron(raw, |de| de.$method($($arg,)* visitor)),
+                    Node::Branch(_) => self.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for &'de Node {
+    type Error = ron::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> ron::Result<V::Value> {
+        match self {
+            Node::Leaf(raw) => parse_ron(raw, |de| de.deserialize_any(visitor)),
+            Node::Branch(map) => {
+                visitor.visit_map(NodeMap {
+                    iter: map.iter(),
+                    value: None,
+                })
+            }
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> ron::Result<V::Value> {
+        match self {
+            Node::Leaf(raw) if !raw.starts_with('"') => visitor.visit_borrowed_str(raw),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> ron::Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> ron::Result<V::Value> {
+        match self {
+            Node::Leaf(raw) if raw == "None" || raw.starts_with("Some(") => {
+                parse_ron(raw, |de| de.deserialize_option(visitor))
+            }
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> ron::Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> ron::Result<V::Value> {
+        match self {
+            Node::Leaf(raw) => parse_ron(raw, |de| de.deserialize_struct(name, fields, visitor)),
+            Node::Branch(_) => self.deserialize_any(visitor),
+        }
+    }
+
+    forward_to_ron! {
+        deserialize_bool();
+        deserialize_i8();
+        deserialize_i16();
+        deserialize_i32();
+        deserialize_i64();
+        deserialize_u8();
+        deserialize_u16();
+        deserialize_u32();
+        deserialize_u64();
+        deserialize_f32();
+        deserialize_f64();
+        deserialize_char();
+        deserialize_bytes();
+        deserialize_byte_buf();
+        deserialize_unit();
+        deserialize_unit_struct(name: &'static str);
+        deserialize_seq();
+        deserialize_tuple(len: usize);
+        deserialize_tuple_struct(name: &'static str, len: usize);
+        deserialize_map();
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]);
+        deserialize_identifier();
+        deserialize_ignored_any();
+    }
+}
+
+struct NodeMap<'de> {
+    iter: std::collections::btree_map::Iter<'de, String, Node>,
+    value: Option<&'de Node>,
+}
+
+impl<'de> MapAccess<'de> for NodeMap<'de> {
+    type Error = ron::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> ron::Result<Option<K::Value>> {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(key.as_str().into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> ron::Result<S::Value> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| <ron::Error as de::Error>::custom("value requested before key"))?;
+        seed.deserialize(value)
+    }
+}
+
+/// Serializes only the struct fields whose path is in `keep` or leads to one.
+pub(crate) struct Filtered<'a, T: ?Sized> {
+    pub(crate) value: &'a T,
+    pub(crate) path: String,
+    pub(crate) keep: &'a [String],
+}
+
+impl<'a, T: Serialize + ?Sized> Serialize for Filtered<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(FilterSerializer {
+            inner: serializer,
+            path: &self.path,
+            keep: self.keep,
+        })
+    }
+}
+
+struct FilterSerializer<'a, S> {
+    inner: S,
+    path: &'a str,
+    keep: &'a [String],
+}
+
+impl<'a, S> FilterSerializer<'a, S> {
+    fn filtered<'v, T: ?Sized>(&self, value: &'v T) -> Filtered<'v, T>
+    where
+        'a: 'v,
+    {
+        Filtered {
+            value,
+            path: self.path.to_string(),
+            keep: self.keep,
+        }
+    }
+}
+
+macro_rules! forward_serialize {
+    ($($method:ident($($arg:ident: $ty:ty),*) -> $ret:ty;)*) => {
+        $(
+            fn $method(self, $($arg: $ty),*) -> Result<$ret, S::Error> {
+                self.inner.$method($($arg),*)
+            }
+        )*
+    };
+}
+
+impl<'a, S: Serializer> Serializer for FilterSerializer<'a, S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = S::SerializeSeq;
+    type SerializeTuple = S::SerializeTuple;
+    type SerializeTupleStruct = S::SerializeTupleStruct;
+    type SerializeTupleVariant = S::SerializeTupleVariant;
+    type SerializeMap = S::SerializeMap;
+    type SerializeStruct = FilterStruct<'a, S::SerializeStruct>;
+    type SerializeStructVariant = S::SerializeStructVariant;
+
+    forward_serialize! {
+        serialize_bool(v: bool) -> S::Ok;
+        serialize_i8(v: i8) -> S::Ok;
+        serialize_i16(v: i16) -> S::Ok;
+        serialize_i32(v: i32) -> S::Ok;
+        serialize_i64(v: i64) -> S::Ok;
+        serialize_u8(v: u8) -> S::Ok;
+        serialize_u16(v: u16) -> S::Ok;
+        serialize_u32(v: u32) -> S::Ok;
+        serialize_u64(v: u64) -> S::Ok;
+        serialize_f32(v: f32) -> S::Ok;
+        serialize_f64(v: f64) -> S::Ok;
+        serialize_char(v: char) -> S::Ok;
+        serialize_str(v: &str) -> S::Ok;
+        serialize_bytes(v: &[u8]) -> S::Ok;
+        serialize_none() -> S::Ok;
+        serialize_unit() -> S::Ok;
+        serialize_unit_struct(name: &'static str) -> S::Ok;
+        serialize_unit_variant(name: &'static str, index: u32, variant: &'static str) -> S::Ok;
+        serialize_seq(len: Option<usize>) -> S::SerializeSeq;
+        serialize_tuple(len: usize) -> S::SerializeTuple;
+        serialize_tuple_struct(name: &'static str, len: usize) -> S::SerializeTupleStruct;
+        serialize_tuple_variant(
+            name: &'static str,
+            index: u32,
+            variant: &'static str,
+            len: usize
+        ) -> S::SerializeTupleVariant;
+        serialize_map(len: Option<usize>) -> S::SerializeMap;
+        serialize_struct_variant(
+            name: &'static str,
+            index: u32,
+            variant: &'static str,
+            len: usize
+        ) -> S::SerializeStructVariant;
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<S::Ok, S::Error> {
+        let value = self.filtered(value);
+        self.inner.serialize_some(&value)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        let value = self.filtered(value);
+        self.inner.serialize_newtype_struct(name, &value)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        self.inner
+            .serialize_newtype_variant(name, index, variant, value)
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, S::Error> {
+        Ok(FilterStruct {
+            inner: self.inner.serialize_struct(name, len)?,
+            path: self.path,
+            keep: self.keep,
+        })
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+struct FilterStruct<'a, S> {
+    inner: S,
+    path: &'a str,
+    keep: &'a [String],
+}
+
+impl<'a, S: ser::SerializeStruct> ser::SerializeStruct for FilterStruct<'a, S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), S::Error> {
+        let path = join(self.path, key);
+        if self.keep.contains(&path) {
+            self.inner.serialize_field(key, value)
+        } else if self
+            .keep
+            .iter()
+            .any(|kept| kept.starts_with(&path) && kept[path.len()..].starts_with('.'))
+        {
+            self.inner.serialize_field(
+                key,
+                &Filtered {
+                    value,
+                    path,
+                    keep: self.keep,
+                },
+            )
+        } else {
+            self.inner.skip_field(key)
+        }
+    }
+
+    fn end(self) -> Result<S::Ok, S::Error> {
+        self.inner.end()
+    }
+}
@@ -0,0 +1,249 @@
+//! Checks of configuration values beyond what the parser accepts.
+
+use std::{fmt, ops::RangeInclusive, path::Path};
+
+use crate::{merge::join, ConfigError, ConfigLayer};
+
+/// Checks the values of a loaded configuration, such as ranges, required fields and rules
+/// involving several fields.
+///
+/// All problems are reported together, loading only fails after the whole configuration has
+/// been checked.
+///
+/// # Example
+///
+/// ```
+/// use amethyst_config::{Validate, ValidationErrors};
+///
+/// struct Audio {
+///     volume: f32,
+///     music: Option<String>,
+///     music_volume: f32,
+/// }
+///
+/// impl Validate for Audio {
+///     fn validate(&self, errors: &mut ValidationErrors) {
+///         errors.range("volume", &self.volume, 0.0..=1.0);
+///         errors.required("music", &self.music);
+///         errors.check(
+///             self.music_volume <= self.volume,
+///             "music_volume",
+///             "must not be louder than `volume`",
+///         );
+///     }
+/// }
+///
+/// let mut errors = ValidationErrors::new();
+/// Audio {
+///     volume: 2.0,
+///     music: None,
+///     music_volume: 0.5,
+/// }
+/// .validate(&mut errors);
+/// assert_eq!(errors.len(), 2);
+/// ```
+pub trait Validate {
+    /// Adds every problem of this configuration to `errors`.
+    fn validate(&self, errors: &mut ValidationErrors);
+}
+
+/// A configuration value which failed validation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    /// Path of the field, such as `window.dimensions`.
+    pub field: String,
+    /// What is wrong with the value.
+    pub message: String,
+    /// The layer the value comes from, if known.
+    pub layer: Option<ConfigLayer>,
+}
+
+impl ValidationError {
+    /// Returns the file the value comes from, if it was read from a file.
+    #[must_use]
+    pub fn file(&self) -> Option<&Path> {
+        match &self.layer {
+            Some(ConfigLayer::File(path) | ConfigLayer::UserFile(path)) => Some(path),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.layer {
+            Some(ConfigLayer::File(path) | ConfigLayer::UserFile(path)) => {
+                write!(f, "{}: {}: {}", path.display(), self.field, self.message)
+            }
+            Some(layer) => write!(f, "{}: {} (from {})", self.field, self.message, layer),
+            None => write!(f, "{}: {}", self.field, self.message),
+        }
+    }
+}
+
+/// Collects the problems found by [`Validate::validate`].
+#[derive(Debug, Default)]
+pub struct ValidationErrors {
+    prefix: String,
+    errors: Vec<ValidationError>,
+}
+
+impl ValidationErrors {
+    /// Creates an empty collection.
+    #[must_use]
+    pub fn new() -> Self {
+        ValidationErrors::default()
+    }
+
+    /// Reports that the value of `field` is invalid.
+    pub fn add<S: Into<String>>(&mut self, field: &str, message: S) {
+        self.errors.push(ValidationError {
+            field: join(&self.prefix, field),
+            message: message.into(),
+            layer: None,
+        });
+    }
+
+    /// Reports `message` for `field` unless `valid` is `true`.
+    pub fn check<S: Into<String>>(&mut self, valid: bool, field: &str, message: S) {
+        if !valid {
+            self.add(field, message);
+        }
+    }
+
+    /// Reports `field` if `value` is outside of `range`.
+    pub fn range<V>(&mut self, field: &str, value: &V, range: RangeInclusive<V>)
+    where
+        V: PartialOrd + fmt::Display,
+    {
+        if !range.contains(value) {
+            self.add(
+                field,
+                format!(
+                    "{} is not between {} and {}",
+                    value,
+                    range.start(),
+                    range.end()
+                ),
+            );
+        }
+    }
+
+    /// Reports `field` if it has no value.
+    pub fn required<V>(&mut self, field: &str, value: &Option<V>) {
+        self.check(value.is_some(), field, "is required");
+    }
+
+    /// Validates the nested configuration in `field`, prefixing the paths of its errors.
+    pub fn nested<V: Validate + ?Sized>(&mut self, field: &str, value: &V) {
+        let prefix = join(&self.prefix, field);
+        let outer = std::mem::replace(&mut self.prefix, prefix);
+        value.validate(self);
+        self.prefix = outer;
+    }
+
+    /// Returns `true` if no problem was found.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Returns the number of problems found.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    /// Iterates over the problems found.
+    pub fn iter(&self) -> impl Iterator<Item = &ValidationError> {
+        self.errors.iter()
+    }
+
+    /// Validates `config` and returns every problem found, with the layer of each value.
+    pub(crate) fn run<V, F>(config: &V, mut layer: F) -> Result<(), ConfigError>
+    where
+        V: Validate + ?Sized,
+        F: FnMut(&str) -> Option<ConfigLayer>,
+    {
+        let mut errors = ValidationErrors::new();
+        config.validate(&mut errors);
+        if errors.is_empty() {
+            return Ok(());
+        }
+        for error in &mut errors.errors {
+            error.layer = layer(&error.field);
+        }
+        Err(ConfigError::Validation(errors.errors))
+    }
+}
+
+impl IntoIterator for ValidationErrors {
+    type Item = ValidationError;
+    type IntoIter = std::vec::IntoIter<ValidationError>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.errors.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use serde::{Deserialize, Serialize};
+
+    use crate::{Config, ConfigError, Validate, ValidationErrors};
+
+    #[derive(Debug, Default, Deserialize, Serialize)]
+    #[serde(default)]
+    struct Window {
+        title: Option<String>,
+        width: u32,
+    }
+
+    impl Validate for Window {
+        fn validate(&self, errors: &mut ValidationErrors) {
+            errors.required("title", &self.title);
+            errors.range("width", &self.width, 1..=8192);
+        }
+    }
+
+    #[derive(Debug, Default, Deserialize, Serialize)]
+    #[serde(default)]
+    struct Game {
+        window: Window,
+        min_players: u32,
+        max_players: u32,
+    }
+
+    impl Validate for Game {
+        fn validate(&self, errors: &mut ValidationErrors) {
+            errors.nested("window", &self.window);
+            errors.check(
+                self.min_players <= self.max_players,
+                "max_players",
+                "must not be less than `min_players`",
+            );
+        }
+    }
+
+    #[test]
+    fn errors_are_collected_with_file_and_field() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/invalid_values.ron");
+        let errors = match Game::load_validated(&path) {
+            Err(ConfigError::Validation(errors)) => errors,
+            other => panic!("Expected validation errors, got {:?}", other),
+        };
+
+        let fields: Vec<_> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, ["window.title", "window.width", "max_players"]);
+        assert!(errors.iter().all(|e| e.file() == Some(path.as_path())));
+        assert_eq!(
+            errors[1].to_string(),
+            format!(
+                "{}: window.width: 0 is not between 1 and 8192",
+                path.display()
+            )
+        );
+    }
+}
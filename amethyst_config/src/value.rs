@@ -0,0 +1,769 @@
+//! Configuration values in serde's data model, used to merge configuration layers.
+
+use std::fmt;
+
+use serde::{
+    de::{
+        self,
+        value::{MapDeserializer, SeqDeserializer, StringDeserializer},
+        DeserializeSeed, Deserializer, EnumAccess, IntoDeserializer, Unexpected, VariantAccess,
+        Visitor,
+    },
+    forward_to_deserialize_any,
+    ser::{self, Serialize, Serializer},
+};
+
+/// A configuration value of any type, as read by [`LayeredConfig::provenance`].
+///
+/// Structs and maps are [`ConfigValue::Map`]s, `None` is [`ConfigValue::Unit`] and `Some` the
+/// value it contains. Unit enum variants are a [`ConfigValue::String`], other variants a map with
+/// the variant name as its only key.
+///
+/// [`LayeredConfig::provenance`]: crate::LayeredConfig::provenance
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigValue {
+    /// `()`, a unit struct or `None`.
+    Unit,
+    /// A boolean.
+    Bool(bool),
+    /// A signed integer.
+    Int(i64),
+    /// An unsigned integer.
+    UInt(u64),
+    /// A floating point number.
+    Float(f64),
+    /// A string or a character.
+    String(String),
+    /// A sequence, tuple or tuple struct.
+    Seq(Vec<ConfigValue>),
+    /// The fields of a struct or the entries of a map, in order.
+    Map(Vec<(String, ConfigValue)>),
+}
+
+impl ConfigValue {
+    /// Returns `true` for `()`, unit structs and `None`.
+    #[must_use]
+    pub fn is_unit(&self) -> bool {
+        matches!(self, ConfigValue::Unit)
+    }
+
+    /// Returns the fields of a struct or the entries of a map.
+    #[must_use]
+    pub fn as_map(&self) -> Option<&[(String, ConfigValue)]> {
+        match self {
+            ConfigValue::Map(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    /// Returns the field `key` of a struct or the entry `key` of a map.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&ConfigValue> {
+        self.as_map()?
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value)
+    }
+
+    /// Returns the value at a path of fields separated by dots, such as `window.title`.
+    #[must_use]
+    pub fn get_path(&self, path: &str) -> Option<&ConfigValue> {
+        if path.is_empty() {
+            return Some(self);
+        }
+        path.split('.').try_fold(self, |value, key| value.get(key))
+    }
+
+    pub(crate) fn get_path_mut(&mut self, path: &str) -> Option<&mut ConfigValue> {
+        if path.is_empty() {
+            return Some(self);
+        }
+        path.split('.').try_fold(self, |value, key| {
+            match value {
+                ConfigValue::Map(entries) => {
+                    entries
+                        .iter_mut()
+                        .find(|(name, _)| name == key)
+                        .map(|(_, value)| value)
+                }
+                _ => None,
+            }
+        })
+    }
+
+    fn unexpected(&self) -> Unexpected<'_> {
+        match self {
+            ConfigValue::Unit => Unexpected::Unit,
+            ConfigValue::Bool(value) => Unexpected::Bool(*value),
+            ConfigValue::Int(value) => Unexpected::Signed(*value),
+            ConfigValue::UInt(value) => Unexpected::Unsigned(*value),
+            ConfigValue::Float(value) => Unexpected::Float(*value),
+            ConfigValue::String(value) => Unexpected::Str(value),
+            ConfigValue::Seq(_) => Unexpected::Seq,
+            ConfigValue::Map(_) => Unexpected::Map,
+        }
+    }
+}
+
+/// Writes the value in RON.
+impl fmt::Display for ConfigValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigValue::Unit => write!(f, "()"),
+            ConfigValue::Bool(value) => write!(f, "{}", value),
+            ConfigValue::Int(value) => write!(f, "{}", value),
+            ConfigValue::UInt(value) => write!(f, "{}", value),
+            ConfigValue::Float(value) => write!(f, "{:?}", value),
+            ConfigValue::String(value) => write!(f, "{:?}", value),
+            ConfigValue::Seq(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    let separator = if i == 0 { "" } else { ", " };
+                    write!(f, "{}{}", separator, item)?;
+                }
+                write!(f, "]")
+            }
+            ConfigValue::Map(entries) => {
+                write!(f, "(")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    let separator = if i == 0 { "" } else { ", " };
+                    write!(f, "{}{}: {}", separator, key, value)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+/// Error converting a value to or from a [`ConfigValue`].
+#[derive(Debug)]
+pub(crate) struct Error(pub(crate) String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+/// Serializes `value` into a [`ConfigValue`].
+pub(crate) fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<ConfigValue, Error> {
+    value.serialize(ValueSerializer)
+}
+
+/// Deserializes a `T` from a [`ConfigValue`].
+pub(crate) fn from_value<T: de::DeserializeOwned>(value: ConfigValue) -> Result<T, Error> {
+    T::deserialize(ValueDeserializer(value))
+}
+
+struct ValueSerializer;
+
+impl Serializer for ValueSerializer {
+    type Ok = ConfigValue;
+    type Error = Error;
+    type SerializeSeq = SerializeSeq;
+    type SerializeTuple = SerializeSeq;
+    type SerializeTupleStruct = SerializeSeq;
+    type SerializeTupleVariant = SerializeSeq;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeMap;
+    type SerializeStructVariant = SerializeMap;
+
+    fn serialize_bool(self, v: bool) -> Result<ConfigValue, Error> {
+        Ok(ConfigValue::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<ConfigValue, Error> {
+        Ok(ConfigValue::Int(v.into()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<ConfigValue, Error> {
+        Ok(ConfigValue::Int(v.into()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<ConfigValue, Error> {
+        Ok(ConfigValue::Int(v.into()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<ConfigValue, Error> {
+        Ok(ConfigValue::Int(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<ConfigValue, Error> {
+        Ok(ConfigValue::UInt(v.into()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<ConfigValue, Error> {
+        Ok(ConfigValue::UInt(v.into()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<ConfigValue, Error> {
+        Ok(ConfigValue::UInt(v.into()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<ConfigValue, Error> {
+        Ok(ConfigValue::UInt(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<ConfigValue, Error> {
+        Ok(ConfigValue::Float(v.into()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<ConfigValue, Error> {
+        Ok(ConfigValue::Float(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<ConfigValue, Error> {
+        Ok(ConfigValue::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<ConfigValue, Error> {
+        Ok(ConfigValue::String(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<ConfigValue, Error> {
+        Ok(ConfigValue::Seq(
+            v.iter()
+                .map(|&byte| ConfigValue::UInt(byte.into()))
+                .collect(),
+        ))
+    }
+
+    fn serialize_none(self) -> Result<ConfigValue, Error> {
+        Ok(ConfigValue::Unit)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<ConfigValue, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<ConfigValue, Error> {
+        Ok(ConfigValue::Unit)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<ConfigValue, Error> {
+        Ok(ConfigValue::Unit)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<ConfigValue, Error> {
+        Ok(ConfigValue::String(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<ConfigValue, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<ConfigValue, Error> {
+        Ok(ConfigValue::Map(vec![(
+            variant.to_string(),
+            to_value(value)?,
+        )]))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeSeq, Error> {
+        Ok(SerializeSeq {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeSeq, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeSeq, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeSeq, Error> {
+        Ok(SerializeSeq {
+            items: Vec::with_capacity(len),
+            variant: Some(variant),
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<SerializeMap, Error> {
+        Ok(SerializeMap {
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+            variant: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeMap, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeMap, Error> {
+        Ok(SerializeMap {
+            entries: Vec::with_capacity(len),
+            key: None,
+            variant: Some(variant),
+        })
+    }
+}
+
+/// Wraps the value of an enum variant in a map with the variant name as its only key.
+fn wrap_variant(variant: Option<&'static str>, value: ConfigValue) -> ConfigValue {
+    match variant {
+        Some(variant) => ConfigValue::Map(vec![(variant.to_string(), value)]),
+        None => value,
+    }
+}
+
+struct SerializeSeq {
+    items: Vec<ConfigValue>,
+    variant: Option<&'static str>,
+}
+
+impl SerializeSeq {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.items.push(to_value(value)?);
+        Ok(())
+    }
+
+    fn finish(self) -> ConfigValue {
+        wrap_variant(self.variant, ConfigValue::Seq(self.items))
+    }
+}
+
+impl ser::SerializeSeq for SerializeSeq {
+    type Ok = ConfigValue;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<ConfigValue, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTuple for SerializeSeq {
+    type Ok = ConfigValue;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<ConfigValue, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeSeq {
+    type Ok = ConfigValue;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<ConfigValue, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeSeq {
+    type Ok = ConfigValue;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<ConfigValue, Error> {
+        Ok(self.finish())
+    }
+}
+
+struct SerializeMap {
+    entries: Vec<(String, ConfigValue)>,
+    key: Option<String>,
+    variant: Option<&'static str>,
+}
+
+impl SerializeMap {
+    fn finish(self) -> ConfigValue {
+        wrap_variant(self.variant, ConfigValue::Map(self.entries))
+    }
+}
+
+impl ser::SerializeMap for SerializeMap {
+    type Ok = ConfigValue;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        let key = match to_value(key)? {
+            ConfigValue::String(key) => key,
+            ConfigValue::Bool(key) => key.to_string(),
+            ConfigValue::Int(key) => key.to_string(),
+            ConfigValue::UInt(key) => key.to_string(),
+            key => {
+                return Err(Error(format!(
+                    "map keys must be strings, numbers or booleans, got {}",
+                    key
+                )))
+            }
+        };
+        self.key = Some(key);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| Error("map value serialized before its key".to_string()))?;
+        self.entries.push((key, to_value(value)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<ConfigValue, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStruct for SerializeMap {
+    type Ok = ConfigValue;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.entries.push((key.to_string(), to_value(value)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<ConfigValue, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeMap {
+    type Ok = ConfigValue;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.entries.push((key.to_string(), to_value(value)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<ConfigValue, Error> {
+        Ok(self.finish())
+    }
+}
+
+/// Deserializes the types of a [`ConfigValue`].
+pub(crate) struct ValueDeserializer(pub(crate) ConfigValue);
+
+impl<'de> Deserializer<'de> for ValueDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            ConfigValue::Unit => visitor.visit_unit(),
+            ConfigValue::Bool(value) => visitor.visit_bool(value),
+            ConfigValue::Int(value) => visitor.visit_i64(value),
+            ConfigValue::UInt(value) => visitor.visit_u64(value),
+            ConfigValue::Float(value) => visitor.visit_f64(value),
+            ConfigValue::String(value) => visitor.visit_string(value),
+            ConfigValue::Seq(items) => {
+                let mut seq = SeqDeserializer::new(items.into_iter().map(ValueDeserializer));
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            ConfigValue::Map(entries) => {
+                let mut map = MapDeserializer::new(
+                    entries
+                        .into_iter()
+                        .map(|(key, value)| (Key(key), ValueDeserializer(value))),
+                );
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            ConfigValue::Unit => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.0 {
+            ConfigValue::String(variant) => {
+                let variant: StringDeserializer<Error> = variant.into_deserializer();
+                visitor.visit_enum(variant)
+            }
+            ConfigValue::Map(mut entries) if entries.len() == 1 => {
+                let (variant, value) = entries.remove(0);
+                visitor.visit_enum(Enum {
+                    variant,
+                    value: ValueDeserializer(value),
+                })
+            }
+            other => {
+                Err(de::Error::invalid_type(
+                    other.unexpected(),
+                    &"an enum variant",
+                ))
+            }
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf unit
+        unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for ValueDeserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// A map key, which may be read as a number or a boolean.
+struct Key(String);
+
+macro_rules! parse_key {
+    ($($method:ident => $visit:ident;)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                match self.0.parse() {
+                    Ok(value) => visitor.$visit(value),
+                    Err(_) => visitor.visit_string(self.0),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Key {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_string(self.0)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let variant: StringDeserializer<Error> = self.0.into_deserializer();
+        visitor.visit_enum(variant)
+    }
+
+    parse_key! {
+        deserialize_bool => visit_bool;
+        deserialize_i8 => visit_i8;
+        deserialize_i16 => visit_i16;
+        deserialize_i32 => visit_i32;
+        deserialize_i64 => visit_i64;
+        deserialize_u8 => visit_u8;
+        deserialize_u16 => visit_u16;
+        deserialize_u32 => visit_u32;
+        deserialize_u64 => visit_u64;
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 f32 f64 char str string bytes byte_buf unit unit_struct seq tuple tuple_struct map
+        struct identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Key {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// An enum variant with a value, read from a map with a single entry.
+struct Enum {
+    variant: String,
+    value: ValueDeserializer,
+}
+
+impl<'de> EnumAccess<'de> for Enum {
+    type Error = Error;
+    type Variant = ValueDeserializer;
+
+    fn variant_seed<S: DeserializeSeed<'de>>(
+        self,
+        seed: S,
+    ) -> Result<(S::Value, ValueDeserializer), Error> {
+        let variant: StringDeserializer<Error> = self.variant.into_deserializer();
+        Ok((seed.deserialize(variant)?, self.value))
+    }
+}
+
+impl<'de> VariantAccess<'de> for ValueDeserializer {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        match self.0 {
+            ConfigValue::Unit => Ok(()),
+            other => {
+                Err(de::Error::invalid_type(
+                    other.unexpected(),
+                    &"a unit variant",
+                ))
+            }
+        }
+    }
+
+    fn newtype_variant_seed<S: DeserializeSeed<'de>>(self, seed: S) -> Result<S::Value, Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_map(visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    enum Mode {
+        Windowed,
+        Fullscreen(u8),
+        Sized(u32, u32),
+        Named { name: String },
+    }
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct Settings {
+        modes: Vec<Mode>,
+        keys: BTreeMap<u16, char>,
+        scale: Option<f32>,
+        offset: Option<i64>,
+        unit: (),
+    }
+
+    #[test]
+    fn values_round_trip() {
+        let mut keys = BTreeMap::new();
+        keys.insert(7, 'a');
+        let settings = Settings {
+            modes: vec![
+                Mode::Windowed,
+                Mode::Fullscreen(1),
+                Mode::Sized(640, 480),
+                Mode::Named {
+                    name: "tv".to_string(),
+                },
+            ],
+            keys,
+            scale: Some(1.5),
+            offset: None,
+            unit: (),
+        };
+
+        let value = to_value(&settings).unwrap();
+        assert_eq!(value.get_path("scale"), Some(&ConfigValue::Float(1.5)));
+        assert_eq!(value.get("keys").unwrap().to_string(), "(7: \"a\")");
+        assert_eq!(from_value::<Settings>(value).unwrap(), settings);
+    }
+}
@@ -0,0 +1,115 @@
+//! Console bundle
+
+use std::{path::PathBuf, sync::mpsc::channel};
+
+use amethyst_core::ecs::{DispatcherBuilder, Resources, SystemBundle, World};
+use amethyst_error::Error;
+use log::error;
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{
+    console::{execute, Console},
+    input::ConsoleInput,
+};
+
+/// Adds the [`Console`] resource and executes submitted lines once per frame.
+///
+/// Lines are executed on the main thread with full access to the `World` and `Resources`.
+/// Changed console variables are written to the cvar file, if one is set, after each frame in
+/// which they changed.
+///
+/// # Example
+///
+/// ```no_run
+/// use amethyst::{
+///     console::{ConsoleBundle, ConsoleInput},
+///     prelude::*,
+/// };
+///
+/// let mut dispatcher = DispatcherBuilder::default();
+/// dispatcher.add_bundle(
+///     ConsoleBundle::default()
+///         .with_input(ConsoleInput::Stdin)
+///         .with_input(ConsoleInput::Socket("127.0.0.1:7878".parse().unwrap()))
+///         .with_cvar_file("cvars.ron"),
+/// );
+/// ```
+#[derive(Debug, Default)]
+pub struct ConsoleBundle {
+    inputs: Vec<ConsoleInput>,
+    cvar_file: Option<PathBuf>,
+}
+
+impl ConsoleBundle {
+    /// Also reads console lines from `input`.
+    #[must_use]
+    pub fn with_input(mut self, input: ConsoleInput) -> Self {
+        self.inputs.push(input);
+        self
+    }
+
+    /// Persists changed console variables to `path` and restores them from it on startup.
+    #[must_use]
+    pub fn with_cvar_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.cvar_file = Some(path.into());
+        self
+    }
+}
+
+impl SystemBundle for ConsoleBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        {
+            let mut console = resources.get_mut_or_insert_with(Console::default);
+            if let Some(path) = &self.cvar_file {
+                console.set_cvar_file(path);
+            }
+        }
+
+        let (sender, receiver) = channel();
+        for input in &self.inputs {
+            input.spawn(sender.clone()).map_err(Error::new)?;
+        }
+
+        builder.add_thread_local_fn(move |world, resources| {
+            #[cfg(feature = "profiler")]
+            profile_scope!("console");
+
+            let pending = match resources.get_mut::<Console>() {
+                Some(mut console) => console.take_pending(),
+                None => return,
+            };
+            for line in pending {
+                let _ = execute(&line, world, resources);
+            }
+
+            for remote in receiver.try_iter() {
+                let output = match execute(&remote.line, world, resources) {
+                    Ok(output) => output,
+                    Err(e) => format!("error: {}", e),
+                };
+                match remote.reply {
+                    Some(reply) => {
+                        let _ = reply.send(output);
+                    }
+                    None if !output.is_empty() => println!("{}", output),
+                    None => {}
+                }
+            }
+
+            let mut console = resources.get_mut::<Console>().unwrap();
+            if console.cvars_changed() {
+                if let Err(e) = console.save_cvars() {
+                    error!("{}", e);
+                }
+            }
+        });
+
+        Ok(())
+    }
+}
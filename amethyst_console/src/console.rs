@@ -0,0 +1,620 @@
+//! The `Console` resource and command execution.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use amethyst_config::{Config, ConfigFormat};
+use amethyst_core::ecs::{Resources, World};
+use amethyst_error::{format_err, Error};
+use log::{error, warn};
+
+use crate::cvar::{CVar, CVarValue, ErasedCVar};
+
+/// Signature of console command handlers.
+///
+/// Handlers receive the arguments following the command name and return the text printed to the
+/// console.
+pub type CommandFn =
+    dyn Fn(&[String], &mut World, &mut Resources) -> Result<String, Error> + Send + Sync;
+
+struct Command {
+    help: String,
+    handler: Arc<CommandFn>,
+}
+
+impl fmt::Debug for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Command").field("help", &self.help).finish()
+    }
+}
+
+/// Resource holding the registered commands, console variables and the console output.
+///
+/// Bundles register their commands and variables during `SystemBundle::load`. Since the
+/// `ConsoleBundle` may be added after them, fetch the console with
+/// `resources.get_mut_or_insert_with(Console::default)`:
+///
+/// ```
+/// use amethyst::{
+///     console::{CVar, Console},
+///     ecs::Resources,
+/// };
+///
+/// let mut resources = Resources::default();
+/// let mut console = resources.get_mut_or_insert_with(Console::default);
+///
+/// console.register_cvar("player_speed", CVar::new(4.0_f32, "Walking speed in m/s"));
+/// console.register_command(
+///     "heal",
+///     "Restores the player's health",
+///     |_args, _world, _resources| Ok("Healed".to_string()),
+/// );
+///
+/// assert_eq!(console.cvar::<f32>("player_speed"), Some(&4.0));
+/// ```
+///
+/// Besides the registered commands, the console understands `help [name]`, `list [prefix]`,
+/// `get <cvar>`, `set <cvar> <value>`, `reset <cvar>` and `clear`. Entering the name of a console
+/// variable prints it, entering it followed by a value sets it. Values are written as RON.
+#[derive(Debug)]
+pub struct Console {
+    commands: BTreeMap<String, Command>,
+    cvars: BTreeMap<String, Box<dyn ErasedCVar>>,
+    // Values read from the cvar file, applied when the variable is registered.
+    saved: BTreeMap<String, String>,
+    cvar_file: Option<PathBuf>,
+    cvars_changed: bool,
+    pending: VecDeque<String>,
+    output: VecDeque<String>,
+    output_capacity: usize,
+    output_generation: u64,
+    history: Vec<String>,
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        Console {
+            commands: BTreeMap::new(),
+            cvars: BTreeMap::new(),
+            saved: BTreeMap::new(),
+            cvar_file: None,
+            cvars_changed: false,
+            pending: VecDeque::new(),
+            output: VecDeque::new(),
+            output_capacity: 256,
+            output_generation: 0,
+            history: Vec::new(),
+        }
+    }
+}
+
+const BUILTINS: &[(&str, &str)] = &[
+    (
+        "help",
+        "Prints all commands and variables, or the help of the given one",
+    ),
+    (
+        "list",
+        "Lists commands and variables starting with the given prefix",
+    ),
+    ("get", "Prints the value of a variable"),
+    ("set", "Sets a variable to the given value"),
+    ("reset", "Resets a variable to its default value"),
+    ("clear", "Clears the console output"),
+];
+
+impl Console {
+    /// Registers a command called `name`, replacing any previous command of that name.
+    pub fn register_command<N, H, F>(&mut self, name: N, help: H, handler: F)
+    where
+        N: Into<String>,
+        H: Into<String>,
+        F: Fn(&[String], &mut World, &mut Resources) -> Result<String, Error>
+            + Send
+            + Sync
+            + 'static,
+    {
+        let name = name.into();
+        if BUILTINS.iter().any(|(builtin, _)| *builtin == name) {
+            warn!("Console command `{}` shadows a builtin command", name);
+        }
+        self.commands.insert(
+            name,
+            Command {
+                help: help.into(),
+                handler: Arc::new(handler),
+            },
+        );
+    }
+
+    /// Removes the command called `name`.
+    pub fn unregister_command(&mut self, name: &str) -> bool {
+        self.commands.remove(name).is_some()
+    }
+
+    /// Registers a console variable called `name`.
+    ///
+    /// If a value for `name` was persisted in the cvar file, it replaces the current value.
+    pub fn register_cvar<N: Into<String>, T: CVarValue>(&mut self, name: N, cvar: CVar<T>) {
+        let name = name.into();
+        let mut cvar: Box<dyn ErasedCVar> = Box::new(cvar);
+        if let Some(saved) = self.saved.get(&name) {
+            if let Err(e) = cvar.set_from_str(saved) {
+                warn!("Ignoring saved value of console variable `{}`: {}", name, e);
+            }
+        }
+        self.cvars.insert(name, cvar);
+    }
+
+    /// Removes the console variable called `name`.
+    pub fn unregister_cvar(&mut self, name: &str) -> bool {
+        self.cvars.remove(name).is_some()
+    }
+
+    /// Returns the value of the console variable `name`, if it exists and holds a `T`.
+    #[must_use]
+    pub fn cvar<T: CVarValue>(&self, name: &str) -> Option<&T> {
+        self.cvars
+            .get(name)
+            .and_then(|cvar| cvar.as_any().downcast_ref::<CVar<T>>())
+            .map(CVar::value)
+    }
+
+    /// Sets the console variable `name`. Returns `false` if it does not exist or does not hold a `T`.
+    pub fn set_cvar<T: CVarValue>(&mut self, name: &str, value: T) -> bool {
+        match self
+            .cvars
+            .get_mut(name)
+            .and_then(|cvar| cvar.as_any_mut().downcast_mut::<CVar<T>>())
+        {
+            Some(cvar) => {
+                cvar.set(value);
+                self.cvars_changed = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Queues `line` for execution. Queued lines are executed by the `ConsoleBundle` once per frame.
+    pub fn submit<S: Into<String>>(&mut self, line: S) {
+        self.pending.push_back(line.into());
+    }
+
+    pub(crate) fn take_pending(&mut self) -> Vec<String> {
+        self.pending.drain(..).collect()
+    }
+
+    /// Returns the names of all commands and variables starting with `prefix`, sorted.
+    #[must_use]
+    pub fn complete(&self, prefix: &str) -> Vec<String> {
+        let mut names: Vec<String> = BUILTINS
+            .iter()
+            .map(|(name, _)| *name)
+            .chain(self.commands.keys().map(String::as_str))
+            .chain(self.cvars.keys().map(String::as_str))
+            .filter(|name| name.starts_with(prefix))
+            .map(str::to_string)
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Completes the first word of `line` as far as it is unambiguous.
+    ///
+    /// Returns `line` unchanged if it already contains arguments or nothing matches.
+    #[must_use]
+    pub fn complete_line(&self, line: &str) -> String {
+        if line.contains(char::is_whitespace) {
+            return line.to_string();
+        }
+        let candidates = self.complete(line);
+        match candidates.as_slice() {
+            [] => line.to_string(),
+            [single] => format!("{} ", single),
+            [first, rest @ ..] => {
+                // Compare characters rather than bytes, so the prefix ends on a char boundary.
+                let mut common = first.len();
+                for other in rest {
+                    common = common.min(
+                        first
+                            .char_indices()
+                            .zip(other.chars())
+                            .find(|((_, a), b)| a != b)
+                            .map_or_else(|| first.len().min(other.len()), |((index, _), _)| index),
+                    );
+                }
+                first[..common].to_string()
+            }
+        }
+    }
+
+    /// Returns the console output, oldest line first.
+    pub fn output(&self) -> impl Iterator<Item = &str> {
+        self.output.iter().map(String::as_str)
+    }
+
+    /// Returns a number which changes whenever the output changes.
+    #[must_use]
+    pub fn output_generation(&self) -> u64 {
+        self.output_generation
+    }
+
+    /// Appends `line` to the console output.
+    pub fn print<S: Into<String>>(&mut self, line: S) {
+        for line in line.into().lines() {
+            if self.output.len() == self.output_capacity {
+                self.output.pop_front();
+            }
+            self.output.push_back(line.to_string());
+        }
+        self.output_generation += 1;
+    }
+
+    /// Clears the console output.
+    pub fn clear_output(&mut self) {
+        self.output.clear();
+        self.output_generation += 1;
+    }
+
+    /// Returns the previously executed lines, oldest first.
+    #[must_use]
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    /// Reads persisted console variable values from `path` and saves changed variables back to it.
+    ///
+    /// Values of variables which are already registered are applied immediately.
+    pub fn set_cvar_file<P: AsRef<Path>>(&mut self, path: P) {
+        let path = path.as_ref().to_path_buf();
+        if path.exists() {
+            match BTreeMap::<String, String>::load(&path) {
+                Ok(saved) => self.saved = saved,
+                Err(e) => error!("Failed to load console variables: {}", e),
+            }
+        }
+        for (name, value) in &self.saved {
+            if let Some(cvar) = self.cvars.get_mut(name) {
+                if let Err(e) = cvar.set_from_str(value) {
+                    warn!("Ignoring saved value of console variable `{}`: {}", name, e);
+                }
+            }
+        }
+        self.cvar_file = Some(path);
+    }
+
+    /// Writes all console variables which differ from their default value to the cvar file.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be written, in which case the variables are still considered
+    /// changed and saved again by the next call.
+    pub fn save_cvars(&mut self) -> Result<(), Error> {
+        let path = match &self.cvar_file {
+            Some(path) => path,
+            None => {
+                self.cvars_changed = false;
+                return Ok(());
+            }
+        };
+        for (name, cvar) in &self.cvars {
+            let value = cvar.value_string();
+            if value == cvar.default_string() {
+                self.saved.remove(name);
+            } else {
+                self.saved.insert(name.clone(), value);
+            }
+        }
+        self.saved
+            .write_format(ConfigFormat::Ron, path)
+            .map_err(|e| format_err!("Failed to save console variables: {}", e))?;
+        self.cvars_changed = false;
+        Ok(())
+    }
+
+    pub(crate) fn cvars_changed(&self) -> bool {
+        self.cvars_changed
+    }
+
+    fn help(&self, name: Option<&str>) -> Result<String, Error> {
+        match name {
+            Some(name) => {
+                BUILTINS
+                    .iter()
+                    .find(|(builtin, _)| *builtin == name)
+                    .map(|(_, help)| (*help).to_string())
+                    .or_else(|| self.commands.get(name).map(|c| c.help.clone()))
+                    .or_else(|| {
+                        self.cvars.get(name).map(|cvar| {
+                            format!(
+                                "{} = {} (default {})",
+                                cvar.help(),
+                                cvar.value_string(),
+                                cvar.default_string()
+                            )
+                        })
+                    })
+                    .ok_or_else(|| self.unknown(name))
+            }
+            None => Ok(self.list("")),
+        }
+    }
+
+    fn list(&self, prefix: &str) -> String {
+        let mut lines = Vec::new();
+        for (name, help) in BUILTINS.iter().filter(|(n, _)| n.starts_with(prefix)) {
+            lines.push(format!("{} - {}", name, help));
+        }
+        for (name, command) in self.commands.range(prefix.to_string()..) {
+            if !name.starts_with(prefix) {
+                break;
+            }
+            lines.push(format!("{} - {}", name, command.help));
+        }
+        for (name, cvar) in self.cvars.range(prefix.to_string()..) {
+            if !name.starts_with(prefix) {
+                break;
+            }
+            lines.push(format!(
+                "{} = {} - {}",
+                name,
+                cvar.value_string(),
+                cvar.help()
+            ));
+        }
+        lines.join("\n")
+    }
+
+    fn get(&self, name: &str) -> Result<String, Error> {
+        self.cvars
+            .get(name)
+            .map(|cvar| format!("{} = {}", name, cvar.value_string()))
+            .ok_or_else(|| self.unknown(name))
+    }
+
+    fn set(&mut self, name: &str, value: &str) -> Result<String, Error> {
+        let cvar = match self.cvars.get_mut(name) {
+            Some(cvar) => cvar,
+            None => return Err(self.unknown(name)),
+        };
+        cvar.set_from_str(value)
+            .map_err(|e| format_err!("Invalid value for `{}`: {}", name, e))?;
+        let value = cvar.value_string();
+        self.cvars_changed = true;
+        Ok(format!("{} = {}", name, value))
+    }
+
+    fn reset(&mut self, name: &str) -> Result<String, Error> {
+        let cvar = match self.cvars.get_mut(name) {
+            Some(cvar) => cvar,
+            None => return Err(self.unknown(name)),
+        };
+        cvar.reset();
+        let value = cvar.value_string();
+        self.cvars_changed = true;
+        Ok(format!("{} = {}", name, value))
+    }
+
+    fn unknown(&self, name: &str) -> Error {
+        let suggestions = self.complete(name);
+        if suggestions.is_empty() {
+            format_err!("Unknown command or variable `{}`", name)
+        } else {
+            format_err!(
+                "Unknown command or variable `{}`, did you mean: {}",
+                name,
+                suggestions.join(", ")
+            )
+        }
+    }
+}
+
+/// Splits `line` into words. Words containing whitespace can be wrapped in double quotes, a
+/// backslash escapes the next character.
+#[must_use]
+pub fn parse_line(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut quoted = false;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+                in_word = true;
+            }
+            '"' => {
+                quoted = !quoted;
+                in_word = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(current);
+    }
+    words
+}
+
+/// Executes a single console line and prints it, along with its result, to the console output.
+///
+/// This is what the `ConsoleBundle` does for every submitted line; call it directly to run
+/// console commands from code or tests.
+///
+/// # Errors
+///
+/// Fails if the command is unknown or returns an error itself.
+///
+/// # Panics
+///
+/// Panics if there is no [`Console`] resource.
+pub fn execute(line: &str, world: &mut World, resources: &mut Resources) -> Result<String, Error> {
+    let words = parse_line(line);
+    let result = match words.split_first() {
+        None => Ok(String::new()),
+        Some((name, args)) => dispatch(name, args, world, resources),
+    };
+
+    let mut console = resources
+        .get_mut::<Console>()
+        .expect("The Console resource is missing");
+    if !line.trim().is_empty() {
+        console.history.push(line.to_string());
+        console.print(format!("> {}", line));
+    }
+    match &result {
+        Ok(output) if !output.is_empty() => console.print(output.clone()),
+        Ok(_) => {}
+        Err(e) => console.print(format!("error: {}", e)),
+    }
+    result
+}
+
+fn dispatch(
+    name: &str,
+    args: &[String],
+    world: &mut World,
+    resources: &mut Resources,
+) -> Result<String, Error> {
+    let handler = {
+        let mut console = resources
+            .get_mut::<Console>()
+            .expect("The Console resource is missing");
+        let arg = |index: usize| args.get(index).map(String::as_str);
+        let required = |index: usize| {
+            arg(index).ok_or_else(|| format_err!("`{}` expects {} argument(s)", name, index + 1))
+        };
+
+        match name {
+            "help" => return console.help(arg(0)),
+            "list" => return Ok(console.list(arg(0).unwrap_or(""))),
+            "get" => return console.get(required(0)?),
+            "set" => return console.set(required(0)?, &args[1..].join(" ")),
+            "reset" => return console.reset(required(0)?),
+            "clear" => {
+                console.clear_output();
+                return Ok(String::new());
+            }
+            _ => {}
+        }
+
+        match console.commands.get(name) {
+            Some(command) => Arc::clone(&command.handler),
+            None if console.cvars.contains_key(name) => {
+                return if args.is_empty() {
+                    console.get(name)
+                } else {
+                    console.set(name, &args.join(" "))
+                };
+            }
+            None => return Err(console.unknown(name)),
+        }
+    };
+
+    // The console is not borrowed here, so handlers may access it through `resources`.
+    handler(args, world, resources)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> (World, Resources) {
+        let mut resources = Resources::default();
+        let mut console = Console::default();
+        console.register_cvar("speed", CVar::new(1.0_f32, "Speed"));
+        console.register_command("echo", "Prints its arguments", |args, _, _| {
+            Ok(args.join(" "))
+        });
+        resources.insert(console);
+        (World::default(), resources)
+    }
+
+    #[test]
+    fn parses_quotes_and_escapes() {
+        assert_eq!(
+            parse_line(r#"say "hello world" a\ b"#),
+            vec!["say", "hello world", "a b"]
+        );
+        assert_eq!(parse_line("  "), Vec::<String>::new());
+    }
+
+    #[test]
+    fn executes_commands_and_cvars() {
+        let (mut world, mut resources) = setup();
+
+        assert_eq!(
+            execute("echo a b", &mut world, &mut resources).unwrap(),
+            "a b"
+        );
+        execute("speed 2.5", &mut world, &mut resources).unwrap();
+        assert!(execute("set speed fast", &mut world, &mut resources).is_err());
+        assert!(execute("sped", &mut world, &mut resources).is_err());
+
+        let console = resources.get::<Console>().unwrap();
+        assert_eq!(console.cvar::<f32>("speed"), Some(&2.5));
+        assert_eq!(console.history().len(), 4);
+    }
+
+    #[test]
+    fn failed_save_keeps_cvars_changed() {
+        let (mut world, mut resources) = setup();
+        let dir =
+            std::env::temp_dir().join(format!("amethyst_console_save_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        resources
+            .get_mut::<Console>()
+            .unwrap()
+            .set_cvar_file(dir.join("cvars.ron"));
+        execute("speed 2.5", &mut world, &mut resources).unwrap();
+
+        let mut console = resources.get_mut::<Console>().unwrap();
+        assert!(console.save_cvars().is_err());
+        assert!(console.cvars_changed());
+
+        std::fs::create_dir_all(&dir).unwrap();
+        console.save_cvars().unwrap();
+        assert!(!console.cvars_changed());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn completes_names() {
+        let (_, resources) = setup();
+        let console = resources.get::<Console>().unwrap();
+
+        assert_eq!(console.complete("s"), vec!["set", "speed"]);
+        assert_eq!(console.complete_line("s"), "s");
+        assert_eq!(console.complete_line("sp"), "speed ");
+        assert_eq!(console.complete_line("re"), "reset ");
+    }
+
+    #[test]
+    fn completes_multibyte_names() {
+        let mut console = Console::default();
+        // `é` and `è` share their first byte in UTF-8.
+        console.register_command("caf\u{e9}", "", |_, _, _| Ok(String::new()));
+        console.register_command("caf\u{e8}", "", |_, _, _| Ok(String::new()));
+
+        assert_eq!(console.complete_line("c"), "caf");
+    }
+}
@@ -0,0 +1,122 @@
+//! Typed console variables.
+
+use std::{any::Any, fmt};
+
+use amethyst_config::{Config, ConfigError, ConfigFormat};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Values which can be stored in a console variable.
+///
+/// Values are parsed from and printed as RON, the same way `amethyst_config::Config` types are
+/// read from disk, so any config type can back a console variable.
+pub trait CVarValue: Serialize + DeserializeOwned + Clone + Send + Sync + 'static {}
+
+impl<T> CVarValue for T where T: Serialize + DeserializeOwned + Clone + Send + Sync + 'static {}
+
+/// A console variable holding a value of type `T`.
+#[derive(Clone, Debug)]
+pub struct CVar<T> {
+    value: T,
+    default: T,
+    help: String,
+}
+
+impl<T: CVarValue> CVar<T> {
+    /// Creates a console variable which starts at, and can be reset to, `default`.
+    pub fn new<S: Into<String>>(default: T, help: S) -> Self {
+        CVar {
+            value: default.clone(),
+            default,
+            help: help.into(),
+        }
+    }
+
+    /// Returns the current value.
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    /// Replaces the current value.
+    pub fn set(&mut self, value: T) {
+        self.value = value;
+    }
+
+    /// Returns the default value.
+    pub fn default_value(&self) -> &T {
+        &self.default
+    }
+}
+
+/// Object safe view on a [`CVar`], used by the [`Console`](crate::Console) to handle all
+/// variables alike.
+pub(crate) trait ErasedCVar: Send + Sync {
+    fn help(&self) -> &str;
+    fn value_string(&self) -> String;
+    fn default_string(&self) -> String;
+    fn set_from_str(&mut self, value: &str) -> Result<(), ConfigError>;
+    fn reset(&mut self);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: CVarValue> ErasedCVar for CVar<T> {
+    fn help(&self) -> &str {
+        &self.help
+    }
+
+    fn value_string(&self) -> String {
+        to_ron(&self.value)
+    }
+
+    fn default_string(&self) -> String {
+        to_ron(&self.default)
+    }
+
+    fn set_from_str(&mut self, value: &str) -> Result<(), ConfigError> {
+        self.value = T::load_bytes_format(ConfigFormat::Ron, value.as_bytes())?;
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.value = self.default.clone();
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl fmt::Debug for dyn ErasedCVar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CVar")
+            .field("value", &self.value_string())
+            .field("help", &self.help())
+            .finish()
+    }
+}
+
+fn to_ron<T: Serialize>(value: &T) -> String {
+    ron::ser::to_string(value).unwrap_or_else(|e| format!("<{}>", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_prints_ron() {
+        let mut cvar = CVar::new((1.0_f32, false), "test");
+        cvar.set_from_str("(2.5, true)").unwrap();
+
+        assert_eq!(*cvar.value(), (2.5, true));
+        assert_eq!(cvar.value_string(), "(2.5,true)");
+        assert!(cvar.set_from_str("oops").is_err());
+
+        cvar.reset();
+        assert_eq!(*cvar.value(), (1.0, false));
+    }
+}
@@ -0,0 +1,114 @@
+//! Headless console input through stdin or a local TCP socket.
+
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::mpsc::{channel, Receiver, Sender},
+    thread,
+};
+
+use log::{error, info};
+
+/// A line received from outside the game, along with where to send the result.
+#[derive(Debug)]
+pub(crate) struct RemoteLine {
+    pub(crate) line: String,
+    /// `None` for stdin, whose results are printed to stdout.
+    pub(crate) reply: Option<Sender<String>>,
+}
+
+/// Where the console reads lines from when there is no in-game UI.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConsoleInput {
+    /// Reads lines from the standard input of the process.
+    Stdin,
+    /// Listens for TCP connections on the given address and reads lines from each of them.
+    ///
+    /// Bind to a loopback address unless you really want to expose the console to the network.
+    Socket(SocketAddr),
+}
+
+impl ConsoleInput {
+    /// Starts a background thread reading lines into `sender`.
+    pub(crate) fn spawn(self, sender: Sender<RemoteLine>) -> io::Result<()> {
+        match self {
+            ConsoleInput::Stdin => {
+                thread::Builder::new()
+                    .name("console stdin".into())
+                    .spawn(move || read_stdin(&sender))?;
+            }
+            ConsoleInput::Socket(address) => {
+                let listener = TcpListener::bind(address)?;
+                info!("Console listening on {}", listener.local_addr()?);
+                thread::Builder::new()
+                    .name("console socket".into())
+                    .spawn(move || accept(&listener, &sender))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_stdin(sender: &Sender<RemoteLine>) {
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        match line {
+            Ok(line) => {
+                if sender.send(RemoteLine { line, reply: None }).is_err() {
+                    // The console was unloaded.
+                    return;
+                }
+            }
+            Err(e) => {
+                error!("Failed to read console input from stdin: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+fn accept(listener: &TcpListener, sender: &Sender<RemoteLine>) {
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let sender = sender.clone();
+                let spawned = thread::Builder::new()
+                    .name("console connection".into())
+                    .spawn(move || {
+                        if let Err(e) = serve(stream, &sender) {
+                            error!("Console connection failed: {}", e);
+                        }
+                    });
+                if let Err(e) = spawned {
+                    error!("Failed to spawn console connection thread: {}", e);
+                }
+            }
+            Err(e) => error!("Failed to accept console connection: {}", e),
+        }
+    }
+}
+
+fn serve(stream: TcpStream, sender: &Sender<RemoteLine>) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let (reply, replies): (Sender<String>, Receiver<String>) = channel();
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if sender
+            .send(RemoteLine {
+                line,
+                reply: Some(reply.clone()),
+            })
+            .is_err()
+        {
+            return Ok(());
+        }
+        match replies.recv() {
+            Ok(output) => {
+                writer.write_all(output.as_bytes())?;
+                writer.write_all(b"\n")?;
+            }
+            Err(_) => return Ok(()),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,35 @@
+//! In-game developer console.
+//!
+//! Games register commands and typed console variables on the [`Console`] resource. Lines are
+//! executed once per frame by the [`ConsoleBundle`], either typed into the in-game console
+//! (`ui` feature) or read from stdin or a local TCP socket for headless builds.
+
+#![doc(
+    html_logo_url = "https://amethyst.rs/brand/logo-standard.svg",
+    html_root_url = "https://docs.amethyst.rs/stable"
+)]
+#![deny(
+    missing_debug_implementations,
+    missing_docs,
+    rust_2018_idioms,
+    rust_2018_compatibility,
+    clippy::all
+)]
+#![warn(clippy::pedantic)]
+#![allow(clippy::new_without_default, clippy::module_name_repetitions)]
+
+#[cfg(feature = "ui")]
+pub use self::ui::{ConsoleUi, ConsoleUiBundle, ConsoleUiSystem};
+pub use self::{
+    bundle::ConsoleBundle,
+    console::{execute, parse_line, CommandFn, Console},
+    cvar::{CVar, CVarValue},
+    input::ConsoleInput,
+};
+
+mod bundle;
+mod console;
+mod cvar;
+mod input;
+#[cfg(feature = "ui")]
+mod ui;
@@ -0,0 +1,297 @@
+//! Console rendered through `amethyst_ui`.
+
+use amethyst_core::{
+    ecs::{
+        DispatcherBuilder, Entity, IntoQuery, ParallelRunnable, Resources, System, SystemBuilder,
+        SystemBundle, World,
+    },
+    shrev::{EventChannel, ReaderId},
+    HiddenPropagate,
+};
+use amethyst_error::{format_err, Error};
+use amethyst_ui::{
+    Anchor, LineMode, Selected, Stretch, TextEditing, UiEvent, UiEventType, UiText, UiTransform,
+};
+use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+
+use crate::Console;
+
+/// Entities making up the console UI.
+#[derive(Debug, Clone, Copy)]
+pub struct ConsoleUi {
+    /// Entity displaying the console output.
+    pub output: Entity,
+    /// Entity holding the editable input line.
+    pub input: Entity,
+    /// Whether the console is currently shown.
+    pub open: bool,
+}
+
+/// Shows the [`Console`] at the top of the screen, toggled with a key.
+///
+/// Lines are entered in a `TextEditing` field and submitted with Enter. Tab completes the command
+/// name, the up and down arrows walk through the history. Requires the `ConsoleBundle` and the
+/// `UiBundle` to be added before this bundle.
+#[derive(Debug)]
+pub struct ConsoleUiBundle {
+    toggle_key: VirtualKeyCode,
+    lines: usize,
+    font_size: f32,
+}
+
+impl Default for ConsoleUiBundle {
+    fn default() -> Self {
+        ConsoleUiBundle {
+            toggle_key: VirtualKeyCode::Grave,
+            lines: 16,
+            font_size: 16.0,
+        }
+    }
+}
+
+impl ConsoleUiBundle {
+    /// Sets the key opening and closing the console, the grave accent key by default.
+    #[must_use]
+    pub fn with_toggle_key(mut self, key: VirtualKeyCode) -> Self {
+        self.toggle_key = key;
+        self
+    }
+
+    /// Sets how many lines of output are shown.
+    #[must_use]
+    pub fn with_lines(mut self, lines: usize) -> Self {
+        self.lines = lines;
+        self
+    }
+
+    /// Sets the font size of the console.
+    #[must_use]
+    pub fn with_font_size(mut self, font_size: f32) -> Self {
+        self.font_size = font_size;
+        self
+    }
+}
+
+impl SystemBundle for ConsoleUiBundle {
+    fn load(
+        &mut self,
+        world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        let line_height = self.font_size * 1.25;
+        #[allow(clippy::cast_precision_loss)]
+        let output_height = line_height * self.lines as f32;
+
+        let mut output_transform = UiTransform::new(
+            "console_output".to_string(),
+            Anchor::TopMiddle,
+            Anchor::TopMiddle,
+            0.0,
+            0.0,
+            1000.0,
+            0.0,
+            output_height,
+        );
+        output_transform.stretch = Stretch::X { x_margin: 0.0 };
+        output_transform.opaque = false;
+        let output = world.push((
+            output_transform,
+            UiText::new(
+                None,
+                String::new(),
+                [0.9, 0.9, 0.9, 1.0],
+                self.font_size,
+                LineMode::Wrap,
+                Anchor::BottomLeft,
+            ),
+            HiddenPropagate::new(),
+        ));
+
+        let mut input_transform = UiTransform::new(
+            "console_input".to_string(),
+            Anchor::TopMiddle,
+            Anchor::TopMiddle,
+            0.0,
+            -output_height,
+            1000.0,
+            0.0,
+            line_height,
+        );
+        input_transform.stretch = Stretch::X { x_margin: 0.0 };
+        let input = world.push((
+            input_transform,
+            UiText::new(
+                None,
+                String::new(),
+                [1.0, 1.0, 1.0, 1.0],
+                self.font_size,
+                LineMode::Single,
+                Anchor::MiddleLeft,
+            ),
+            TextEditing::new(256, [0.0, 0.0, 0.0, 1.0], [0.8, 0.8, 0.8, 1.0], false),
+            HiddenPropagate::new(),
+        ));
+
+        resources.insert(ConsoleUi {
+            output,
+            input,
+            open: false,
+        });
+
+        let ui_reader = resources
+            .get_mut::<EventChannel<UiEvent>>()
+            .ok_or_else(|| format_err!("ConsoleUiBundle requires the UiBundle to be added first"))?
+            .register_reader();
+        let window_reader = resources
+            .get_mut::<EventChannel<Event<'static, ()>>>()
+            .ok_or_else(|| format_err!("ConsoleUiBundle requires the window event channel"))?
+            .register_reader();
+
+        builder.add_system(ConsoleUiSystem {
+            ui_reader,
+            window_reader,
+            toggle_key: self.toggle_key,
+            lines: self.lines,
+            shown_generation: None,
+            history_index: None,
+        });
+
+        Ok(())
+    }
+}
+
+/// Opens and closes the console, submits lines and shows the output.
+#[derive(Debug)]
+pub struct ConsoleUiSystem {
+    ui_reader: ReaderId<UiEvent>,
+    window_reader: ReaderId<Event<'static, ()>>,
+    toggle_key: VirtualKeyCode,
+    lines: usize,
+    shown_generation: Option<u64>,
+    history_index: Option<usize>,
+}
+
+impl System for ConsoleUiSystem {
+    fn build(mut self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("ConsoleUiSystem")
+                .write_resource::<Console>()
+                .write_resource::<ConsoleUi>()
+                .read_resource::<EventChannel<UiEvent>>()
+                .read_resource::<EventChannel<Event<'static, ()>>>()
+                .with_query(<(Entity, &mut UiText, Option<&mut TextEditing>)>::query())
+                .build(
+                    move |commands, world, (console, ui, ui_events, window_events), texts| {
+                        let mut pressed = Vec::new();
+                        for event in window_events.read(&mut self.window_reader) {
+                            if let Event::WindowEvent {
+                                event:
+                                    WindowEvent::KeyboardInput {
+                                        input:
+                                            KeyboardInput {
+                                                state: ElementState::Pressed,
+                                                virtual_keycode: Some(key),
+                                                ..
+                                            },
+                                        ..
+                                    },
+                                ..
+                            } = *event
+                            {
+                                pressed.push(key);
+                            }
+                        }
+
+                        let mut submitted = false;
+                        for event in ui_events.read(&mut self.ui_reader) {
+                            if event.event_type == UiEventType::ValueCommit
+                                && event.target == ui.input
+                            {
+                                submitted = true;
+                            }
+                        }
+
+                        let mut toggled = false;
+                        for key in &pressed {
+                            if *key == self.toggle_key {
+                                ui.open = !ui.open;
+                                toggled = true;
+                                if ui.open {
+                                    commands.remove_component::<HiddenPropagate>(ui.output);
+                                    commands.remove_component::<HiddenPropagate>(ui.input);
+                                    commands.add_component(ui.input, Selected);
+                                } else {
+                                    commands.add_component(ui.output, HiddenPropagate::new());
+                                    commands.add_component(ui.input, HiddenPropagate::new());
+                                    commands.remove_component::<Selected>(ui.input);
+                                }
+                            }
+                        }
+
+                        for (entity, text, editing) in texts.iter_mut(world) {
+                            if *entity == ui.input && ui.open {
+                                let before = text.text.clone();
+                                if toggled {
+                                    // Drop the character typed with the toggle key.
+                                    text.text.clear();
+                                    self.history_index = None;
+                                }
+                                for key in &pressed {
+                                    match key {
+                                        VirtualKeyCode::Tab => {
+                                            text.text = console.complete_line(&text.text);
+                                        }
+                                        VirtualKeyCode::Up => {
+                                            let history = console.history();
+                                            if !history.is_empty() {
+                                                let index = self
+                                                    .history_index
+                                                    .map_or(history.len() - 1, |i| {
+                                                        i.saturating_sub(1)
+                                                    });
+                                                self.history_index = Some(index);
+                                                text.text = history[index].clone();
+                                            }
+                                        }
+                                        VirtualKeyCode::Down => {
+                                            if let Some(index) = self.history_index {
+                                                let history = console.history();
+                                                if index + 1 < history.len() {
+                                                    self.history_index = Some(index + 1);
+                                                    text.text = history[index + 1].clone();
+                                                } else {
+                                                    self.history_index = None;
+                                                    text.text.clear();
+                                                }
+                                            }
+                                        }
+                                        _ => {}
+                                    }
+                                }
+                                if submitted {
+                                    console.submit(std::mem::take(&mut text.text));
+                                    self.history_index = None;
+                                }
+                                if let Some(editing) = editing {
+                                    if text.text != before {
+                                        #[allow(clippy::cast_possible_wrap)]
+                                        let end = text.text.chars().count() as isize;
+                                        editing.cursor_position = end;
+                                        editing.highlight_vector = 0;
+                                    }
+                                }
+                            } else if *entity == ui.output
+                                && self.shown_generation != Some(console.output_generation())
+                            {
+                                let output: Vec<&str> = console.output().collect();
+                                let start = output.len().saturating_sub(self.lines);
+                                text.text = output[start..].join("\n");
+                                self.shown_generation = Some(console.output_generation());
+                            }
+                        }
+                    },
+                ),
+        )
+    }
+}
@@ -0,0 +1,342 @@
+//! World checksums for detecting divergence between two simulations.
+//!
+//! Lockstep multiplayer and replay validation rely on two runs producing exactly the same world
+//! when fed the same input. [`WorldChecksum`] hashes a selected set of components into a single
+//! `u64` which can be compared between peers or against a recorded run. The [`ChecksumBundle`]
+//! computes it at the end of every frame and keeps a bounded [`ChecksumHistory`].
+//!
+//! The checksum does not depend on entity ids or on the order in which entities are stored, since
+//! neither is guaranteed to be stable between two processes. Each component type is identified by
+//! a name chosen by the caller, which must be the same in every build taking part in a comparison.
+//!
+//! # Examples
+//!
+//! ```
+//! use amethyst::core::{checksum::WorldChecksum, ecs::World, Transform};
+//!
+//! #[derive(Hash)]
+//! struct Health(u32);
+//!
+//! let checksum = WorldChecksum::new()
+//!     .with_component::<Health>("health")
+//!     .with_component_by::<Transform, _>("transform", |transform, hasher| {
+//!         for value in transform.matrix().iter() {
+//!             hasher.write_f32(*value);
+//!         }
+//!     });
+//!
+//! let mut world = World::default();
+//! world.push((Health(10), Transform::default()));
+//! let first = checksum.compute(&world);
+//!
+//! world.push((Health(5),));
+//! assert_ne!(first, checksum.compute(&world));
+//! ```
+
+use std::{
+    collections::VecDeque,
+    convert::TryFrom,
+    fmt,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use amethyst_error::Error;
+
+use crate::ecs::{
+    storage::Component, DispatcherBuilder, IntoQuery, Resources, SystemBundle, World,
+};
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// A 64 bit FNV-1a hasher.
+///
+/// Unlike `std::collections::hash_map::DefaultHasher` its output is specified, so checksums can be
+/// compared between builds, platforms and processes.
+#[derive(Clone, Copy, Debug)]
+pub struct ChecksumHasher(u64);
+
+impl Default for ChecksumHasher {
+    fn default() -> Self {
+        ChecksumHasher(FNV_OFFSET)
+    }
+}
+
+impl ChecksumHasher {
+    /// Feeds the bit pattern of a `f32` into the hasher.
+    pub fn write_f32(&mut self, value: f32) {
+        self.write_u32(value.to_bits());
+    }
+
+    /// Feeds the bit pattern of a `f64` into the hasher.
+    pub fn write_f64(&mut self, value: f64) {
+        self.write_u64(value.to_bits());
+    }
+}
+
+impl Hasher for ChecksumHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    // Hash integers as little endian, so the result does not depend on the platform.
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+}
+
+type ComponentChecksum = Arc<dyn Fn(&World) -> (u64, u64) + Send + Sync>;
+
+/// Computes a checksum over the selected component types of a [World].
+#[derive(Clone, Default)]
+pub struct WorldChecksum {
+    components: Vec<(&'static str, ComponentChecksum)>,
+}
+
+impl fmt::Debug for WorldChecksum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WorldChecksum")
+            .field(
+                "components",
+                &self
+                    .components
+                    .iter()
+                    .map(|(name, _)| name)
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl WorldChecksum {
+    /// Creates a checksum which does not cover any components yet.
+    #[must_use]
+    pub fn new() -> Self {
+        WorldChecksum::default()
+    }
+
+    /// Includes all components of type `T` in the checksum, using their `Hash` implementation.
+    ///
+    /// `name` identifies the component type in the checksum. Unlike `std::any::type_name` it does
+    /// not change between compiler versions, so peers built differently still agree.
+    #[must_use]
+    pub fn with_component<T: Component + Hash>(self, name: &'static str) -> Self {
+        self.with_component_by::<T, _>(name, |component, hasher| component.hash(hasher))
+    }
+
+    /// Includes all components of type `T` in the checksum under `name`, using `hash` to feed each
+    /// of them into the hasher. Use this for components which do not implement `Hash`, e.g.
+    /// because they hold floating point values.
+    #[must_use]
+    pub fn with_component_by<T, F>(mut self, name: &'static str, hash: F) -> Self
+    where
+        T: Component,
+        F: Fn(&T, &mut ChecksumHasher) + Send + Sync + 'static,
+    {
+        let checksum = move |world: &World| {
+            let mut sum = 0_u64;
+            let mut count = 0_u64;
+            <&T>::query().for_each(world, |component| {
+                let mut hasher = ChecksumHasher::default();
+                hash(component, &mut hasher);
+                // Summing keeps the result independent of the storage order.
+                sum = sum.wrapping_add(hasher.finish());
+                count += 1;
+            });
+            (sum, count)
+        };
+        self.components.push((name, Arc::new(checksum)));
+        self
+    }
+
+    /// Computes the checksum of `world`.
+    #[must_use]
+    pub fn compute(&self, world: &World) -> u64 {
+        let mut hasher = ChecksumHasher::default();
+        for (name, checksum) in &self.components {
+            let (sum, count) = checksum(world);
+            hasher.write(name.as_bytes());
+            hasher.write_u64(count);
+            hasher.write_u64(sum);
+        }
+        hasher.finish()
+    }
+}
+
+/// Checksums recorded by the [`ChecksumBundle`], oldest first.
+#[derive(Clone, Debug)]
+pub struct ChecksumHistory {
+    frames: VecDeque<(u64, u64)>,
+    capacity: usize,
+    next_frame: u64,
+}
+
+impl ChecksumHistory {
+    /// Creates an empty history which keeps the checksums of the last `capacity` frames.
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        ChecksumHistory {
+            frames: VecDeque::with_capacity(capacity),
+            capacity,
+            next_frame: 0,
+        }
+    }
+
+    /// Records the checksum of the next frame.
+    pub fn push(&mut self, checksum: u64) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back((self.next_frame, checksum));
+        self.next_frame += 1;
+    }
+
+    /// Returns the checksum recorded for `frame`, if it is still in the history.
+    #[must_use]
+    pub fn get(&self, frame: u64) -> Option<u64> {
+        let first = self.frames.front()?.0;
+        let index = usize::try_from(frame.checked_sub(first)?).ok()?;
+        self.frames.get(index).map(|(_, checksum)| *checksum)
+    }
+
+    /// Returns the most recent frame number and its checksum.
+    #[must_use]
+    pub fn latest(&self) -> Option<(u64, u64)> {
+        self.frames.back().copied()
+    }
+
+    /// Iterates over `(frame, checksum)` pairs, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.frames.iter().copied()
+    }
+
+    /// Returns the first frame known to both histories for which the checksums differ.
+    #[must_use]
+    pub fn first_divergence(&self, other: &ChecksumHistory) -> Option<u64> {
+        self.iter().find_map(|(frame, checksum)| {
+            other
+                .get(frame)
+                .filter(|other| *other != checksum)
+                .map(|_| frame)
+        })
+    }
+}
+
+/// Records a [`WorldChecksum`] into the [`ChecksumHistory`] resource at the end of every frame.
+///
+/// Add this bundle last, so the checksum covers everything the other systems did in the frame.
+#[derive(Debug)]
+pub struct ChecksumBundle {
+    checksum: WorldChecksum,
+    capacity: usize,
+}
+
+impl ChecksumBundle {
+    /// Creates a bundle recording `checksum` for the last `capacity` frames.
+    #[must_use]
+    pub fn new(checksum: WorldChecksum, capacity: usize) -> Self {
+        ChecksumBundle { checksum, capacity }
+    }
+}
+
+impl SystemBundle for ChecksumBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        resources.insert(ChecksumHistory::new(self.capacity));
+
+        let checksum = self.checksum.clone();
+        builder.add_thread_local_fn(move |world, resources| {
+            let value = checksum.compute(world);
+            resources
+                .get_mut::<ChecksumHistory>()
+                .expect("ChecksumHistory resource was removed")
+                .push(value);
+        });
+
+        Ok(())
+    }
+
+    fn unload(&mut self, _world: &mut World, resources: &mut Resources) -> Result<(), Error> {
+        resources.remove::<ChecksumHistory>();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Hash)]
+    struct Health(u32);
+
+    #[test]
+    fn checksum_ignores_insertion_order() {
+        let checksum = WorldChecksum::new().with_component::<Health>("health");
+
+        let mut a = World::default();
+        a.push((Health(1),));
+        a.push((Health(2),));
+
+        let mut b = World::default();
+        b.push((Health(2),));
+        b.push((Health(1),));
+
+        assert_eq!(checksum.compute(&a), checksum.compute(&b));
+
+        b.push((Health(3),));
+        assert_ne!(checksum.compute(&a), checksum.compute(&b));
+    }
+
+    #[test]
+    fn checksum_depends_on_component_names() {
+        let mut world = World::default();
+        world.push((Health(1),));
+
+        let health = WorldChecksum::new().with_component::<Health>("health");
+        let renamed = WorldChecksum::new().with_component::<Health>("hit_points");
+        assert_ne!(health.compute(&world), renamed.compute(&world));
+    }
+
+    #[test]
+    fn history_reports_first_divergence() {
+        let mut a = ChecksumHistory::new(4);
+        let mut b = ChecksumHistory::new(4);
+        for value in &[1, 2, 3, 4, 5] {
+            a.push(*value);
+        }
+        for value in &[1, 2, 3, 9, 10] {
+            b.push(*value);
+        }
+
+        assert_eq!(a.get(0), None);
+        assert_eq!(a.latest(), Some((4, 5)));
+        assert_eq!(a.first_divergence(&b), Some(3));
+    }
+}
@@ -0,0 +1,290 @@
+//! Named clocks which can be paused and scaled independently.
+//!
+//! The [`Clocks`] resource always contains two clocks:
+//!
+//! * [`REAL_CLOCK`] follows wall time. It can't be paused or scaled, which makes it the right
+//!   choice for menus, UI animations and anything else that must keep running while gameplay is
+//!   paused.
+//! * [`GAME_CLOCK`] drives gameplay. Its time scale and pause state are mirrored into the
+//!   [`Time`](crate::Time) resource whenever they change, so systems using `Time::delta_time`
+//!   follow it. A scale set directly with `Time::set_time_scale` is kept until the next change.
+//!
+//! Further clocks can be added with [`Clocks::insert`], e.g. to slow down a single group of
+//! entities. Systems opt into a clock by looking it up by name.
+//!
+//! # Examples
+//!
+//! ```
+//! use amethyst::core::clock::{Clock, Clocks, GAME_CLOCK};
+//!
+//! let mut clocks = Clocks::default();
+//! clocks.insert("enemies", Clock::new());
+//!
+//! // Bullet time for everybody but the player.
+//! clocks.get_mut("enemies").unwrap().set_time_scale(0.25);
+//! // Opening the pause menu.
+//! clocks.get_mut(GAME_CLOCK).unwrap().pause();
+//! ```
+
+use std::{collections::HashMap, time::Duration};
+
+/// Name of the unscaled clock following wall time.
+pub const REAL_CLOCK: &str = "real";
+
+/// Name of the clock driving gameplay and the [`Time`](crate::Time) resource.
+pub const GAME_CLOCK: &str = "game";
+
+/// A clock which is advanced once per frame, scaled by its time scale and stopped while paused.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Clock {
+    time_scale: f32,
+    paused: bool,
+    delta_time: Duration,
+    absolute_time: Duration,
+    frame_number: u64,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock {
+            time_scale: 1.0,
+            paused: false,
+            delta_time: Duration::from_secs(0),
+            absolute_time: Duration::from_secs(0),
+            frame_number: 0,
+        }
+    }
+}
+
+impl Clock {
+    /// Creates a running clock with a time scale of `1.0`.
+    #[must_use]
+    pub fn new() -> Self {
+        Clock::default()
+    }
+
+    /// Returns the time scale of this clock.
+    #[must_use]
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    /// Sets the time scale of this clock. `0.5` runs at half speed, `2.0` at double speed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `time_scale` is negative or not finite.
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        assert!(
+            time_scale >= 0.0 && time_scale.is_finite(),
+            "Invalid time scale {}",
+            time_scale
+        );
+        self.time_scale = time_scale;
+    }
+
+    /// Returns `true` if this clock is paused.
+    #[must_use]
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Stops this clock. The time scale is kept and applies again once the clock is resumed.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Resumes this clock after a call to [`Clock::pause`].
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// Returns the scale the clock currently advances with, `0.0` while paused.
+    #[must_use]
+    pub fn effective_time_scale(&self) -> f32 {
+        if self.paused {
+            0.0
+        } else {
+            self.time_scale
+        }
+    }
+
+    /// Returns the time this clock advanced by during the last frame.
+    #[must_use]
+    pub fn delta_time(&self) -> Duration {
+        self.delta_time
+    }
+
+    /// Returns the time this clock advanced by during the last frame, in seconds.
+    #[must_use]
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta_time.as_secs_f32()
+    }
+
+    /// Returns the total time this clock has advanced by.
+    #[must_use]
+    pub fn absolute_time(&self) -> Duration {
+        self.absolute_time
+    }
+
+    /// Returns the total time this clock has advanced by, in seconds.
+    #[must_use]
+    pub fn absolute_time_seconds(&self) -> f64 {
+        self.absolute_time.as_secs_f64()
+    }
+
+    /// Returns the number of frames this clock advanced while not paused.
+    #[must_use]
+    pub fn frame_number(&self) -> u64 {
+        self.frame_number
+    }
+
+    /// Advances this clock by `real_delta` wall time, applying its time scale.
+    pub fn advance(&mut self, real_delta: Duration) {
+        if self.paused {
+            self.delta_time = Duration::from_secs(0);
+            return;
+        }
+        self.delta_time = real_delta.mul_f64(f64::from(self.time_scale));
+        self.absolute_time += self.delta_time;
+        self.frame_number += 1;
+    }
+}
+
+/// Resource holding all named clocks.
+///
+/// Advanced by the application once per frame, right before the [`Time`](crate::Time) resource.
+#[derive(Clone, Debug)]
+pub struct Clocks {
+    real: Clock,
+    clocks: HashMap<String, Clock>,
+}
+
+impl Default for Clocks {
+    fn default() -> Self {
+        let mut clocks = HashMap::new();
+        clocks.insert(GAME_CLOCK.to_string(), Clock::new());
+        Clocks {
+            real: Clock::new(),
+            clocks,
+        }
+    }
+}
+
+impl Clocks {
+    /// Returns the clock called `name`, including [`REAL_CLOCK`].
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Clock> {
+        if name == REAL_CLOCK {
+            Some(&self.real)
+        } else {
+            self.clocks.get(name)
+        }
+    }
+
+    /// Returns the clock called `name` for modification.
+    ///
+    /// Always returns `None` for [`REAL_CLOCK`], since it can't be paused or scaled.
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Clock> {
+        self.clocks.get_mut(name)
+    }
+
+    /// Returns the unscaled clock following wall time.
+    #[must_use]
+    pub fn real(&self) -> &Clock {
+        &self.real
+    }
+
+    /// Returns the clock driving gameplay.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the game clock was removed.
+    #[must_use]
+    pub fn game(&self) -> &Clock {
+        self.clocks
+            .get(GAME_CLOCK)
+            .expect("The game clock must not be removed")
+    }
+
+    /// Returns the clock driving gameplay for modification.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the game clock was removed.
+    pub fn game_mut(&mut self) -> &mut Clock {
+        self.clocks
+            .get_mut(GAME_CLOCK)
+            .expect("The game clock must not be removed")
+    }
+
+    /// Adds a clock called `name`, returning the clock it replaced.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is [`REAL_CLOCK`].
+    pub fn insert<N: Into<String>>(&mut self, name: N, clock: Clock) -> Option<Clock> {
+        let name = name.into();
+        assert_ne!(name, REAL_CLOCK, "The real clock can't be replaced");
+        self.clocks.insert(name, clock)
+    }
+
+    /// Removes the clock called `name`.
+    pub fn remove(&mut self, name: &str) -> Option<Clock> {
+        self.clocks.remove(name)
+    }
+
+    /// Iterates over all clocks and their names, including [`REAL_CLOCK`].
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Clock)> {
+        std::iter::once((REAL_CLOCK, &self.real)).chain(
+            self.clocks
+                .iter()
+                .map(|(name, clock)| (name.as_str(), clock)),
+        )
+    }
+
+    /// Advances all clocks by `real_delta` wall time.
+    pub fn advance(&mut self, real_delta: Duration) {
+        self.real.advance(real_delta);
+        for clock in self.clocks.values_mut() {
+            clock.advance(real_delta);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paused_clock_does_not_advance() {
+        let mut clocks = Clocks::default();
+        clocks.game_mut().pause();
+        clocks.advance(Duration::from_secs(1));
+
+        assert_eq!(clocks.game().delta_time(), Duration::from_secs(0));
+        assert_eq!(clocks.game().frame_number(), 0);
+        assert_eq!(clocks.real().delta_time(), Duration::from_secs(1));
+
+        clocks.game_mut().resume();
+        clocks.advance(Duration::from_secs(1));
+        assert_eq!(clocks.game().absolute_time(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn time_scale_applies_per_clock() {
+        let mut clocks = Clocks::default();
+        let mut slow = Clock::new();
+        slow.set_time_scale(0.5);
+        clocks.insert("slow", slow);
+
+        clocks.advance(Duration::from_secs(1));
+
+        assert_eq!(
+            clocks.get("slow").unwrap().delta_time(),
+            Duration::from_millis(500)
+        );
+        assert_eq!(clocks.game().delta_time(), Duration::from_secs(1));
+        assert!(clocks.get_mut(REAL_CLOCK).is_none());
+    }
+}
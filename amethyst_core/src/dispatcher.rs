@@ -1,10 +1,36 @@
+use std::any::TypeId;
+
 use amethyst_error::Error;
 
-use crate::ecs::{
-    systems::{Executor, ParallelRunnable, Step},
-    Resources, Runnable, Schedule, World,
+use crate::{
+    ecs::{
+        systems::{Executor, ParallelRunnable, Step},
+        Resources, Runnable, Schedule, World,
+    },
+    event_bus::Events,
+    ArcThreadPool,
 };
 
+/// Controls how the [Dispatcher] schedules the systems added to it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExecutionMode {
+    /// Systems without conflicting component or resource access run in parallel (when the
+    /// `parallel` feature is enabled). This is the default.
+    Parallel,
+    /// Every system runs on its own, strictly in the order it was added to the builder.
+    ///
+    /// Command buffers are still flushed at the same points as in `Parallel` mode, so two runs
+    /// fed with the same input produce the same world. Use this for replays and lockstep
+    /// networking.
+    Deterministic,
+}
+
+impl Default for ExecutionMode {
+    fn default() -> Self {
+        ExecutionMode::Parallel
+    }
+}
+
 /// A `SystemBundle` is a structure that adds multiple systems to the [Dispatcher] and loads/unloads all required resources.
 pub trait SystemBundle {
     /// This method is lazily evaluated when [Dispatcher] is built with [`DispatcherBuilder::build`].
@@ -67,6 +93,10 @@ pub struct DispatcherData<'a> {
     accumulator: Vec<Box<dyn ParallelRunnable + 'static>>,
     /// Bundles that can be later used for cleanup by calling [SystemBundle::unload].
     bundles: Vec<Box<dyn SystemBundle + 'a>>,
+    /// How the accumulated systems are grouped into executors.
+    mode: ExecutionMode,
+    /// Event types whose buffers are swapped at the end of every frame.
+    events: Vec<EventRegistration>,
 }
 
 impl<'a> DispatcherData<'a> {
@@ -74,8 +104,18 @@ impl<'a> DispatcherData<'a> {
         if !self.accumulator.is_empty() {
             let mut systems = Vec::new();
             std::mem::swap(&mut self.accumulator, &mut systems);
-            let executor = Executor::new(systems);
-            self.steps.push(Step::Systems(executor));
+            match self.mode {
+                ExecutionMode::Parallel => {
+                    let executor = Executor::new(systems);
+                    self.steps.push(Step::Systems(executor));
+                }
+                ExecutionMode::Deterministic => {
+                    // One executor per system, so no two systems can ever overlap.
+                    for system in systems {
+                        self.steps.push(Step::Systems(Executor::new(vec![system])));
+                    }
+                }
+            }
         }
     }
 }
@@ -85,9 +125,24 @@ impl<'a> DispatcherData<'a> {
 #[allow(missing_debug_implementations)]
 pub struct DispatcherBuilder {
     items: Vec<DispatcherItem>,
+    mode: ExecutionMode,
+    thread_pool: Option<ArcThreadPool>,
 }
 
 impl<'a> DispatcherBuilder {
+    /// Sets the [`ExecutionMode`] used by the built [Dispatcher]. Defaults to [`ExecutionMode::Parallel`].
+    pub fn with_execution_mode(&mut self, mode: ExecutionMode) -> &mut Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Executes the built [Dispatcher] on the given thread pool instead of the [`ArcThreadPool`]
+    /// resource.
+    pub fn with_thread_pool(&mut self, pool: ArcThreadPool) -> &mut Self {
+        self.thread_pool = Some(pool);
+        self
+    }
+
     /// Adds a system to the schedule.
     pub fn add_system<S: System + 'a>(&mut self, system: S) -> &mut Self {
         log::debug!("Building system");
@@ -128,6 +183,18 @@ impl<'a> DispatcherBuilder {
         self
     }
 
+    /// Registers the event type `T`.
+    ///
+    /// This inserts the [`Events<T>`] resource if it is missing and swaps its buffers at the end
+    /// of every frame, so events sent during a frame can be read until the end of the next one.
+    /// Adding the same event type more than once has no further effect. Each event type should
+    /// only be registered with one dispatcher.
+    pub fn add_event<T: Send + Sync + 'static>(&mut self) -> &mut Self {
+        self.items
+            .push(DispatcherItem::Event(EventRegistration::of::<T>()));
+        self
+    }
+
     /// Evaluates all system bundles (recursively). Resulting systems and unpacked bundles are put into [`DispatcherData`].
     pub fn load(
         &'a mut self,
@@ -150,6 +217,12 @@ impl<'a> DispatcherBuilder {
                     data.finalize_executor();
                     data.steps.push(Step::ThreadLocalSystem(s));
                 }
+                DispatcherItem::Event(event) => {
+                    if data.events.iter().all(|e| e.type_id != event.type_id) {
+                        (event.insert)(resources);
+                        data.events.push(event);
+                    }
+                }
                 DispatcherItem::SystemBundle(mut bundle) => {
                     {
                         let mut builder = DispatcherBuilder::default();
@@ -170,13 +243,28 @@ impl<'a> DispatcherBuilder {
         world: &mut World,
         resources: &mut Resources,
     ) -> Result<Dispatcher, Error> {
-        let mut data = DispatcherData::default();
+        let mut data = DispatcherData {
+            mode: self.mode,
+            ..DispatcherData::default()
+        };
 
         self.flush().load(world, resources, &mut data)?;
 
+        if !data.events.is_empty() {
+            let events = std::mem::take(&mut data.events);
+            data.steps
+                .push(Step::ThreadLocalFn(Box::new(move |_, resources| {
+                    for event in &events {
+                        (event.update)(resources);
+                    }
+                })));
+        }
+
         Ok(Dispatcher {
             schedule: Schedule::from(data.steps),
             bundles: data.bundles,
+            mode: self.mode,
+            thread_pool: self.thread_pool.take(),
         })
     }
 }
@@ -194,6 +282,34 @@ pub enum DispatcherItem {
     ThreadLocalSystem(Box<dyn Runnable + 'static>),
     /// A system bundle
     SystemBundle(Box<dyn SystemBundle + 'static>),
+    /// An event type registered with [`DispatcherBuilder::add_event`].
+    Event(EventRegistration),
+}
+
+/// Type erased [`Events`] resource handling, created by [`DispatcherBuilder::add_event`].
+#[derive(Debug)]
+pub struct EventRegistration {
+    type_id: TypeId,
+    insert: fn(&mut Resources),
+    update: fn(&mut Resources),
+}
+
+impl EventRegistration {
+    fn of<T: Send + Sync + 'static>() -> Self {
+        EventRegistration {
+            type_id: TypeId::of::<T>(),
+            insert: |resources| {
+                if !resources.contains::<Events<T>>() {
+                    resources.insert(Events::<T>::default());
+                }
+            },
+            update: |resources| {
+                if let Some(mut events) = resources.get_mut::<Events<T>>() {
+                    events.update();
+                }
+            },
+        }
+    }
 }
 
 /// Dispatcher is created by [`DispatcherBuilder`] and contains [Schedule] used to execute all systems.
@@ -202,15 +318,40 @@ pub struct Dispatcher {
     // Used to execute unload on system bundles once dispatcher is disposed.
     bundles: Vec<Box<dyn SystemBundle>>,
     schedule: Schedule,
+    mode: ExecutionMode,
+    // Pool supplied through `DispatcherBuilder::with_thread_pool`, takes precedence over the resource.
+    #[cfg_attr(not(feature = "parallel"), allow(dead_code))]
+    thread_pool: Option<ArcThreadPool>,
 }
 
 impl Dispatcher {
     /// Executes systems according to the [Schedule].
+    ///
+    /// Systems run on the thread pool given to [`DispatcherBuilder::with_thread_pool`] or, failing
+    /// that, on the [`ArcThreadPool`] resource. Without either, rayon's global pool is used.
     pub fn execute(&mut self, world: &mut World, resources: &mut Resources) {
-        // TODO: use ArcThreadPool from resources to dispatch legion
+        #[cfg(feature = "parallel")]
+        {
+            let pool = self
+                .thread_pool
+                .clone()
+                .or_else(|| resources.get::<ArcThreadPool>().map(|pool| pool.clone()));
+            if let Some(pool) = pool {
+                self.schedule
+                    .execute_in_thread_pool(world, resources, &pool);
+                return;
+            }
+        }
+
         self.schedule.execute(world, resources);
     }
 
+    /// Returns the [`ExecutionMode`] this dispatcher was built with.
+    #[must_use]
+    pub fn execution_mode(&self) -> ExecutionMode {
+        self.mode
+    }
+
     /// Unloads any resources by calling [`SystemBundle::unload`] for stored system bundles and returns [`DispatcherBuilder`]
     /// containing the same bundles.
     pub fn unload(mut self, world: &mut World, resources: &mut Resources) -> Result<(), Error> {
@@ -303,4 +444,82 @@ pub mod tests {
 
         assert!(resources.get::<MyResource>().unwrap().0, true);
     }
+
+    #[test]
+    fn deterministic_mode_runs_systems_in_insertion_order() {
+        #[derive(Default)]
+        struct Order(std::sync::Mutex<Vec<usize>>);
+
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        resources.insert(Order::default());
+
+        let mut builder = DispatcherBuilder::default();
+        builder.with_execution_mode(ExecutionMode::Deterministic);
+        for index in 0..8 {
+            // Only read access, so the parallel executor would be free to reorder these.
+            builder.add_system(move || {
+                SystemBuilder::new(format!("push_{}", index))
+                    .read_resource::<Order>()
+                    .build(move |_, _, order, _| order.0.lock().unwrap().push(index))
+            });
+        }
+
+        let pool = std::sync::Arc::new(rayon::ThreadPoolBuilder::new().build().unwrap());
+        let mut dispatcher = builder
+            .with_thread_pool(pool)
+            .build(&mut world, &mut resources)
+            .unwrap();
+        assert_eq!(dispatcher.execution_mode(), ExecutionMode::Deterministic);
+
+        dispatcher.execute(&mut world, &mut resources);
+
+        let order = resources.get::<Order>().unwrap();
+        assert_eq!(*order.0.lock().unwrap(), (0..8).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn registered_events_are_swapped_every_frame() {
+        use crate::event_bus::EventCursor;
+
+        #[derive(Default)]
+        struct Received(Vec<u32>);
+
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        resources.insert(Received::default());
+
+        let mut dispatcher = DispatcherBuilder::default()
+            .add_event::<u32>()
+            .add_event::<u32>()
+            // Runs before the sender, so only sees events from the previous frame.
+            .add_system(|| {
+                let mut cursor = EventCursor::named("receiver");
+                SystemBuilder::new("receiver")
+                    .read_resource::<Events<u32>>()
+                    .write_resource::<Received>()
+                    .build(move |_, _, (events, received), _| {
+                        received.0.extend(cursor.read(events));
+                    })
+            })
+            .add_system(|| {
+                let mut frame = 0;
+                SystemBuilder::new("sender")
+                    .write_resource::<Events<u32>>()
+                    .build(move |_, _, events, _| {
+                        frame += 1;
+                        events.send(frame);
+                    })
+            })
+            .build(&mut world, &mut resources)
+            .unwrap();
+
+        for _ in 0..3 {
+            dispatcher.execute(&mut world, &mut resources);
+        }
+
+        assert_eq!(resources.get::<Received>().unwrap().0, vec![1, 2]);
+        // Only the event of the last frame is still buffered.
+        assert_eq!(resources.get::<Events<u32>>().unwrap().len(), 1);
+    }
 }
@@ -0,0 +1,254 @@
+//! Double-buffered, typed events.
+//!
+//! [`Events<T>`] is an ordinary resource, so systems declare what they do with an event type
+//! through `SystemBuilder` like with any other resource: `write_resource::<Events<T>>()` to send
+//! and `read_resource::<Events<T>>()` to read. Any number of systems can read the same events in
+//! parallel.
+//!
+//! Readers don't need to be registered anywhere. An [`EventCursor`] only remembers how far it has
+//! read, and events are dropped after two frames whether they have been read or not, so a
+//! forgotten or paused reader can no longer make a channel grow forever. A reader which does not
+//! run for more than a frame misses events; this is logged as a warning and counted in
+//! [`EventCursor::missed`].
+//!
+//! Event types are registered with [`DispatcherBuilder::add_event`], which inserts the resource
+//! and swaps its buffers at the end of every frame.
+//!
+//! # Example
+//!
+//! ```
+//! use amethyst::core::{
+//!     ecs::{DispatcherBuilder, Resources, SystemBuilder, World},
+//!     event_bus::{EventCursor, Events},
+//! };
+//!
+//! struct Explosion {
+//!     radius: f32,
+//! }
+//!
+//! let mut world = World::default();
+//! let mut resources = Resources::default();
+//!
+//! let mut dispatcher = DispatcherBuilder::default()
+//!     .add_event::<Explosion>()
+//!     .add_system(|| {
+//!         SystemBuilder::new("BombSystem")
+//!             .write_resource::<Events<Explosion>>()
+//!             .build(|_, _, explosions, _| explosions.send(Explosion { radius: 2.0 }))
+//!     })
+//!     .add_system(|| {
+//!         let mut cursor = EventCursor::named("ShakeSystem");
+//!         SystemBuilder::new("ShakeSystem")
+//!             .read_resource::<Events<Explosion>>()
+//!             .build(move |_, _, explosions, _| {
+//!                 for explosion in cursor.read(explosions) {
+//!                     println!("Boom! {}", explosion.radius);
+//!                 }
+//!             })
+//!     })
+//!     .build(&mut world, &mut resources)
+//!     .unwrap();
+//!
+//! dispatcher.execute(&mut world, &mut resources);
+//! ```
+//!
+//! [`DispatcherBuilder::add_event`]: crate::ecs::DispatcherBuilder::add_event
+
+use std::{any::type_name, borrow::Cow, fmt, marker::PhantomData};
+
+use log::warn;
+
+/// Events of type `T` sent during the current and the previous frame.
+#[derive(Debug)]
+pub struct Events<T> {
+    previous: Vec<T>,
+    current: Vec<T>,
+    /// Id of the first event in `previous`. Ids keep increasing for the lifetime of the resource.
+    previous_start: u64,
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Events {
+            previous: Vec::new(),
+            current: Vec::new(),
+            previous_start: 0,
+        }
+    }
+}
+
+impl<T> Events<T> {
+    /// Sends an event, readable until the end of the next frame.
+    pub fn send(&mut self, event: T) {
+        self.current.push(event);
+    }
+
+    /// Sends all events of `events`.
+    pub fn send_batch<I: IntoIterator<Item = T>>(&mut self, events: I) {
+        self.current.extend(events);
+    }
+
+    /// Number of buffered events.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    /// Returns `true` if there are no buffered events.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops all buffered events. Readers which have not read them yet will report them as missed.
+    pub fn clear(&mut self) {
+        self.update();
+        self.update();
+    }
+
+    /// Drops the events of the previous frame and starts a new one.
+    ///
+    /// Called at the end of every frame for event types registered with
+    /// [`DispatcherBuilder::add_event`](crate::ecs::DispatcherBuilder::add_event).
+    pub fn update(&mut self) {
+        self.previous_start += self.previous.len() as u64;
+        std::mem::swap(&mut self.previous, &mut self.current);
+        self.current.clear();
+    }
+
+    fn oldest_id(&self) -> u64 {
+        self.previous_start
+    }
+
+    fn next_id(&self) -> u64 {
+        self.previous_start + self.len() as u64
+    }
+}
+
+/// Reading position of a single reader in an [`Events`] resource.
+///
+/// A new cursor starts with the oldest events still buffered.
+pub struct EventCursor<T> {
+    name: Option<Cow<'static, str>>,
+    next: Option<u64>,
+    missed: u64,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for EventCursor<T> {
+    fn default() -> Self {
+        EventCursor {
+            name: None,
+            next: None,
+            missed: 0,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for EventCursor<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventCursor")
+            .field("event", &type_name::<T>())
+            .field("name", &self.name)
+            .field("next", &self.next)
+            .field("missed", &self.missed)
+            .finish()
+    }
+}
+
+impl<T> EventCursor<T> {
+    /// Creates a cursor whose name is used when reporting missed events, typically the name of
+    /// the reading system.
+    pub fn named<N: Into<Cow<'static, str>>>(name: N) -> Self {
+        EventCursor {
+            name: Some(name.into()),
+            ..EventCursor::default()
+        }
+    }
+
+    /// Returns all events sent since the last call.
+    pub fn read<'a>(&mut self, events: &'a Events<T>) -> impl Iterator<Item = &'a T> + 'a {
+        let oldest = events.oldest_id();
+        let end = events.next_id();
+        let start = match self.next {
+            Some(next) if next < oldest => {
+                let missed = oldest - next;
+                self.missed += missed;
+                warn!(
+                    "Event reader `{}` fell behind and missed {} `{}` events",
+                    self.name.as_deref().unwrap_or("<unnamed>"),
+                    missed,
+                    type_name::<T>(),
+                );
+                oldest
+            }
+            Some(next) => next.min(end),
+            None => oldest,
+        };
+        self.next = Some(end);
+
+        #[allow(clippy::cast_possible_truncation)]
+        let skip = (start - oldest) as usize;
+        events
+            .previous
+            .iter()
+            .chain(events.current.iter())
+            .skip(skip)
+    }
+
+    /// Total number of events this cursor missed by not reading for more than a frame.
+    #[must_use]
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(cursor: &mut EventCursor<u32>, events: &Events<u32>) -> Vec<u32> {
+        cursor.read(events).copied().collect()
+    }
+
+    #[test]
+    fn events_live_for_two_frames() {
+        let mut events = Events::default();
+        let mut early = EventCursor::named("early");
+        let mut late = EventCursor::named("late");
+
+        // Frame 1: `early` runs before the event is sent, `late` after.
+        assert!(read(&mut early, &events).is_empty());
+        events.send(1);
+        assert_eq!(read(&mut late, &events), vec![1]);
+        events.update();
+
+        // Frame 2: `early` still sees the event sent after it ran last frame.
+        assert_eq!(read(&mut early, &events), vec![1]);
+        events.send(2);
+        assert_eq!(read(&mut late, &events), vec![2]);
+        events.update();
+        events.update();
+
+        assert!(events.is_empty());
+        assert_eq!(early.missed(), 0);
+        assert_eq!(late.missed(), 0);
+    }
+
+    #[test]
+    fn lagging_reader_reports_missed_events() {
+        let mut events = Events::default();
+        let mut cursor = EventCursor::named("lagging");
+        assert!(read(&mut cursor, &events).is_empty());
+
+        events.send_batch(vec![1, 2]);
+        events.update();
+        events.send(3);
+        events.update();
+        events.send(4);
+
+        assert_eq!(read(&mut cursor, &events), vec![3, 4]);
+        assert_eq!(cursor.missed(), 2);
+    }
+}
@@ -33,9 +33,13 @@ pub use simba as simd;
 
 pub use self::{
     axis::{Axis2, Axis3},
+    clock::{Clock, Clocks},
     event::EventReader,
+    event_bus::{EventCursor, Events},
     hidden::{Hidden, HiddenPropagate},
-    logger::{start_logger, LevelFilter as LogLevelFilter, Logger, LoggerConfig, StdoutLog},
+    logger::{
+        start_logger, LevelFilter as LogLevelFilter, LogBuffer, Logger, LoggerConfig, StdoutLog,
+    },
     named::Named,
     shrev::EventChannel,
     timing::Stopwatch,
@@ -50,15 +54,25 @@ pub mod ecs {
         *,
     };
 
-    pub use crate::dispatcher::{Dispatcher, DispatcherBuilder, System, SystemBundle};
+    pub use crate::dispatcher::{
+        Dispatcher, DispatcherBuilder, ExecutionMode, System, SystemBundle,
+    };
 }
 
 /// Re-export under name legion for proc macros
 pub use ecs as legion;
 
+/// Named clocks which can be paused and scaled.
+pub mod clock;
+
+/// World checksums for replays and lockstep networking.
+pub mod checksum;
+
 /// Dispatcher module.
 pub mod dispatcher;
 
+pub mod event_bus;
+
 /// The frame limiter module.
 pub mod frame_limiter;
 
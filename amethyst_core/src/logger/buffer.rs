@@ -0,0 +1,122 @@
+//! In-memory log sink which can be read back, e.g. by an in-game console.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use log::{Level, Log, Metadata, Record};
+
+use super::{collect_fields, frame_number};
+
+/// A single log message kept by the [`LogBuffer`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogEntry {
+    /// Monotonically increasing number of this entry, starting at zero.
+    pub sequence: u64,
+    /// The frame the message was logged in.
+    pub frame: u64,
+    /// Level of the message.
+    pub level: Level,
+    /// Target of the message, usually the module path.
+    pub target: String,
+    /// The message itself.
+    pub message: String,
+    /// Structured key/value fields attached to the message.
+    pub fields: Vec<(String, String)>,
+}
+
+#[derive(Debug)]
+struct Entries {
+    entries: VecDeque<LogEntry>,
+    capacity: usize,
+    next_sequence: u64,
+}
+
+/// Ring buffer holding the most recent log messages.
+///
+/// Enabled through [`LoggerConfig::log_buffer_capacity`](super::LoggerConfig::log_buffer_capacity).
+/// The application inserts it as a resource, cloning it is cheap and all clones share the same
+/// messages.
+#[derive(Clone, Debug)]
+pub struct LogBuffer {
+    inner: Arc<Mutex<Entries>>,
+}
+
+impl LogBuffer {
+    /// Creates a buffer which keeps the last `capacity` messages.
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        LogBuffer {
+            inner: Arc::new(Mutex::new(Entries {
+                entries: VecDeque::with_capacity(capacity),
+                capacity,
+                next_sequence: 0,
+            })),
+        }
+    }
+
+    /// Appends `entry`, dropping the oldest message if the buffer is full. The sequence number of
+    /// `entry` is replaced.
+    pub fn push(&self, mut entry: LogEntry) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if inner.capacity == 0 {
+            return;
+        }
+        if inner.entries.len() == inner.capacity {
+            inner.entries.pop_front();
+        }
+        entry.sequence = inner.next_sequence;
+        inner.next_sequence += 1;
+        inner.entries.push_back(entry);
+    }
+
+    /// Returns a copy of all buffered messages, oldest first.
+    #[must_use]
+    pub fn entries(&self) -> Vec<LogEntry> {
+        self.entries_since(0)
+    }
+
+    /// Returns a copy of the buffered messages with a sequence number of at least `sequence`.
+    ///
+    /// Readers can remember the sequence number following the last entry they have seen to only
+    /// fetch new messages.
+    #[must_use]
+    pub fn entries_since(&self, sequence: u64) -> Vec<LogEntry> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner
+            .entries
+            .iter()
+            .filter(|entry| entry.sequence >= sequence)
+            .cloned()
+            .collect()
+    }
+
+    /// Removes all buffered messages. Sequence numbers keep counting up.
+    pub fn clear(&self) {
+        self.inner
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entries
+            .clear();
+    }
+}
+
+impl Log for LogBuffer {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        self.push(LogEntry {
+            sequence: 0,
+            frame: frame_number(),
+            level: record.level(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+            fields: collect_fields(record),
+        });
+    }
+
+    fn flush(&self) {}
+}
@@ -0,0 +1,212 @@
+//! Log file output with optional JSON lines encoding and size based rotation.
+
+#[cfg(feature = "json-log")]
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use log::{Log, Metadata, Record};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "json-log")]
+use super::{collect_fields, frame_number};
+
+/// Encoding of the log file.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum LogFileFormat {
+    /// One line of text per message, formatted like the terminal output.
+    Text,
+    /// One JSON object per line, holding the timestamp, frame, level, target, message and fields.
+    /// Requires the `json-log` feature.
+    #[cfg(feature = "json-log")]
+    JsonLines,
+}
+
+impl Default for LogFileFormat {
+    fn default() -> Self {
+        LogFileFormat::Text
+    }
+}
+
+/// Log file writer which rolls over to a fresh file once `max_size` bytes are reached.
+///
+/// Older files are kept as `<path>.1` (most recent) up to `<path>.<max_backups>`.
+#[derive(Debug)]
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: Option<u64>,
+    max_backups: usize,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf, max_size: Option<u64>, max_backups: usize) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFile {
+            path,
+            file,
+            size,
+            max_size,
+            max_backups,
+        })
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if let Some(max_size) = self.max_size {
+            if self.size > 0 && self.size + line.len() as u64 > max_size {
+                self.rotate()?;
+            }
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_backups == 0 {
+            self.file = File::create(&self.path)?;
+        } else {
+            let _ = fs::remove_file(backup_path(&self.path, self.max_backups));
+            for index in (1..self.max_backups).rev() {
+                let from = backup_path(&self.path, index);
+                if from.exists() {
+                    fs::rename(&from, backup_path(&self.path, index + 1))?;
+                }
+            }
+            fs::rename(&self.path, backup_path(&self.path, 1))?;
+            self.file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+        }
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn backup_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", index));
+    PathBuf::from(name)
+}
+
+#[cfg(feature = "json-log")]
+#[derive(Serialize)]
+struct JsonRecord<'a> {
+    timestamp_ms: u128,
+    frame: u64,
+    level: &'a str,
+    target: &'a str,
+    message: String,
+    #[serde(skip_serializing_if = "serde_json::Map::is_empty")]
+    fields: serde_json::Map<String, serde_json::Value>,
+}
+
+/// `log::Log` implementation writing to a [`RotatingFile`].
+#[derive(Debug)]
+pub(super) struct FileLog {
+    file: Mutex<RotatingFile>,
+    format: LogFileFormat,
+}
+
+impl FileLog {
+    pub(super) fn open(
+        path: PathBuf,
+        format: LogFileFormat,
+        max_size: Option<u64>,
+        max_backups: usize,
+    ) -> io::Result<Self> {
+        Ok(FileLog {
+            file: Mutex::new(RotatingFile::open(path, max_size, max_backups)?),
+            format,
+        })
+    }
+
+    fn format(&self, record: &Record<'_>) -> String {
+        match self.format {
+            LogFileFormat::Text => format!("{}\n", record.args()),
+            #[cfg(feature = "json-log")]
+            LogFileFormat::JsonLines => {
+                let json = JsonRecord {
+                    timestamp_ms: SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map(|time| time.as_millis())
+                        .unwrap_or(0),
+                    frame: frame_number(),
+                    level: record.level().as_str(),
+                    target: record.target(),
+                    message: record.args().to_string(),
+                    fields: collect_fields(record)
+                        .into_iter()
+                        .map(|(key, value)| (key, serde_json::Value::String(value)))
+                        .collect(),
+                };
+                let mut line =
+                    serde_json::to_string(&json).expect("Log records always serialize to JSON");
+                line.push('\n');
+                line
+            }
+        }
+    }
+}
+
+impl Log for FileLog {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        let line = self.format(record);
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = file.write_line(&line) {
+            eprintln!("Unable to write to the log file: {}", e);
+        }
+    }
+
+    fn flush(&self) {
+        let _ = self
+            .file
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .file
+            .flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_when_exceeding_max_size() {
+        let dir =
+            std::env::temp_dir().join(format!("amethyst_log_rotation_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("game.log");
+
+        let mut file = RotatingFile::open(path.clone(), Some(10), 2).unwrap();
+        for line in &["first\n", "second\n", "third\n", "fourth\n"] {
+            file.write_line(line).unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fourth\n");
+        assert_eq!(
+            fs::read_to_string(backup_path(&path, 1)).unwrap(),
+            "third\n"
+        );
+        assert_eq!(
+            fs::read_to_string(backup_path(&path, 2)).unwrap(),
+            "second\n"
+        );
+        assert!(!backup_path(&path, 3).exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
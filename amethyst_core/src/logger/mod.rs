@@ -1,9 +1,82 @@
-use std::{borrow::Cow, env, fmt, io, path::PathBuf, str::FromStr};
+use std::{
+    borrow::Cow,
+    env, fmt, io,
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
 
-use log::debug;
 pub use log::LevelFilter;
+use log::{
+    debug,
+    kv::{self, Key, Value, VisitSource},
+    Log,
+};
 use serde::{Deserialize, Serialize};
 
+use self::file::FileLog;
+pub use self::{
+    buffer::{LogBuffer, LogEntry},
+    file::LogFileFormat,
+};
+
+mod buffer;
+mod file;
+
+type Formatter = Box<
+    dyn Fn(fern::FormatCallback<'_>, &fmt::Arguments<'_>, &log::Record<'_>) + Sync + Send + 'static,
+>;
+
+static FRAME_NUMBER: AtomicU64 = AtomicU64::new(0);
+static LOG_BUFFER: Mutex<Option<LogBuffer>> = Mutex::new(None);
+
+/// Sets the frame number attached to every following log message.
+///
+/// The application calls this once per frame, there is no need to call it manually.
+pub fn set_frame_number(frame: u64) {
+    FRAME_NUMBER.store(frame, Ordering::Relaxed);
+}
+
+/// Returns the frame number attached to log messages.
+#[must_use]
+pub fn frame_number() -> u64 {
+    FRAME_NUMBER.load(Ordering::Relaxed)
+}
+
+/// Returns the [`LogBuffer`] of the running logger, if it was enabled through
+/// [`LoggerConfig::log_buffer_capacity`].
+#[must_use]
+pub fn log_buffer() -> Option<LogBuffer> {
+    LOG_BUFFER.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Collects the structured key/value fields of `record`, e.g. those of
+/// `log::info!(player = name; "Player joined")`.
+pub(crate) fn collect_fields(record: &log::Record<'_>) -> Vec<(String, String)> {
+    struct Collect(Vec<(String, String)>);
+
+    impl<'kvs> VisitSource<'kvs> for Collect {
+        fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+            self.0.push((key.to_string(), value.to_string()));
+            Ok(())
+        }
+    }
+
+    let mut fields = Collect(Vec::new());
+    let _ = record.key_values().visit(&mut fields);
+    fields.0
+}
+
+fn format_fields(record: &log::Record<'_>) -> String {
+    collect_fields(record)
+        .into_iter()
+        .map(|(key, value)| format!(" {}={}", key, value))
+        .collect()
+}
+
 /// An enum that contains options for logging to the terminal.
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum StdoutLog {
@@ -33,6 +106,14 @@ pub struct LoggerConfig {
     pub log_gfx_rendy_level: Option<LevelFilter>,
     /// Sets the levels for specific modules.
     pub module_levels: Vec<(String, LevelFilter)>,
+    /// Encoding of the log file.
+    pub log_file_format: LogFileFormat,
+    /// If set, the log file is rotated once it grows beyond this many bytes.
+    pub log_file_max_size: Option<u64>,
+    /// Number of rotated log files to keep next to the current one.
+    pub log_file_max_backups: usize,
+    /// If set, the last messages are kept in a [`LogBuffer`] of this capacity.
+    pub log_buffer_capacity: Option<usize>,
 }
 
 impl Default for LoggerConfig {
@@ -45,6 +126,10 @@ impl Default for LoggerConfig {
             log_gfx_backend_level: Some(LevelFilter::Warn),
             log_gfx_rendy_level: Some(LevelFilter::Warn),
             module_levels: Vec::new(),
+            log_file_format: LogFileFormat::Text,
+            log_file_max_size: None,
+            log_file_max_backups: 5,
+            log_buffer_capacity: None,
         }
     }
 }
@@ -52,6 +137,14 @@ impl Default for LoggerConfig {
 /// Allows the creation of a custom logger with a set of custom configurations. If no custom
 /// formatting or configuration is required [`start_logger`] can be used instead.
 ///
+/// The default text output starts with the frame number, see [`set_frame_number`]. Structured
+/// fields given with the key/value syntax of the `log` macros are appended to text output and
+/// stored separately in JSON lines files and the [`LogBuffer`]:
+///
+/// ```
+/// log::info!(player = "ferris", team = 2; "Player joined");
+/// ```
+///
 /// # Examples
 /// ```
 /// amethyst::Logger::from_config(Default::default())
@@ -72,47 +165,40 @@ impl Default for LoggerConfig {
 #[allow(missing_debug_implementations)]
 pub struct Logger {
     dispatch: fern::Dispatch,
+    log_buffer: Option<LogBuffer>,
 }
 
 impl Logger {
-    fn new() -> Self {
-        let dispatch = fern::Dispatch::new().format(|out, message, record| {
+    fn default_formatter() -> Formatter {
+        Box::new(|out, message, record| {
             out.finish(format_args!(
-                "[{level}][{target}] {message}",
+                "[{frame}][{level}][{target}] {message}{fields}",
+                frame = frame_number(),
                 level = record.level(),
                 target = record.target(),
                 message = message,
+                fields = format_fields(record),
             ));
-        });
-        Self { dispatch }
-    }
-
-    /// Create a new Logger with a passed in formatter callback
-    fn new_formatter<F>(formatter: F) -> Self
-    where
-        F: Fn(fern::FormatCallback<'_>, &fmt::Arguments<'_>, &log::Record<'_>)
-            + Sync
-            + Send
-            + 'static,
-    {
-        let dispatch = fern::Dispatch::new().format(formatter);
-        Self { dispatch }
+        })
     }
 
-    /// Create a new logger from [`LoggerConfig`] and the Logger it will be added to
-    fn new_with_config(mut config: LoggerConfig, mut logger: Self) -> Self {
+    /// Create a new logger from [`LoggerConfig`] and the formatter used for text output
+    fn new_with_config(mut config: LoggerConfig, formatter: Formatter) -> Self {
         if config.allow_env_override {
             env_var_override(&mut config);
         }
 
-        logger.dispatch = logger.dispatch.level(config.level_filter);
+        // Text outputs share the formatter, structured outputs receive the unformatted record.
+        let mut text = fern::Dispatch::new().format(formatter);
+        let mut logger = Self {
+            dispatch: fern::Dispatch::new().level(config.level_filter),
+            log_buffer: None,
+        };
 
         match config.stdout {
-            StdoutLog::Plain => logger.dispatch = logger.dispatch.chain(io::stdout()),
+            StdoutLog::Plain => text = text.chain(io::stdout()),
             StdoutLog::Colored => {
-                logger.dispatch = logger
-                    .dispatch
-                    .chain(colored_stdout(fern::colors::ColoredLevelConfig::new()));
+                text = text.chain(colored_stdout(fern::colors::ColoredLevelConfig::new()));
             }
             StdoutLog::Off => {}
         }
@@ -158,20 +244,42 @@ impl Logger {
         }
 
         if let Some(path) = config.log_file {
-            if let Ok(log_file) = fern::log_file(path) {
-                logger.dispatch = logger.dispatch.chain(log_file);
-            } else {
-                eprintln!("Unable to access the log file, as such it will not be used");
+            match FileLog::open(
+                path,
+                config.log_file_format,
+                config.log_file_max_size,
+                config.log_file_max_backups,
+            ) {
+                Ok(log_file) => {
+                    let log_file = Box::new(log_file) as Box<dyn Log>;
+                    match config.log_file_format {
+                        LogFileFormat::Text => text = text.chain(log_file),
+                        #[cfg(feature = "json-log")]
+                        LogFileFormat::JsonLines => {
+                            logger.dispatch = logger.dispatch.chain(log_file);
+                        }
+                    }
+                }
+                Err(_) => eprintln!("Unable to access the log file, as such it will not be used"),
             }
         }
 
+        if let Some(capacity) = config.log_buffer_capacity {
+            let buffer = LogBuffer::new(capacity);
+            logger.dispatch = logger
+                .dispatch
+                .chain(Box::new(buffer.clone()) as Box<dyn Log>);
+            logger.log_buffer = Some(buffer);
+        }
+
+        logger.dispatch = logger.dispatch.chain(text);
         logger
     }
 
     /// Create a new Logger from [`LoggerConfig`]
     #[must_use]
     pub fn from_config(config: LoggerConfig) -> Self {
-        Logger::new_with_config(config, Logger::new())
+        Logger::new_with_config(config, Logger::default_formatter())
     }
 
     /// Create a new Logger from [`LoggerConfig`] and a formatter
@@ -182,7 +290,7 @@ impl Logger {
             + Send
             + 'static,
     {
-        Logger::new_with_config(config, Logger::new_formatter(formatter))
+        Logger::new_with_config(config, Box::new(formatter))
     }
 
     /// Set individual log levels for modules.
@@ -191,11 +299,23 @@ impl Logger {
         self
     }
 
+    /// Returns the [`LogBuffer`] this logger writes to, if enabled in the [`LoggerConfig`].
+    #[must_use]
+    pub fn log_buffer(&self) -> Option<&LogBuffer> {
+        self.log_buffer.as_ref()
+    }
+
     /// Starts [`Logger`] by consuming it.
     pub fn start(self) {
-        self.dispatch.apply().unwrap_or_else(|_| {
-            debug!("Global logger already set, default Amethyst logger will not be used");
-        });
+        let log_buffer = self.log_buffer;
+        match self.dispatch.apply() {
+            Ok(()) => {
+                *LOG_BUFFER.lock().unwrap_or_else(|e| e.into_inner()) = log_buffer;
+            }
+            Err(_) => {
+                debug!("Global logger already set, default Amethyst logger will not be used");
+            }
+        }
     }
 }
 
@@ -215,6 +335,9 @@ impl Logger {
 ///     * "trace" everything
 /// * `AMETHYST_LOG_FILE_PATH` - if set, enables logging to the file at the path
 ///     * the value is expected to be a path to the logging file
+/// * `AMETHYST_LOG_FILE_FORMAT` - sets the encoding of the log file
+///     * "text" writes the same lines as the terminal
+///     * "json" writes one JSON object per line, with the `json-log` feature
 pub fn start_logger(config: LoggerConfig) {
     Logger::from_config(config).start();
 }
@@ -236,6 +359,14 @@ fn env_var_override(config: &mut LoggerConfig) {
     if let Ok(path) = env::var("AMETHYST_LOG_FILE_PATH") {
         config.log_file = Some(PathBuf::from(path));
     }
+    if let Ok(var) = env::var("AMETHYST_LOG_FILE_FORMAT") {
+        match var.to_lowercase().as_ref() {
+            "text" | "plain" => config.log_file_format = LogFileFormat::Text,
+            #[cfg(feature = "json-log")]
+            "json" | "jsonl" | "json_lines" => config.log_file_format = LogFileFormat::JsonLines,
+            _ => {}
+        }
+    }
 }
 
 fn colored_stdout(color_config: fern::colors::ColoredLevelConfig) -> fern::Dispatch {
@@ -269,4 +400,55 @@ mod tests {
 
         assert_eq!(config.stdout, StdoutLog::Plain);
     }
+
+    #[test]
+    fn text_output_starts_with_frame() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let (_, log) = fern::Dispatch::new()
+            .format(Logger::default_formatter())
+            .chain(sender)
+            .into_log();
+        // The same frame number as the other tests, which may run at the same time.
+        set_frame_number(42);
+        log.log(
+            &log::Record::builder()
+                .args(format_args!("Level loaded"))
+                .level(log::Level::Info)
+                .target("test")
+                .key_values(&[("map", "arena")])
+                .build(),
+        );
+
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            "[42][INFO][test] Level loaded map=arena\n"
+        );
+    }
+
+    #[test]
+    fn buffer_keeps_fields_and_frame() {
+        let buffer = LogBuffer::new(2);
+        set_frame_number(42);
+        for message in &["one", "two", "three"] {
+            buffer.log(
+                &log::Record::builder()
+                    .args(format_args!("{}", message))
+                    .level(log::Level::Warn)
+                    .target("test")
+                    .key_values(&[("player", "ferris")])
+                    .build(),
+            );
+        }
+
+        let entries = buffer.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].message, "two");
+        assert_eq!(entries[1].sequence, 2);
+        assert_eq!(entries[1].frame, 42);
+        assert_eq!(
+            entries[1].fields,
+            vec![("player".to_string(), "ferris".to_string())]
+        );
+        assert_eq!(buffer.entries_since(2).len(), 1);
+    }
 }
@@ -0,0 +1,220 @@
+//! `ConfigDocs` Implementation
+
+use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::{Attribute, Data, DeriveInput, Fields, Lit, LitStr, Meta, NestedMeta};
+
+pub fn impl_config_docs(ast: &DeriveInput) -> TokenStream {
+    let fields = match &ast.data {
+        Data::Struct(data) => {
+            match &data.fields {
+                Fields::Named(fields) => &fields.named,
+                _ => panic!("ConfigDocs derive only supports structs with named fields"),
+            }
+        }
+        _ => panic!("ConfigDocs derive only supports structs"),
+    };
+
+    // Renaming the type itself with `rename` doesn't change the paths of its fields.
+    let mut rename: fn(&str) -> String = ToString::to_string;
+    for meta in ast
+        .attrs
+        .iter()
+        .filter_map(|attr| list(attr, "serde"))
+        .flatten()
+    {
+        if let Some(rule) = serialized_name(meta, "rename_all") {
+            if let Some(rule_fn) = rename_rule(&rule.value()) {
+                rename = rule_fn;
+            } else {
+                let message = format!("unknown serde rename_all rule `{}`", rule.value());
+                return quote_spanned!(rule.span()=> compile_error!(#message););
+            }
+        }
+    }
+
+    let mut pushes = Vec::new();
+    for field in fields {
+        let mut key = rename(&field.ident.as_ref().unwrap().to_string());
+        let mut nested = false;
+        let mut skipped = false;
+        let mut flattened = false;
+        for meta in field
+            .attrs
+            .iter()
+            .filter_map(|attr| list(attr, "serde"))
+            .flatten()
+        {
+            match meta {
+                Meta::Path(path) if path.is_ident("skip") || path.is_ident("skip_serializing") => {
+                    skipped = true;
+                }
+                Meta::Path(path) if path.is_ident("flatten") => flattened = true,
+                meta => {
+                    if let Some(rename) = serialized_name(meta, "rename") {
+                        key = rename.value();
+                    }
+                }
+            }
+        }
+        for meta in field
+            .attrs
+            .iter()
+            .filter_map(|attr| list(attr, "config_docs"))
+            .flatten()
+        {
+            match meta {
+                Meta::Path(path) if path.is_ident("nested") => nested = true,
+                _ => panic!("config_docs attribute only supports `nested`"),
+            }
+        }
+        if skipped {
+            continue;
+        }
+
+        // The fields of a flattened field are written in place of it, without a path of its own.
+        let docs = docs(&field.attrs);
+        if !docs.is_empty() && !flattened {
+            pushes.push(quote! {
+                docs.push((#key.to_string(), #docs));
+            });
+        }
+        if nested {
+            let ty = &field.ty;
+            let path = if flattened {
+                quote!(path)
+            } else {
+                quote!(format!("{}.{}", #key, path))
+            };
+            pushes.push(quote! {
+                docs.extend(
+                    <#ty as ConfigDocs>::field_docs()
+                        .into_iter()
+                        .map(|(path, doc)| (#path, doc)),
+                );
+            });
+        }
+    }
+
+    let name = &ast.ident;
+    let type_docs = docs(&ast.attrs);
+    let (impl_generics, type_generics, where_clause) = ast.generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics ConfigDocs for #name #type_generics #where_clause {
+            fn type_docs() -> &'static str {
+                #type_docs
+            }
+
+            fn field_docs() -> Vec<(String, &'static str)> {
+                let mut docs = Vec::new();
+                #(#pushes)*
+                docs
+            }
+        }
+    }
+}
+
+/// Returns the doc comments in `attrs`, one line each.
+fn docs(attrs: &[Attribute]) -> String {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("doc"))
+        .filter_map(|attr| {
+            match attr.parse_meta() {
+                Ok(Meta::NameValue(nv)) => {
+                    match nv.lit {
+                        Lit::Str(s) => Some(s.value()),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            }
+        })
+        .flat_map(|doc| {
+            doc.split('\n')
+                .map(|line| {
+                    line.strip_prefix(' ')
+                        .unwrap_or(line)
+                        .trim_end()
+                        .to_string()
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    lines.join("\n").trim().to_string()
+}
+
+/// Returns the value of a serde `name = "..."` or `name(serialize = "...")` attribute item.
+fn serialized_name(meta: Meta, name: &str) -> Option<LitStr> {
+    let lit = match meta {
+        Meta::NameValue(nv) if nv.path.is_ident(name) => nv.lit,
+        Meta::List(list) if list.path.is_ident(name) => {
+            list.nested.into_iter().find_map(|nested| {
+                match nested {
+                    NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("serialize") => {
+                        Some(nv.lit)
+                    }
+                    _ => None,
+                }
+            })?
+        }
+        _ => return None,
+    };
+    match lit {
+        Lit::Str(s) => Some(s),
+        _ => None,
+    }
+}
+
+/// Returns the function renaming fields like serde's `rename_all = "rule"`.
+fn rename_rule(rule: &str) -> Option<fn(&str) -> String> {
+    Some(match rule {
+        "lowercase" | "snake_case" => ToString::to_string,
+        "UPPERCASE" | "SCREAMING_SNAKE_CASE" => str::to_ascii_uppercase,
+        "PascalCase" => pascal_case,
+        "camelCase" => {
+            |field| {
+                let pascal = pascal_case(field);
+                pascal[..1].to_ascii_lowercase() + &pascal[1..]
+            }
+        }
+        "kebab-case" => |field| field.replace('_', "-"),
+        "SCREAMING-KEBAB-CASE" => |field| field.to_ascii_uppercase().replace('_', "-"),
+        _ => return None,
+    })
+}
+
+fn pascal_case(field: &str) -> String {
+    let mut pascal = String::new();
+    let mut capitalize = true;
+    for c in field.chars() {
+        if c == '_' {
+            capitalize = true;
+        } else if capitalize {
+            pascal.push(c.to_ascii_uppercase());
+            capitalize = false;
+        } else {
+            pascal.push(c);
+        }
+    }
+    pascal
+}
+
+/// Returns the items of a `#[name(...)]` attribute.
+fn list(attr: &Attribute, name: &str) -> Option<impl Iterator<Item = Meta>> {
+    if !attr.path.is_ident(name) {
+        return None;
+    }
+    match attr.parse_meta() {
+        Ok(Meta::List(list)) => {
+            Some(list.nested.into_iter().filter_map(|nested| {
+                match nested {
+                    NestedMeta::Meta(meta) => Some(meta),
+                    NestedMeta::Lit(_) => None,
+                }
+            }))
+        }
+        _ => None,
+    }
+}
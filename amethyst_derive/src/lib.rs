@@ -23,6 +23,7 @@ use proc_macro::TokenStream;
 use proc_macro2::{Ident, Span};
 use syn::{parse_macro_input, DeriveInput};
 
+mod config_docs;
 mod event_reader;
 mod widget_id;
 
@@ -46,6 +47,33 @@ pub fn widget_id_derive(input: TokenStream) -> TokenStream {
     gen.into()
 }
 
+/// Implements `ConfigDocs` with the doc comments of a configuration struct and its fields, so
+/// `amethyst_config::default_config_string` can write them as comments.
+///
+/// Fields whose type implements `ConfigDocs` as well can be marked with
+/// `#[config_docs(nested)]` to include the documentation of their fields. Field names follow
+/// `#[serde(rename = "...")]` and `#[serde(rename_all = "...")]`, skipped fields are left out and
+/// the fields of nested flattened fields are documented as fields of the struct itself.
+///
+/// ```ignore
+/// use amethyst::{config::ConfigDocs, derive::ConfigDocs};
+///
+/// /// Settings of the game.
+/// #[derive(ConfigDocs, Default, Serialize)]
+/// struct GameConfig {
+///     /// Volume of all sounds, between 0 and 1.
+///     volume: f32,
+///     #[config_docs(nested)]
+///     window: WindowConfig,
+/// }
+/// ```
+#[proc_macro_derive(ConfigDocs, attributes(config_docs))]
+pub fn config_docs_derive(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    let gen = config_docs::impl_config_docs(&ast);
+    gen.into()
+}
+
 use std::path::Path;
 
 use glob::glob;
@@ -0,0 +1,50 @@
+use amethyst::config::ConfigDocs;
+use amethyst_derive::ConfigDocs;
+use serde::Serialize;
+
+/// Settings of the window.
+#[derive(ConfigDocs, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct WindowConfig {
+    /// Covers the whole screen.
+    full_screen: bool,
+    /// Title of the window.
+    #[serde(rename = "name")]
+    window_title: String,
+}
+
+/// Settings of the game.
+#[derive(ConfigDocs, Serialize)]
+#[serde(rename = "Game", rename_all = "camelCase")]
+struct GameConfig {
+    /// Volume of all sounds.
+    sound_volume: f32,
+    /// The main window.
+    #[config_docs(nested)]
+    main_window: WindowConfig,
+    #[serde(flatten)]
+    #[config_docs(nested)]
+    flattened: WindowConfig,
+    /// Not saved.
+    #[serde(skip)]
+    _cache: Vec<u8>,
+}
+
+#[test]
+fn config_docs_follow_serde_names() {
+    assert_eq!(GameConfig::type_docs(), "Settings of the game.");
+    assert_eq!(
+        GameConfig::field_docs(),
+        vec![
+            ("soundVolume".to_string(), "Volume of all sounds."),
+            ("mainWindow".to_string(), "The main window."),
+            (
+                "mainWindow.full-screen".to_string(),
+                "Covers the whole screen."
+            ),
+            ("mainWindow.name".to_string(), "Title of the window."),
+            ("full-screen".to_string(), "Covers the whole screen."),
+            ("name".to_string(), "Title of the window."),
+        ]
+    );
+}
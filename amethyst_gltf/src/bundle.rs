@@ -4,7 +4,9 @@ use amethyst_core::{
 };
 use amethyst_error::Error;
 
-use crate::system::{animation_hierarchy_loading, material_handle_loading, mesh_handle_loading};
+use crate::system::{
+    animation_hierarchy_loading, material_handle_loading, mesh_handle_loading, mesh_lod_loading,
+};
 
 /// Bundle that initializes needed resources to use GLTF
 #[derive(Debug)]
@@ -18,6 +20,7 @@ impl SystemBundle for GltfBundle {
         builder: &mut DispatcherBuilder,
     ) -> Result<(), Error> {
         builder.add_thread_local_fn(mesh_handle_loading);
+        builder.add_thread_local_fn(mesh_lod_loading);
         builder.add_thread_local_fn(material_handle_loading);
         builder.add_thread_local_fn(animation_hierarchy_loading);
         Ok(())
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+
+use gltf::{Document, Glb, Node};
+use log::warn;
+use serde_json::Value;
+
+/// Levels of detail of a node, from the `MSFT_lod` extension or from the `_LOD<n>` suffix of the
+/// names of sibling nodes.
+#[derive(Debug)]
+pub struct LodChain<'a> {
+    /// Nodes of the less detailed levels, in order.
+    pub nodes: Vec<Node<'a>>,
+    /// Thresholds of the levels starting with the node of the chain, see `LodMetric::ScreenSize`.
+    pub thresholds: Vec<f32>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct LodIndices {
+    nodes: Vec<usize>,
+    screen_coverages: Vec<f32>,
+}
+
+/// Returns the level of detail chains of `document` by index of the node of their most detailed
+/// level. `bytes` are the imported bytes, the extension being read from the raw JSON.
+pub fn load_lod_chains<'a>(document: &'a Document, bytes: &[u8]) -> HashMap<usize, LodChain<'a>> {
+    let mut chains = match raw_json(bytes) {
+        Ok(json) => msft_lod_chains(&json),
+        Err(err) => {
+            warn!(
+                "Failed to read the levels of detail of the Gltf file: {}",
+                err
+            );
+            HashMap::new()
+        }
+    };
+
+    let mut parents = HashMap::new();
+    for node in document.nodes() {
+        for child in node.children() {
+            parents.insert(child.index(), node.index());
+        }
+    }
+    let named = named_lod_chains(document.nodes().map(|node| {
+        (
+            node.index(),
+            parents.get(&node.index()).copied(),
+            node.name(),
+        )
+    }));
+    for (node, chain) in named {
+        chains.entry(node).or_insert(chain);
+    }
+
+    chains
+        .into_iter()
+        .map(|(index, chain)| {
+            let levels = chain.nodes.len() + 1;
+            let chain = LodChain {
+                nodes: chain
+                    .nodes
+                    .iter()
+                    .filter_map(|node| document.nodes().nth(*node))
+                    .collect(),
+                thresholds: thresholds(&chain.screen_coverages, levels),
+            };
+            (index, chain)
+        })
+        .collect()
+}
+
+/// Returns true if `node` is one of the less detailed levels of a chain, loaded with the node of
+/// the chain instead of on its own.
+pub fn is_lower_level(node: &Node<'_>, chains: &HashMap<usize, LodChain<'_>>) -> bool {
+    chains.values().any(|chain| {
+        chain
+            .nodes
+            .iter()
+            .any(|level| level.index() == node.index())
+    })
+}
+
+fn raw_json(bytes: &[u8]) -> Result<Value, String> {
+    if bytes.starts_with(b"glTF") {
+        let glb = Glb::from_slice(bytes).map_err(|err| err.to_string())?;
+        serde_json::from_slice(&glb.json).map_err(|err| err.to_string())
+    } else {
+        serde_json::from_slice(bytes).map_err(|err| err.to_string())
+    }
+}
+
+/// Reads the `MSFT_lod` extension of the nodes, and their `MSFT_screencoverage` extras.
+fn msft_lod_chains(json: &Value) -> HashMap<usize, LodIndices> {
+    let nodes = match json.get("nodes").and_then(Value::as_array) {
+        Some(nodes) => nodes,
+        None => return HashMap::new(),
+    };
+    nodes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, node)| {
+            let ids = node.pointer("/extensions/MSFT_lod/ids")?.as_array()?;
+            let screen_coverages = node
+                .pointer("/extras/MSFT_screencoverage")
+                .and_then(Value::as_array)
+                .map_or_else(Vec::new, |coverages| {
+                    coverages
+                        .iter()
+                        .filter_map(Value::as_f64)
+                        .map(|coverage| coverage as f32)
+                        .collect()
+                });
+            Some((
+                index,
+                LodIndices {
+                    nodes: ids
+                        .iter()
+                        .filter_map(Value::as_u64)
+                        .map(|id| id as usize)
+                        .collect(),
+                    screen_coverages,
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Groups the sibling nodes named `<name>_LOD<n>`, from their index, parent and name.
+fn named_lod_chains<'a>(
+    nodes: impl IntoIterator<Item = (usize, Option<usize>, Option<&'a str>)>,
+) -> HashMap<usize, LodIndices> {
+    let mut groups: HashMap<(Option<usize>, &str), Vec<(u32, usize)>> = HashMap::new();
+    for (index, parent, name) in nodes {
+        let level = name.and_then(|name| {
+            let (base, level) = name.split_at(name.rfind("_LOD")?);
+            Some((base, level["_LOD".len()..].parse::<u32>().ok()?))
+        });
+        if let Some((base, level)) = level {
+            groups
+                .entry((parent, base))
+                .or_default()
+                .push((level, index));
+        }
+    }
+
+    groups
+        .into_values()
+        .filter_map(|mut levels| {
+            levels.sort_unstable();
+            let (first, index) = levels[0];
+            if first != 0 || levels.len() < 2 {
+                return None;
+            }
+            Some((
+                index,
+                LodIndices {
+                    nodes: levels[1..].iter().map(|(_, node)| *node).collect(),
+                    screen_coverages: Vec::new(),
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Screen size thresholds of `levels` levels. `MSFT_screencoverage` is a fraction of the screen
+/// area while the screen size is a fraction of its height, hence the square root. Without screen
+/// coverages, each level is drawn down to half the size of the previous one and the last one is
+/// never culled.
+fn thresholds(screen_coverages: &[f32], levels: usize) -> Vec<f32> {
+    if screen_coverages.len() >= levels {
+        screen_coverages[..levels]
+            .iter()
+            .map(|coverage| coverage.max(0.0).sqrt())
+            .collect()
+    } else {
+        (0..levels)
+            .map(|level| {
+                if level + 1 == levels {
+                    0.0
+                } else {
+                    0.5f32.powi(level as i32 + 1)
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_read_msft_lod() {
+        let json: Value = serde_json::from_str(
+            r#"{
+                "nodes": [
+                    {
+                        "mesh": 0,
+                        "extensions": { "MSFT_lod": { "ids": [1, 2] } },
+                        "extras": { "MSFT_screencoverage": [0.25, 0.04, 0.01] }
+                    },
+                    { "mesh": 1 },
+                    { "mesh": 2 }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let chains = msft_lod_chains(&json);
+        assert_eq!(chains.len(), 1);
+        assert_eq!(
+            chains[&0],
+            LodIndices {
+                nodes: vec![1, 2],
+                screen_coverages: vec![0.25, 0.04, 0.01],
+            }
+        );
+        assert_eq!(
+            thresholds(&chains[&0].screen_coverages, 3),
+            vec![0.5, 0.2, 0.1]
+        );
+    }
+
+    #[test]
+    fn should_group_lod_names() {
+        let chains = named_lod_chains(vec![
+            (0, None, Some("Tree_LOD1")),
+            (1, None, Some("Tree_LOD0")),
+            (2, None, Some("Tree_LOD2")),
+            (3, Some(7), Some("Tree_LOD1")),
+            (4, None, Some("Rock_LOD0")),
+            (5, None, Some("Rock")),
+            (6, None, None),
+        ]);
+        assert_eq!(chains.len(), 1);
+        assert_eq!(chains[&1].nodes, vec![0, 2]);
+        assert_eq!(thresholds(&[], 3), vec![0.5, 0.25, 0.0]);
+    }
+}
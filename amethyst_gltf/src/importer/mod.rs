@@ -16,7 +16,10 @@ use amethyst_core::{
     math::{convert, Quaternion, Unit, Vector3, Vector4},
     transform::Transform,
 };
-use amethyst_rendy::{light::Light, types::MeshData, Camera, Material};
+use amethyst_rendy::{
+    light::Light, mesh_vertices::MeshVertices, rendy::mesh::MeshBuilder, types::MeshData, Camera,
+    Material,
+};
 use gltf::{buffer::Data, Document, Node};
 use log::debug;
 use serde::{Deserialize, Serialize};
@@ -26,17 +29,19 @@ use crate::{
     importer::{
         animation::load_animations,
         gltf_bytes_converter::convert_bytes,
+        lod::{is_lower_level, load_lod_chains, LodChain},
         material::{convert_optional_index_to_string, load_material},
         mesh::load_mesh,
         skin::load_skin,
     },
-    types::{GltfNodeExtent, MaterialHandle, MeshHandle},
+    types::{GltfNodeExtent, MaterialHandle, MeshHandle, MeshLodHandles},
     GltfSceneOptions,
 };
 
 mod animation;
 mod gltf_bytes_converter;
 mod images;
+mod lod;
 mod material;
 mod mesh;
 mod skin;
@@ -122,6 +127,7 @@ impl Importer for GltfImporter {
 
         let mut skin_map = HashMap::new();
         let mut node_map = HashMap::new();
+        let lod_chains = load_lod_chains(&doc, &bytes);
 
         scene.nodes().into_iter().for_each(|node| {
             let mut node_assets = load_node(
@@ -135,6 +141,7 @@ impl Importer for GltfImporter {
                 &mut node_map,
                 &mut skin_map,
                 None,
+                &lod_chains,
             );
             asset_accumulator.append(&mut node_assets);
         });
@@ -195,7 +202,13 @@ fn load_node(
     node_map: &mut HashMap<usize, Entity>,
     skin_map: &mut HashMap<Entity, SkinInfo>,
     parent_bounding_box: Option<&mut GltfNodeExtent>,
+    lod_chains: &HashMap<usize, LodChain<'_>>,
 ) -> Vec<ImportedAsset> {
+    if is_lower_level(node, lod_chains) {
+        // Loaded with the most detailed level of its chain
+        return Vec::new();
+    }
+
     let current_node_entity = world.push(());
     node_map.insert(node.index(), current_node_entity);
     let mut imported_assets = Vec::new();
@@ -270,6 +283,7 @@ fn load_node(
                         .entry(format!("{}_{}", name, 0))
                         .or_insert_with(|| op.new_asset_uuid());
 
+                    let vertices = load_mesh_vertices(&mesh, options);
                     let mesh_data: MeshData = mesh.into();
                     imported_assets.push(ImportedAsset {
                         id: mesh_asset_id,
@@ -284,6 +298,29 @@ fn load_node(
                         .entry(current_node_entity)
                         .expect("We just added this entity")
                         .add_component(MeshHandle(make_handle(mesh_asset_id)));
+                    if let Some(vertices) = vertices {
+                        world
+                            .entry(current_node_entity)
+                            .expect("We just added this entity")
+                            .add_component(vertices);
+                    }
+
+                    if let Some(chain) = lod_chains.get(&node.index()) {
+                        debug!("Adding the levels of detail to the current node entity");
+                        let lod_handles = load_lod_levels(
+                            mesh_asset_id,
+                            chain,
+                            op,
+                            state,
+                            options,
+                            buffers,
+                            &mut imported_assets,
+                        );
+                        world
+                            .entry(current_node_entity)
+                            .expect("We just added this entity")
+                            .add_component(lod_handles);
+                    }
 
                     debug!("Adding a mesh component to to the current node entity");
 
@@ -313,6 +350,9 @@ fn load_node(
                 }
             }
             Ordering::Greater => {
+                if lod_chains.contains_key(&node.index()) {
+                    log::warn!("Levels of detail of meshes with several primitives are ignored");
+                }
                 let mut primitive_index = 0;
                 while let Some((name, mesh, material_index, _bounds)) = loaded_primitives.pop() {
                     let mesh_asset_id = *state
@@ -322,6 +362,7 @@ fn load_node(
                         .entry(format!("{}_{}", name, primitive_index))
                         .or_insert_with(|| op.new_asset_uuid());
 
+                    let vertices = load_mesh_vertices(&mesh, options);
                     let mesh_data: MeshData = mesh.into();
                     imported_assets.push(ImportedAsset {
                         id: mesh_asset_id,
@@ -344,6 +385,12 @@ fn load_node(
                                 .expect("A requested material is not loaded"),
                         )),
                     ));
+                    if let Some(vertices) = vertices {
+                        world
+                            .entry(current_primitive_entity)
+                            .expect("We just added this entity")
+                            .add_component(vertices);
+                    }
                     primitive_index += 1;
 
                     // Should add an entity per primitive
@@ -371,6 +418,7 @@ fn load_node(
             node_map,
             skin_map,
             Some(&mut bounding_box),
+            lod_chains,
         );
         imported_assets.append(&mut child_assets);
     }
@@ -395,6 +443,63 @@ fn load_node(
     imported_assets
 }
 
+// Reads the vertices of a mesh for its `MeshVertices` component, if the options ask for it.
+fn load_mesh_vertices(mesh: &MeshBuilder<'_>, options: &GltfSceneOptions) -> Option<MeshVertices> {
+    if !options.mesh_vertices {
+        return None;
+    }
+    MeshVertices::from_mesh_builder(mesh)
+        .map_err(|e| log::warn!("Could not attach the vertices of a mesh: {}", e))
+        .ok()
+}
+
+// Loads the meshes of the less detailed levels of a chain, the most detailed one being the already
+// loaded `mesh_asset_id`. Only one primitive of each level is loaded.
+fn load_lod_levels(
+    mesh_asset_id: AssetUuid,
+    chain: &LodChain<'_>,
+    op: &mut ImportOp,
+    state: &mut GltfImporterState,
+    options: &GltfSceneOptions,
+    buffers: &[Data],
+    imported_assets: &mut Vec<ImportedAsset>,
+) -> MeshLodHandles {
+    let mut meshes = vec![make_handle(mesh_asset_id)];
+    let mut thresholds = vec![chain.thresholds[0]];
+    for (node, threshold) in chain.nodes.iter().zip(&chain.thresholds[1..]) {
+        let primitive = node
+            .mesh()
+            .and_then(|mesh| load_mesh(&mesh, buffers, options).ok())
+            .and_then(|mut primitives| primitives.pop());
+        let (name, mesh, _, _) = match primitive {
+            Some(primitive) => primitive,
+            None => {
+                log::warn!("Level of detail node {} has no mesh", node.index());
+                continue;
+            }
+        };
+        let mesh_asset_id = *state
+            .mesh_uuids
+            .as_mut()
+            .expect("Meshes hashmap didn't work")
+            .entry(format!("{}_{}", name, 0))
+            .or_insert_with(|| op.new_asset_uuid());
+
+        let mesh_data: MeshData = mesh.into();
+        imported_assets.push(ImportedAsset {
+            id: mesh_asset_id,
+            search_tags: vec![],
+            build_deps: vec![],
+            load_deps: vec![],
+            build_pipeline: None,
+            asset_data: Box::new(mesh_data),
+        });
+        meshes.push(make_handle(mesh_asset_id));
+        thresholds.push(*threshold);
+    }
+    MeshLodHandles { meshes, thresholds }
+}
+
 fn load_light(node: &Node<'_>) -> Option<Light> {
     if let Some(light) = node.light() {
         return Some(Light::from(light));
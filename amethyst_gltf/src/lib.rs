@@ -39,6 +39,7 @@ inventory::submit! {
     amethyst_assets::SourceFileImporter {
         extension: "gltf",
         instantiator: || Box::new(GltfImporter::default()),
+        defaults: amethyst_assets::merge_import_defaults::<GltfSceneOptions>,
     }
 }
 
@@ -46,6 +47,7 @@ inventory::submit! {
     amethyst_assets::SourceFileImporter {
         extension: "glb",
         instantiator: || Box::new(GltfImporter::default()),
+        defaults: amethyst_assets::merge_import_defaults::<GltfSceneOptions>,
     }
 }
 
@@ -53,6 +55,10 @@ register_component_type!(Joint);
 register_asset_type!(Animation<Transform> => Animation<Transform>; AssetProcessorSystem<Animation<Transform>>);
 
 /// Options used when loading a GLTF file
+///
+/// These are the importer options of `.gltf` and `.glb` files, changed for a single file in the
+/// `importer_options` of its `.meta` file or for a folder in a `glb.import_defaults` file. Missing
+/// fields keep their default value.
 #[derive(Debug, Clone, Derivative, Serialize, Deserialize, TypeUuid)]
 #[serde(default)]
 #[derivative(Default)]
@@ -80,4 +86,7 @@ pub struct GltfSceneOptions {
     /// Load the given scene index, if not supplied will either load the default scene (if set),
     /// or the first scene (only if there is only one scene, otherwise an `Error` will be returned).
     pub scene_index: Option<usize>,
+    /// Attach a `MeshVertices` component with the vertices of their mesh to the mesh entities,
+    /// for picking against triangles and static batching
+    pub mesh_vertices: bool,
 }
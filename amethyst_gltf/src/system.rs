@@ -3,11 +3,12 @@ use amethyst_core::{
     ecs::{component, Entity, IntoQuery, Resources, World},
     Transform,
 };
+use amethyst_rendy::lod::{LodMetric, MeshLod};
 use log::debug;
 
 use crate::{
     importer::{NodeEntityIdentifier, UniqueAnimationHierarchyId},
-    types::{MaterialHandle, MeshHandle},
+    types::{MaterialHandle, MeshHandle, MeshLodHandles},
 };
 
 /// This will attach a Handle<Mesh> to any Entity with a `MeshHandle`, and remove the Meshhandle
@@ -30,6 +31,31 @@ pub(crate) fn mesh_handle_loading(world: &mut World, _resources: &mut Resources)
     }
 }
 
+/// This will attach a `MeshLod` to any Entity with a `MeshLodHandles`, and remove the `MeshLodHandles`
+pub(crate) fn mesh_lod_loading(world: &mut World, _resources: &mut Resources) {
+    let mut entity_lod = Vec::new();
+
+    <(Entity, &MeshLodHandles)>::query().for_each(world, |(entity, lod_handles)| {
+        entity_lod.push((*entity, lod_handles.clone()));
+    });
+
+    while let Some((entity, lod_handles)) = entity_lod.pop() {
+        let lod = MeshLod {
+            meshes: lod_handles.meshes,
+            thresholds: lod_handles.thresholds,
+            ..MeshLod::new(LodMetric::ScreenSize)
+        };
+        world
+            .entry(entity)
+            .expect("This can't exist because we just register this entity from the world")
+            .remove_component::<MeshLodHandles>();
+        world
+            .entry(entity)
+            .expect("This can't exist because we just register this entity from the world")
+            .add_component(lod);
+    }
+}
+
 /// This will attach a Handle<Material> to any Entity with a `MaterialHandle`, and remove the `MaterialHandle`
 pub(crate) fn material_handle_loading(world: &mut World, _resources: &mut Resources) {
     let mut entity_material = Vec::new();
@@ -3,7 +3,7 @@ use std::ops::Range;
 use amethyst_assets::{
     erased_serde::private::serde::{de, de::SeqAccess, ser::SerializeSeq},
     prefab::{
-        register_component_type,
+        register_component_type, serde_diff,
         serde_diff::{ApplyContext, DiffContext},
         SerdeDiff,
     },
@@ -82,6 +82,20 @@ impl SerdeDiff for MaterialHandle {
 
 register_component_type!(MaterialHandle);
 
+/// `MeshLodHandles` is a component that will handle the fact that we attach the meshes of the
+/// levels of detail of a node, later replaced by a `MeshLod`.
+#[derive(Serialize, Deserialize, TypeUuid, Clone, Default, PartialEq, SerdeDiff)]
+#[uuid = "77911616-c60f-47fd-b00b-734265ebdbab"]
+pub struct MeshLodHandles {
+    /// Meshes of the levels, the most detailed first
+    #[serde_diff(opaque)]
+    pub meshes: Vec<Handle<Mesh>>,
+    /// Screen size threshold of each level
+    pub thresholds: Vec<f32>,
+}
+
+register_component_type!(MeshLodHandles);
+
 /// A GLTF node extent
 #[derive(Serialize, Deserialize, TypeUuid, Clone, Debug)]
 #[uuid = "e569daf6-f391-4235-b75b-28d55b26b0a1"]
@@ -15,20 +15,37 @@
     clippy::pedantic
 )]
 
+use std::{path::Path, sync::Arc};
+
 use amethyst_assets::{
     register_asset_type, register_importer, Asset, AssetProcessorSystem, AssetStorage, Format,
     LoadHandle, ProcessableAsset, ProcessingState,
 };
-use amethyst_error::Error;
-pub use fluent::{concurrent::FluentBundle, FluentResource};
+use amethyst_error::{format_err, Error};
+pub use fluent::{concurrent::FluentBundle, FluentArgs, FluentResource, FluentValue};
 use serde::{Deserialize, Serialize};
 use type_uuid::TypeUuid;
-use unic_langid::langid;
+pub use unic_langid::{langid, LanguageIdentifier};
+
+pub use crate::{
+    localization::{Localization, LocalizationBundle},
+    pseudo::pseudo_localize,
+    report::{LanguageCoverage, MissingMessagesReport},
+};
+
+mod localization;
+mod pseudo;
+mod report;
 
 /// Internal representation of a Locale
 #[derive(Clone, Debug, Serialize, Deserialize, TypeUuid)]
 #[uuid = "442ea0e0-48d8-4a3c-ab36-faba55f2c0db"]
-pub struct LocaleData(pub Vec<u8>);
+pub struct LocaleData {
+    /// Language of the messages set in the import settings, if any.
+    pub language: Option<String>,
+    /// Contents of the `.ftl` file.
+    pub bytes: Vec<u8>,
+}
 register_asset_type!(LocaleData => Locale; AssetProcessorSystem<Locale>);
 
 /// A loaded locale.
@@ -36,8 +53,14 @@ register_asset_type!(LocaleData => Locale; AssetProcessorSystem<Locale>);
 #[derive(TypeUuid)]
 #[uuid = "bf7713bb-6e1f-4873-bf0b-9d7c2253f46a"]
 pub struct Locale {
+    /// Language of the messages set in the import settings, `und` if it is unknown.
+    ///
+    /// The [`Localization`] resource falls back to the language in the file name.
+    pub language: LanguageIdentifier,
+    /// The parsed messages.
+    pub resource: Arc<FluentResource>,
     /// The bundle stores its resources for now.
-    pub bundle: FluentBundle<FluentResource>,
+    pub bundle: FluentBundle<Arc<FluentResource>>,
 }
 
 impl Asset for Locale {
@@ -53,24 +76,45 @@ impl ProcessableAsset for Locale {
         _storage: &mut AssetStorage<Locale>,
         _handle: &LoadHandle,
     ) -> Result<amethyst_assets::ProcessingState<LocaleData, Locale>, Error> {
-        let s = String::from_utf8(data.0)?;
+        let language = match &data.language {
+            Some(language) => {
+                language
+                    .parse::<LanguageIdentifier>()
+                    .map_err(|e| format_err!("Invalid locale language `{}`: {}", language, e))?
+            }
+            None => LanguageIdentifier::default(),
+        };
+        let s = String::from_utf8(data.bytes)?;
 
-        let resource = FluentResource::try_new(s).expect("Failed to parse locale data");
-        let lang_en = langid!("en");
-        let mut bundle = FluentBundle::new(vec![lang_en]);
+        let resource = FluentResource::try_new(s)
+            .map_err(|(_, errors)| format_err!("Failed to parse locale data: {:?}", errors))?;
+        let resource = Arc::new(resource);
+        let mut bundle = FluentBundle::new(vec![language.clone()]);
 
         bundle
-            .add_resource(resource)
-            .expect("Failed to add resource");
+            .add_resource(resource.clone())
+            .map_err(|errors| format_err!("Failed to add locale resource: {:?}", errors))?;
 
-        Ok(ProcessingState::Loaded(Locale { bundle }))
+        Ok(ProcessingState::Loaded(Locale {
+            language,
+            resource,
+            bundle,
+        }))
     }
 }
 
 /// Loads the strings from localisation files.
+///
+/// The language of the messages can be set with `language` in the import settings of the
+/// `.meta` file. Otherwise it is taken from the file name when the locale is added to the
+/// [`Localization`], see [`language_from_path`].
 #[derive(Clone, Debug, Default, TypeUuid, Serialize, Deserialize)]
 #[uuid = "fe7720ec-ecb5-4f59-8a09-656805eb4eff"]
-pub struct FTLFormat;
+pub struct FTLFormat {
+    /// Language of the messages, such as `fr-FR`.
+    #[serde(default)]
+    pub language: Option<String>,
+}
 
 register_importer!(".ftl", FTLFormat);
 impl Format<LocaleData> for FTLFormat {
@@ -79,6 +123,93 @@ impl Format<LocaleData> for FTLFormat {
     }
 
     fn import_simple(&self, bytes: Vec<u8>) -> Result<LocaleData, Error> {
-        Ok(LocaleData(bytes))
+        Ok(LocaleData {
+            language: self.language.clone(),
+            bytes,
+        })
+    }
+}
+
+/// ISO 639-1 language codes, sorted. Only these are accepted in locale paths on their own.
+const KNOWN_LANGUAGES: &[&str] = &[
+    "aa", "ab", "ae", "af", "ak", "am", "an", "ar", "as", "av", "ay", "az", "ba", "be", "bg", "bh",
+    "bi", "bm", "bn", "bo", "br", "bs", "ca", "ce", "ch", "co", "cr", "cs", "cu", "cv", "cy", "da",
+    "de", "dv", "dz", "ee", "el", "en", "eo", "es", "et", "eu", "fa", "ff", "fi", "fj", "fo", "fr",
+    "fy", "ga", "gd", "gl", "gn", "gu", "gv", "ha", "he", "hi", "ho", "hr", "ht", "hu", "hy", "hz",
+    "ia", "id", "ie", "ig", "ii", "ik", "io", "is", "it", "iu", "ja", "jv", "ka", "kg", "ki", "kj",
+    "kk", "kl", "km", "kn", "ko", "kr", "ks", "ku", "kv", "kw", "ky", "la", "lb", "lg", "li", "ln",
+    "lo", "lt", "lu", "lv", "mg", "mh", "mi", "mk", "ml", "mn", "mr", "ms", "mt", "my", "na", "nb",
+    "nd", "ne", "ng", "nl", "nn", "no", "nr", "nv", "ny", "oc", "oj", "om", "or", "os", "pa", "pi",
+    "pl", "ps", "pt", "qu", "rm", "rn", "ro", "ru", "rw", "sa", "sc", "sd", "se", "sg", "si", "sk",
+    "sl", "sm", "sn", "so", "sq", "sr", "ss", "st", "su", "sv", "sw", "ta", "te", "tg", "th", "ti",
+    "tk", "tl", "tn", "to", "tr", "ts", "tt", "tw", "ty", "ug", "uk", "ur", "uz", "ve", "vi", "vo",
+    "wa", "wo", "xh", "yi", "yo", "za", "zh", "zu",
+];
+
+/// Returns the language identifier in a locale path, such as `locale/fr/menu.ftl`,
+/// `locale/fr-FR.ftl` or `locale/menu_fr.ftl`.
+///
+/// The name of the parent directory is tried first, then the parts of the file name separated by
+/// `_` or `.` from the last one. A part is only accepted if it is a known two letter language,
+/// or an explicit tag with a script or region such as `fil-PH`, so that `locale/en/ui.ftl` is
+/// English rather than a language called `ui`.
+#[must_use]
+pub fn language_from_path(path: &str) -> Option<LanguageIdentifier> {
+    let path = Path::new(path);
+    let parent = path
+        .parent()
+        .and_then(Path::file_name)
+        .and_then(std::ffi::OsStr::to_str);
+    let stem = path.file_stem().and_then(std::ffi::OsStr::to_str);
+
+    parent
+        .into_iter()
+        .chain(stem.into_iter().flat_map(|stem| stem.rsplit(['_', '.'])))
+        .find_map(language_tag)
+}
+
+/// Parses `part` if it is a known language or an explicit language tag.
+fn language_tag(part: &str) -> Option<LanguageIdentifier> {
+    let language = part.parse::<LanguageIdentifier>().ok()?;
+    let code = language.language.as_str();
+    let known = KNOWN_LANGUAGES.binary_search(&code).is_ok();
+    let explicit = language.script.is_some() || language.region.is_some();
+    if (2..=3).contains(&code.len()) && (known || explicit) {
+        Some(language)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use unic_langid::langid;
+
+    use super::language_from_path;
+
+    #[test]
+    fn language_is_read_from_path() {
+        assert_eq!(
+            language_from_path("locale/fr-FR.ftl"),
+            Some(langid!("fr-FR"))
+        );
+        assert_eq!(
+            language_from_path("locale/locale_fr.ftl"),
+            Some(langid!("fr"))
+        );
+        assert_eq!(
+            language_from_path("locale/de/menu.ftl"),
+            Some(langid!("de"))
+        );
+        assert_eq!(
+            language_from_path("assets/locale/en/ui.ftl"),
+            Some(langid!("en"))
+        );
+        assert_eq!(
+            language_from_path("locale/menu_fil-PH.ftl"),
+            Some(langid!("fil-PH"))
+        );
+        assert_eq!(language_from_path("locale/ui.ftl"), None);
+        assert_eq!(language_from_path("locale/locale.ftl"), None);
     }
 }
@@ -0,0 +1,573 @@
+//! Message lookup through the locales of the preferred languages.
+
+use std::{
+    collections::BTreeSet,
+    fmt,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use amethyst_assets::{AssetStorage, Handle, Loader};
+use amethyst_core::ecs::{
+    DispatcherBuilder, ParallelRunnable, Resources, System, SystemBuilder, SystemBundle, World,
+};
+use amethyst_error::Error;
+use fluent::{FluentArgs, FluentResource};
+use fluent_langneg::{negotiate_languages, NegotiationStrategy};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+use unic_langid::LanguageIdentifier;
+
+use crate::{
+    language_from_path, pseudo_localize, FluentBundle, LanguageCoverage, Locale,
+    MissingMessagesReport,
+};
+
+struct LocaleSource {
+    handle: Handle<Locale>,
+    /// Language from the file name, used if the locale has none in its import settings.
+    language: Option<LanguageIdentifier>,
+    /// Version of the asset the bundles were built from.
+    version: Option<u32>,
+}
+
+/// Formats messages in the preferred languages of the user.
+///
+/// Locales are added with [`Localization::load`] or [`Localization::add`], several files can
+/// provide messages for the same language. The requested languages are negotiated against the
+/// languages of the loaded locales, and messages missing in one language are looked up in the
+/// next one of the fallback chain, ending with the default language.
+///
+/// The bundles are rebuilt by the system added with the [`LocalizationBundle`] when locales are
+/// loaded or hot-reloaded.
+///
+/// # Example
+///
+/// ```no_run
+/// use amethyst::{
+///     assets::DefaultLoader,
+///     core::ecs::Resources,
+///     locale::{langid, FluentArgs, Localization},
+/// };
+///
+/// # fn load(resources: &Resources) {
+/// let loader = resources.get::<DefaultLoader>().unwrap();
+/// let mut localization = resources.get_mut::<Localization>().unwrap();
+/// localization.load(&*loader, "locale/en.ftl");
+/// localization.load(&*loader, "locale/fr.ftl");
+/// localization.set_requested_languages(vec![langid!("fr-CA"), langid!("en-US")]);
+///
+/// // Later, once the locales are loaded.
+/// let mut args = FluentArgs::new();
+/// args.set("name", "Ferris");
+/// println!("{}", localization.format_or_id("greeting", Some(&args)));
+/// # }
+/// ```
+pub struct Localization {
+    default_language: LanguageIdentifier,
+    requested: Vec<LanguageIdentifier>,
+    sources: Vec<LocaleSource>,
+    bundles: Vec<(LanguageIdentifier, FluentBundle<Arc<FluentResource>>)>,
+    /// Indices into `bundles`, in the order they are searched.
+    chain: Vec<usize>,
+    use_isolating: bool,
+    pseudo: bool,
+    /// Ids passed to `format`, if missing messages are tracked.
+    requested_ids: Option<Mutex<BTreeSet<String>>>,
+    revision: u64,
+}
+
+impl fmt::Debug for Localization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Localization")
+            .field("default_language", &self.default_language)
+            .field("requested", &self.requested)
+            .field("locales", &self.sources.len())
+            .field("languages", &self.languages().collect::<Vec<_>>())
+            .field("pseudo", &self.pseudo)
+            .field("revision", &self.revision)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Localization {
+    /// Creates a localization falling back to `default_language`, which should be the language
+    /// with the most complete locale.
+    #[must_use]
+    pub fn new(default_language: LanguageIdentifier) -> Self {
+        Localization {
+            default_language,
+            requested: Vec::new(),
+            sources: Vec::new(),
+            bundles: Vec::new(),
+            chain: Vec::new(),
+            use_isolating: true,
+            pseudo: false,
+            requested_ids: None,
+            revision: 0,
+        }
+    }
+
+    /// Loads the locale at `path`, its language is taken from the file name unless it is set in
+    /// the import settings. See [`language_from_path`].
+    pub fn load<L: Loader>(&mut self, loader: &L, path: &str) -> Handle<Locale> {
+        let handle = loader.load(path);
+        self.sources.push(LocaleSource {
+            handle: handle.clone(),
+            language: language_from_path(path),
+            version: None,
+        });
+        handle
+    }
+
+    /// Adds a locale whose language is set in its import settings.
+    pub fn add(&mut self, handle: Handle<Locale>) {
+        self.sources.push(LocaleSource {
+            handle,
+            language: None,
+            version: None,
+        });
+    }
+
+    /// Adds a locale for `language`, unless its import settings say otherwise.
+    pub fn add_with_language(&mut self, handle: Handle<Locale>, language: LanguageIdentifier) {
+        self.sources.push(LocaleSource {
+            handle,
+            language: Some(language),
+            version: None,
+        });
+    }
+
+    /// Sets the languages preferred by the user, the most preferred first.
+    pub fn set_requested_languages<I>(&mut self, languages: I)
+    where
+        I: IntoIterator<Item = LanguageIdentifier>,
+    {
+        self.requested = languages.into_iter().collect();
+        self.negotiate();
+    }
+
+    /// Sets whether arguments are surrounded by Unicode isolation marks, which keep
+    /// right-to-left and left-to-right text apart. Enabled by default.
+    ///
+    /// Fonts without these invisible characters draw them as boxes.
+    pub fn set_use_isolating(&mut self, use_isolating: bool) {
+        self.use_isolating = use_isolating;
+        for (_, bundle) in &mut self.bundles {
+            bundle.set_use_isolating(use_isolating);
+        }
+        self.revision += 1;
+    }
+
+    /// Enables or disables pseudo-localization of every formatted message, see
+    /// [`pseudo_localize`].
+    ///
+    /// Meant for testing the UI: text which isn't localized stays plain, and the longer
+    /// messages show which labels overflow.
+    pub fn set_pseudo_localization(&mut self, enabled: bool) {
+        self.pseudo = enabled;
+        self.revision += 1;
+    }
+
+    /// Returns `true` if formatted messages are pseudo-localized.
+    #[must_use]
+    pub fn pseudo_localization(&self) -> bool {
+        self.pseudo
+    }
+
+    /// Enables or disables recording the ids of formatted messages, which
+    /// [`Localization::missing_messages_report`] checks against the loaded locales.
+    ///
+    /// Disabling it forgets the recorded ids.
+    pub fn track_missing_messages(&mut self, enabled: bool) {
+        if !enabled {
+            self.requested_ids = None;
+        } else if self.requested_ids.is_none() {
+            self.requested_ids = Some(Mutex::default());
+        }
+    }
+
+    /// Returns, for each loaded language, the ids formatted since tracking was enabled with
+    /// [`Localization::track_missing_messages`] which the language doesn't have.
+    #[must_use]
+    pub fn missing_messages_report(&self) -> MissingMessagesReport {
+        let requested = match &self.requested_ids {
+            Some(ids) => ids.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+            None => BTreeSet::new(),
+        };
+        let languages = self
+            .bundles
+            .iter()
+            .map(|(language, bundle)| {
+                let missing: Vec<String> = requested
+                    .iter()
+                    .filter(|id| !bundle.has_message(id))
+                    .cloned()
+                    .collect();
+                let coverage = if requested.is_empty() {
+                    1.0
+                } else {
+                    (requested.len() - missing.len()) as f32 / requested.len() as f32
+                };
+                (language.to_string(), LanguageCoverage { coverage, missing })
+            })
+            .collect();
+        MissingMessagesReport {
+            requested: requested.len(),
+            languages,
+        }
+    }
+
+    /// Returns the languages preferred by the user.
+    #[must_use]
+    pub fn requested_languages(&self) -> &[LanguageIdentifier] {
+        &self.requested
+    }
+
+    /// Returns the language used when no requested language is available.
+    #[must_use]
+    pub fn default_language(&self) -> &LanguageIdentifier {
+        &self.default_language
+    }
+
+    /// Returns the languages of the loaded locales.
+    pub fn available_languages(&self) -> impl Iterator<Item = &LanguageIdentifier> {
+        self.bundles.iter().map(|(language, _)| language)
+    }
+
+    /// Returns the fallback chain, the languages in which messages are looked up in order.
+    pub fn languages(&self) -> impl Iterator<Item = &LanguageIdentifier> {
+        self.chain.iter().map(move |i| &self.bundles[*i].0)
+    }
+
+    /// Returns a number which changes whenever formatted messages may change, because the
+    /// requested languages changed or a locale was loaded or reloaded.
+    #[must_use]
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Returns `true` if a language of the fallback chain has the message `id`.
+    #[must_use]
+    pub fn has_message(&self, id: &str) -> bool {
+        self.chain
+            .iter()
+            .any(|i| self.bundles[*i].1.has_message(id))
+    }
+
+    /// Formats the message `id` in the first language of the fallback chain which has it.
+    ///
+    /// The message is pseudo-localized if enabled with
+    /// [`Localization::set_pseudo_localization`].
+    ///
+    /// Returns `None` if no language has the message. Errors while formatting, such as missing
+    /// arguments, are logged and the faulty parts of the message are replaced by their source.
+    #[must_use]
+    pub fn format(&self, id: &str, args: Option<&FluentArgs<'_>>) -> Option<String> {
+        if let Some(ids) = &self.requested_ids {
+            let mut ids = ids.lock().unwrap_or_else(|e| e.into_inner());
+            if !ids.contains(id) {
+                ids.insert(id.to_string());
+            }
+        }
+        let text = self.chain.iter().find_map(|i| {
+            let (language, bundle) = &self.bundles[*i];
+            let pattern = bundle.get_message(id)?.value?;
+            let mut errors = Vec::new();
+            let text = bundle.format_pattern(pattern, args, &mut errors);
+            if !errors.is_empty() {
+                log::warn!(
+                    "Failed to format message `{}` in {}: {:?}",
+                    id,
+                    language,
+                    errors
+                );
+            }
+            Some(text.into_owned())
+        })?;
+        if self.pseudo {
+            Some(pseudo_localize(&text))
+        } else {
+            Some(text)
+        }
+    }
+
+    /// Formats the message `id` like [`Localization::format`], or returns `id` if no language
+    /// has the message.
+    #[must_use]
+    pub fn format_or_id(&self, id: &str, args: Option<&FluentArgs<'_>>) -> String {
+        self.format(id, args).unwrap_or_else(|| id.to_string())
+    }
+
+    /// Rebuilds the bundles if a locale has been loaded, reloaded or unloaded.
+    pub fn update(&mut self, storage: &AssetStorage<Locale>) {
+        let mut changed = false;
+        for source in &mut self.sources {
+            let version = storage.get_version(&source.handle);
+            if version != source.version {
+                source.version = version;
+                changed = true;
+            }
+        }
+        if !changed {
+            return;
+        }
+
+        let mut resources = Vec::new();
+        let loaded = self
+            .sources
+            .iter()
+            .filter_map(|source| Some((source, storage.get(&source.handle)?)));
+        for (source, locale) in loaded {
+            let language = if locale.language.language.is_empty() {
+                source.language.clone()
+            } else {
+                Some(locale.language.clone())
+            };
+            match language {
+                Some(language) => resources.push((language, locale.resource.clone())),
+                None => {
+                    log::error!(
+                        "The language of a locale is unknown, put it in the file name like \
+                     `fr-FR.ftl` or set `language` in its import settings"
+                    )
+                }
+            }
+        }
+        self.build(resources);
+    }
+
+    /// Replaces the bundles with one per language containing all its resources.
+    fn build(&mut self, resources: Vec<(LanguageIdentifier, Arc<FluentResource>)>) {
+        self.bundles.clear();
+        for (language, resource) in resources {
+            let bundles = &mut self.bundles;
+            let index = bundles
+                .iter()
+                .position(|(l, _)| *l == language)
+                .unwrap_or_else(|| {
+                    bundles.push((language.clone(), FluentBundle::new(vec![language])));
+                    bundles.len() - 1
+                });
+            let (language, bundle) = &mut self.bundles[index];
+            bundle.set_use_isolating(self.use_isolating);
+            if let Err(errors) = bundle.add_resource(resource) {
+                log::warn!(
+                    "Conflicting messages in the locales for {}: {:?}",
+                    language,
+                    errors
+                );
+            }
+        }
+        self.negotiate();
+    }
+
+    fn negotiate(&mut self) {
+        let available: Vec<_> = self.available_languages().cloned().collect();
+        let default = available
+            .iter()
+            .find(|language| **language == self.default_language);
+        let negotiated = negotiate_languages(
+            &self.requested,
+            &available,
+            default,
+            NegotiationStrategy::Filtering,
+        );
+        self.chain = negotiated
+            .into_iter()
+            .filter_map(|language| available.iter().position(|l| l == language))
+            .collect();
+        self.revision += 1;
+    }
+}
+
+/// Updates the [`Localization`] resource when locales are loaded.
+struct LocalizationSystem;
+
+impl System for LocalizationSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("localization_system")
+                .read_resource::<AssetStorage<Locale>>()
+                .write_resource::<Localization>()
+                .build(move |_, _, (storage, localization), _| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("localization_system");
+
+                    localization.update(storage);
+                }),
+        )
+    }
+}
+
+/// Inserts the [`Localization`] resource and keeps it up to date with the loaded locales.
+///
+/// Requires the `LoaderBundle`.
+#[derive(Debug)]
+pub struct LocalizationBundle {
+    default_language: LanguageIdentifier,
+    requested: Vec<LanguageIdentifier>,
+    pseudo: bool,
+    report_dir: Option<PathBuf>,
+}
+
+impl LocalizationBundle {
+    /// Creates a bundle falling back to `default_language`.
+    #[must_use]
+    pub fn new(default_language: LanguageIdentifier) -> Self {
+        LocalizationBundle {
+            default_language,
+            requested: Vec::new(),
+            pseudo: false,
+            report_dir: None,
+        }
+    }
+
+    /// Sets the languages preferred by the user, the most preferred first.
+    #[must_use]
+    pub fn with_requested_languages<I>(mut self, languages: I) -> Self
+    where
+        I: IntoIterator<Item = LanguageIdentifier>,
+    {
+        self.requested = languages.into_iter().collect();
+        self
+    }
+
+    /// Pseudo-localizes every formatted message, see
+    /// [`Localization::set_pseudo_localization`].
+    #[must_use]
+    pub fn with_pseudo_localization(mut self) -> Self {
+        self.pseudo = true;
+        self
+    }
+
+    /// Tracks the formatted messages and writes a [`MissingMessagesReport`] to `dir` when the
+    /// application stops, in a new `missing_messages_<timestamp>.json` file for each run.
+    #[must_use]
+    pub fn with_missing_messages_report<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.report_dir = Some(dir.into());
+        self
+    }
+}
+
+impl SystemBundle for LocalizationBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        let mut localization = Localization::new(self.default_language.clone());
+        localization.set_requested_languages(self.requested.drain(..));
+        localization.set_pseudo_localization(self.pseudo);
+        localization.track_missing_messages(self.report_dir.is_some());
+        resources.insert(localization);
+        builder.add_system(LocalizationSystem);
+        Ok(())
+    }
+
+    fn unload(&mut self, _world: &mut World, resources: &mut Resources) -> Result<(), Error> {
+        let dir = match &self.report_dir {
+            Some(dir) => dir,
+            None => return Ok(()),
+        };
+        let localization = match resources.get::<Localization>() {
+            Some(localization) => localization,
+            None => return Ok(()),
+        };
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        let path = dir.join(format!("missing_messages_{}.json", timestamp));
+        localization.missing_messages_report().write(&path)?;
+        log::info!("Wrote the missing messages report to {}", path.display());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use unic_langid::langid;
+
+    use super::*;
+
+    fn resource(source: &str) -> Arc<FluentResource> {
+        Arc::new(FluentResource::try_new(source.to_string()).unwrap())
+    }
+
+    fn localization() -> Localization {
+        let mut localization = Localization::new(langid!("en"));
+        localization.build(vec![
+            (
+                langid!("en"),
+                resource("hello = Hello { $name }\nbye = Bye\nquit = Quit"),
+            ),
+            (langid!("fr"), resource("hello = Bonjour { $name }")),
+            (langid!("fr"), resource("bye = Au revoir")),
+            (langid!("de"), resource("hello = Hallo { $name }")),
+        ]);
+        localization
+    }
+
+    #[test]
+    fn requested_languages_fall_back_to_the_default() {
+        let mut localization = localization();
+        localization.set_requested_languages(vec![langid!("fr-CA")]);
+        assert_eq!(
+            localization.languages().collect::<Vec<_>>(),
+            [&langid!("fr"), &langid!("en")]
+        );
+
+        let mut args = FluentArgs::new();
+        args.set("name", "Ferris");
+        localization.set_use_isolating(false);
+        assert_eq!(
+            localization.format("hello", Some(&args)).unwrap(),
+            "Bonjour Ferris"
+        );
+        assert_eq!(localization.format("bye", None).unwrap(), "Au revoir");
+        assert_eq!(localization.format("quit", None).unwrap(), "Quit");
+        assert_eq!(localization.format("missing", None), None);
+        assert_eq!(localization.format_or_id("missing", None), "missing");
+    }
+
+    #[test]
+    fn report_lists_requested_messages_missing_per_language() {
+        let mut localization = localization();
+        localization.track_missing_messages(true);
+        localization.set_requested_languages(vec![langid!("de")]);
+        let _ = localization.format("hello", None);
+        let _ = localization.format("bye", None);
+        let _ = localization.format("bye", None);
+        let _ = localization.format("unknown", None);
+
+        let report = localization.missing_messages_report();
+        assert_eq!(report.requested, 3);
+        assert!(!report.is_complete());
+        assert_eq!(report.languages["en"].missing, ["unknown"]);
+        assert_eq!(report.languages["fr"].missing, ["unknown"]);
+        assert_eq!(report.languages["de"].missing, ["bye", "unknown"]);
+        assert!((report.languages["de"].coverage - 1.0 / 3.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn pseudo_localization_changes_formatted_messages_only() {
+        let mut localization = localization();
+        localization.set_pseudo_localization(true);
+        assert_eq!(localization.format("quit", None).unwrap(), "[Ǫûîţ~~]");
+        assert_eq!(localization.format_or_id("missing", None), "missing");
+    }
+
+    #[test]
+    fn changing_languages_changes_the_revision() {
+        let mut localization = localization();
+        let revision = localization.revision();
+        localization.set_requested_languages(vec![langid!("de")]);
+        assert_ne!(localization.revision(), revision);
+        assert_eq!(
+            localization.languages().collect::<Vec<_>>(),
+            [&langid!("de"), &langid!("en")]
+        );
+    }
+}
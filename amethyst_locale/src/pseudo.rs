@@ -0,0 +1,39 @@
+//! Pseudo-localization of formatted messages.
+
+const LOWER: &str = "àƀçđéƒĝĥîĵķļɱñöþǫŕšţûṽŵẋýž";
+const UPPER: &str = "ÀƁÇĐÉƑĜĤÎĴĶĻṀÑÖÞǪŔŠŢÛṼŴẊÝŽ";
+
+/// Turns `text` into pseudo-localized text, which is still readable but shows problems of a
+/// translation before it exists.
+///
+/// ASCII letters get accents, which shows text which isn't localized and glyphs missing from a
+/// font. The text is made 30% longer to leave room for languages longer than English, and
+/// surrounded by brackets to reveal truncated or concatenated messages.
+///
+/// ```
+/// use amethyst_locale::pseudo_localize;
+///
+/// assert_eq!(pseudo_localize("Play"), "[Þļàý~~]");
+/// ```
+#[must_use]
+pub fn pseudo_localize(text: &str) -> String {
+    let len = text.chars().count();
+    let padding = (len * 3).div_ceil(10);
+
+    let mut out = String::with_capacity(text.len() * 2 + padding + 2);
+    out.push('[');
+    out.extend(text.chars().map(accent));
+    out.extend(std::iter::repeat_n('~', padding));
+    out.push(']');
+    out
+}
+
+fn accent(c: char) -> char {
+    let table = match c {
+        'a'..='z' => LOWER,
+        'A'..='Z' => UPPER,
+        _ => return c,
+    };
+    let index = (c.to_ascii_lowercase() as u8 - b'a') as usize;
+    table.chars().nth(index).unwrap_or(c)
+}
@@ -0,0 +1,47 @@
+//! Report of the messages missing from the loaded locales.
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use amethyst_error::{format_err, Error};
+use serde::{Deserialize, Serialize};
+
+/// Messages requested at runtime but missing in some languages, see
+/// [`Localization::missing_messages_report`](crate::Localization::missing_messages_report).
+///
+/// Written as JSON by [`MissingMessagesReport::write`].
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct MissingMessagesReport {
+    /// Number of different message ids requested.
+    pub requested: usize,
+    /// Coverage of each loaded language.
+    pub languages: BTreeMap<String, LanguageCoverage>,
+}
+
+/// The requested messages missing in a language.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct LanguageCoverage {
+    /// Fraction of the requested messages the language has, from 0 to 1.
+    pub coverage: f32,
+    /// Ids of the requested messages the language doesn't have, sorted.
+    pub missing: Vec<String>,
+}
+
+impl MissingMessagesReport {
+    /// Returns `true` if every loaded language has all requested messages.
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.languages
+            .values()
+            .all(|language| language.missing.is_empty())
+    }
+
+    /// Writes the report to `path` as JSON.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let json = serde_json::to_string_pretty(self).map_err(Error::new)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(Error::new)?;
+        }
+        fs::write(path, json).map_err(|e| format_err!("Could not write {}: {}", path.display(), e))
+    }
+}
@@ -0,0 +1,28 @@
+#version 450
+
+// One direction of a separable 9 taps gaussian blur, using bilinear filtering to read two
+// pixels per sample.
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(std140, set = 0, binding = 1) uniform BlurArgs {
+    // Blur direction, scaled by the distance between the taps in pixels.
+    vec2 direction;
+};
+
+layout(location = 0) in vec2 tex_coord;
+
+layout(location = 0) out vec4 out_color;
+
+const float OFFSETS[3] = float[](0.0, 1.3846153846, 3.2307692308);
+const float WEIGHTS[3] = float[](0.2270270270, 0.3162162162, 0.0702702703);
+
+void main() {
+    vec2 texel = direction / vec2(textureSize(source, 0));
+    vec3 color = texture(source, tex_coord).rgb * WEIGHTS[0];
+    for (int i = 1; i < 3; i++) {
+        color += texture(source, tex_coord + texel * OFFSETS[i]).rgb * WEIGHTS[i];
+        color += texture(source, tex_coord - texel * OFFSETS[i]).rgb * WEIGHTS[i];
+    }
+    out_color = vec4(color, 1.0);
+}
@@ -0,0 +1,32 @@
+#version 450
+
+// Keeps the parts of the image brighter than the threshold, at half resolution.
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(std140, set = 0, binding = 1) uniform BloomArgs {
+    float threshold;
+    float knee;
+};
+
+layout(location = 0) in vec2 tex_coord;
+
+layout(location = 0) out vec4 out_color;
+
+void main() {
+    // Four bilinear samples average the 4x4 source pixels around this pixel.
+    vec2 texel = 1.0 / vec2(textureSize(source, 0));
+    vec3 color = texture(source, tex_coord + texel * vec2(-1.0, -1.0)).rgb;
+    color += texture(source, tex_coord + texel * vec2(1.0, -1.0)).rgb;
+    color += texture(source, tex_coord + texel * vec2(-1.0, 1.0)).rgb;
+    color += texture(source, tex_coord + texel * vec2(1.0, 1.0)).rgb;
+    color *= 0.25;
+
+    // Soft threshold, quadratic within `knee` of the threshold.
+    float brightness = max(color.r, max(color.g, color.b));
+    float soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee + 0.00001);
+    float contribution = max(soft, brightness - threshold) / max(brightness, 0.00001);
+
+    out_color = vec4(color * contribution, 1.0);
+}
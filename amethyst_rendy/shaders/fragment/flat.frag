@@ -13,11 +13,13 @@ layout(location = 0) in VertexData {
     vec3 position;
     vec2 tex_coord;
     vec4 color;
+    float lod_fade;
 } vertex;
 
 layout(location = 0) out vec4 out_color;
 
 void main() {
+    if(lod_faded_out(vertex.lod_fade)) discard;
     vec4 albedo = texture(albedo, tex_coords(vertex.tex_coord, uv_offset));
     if(albedo.w < alpha_cutoff) discard;
     out_color = albedo * vertex.color;
@@ -0,0 +1,55 @@
+#version 450
+
+// Fast approximate anti-aliasing, blurring the pixels along the edges found from the luminance.
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(location = 0) in vec2 tex_coord;
+
+layout(location = 0) out vec4 out_color;
+
+const float SPAN_MAX = 8.0;
+const float REDUCE_MUL = 1.0 / 8.0;
+const float REDUCE_MIN = 1.0 / 128.0;
+
+// Perceived luminance, from the approximately gamma encoded color.
+float luma(vec3 color) {
+    return dot(sqrt(color), vec3(0.299, 0.587, 0.114));
+}
+
+void main() {
+    vec2 texel = 1.0 / vec2(textureSize(source, 0));
+    vec3 color = texture(source, tex_coord).rgb;
+    float luma_nw = luma(texture(source, tex_coord + vec2(-1.0, -1.0) * texel).rgb);
+    float luma_ne = luma(texture(source, tex_coord + vec2(1.0, -1.0) * texel).rgb);
+    float luma_sw = luma(texture(source, tex_coord + vec2(-1.0, 1.0) * texel).rgb);
+    float luma_se = luma(texture(source, tex_coord + vec2(1.0, 1.0) * texel).rgb);
+    float luma_m = luma(color);
+    float luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    float luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    // Blur along the edge, perpendicular to the luminance gradient.
+    vec2 direction = vec2(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se)
+    );
+    float reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    float scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+    direction = clamp(direction * scale, vec2(-SPAN_MAX), vec2(SPAN_MAX)) * texel;
+
+    vec3 near = 0.5 * (
+        texture(source, tex_coord + direction * (1.0 / 3.0 - 0.5)).rgb +
+        texture(source, tex_coord + direction * (2.0 / 3.0 - 0.5)).rgb
+    );
+    vec3 far = near * 0.5 + 0.25 * (
+        texture(source, tex_coord - direction * 0.5).rgb +
+        texture(source, tex_coord + direction * 0.5).rgb
+    );
+    float luma_far = luma(far);
+    // The wider blur is only kept if it didn't cross another edge.
+    if (luma_far < luma_min || luma_far > luma_max) {
+        out_color = vec4(near, 1.0);
+    } else {
+        out_color = vec4(far, 1.0);
+    }
+}
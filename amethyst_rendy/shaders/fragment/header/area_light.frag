@@ -0,0 +1,121 @@
+#ifndef AREA_LIGHT_FRAG
+#define AREA_LIGHT_FRAG
+
+// Area lights shaded with linearly transformed cosines, see amethyst_rendy/src/ltc.rs.
+// Requires header/environment.frag.
+
+// Maps [0, 1] to the centers of the first and last texels of the 64x64 tables.
+const float LTC_SCALE = 63.0 / 64.0;
+const float LTC_BIAS = 0.5 / 64.0;
+
+// Vector form factor of the arc between the unit vectors `a` and `b`.
+vec3 integrate_edge(vec3 a, vec3 b) {
+    float x = dot(a, b);
+    float y = abs(x);
+    float numerator = 0.8543985 + (0.4965155 + 0.0145206 * y) * y;
+    float denominator = 3.4175940 + (4.1616724 + y) * y;
+    float v = numerator / denominator;
+    float theta_sintheta = x > 0.0 ? v : 0.5 * inversesqrt(max(1.0 - x * x, 1e-7)) - v;
+    return cross(a, b) * theta_sintheta;
+}
+
+// Integral over the polygon `points` of the cosine distribution transformed by `transform`
+// around `normal`, seen from `position`. The polygon is counter-clockwise seen from the side
+// the light emits to.
+float ltc_evaluate(vec3 normal,
+                   vec3 view_direction,
+                   vec3 position,
+                   mat3 transform,
+                   vec3 points[8],
+                   int count,
+                   bool two_sided) {
+    vec3 light_normal = cross(points[1] - points[0], points[2] - points[0]);
+    bool front = dot(light_normal, position - points[0]) > 0.0;
+    if (!front && !two_sided) {
+        return 0.0;
+    }
+
+    // Tangent space with the view direction in the XZ plane.
+    vec3 tangent = view_direction - normal * dot(view_direction, normal);
+    if (dot(tangent, tangent) < 1e-8) {
+        tangent = cross(normal, abs(normal.x) < 0.9 ? vec3(1.0, 0.0, 0.0) : vec3(0.0, 1.0, 0.0));
+    }
+    tangent = normalize(tangent);
+    transform = transform * transpose(mat3(tangent, cross(normal, tangent), normal));
+
+    vec3 first = normalize(transform * (points[0] - position));
+    vec3 previous = first;
+    vec3 form_factor = vec3(0.0);
+    for (int i = 1; i < count; i++) {
+        vec3 current = normalize(transform * (points[i] - position));
+        form_factor += integrate_edge(previous, current);
+        previous = current;
+    }
+    form_factor += integrate_edge(previous, first);
+
+    // The polygon is clipped to the horizon by approximating it with a sphere of the same
+    // vector form factor, whose clipped form factor is tabulated.
+    float form_factor_length = length(form_factor);
+    float z = form_factor.z / max(form_factor_length, 1e-7);
+    if (front) {
+        z = -z;
+    }
+    vec2 uv = vec2(z * 0.5 + 0.5, form_factor_length) * LTC_SCALE + LTC_BIAS;
+    return form_factor_length * texture(ltc_2, uv).w;
+}
+
+// Corners of the shape of `light`. Discs are octagons of the same area.
+int area_light_points(AreaLight light, out vec3 points[8]) {
+    if (light.shape == 0) {
+        points[0] = light.position - light.right - light.up;
+        points[1] = light.position - light.right + light.up;
+        points[2] = light.position + light.right + light.up;
+        points[3] = light.position + light.right - light.up;
+        return 4;
+    }
+    float scale = 1.0539;
+    for (int i = 0; i < 8; i++) {
+        float angle = -float(i) * PI / 4.0;
+        points[i] = light.position + scale * (cos(angle) * light.right + sin(angle) * light.up);
+    }
+    return 8;
+}
+
+// Falloff of `light` reaching its range.
+float area_light_window(AreaLight light, vec3 position) {
+    return distance_window(length(position - light.position), light.range, 4.0);
+}
+
+// Fraction of the radiance of `light` reflected by a lambertian surface.
+float area_light_diffuse(AreaLight light, vec3 position, vec3 normal) {
+    vec3 points[8];
+    int count = area_light_points(light, points);
+    return ltc_evaluate(normal, normal, position, mat3(1.0), points, count, light.two_sided != 0)
+        * area_light_window(light, position);
+}
+
+// Fraction of the radiance of `light` reflected by the GGX lobe of a surface with `roughness`.
+vec3 area_light_specular(AreaLight light,
+                         vec3 position,
+                         vec3 normal,
+                         vec3 view_direction,
+                         float roughness,
+                         vec3 fresnel_base) {
+    float NdotV = clamp(dot(normal, view_direction), 0.0, 1.0);
+    vec2 uv = vec2(roughness, sqrt(1.0 - NdotV)) * LTC_SCALE + LTC_BIAS;
+    vec4 t1 = texture(ltc_1, uv);
+    vec4 t2 = texture(ltc_2, uv);
+    mat3 transform = mat3(
+        vec3(t1.x, 0.0, t1.y),
+        vec3(0.0, 1.0, 0.0),
+        vec3(t1.z, 0.0, t1.w)
+    );
+
+    vec3 points[8];
+    int count = area_light_points(light, points);
+    float specular = ltc_evaluate(normal, view_direction, position, transform, points, count,
+                                  light.two_sided != 0);
+    return specular * (fresnel_base * t2.x + t2.y) * area_light_window(light, position);
+}
+
+#endif
@@ -6,12 +6,17 @@ struct PointLight {
     vec3 position;
     vec3 color;
     float intensity;
+    float radius;
+    float smoothness;
+    int shadow;
 };
 
 struct DirectionalLight {
     vec3 color;
     float intensity;
     vec3 direction;
+    int shadow;
+    int cascades;
 };
 
 struct SpotLight {
@@ -22,6 +27,31 @@ struct SpotLight {
     float intensity;
     float range;
     float smoothness;
+    int shadow;
+};
+
+struct AreaLight {
+    vec3 position;
+    vec3 color;
+    vec3 right;
+    vec3 up;
+    float intensity;
+    float range;
+    int shape;
+    int two_sided;
+};
+
+struct ShadowView {
+    mat4 projview;
+    vec4 tile;
+    float bias;
+    float normal_bias;
+};
+
+layout(std140, set = 0, binding = 0) uniform Projview {
+    mat4 proj;
+    mat4 view;
+    mat4 proj_view;
 };
 
 layout(std140, set = 0, binding = 1) uniform Environment {
@@ -30,16 +60,61 @@ layout(std140, set = 0, binding = 1) uniform Environment {
     int point_light_count;
     int directional_light_count;
     int spot_light_count;
+    int area_light_count;
+    uvec3 cluster_count;
+    float cluster_depth_scale;
+    float cluster_depth_bias;
 };
 
-layout(std140, set = 0, binding = 2) uniform PointLights {
-    PointLight plight[128];
+layout(std140, set = 0, binding = 2) readonly buffer PointLights {
+    PointLight plight[];
 };
 
 layout(std140, set = 0, binding = 3) uniform DirectionalLights {
     DirectionalLight dlight[16];
 };
 
-layout(std140, set = 0, binding = 4) uniform SpotLights {
-    SpotLight slight[128];
-};
\ No newline at end of file
+layout(std140, set = 0, binding = 4) readonly buffer SpotLights {
+    SpotLight slight[];
+};
+
+layout(std140, set = 0, binding = 5) uniform ShadowViews {
+    ShadowView shadow_views[64];
+};
+
+layout(set = 0, binding = 6) uniform sampler2DShadow shadow_map;
+
+layout(std140, set = 0, binding = 7) readonly buffer AreaLights {
+    AreaLight alight[];
+};
+
+// Offset in light_indices of the lights of each cluster, then its number of point, spot and
+// area lights, see amethyst_rendy/src/cluster.rs.
+layout(std430, set = 0, binding = 8) readonly buffer Clusters {
+    uvec4 clusters[];
+};
+
+layout(std430, set = 0, binding = 9) readonly buffer LightIndices {
+    uint light_indices[];
+};
+
+layout(set = 0, binding = 10) uniform sampler2D ltc_1;
+layout(set = 0, binding = 11) uniform sampler2D ltc_2;
+
+// Returns the cluster containing `position`, in world space.
+uvec4 light_cluster(vec3 position) {
+    vec4 clip = proj_view * vec4(position, 1.0);
+    vec2 tile = clamp((clip.xy / clip.w * 0.5 + 0.5) * vec2(cluster_count.xy),
+                      vec2(0.0), vec2(cluster_count.xy) - 1.0);
+    float depth = max(-(view * vec4(position, 1.0)).z, 1e-6);
+    float slice = clamp(log(depth) * cluster_depth_scale + cluster_depth_bias,
+                        0.0, float(cluster_count.z) - 1.0);
+    uvec3 cluster = uvec3(tile, slice);
+    return clusters[cluster.x + cluster_count.x * (cluster.y + cluster_count.y * cluster.z)];
+}
+
+// Smooth falloff of a light reaching `radius`, from the Frostbite engine.
+float distance_window(float distance, float radius, float smoothness) {
+    float window = clamp(1.0 - pow(distance / max(radius, 0.00001), smoothness), 0.0, 1.0);
+    return window * window;
+}
\ No newline at end of file
@@ -46,4 +46,11 @@ float s_curve (float x) {
 		return -x * abs(x) * 0.5 + x + 0.5;
 }
 
+// Level of detail cross-fade: below 1.0 the incoming level keeps the pixels whose noise is below
+// the fade, above 1.0 the outgoing level keeps the others, so both levels never overlap.
+bool lod_faded_out(float fade) {
+    float noise = fract(52.9829189 * fract(dot(gl_FragCoord.xy, vec2(0.06711056, 0.00583715))));
+    return fade > 1.0 ? noise < fade - 1.0 : noise >= fade;
+}
+
 #endif
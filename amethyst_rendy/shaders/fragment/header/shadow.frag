@@ -0,0 +1,71 @@
+#ifndef SHADOW_FRAG
+#define SHADOW_FRAG
+
+// Shadow atlas sampling, see amethyst_rendy/src/shadow.rs.
+// Requires header/environment.frag.
+
+// Fraction of light reaching `position` in the shadow view `view`, from 0 in shadow to 1 lit.
+float shadow_factor(int view, vec3 position, vec3 normal, vec3 light_direction) {
+    ShadowView shadow_view = shadow_views[view];
+    vec3 offset = light_direction * shadow_view.bias
+        + normal * shadow_view.normal_bias * (1.0 - max(dot(normal, light_direction), 0.0));
+    vec4 projected = shadow_view.projview * vec4(position + offset, 1.0);
+    vec3 ndc = projected.xyz / projected.w;
+    if (any(greaterThan(abs(ndc.xy), vec2(1.0))) || ndc.z < 0.0 || ndc.z > 1.0) {
+        return 1.0;
+    }
+
+    // 3x3 percentage closer filtering, kept inside the tile of the view.
+    vec2 texel = 1.0 / vec2(textureSize(shadow_map, 0));
+    vec2 uv = shadow_view.tile.xy + (ndc.xy * 0.5 + 0.5) * shadow_view.tile.zw;
+    vec2 tile_min = shadow_view.tile.xy + texel * 1.5;
+    vec2 tile_max = shadow_view.tile.xy + shadow_view.tile.zw - texel * 1.5;
+    uv = clamp(uv, tile_min, tile_max);
+    float lit = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            lit += texture(shadow_map, vec3(uv + vec2(x, y) * texel, ndc.z));
+        }
+    }
+    return lit / 9.0;
+}
+
+float point_shadow(PointLight light, vec3 position, vec3 normal) {
+    if (light.shadow < 0) {
+        return 1.0;
+    }
+    // Faces are ordered +X, -X, +Y, -Y, +Z, -Z.
+    vec3 to_position = position - light.position;
+    vec3 distance = abs(to_position);
+    int face;
+    if (distance.x >= distance.y && distance.x >= distance.z) {
+        face = to_position.x > 0.0 ? 0 : 1;
+    } else if (distance.y >= distance.z) {
+        face = to_position.y > 0.0 ? 2 : 3;
+    } else {
+        face = to_position.z > 0.0 ? 4 : 5;
+    }
+    return shadow_factor(light.shadow + face, position, normal, -normalize(to_position));
+}
+
+float directional_shadow(DirectionalLight light, vec3 position, vec3 normal) {
+    vec3 light_direction = -normalize(light.direction);
+    // The first cascade containing the point is the most detailed one.
+    for (int i = 0; i < light.cascades; i++) {
+        vec4 projected = shadow_views[light.shadow + i].projview * vec4(position, 1.0);
+        vec3 ndc = projected.xyz / projected.w;
+        if (all(lessThanEqual(abs(ndc.xy), vec2(0.98))) && ndc.z >= 0.0 && ndc.z <= 1.0) {
+            return shadow_factor(light.shadow + i, position, normal, light_direction);
+        }
+    }
+    return 1.0;
+}
+
+float spot_shadow(SpotLight light, vec3 position, vec3 normal) {
+    if (light.shadow < 0) {
+        return 1.0;
+    }
+    return shadow_factor(light.shadow, position, normal, normalize(light.position - position));
+}
+
+#endif
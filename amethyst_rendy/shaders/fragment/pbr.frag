@@ -4,6 +4,10 @@
 
 #include "header/environment.frag"
 
+#include "header/shadow.frag"
+
+#include "header/area_light.frag"
+
 layout(std140, set = 1, binding = 0) uniform Material {
     UvOffset uv_offset;
     float alpha_cutoff;
@@ -23,6 +27,7 @@ layout(location = 0) in VertexData {
     float tang_handedness;
     vec2 tex_coord;
     vec4 color;
+    float lod_fade;
 } vertex;
 
 layout(location = 0) out vec4 out_color;
@@ -64,6 +69,7 @@ vec3 compute_light(vec3 attenuation,
 }
 
 void main() {
+    if(lod_faded_out(vertex.lod_fade)) discard;
     vec2 final_tex_coords   = tex_coords(vertex.tex_coord, uv_offset);
     vec4 albedo_alpha       = texture(albedo, final_tex_coords);
     float alpha             = albedo_alpha.a;
@@ -93,26 +99,35 @@ void main() {
 
     vec3 view_direction = normalize(camera_position - vertex.position);
     vec3 lighted = vec3(0.0);
-    for (int i = 0; i < point_light_count; i++) {
-        vec3 light_direction = normalize(plight[i].position - vertex.position);
-        float attenuation = plight[i].intensity / dot(light_direction, light_direction);
-
-        vec3 light = compute_light(vec3(attenuation),
-                                   plight[i].color,
-                                   view_direction,
-                                   light_direction,
-                                   albedo,
-                                   normal,
-                                   roughness2,
-                                   metallic,
-                                   fresnel_base);
-
-        lighted += light;
+    uvec4 cluster = light_cluster(vertex.position);
+    uint first_point = cluster.x;
+    uint first_spot = first_point + cluster.y;
+    uint first_area = first_spot + cluster.z;
+    for (uint i = first_point; i < first_spot; i++) {
+        PointLight light = plight[light_indices[i]];
+        vec3 light_vec = light.position - vertex.position;
+        vec3 light_direction = normalize(light_vec);
+        float attenuation = light.intensity / dot(light_direction, light_direction);
+        attenuation *= distance_window(length(light_vec), light.radius, light.smoothness);
+        attenuation *= point_shadow(light, vertex.position, vertex_normal);
+
+        vec3 light_color = compute_light(vec3(attenuation),
+                                         light.color,
+                                         view_direction,
+                                         light_direction,
+                                         albedo,
+                                         normal,
+                                         roughness2,
+                                         metallic,
+                                         fresnel_base);
+
+        lighted += light_color;
     }
 
     for (int i = 0; i < directional_light_count; i++) {
         vec3 light_direction = -normalize(dlight[i].direction);
         float attenuation = dlight[i].intensity;
+        attenuation *= directional_shadow(dlight[i], vertex.position, vertex_normal);
 
         vec3 light = compute_light(vec3(attenuation),
                                    dlight[i].color,
@@ -127,8 +142,9 @@ void main() {
         lighted += light;
     }
 
-    for (int i = 0; i < spot_light_count; i++) {
-        vec3 light_vec = slight[i].position - vertex.position;
+    for (uint i = first_spot; i < first_area; i++) {
+        SpotLight light = slight[light_indices[i]];
+        vec3 light_vec = light.position - vertex.position;
         vec3 normalized_light_vec = normalize(light_vec);
 
         // The distance between the current fragment and the "core" of the light
@@ -136,7 +152,7 @@ void main() {
 
         // The allowed "length", everything after this won't be lit.
         // Later on we are dividing by this range, so it can't be 0
-        float range = max(slight[i].range, 0.00001);
+        float range = max(light.range, 0.00001);
 
         // get normalized range, so everything 0..1 could be lit, everything else can't.
         float normalized_range = light_length / max(0.00001, range);
@@ -147,9 +163,9 @@ void main() {
 
         // this is actually the cosine of the angle, so it can be compared with the
         // "dotted" frag_angle below a lot cheaper.
-        float spot_angle = max(slight[i].angle, 0.00001);
-        vec3 spot_direction = normalize(slight[i].direction);
-        float smoothness = 1.0 - slight[i].smoothness;
+        float spot_angle = max(light.angle, 0.00001);
+        vec3 spot_direction = normalize(light.direction);
+        float smoothness = 1.0 - light.smoothness;
 
         // Here we check if the current fragment is within the "ring" of the spotlight.
         float frag_angle = dot(spot_direction, -normalized_light_vec);
@@ -165,18 +181,31 @@ void main() {
         float ring_attenuation = 1.0 - rim_attenuation;
 
         // combine the attenuations and intensity
-        float attenuation = range_attenuation * ring_attenuation * slight[i].intensity;
+        float attenuation = range_attenuation * ring_attenuation * light.intensity;
+        attenuation *= spot_shadow(light, vertex.position, vertex_normal);
+
+        vec3 light_color = compute_light(vec3(attenuation),
+                                         light.color,
+                                         view_direction,
+                                         normalize(light_vec),
+                                         albedo,
+                                         normal,
+                                         roughness2,
+                                         metallic,
+                                         fresnel_base);
+        lighted += light_color;
+    }
 
-        vec3 light = compute_light(vec3(attenuation),
-                                   slight[i].color,
-                                   view_direction,
-                                   normalize(light_vec),
-                                   albedo,
-                                   normal,
-                                   roughness2,
-                                   metallic,
-                                   fresnel_base);
-        lighted += light;
+    for (uint i = first_area; i < first_area + cluster.w; i++) {
+        AreaLight light = alight[light_indices[i]];
+        vec3 specular = area_light_specular(light,
+                                            vertex.position,
+                                            normal,
+                                            view_direction,
+                                            roughness,
+                                            fresnel_base);
+        float diffuse = area_light_diffuse(light, vertex.position, normal);
+        lighted += (diffuse * albedo * (1.0 - metallic) + specular) * light.color * light.intensity;
     }
 
     vec3 ambient = ambient_color * albedo * ambient_occlusion;
@@ -4,6 +4,10 @@
 
 #include "header/environment.frag"
 
+#include "header/shadow.frag"
+
+#include "header/area_light.frag"
+
 layout(set = 1, binding = 0) uniform Material {
     UvOffset uv_offset;
     float alpha_cutoff;
@@ -17,12 +21,14 @@ layout(location = 0) in VertexData {
     vec3 normal;
     vec2 tex_coord;
     vec4 color;
+    float lod_fade;
 } vertex;
 
 layout(location = 0) out vec4 out_color;
 
 
 void main() {
+    if(lod_faded_out(vertex.lod_fade)) discard;
     vec2 final_tex_coords   = tex_coords(vertex.tex_coord, uv_offset);
     vec4 albedo_alpha       = texture(albedo, final_tex_coords);
     float alpha             = albedo_alpha.a;
@@ -33,22 +39,32 @@ void main() {
 
     vec3 lighting = vec3(0.0);
     vec3 normal = normalize(vertex.normal);
-    for (uint i = 0u; i < point_light_count; i++) {
+    uvec4 cluster = light_cluster(vertex.position);
+    for (uint i = cluster.x; i < cluster.x + cluster.y; i++) {
+        PointLight light = plight[light_indices[i]];
         // Calculate diffuse light
-        vec3 light_dir = normalize(plight[i].position - vertex.position);
+        vec3 light_dir = normalize(light.position - vertex.position);
         float diff = max(dot(light_dir, normal), 0.0);
-        vec3 diffuse = diff * normalize(plight[i].color);
+        vec3 diffuse = diff * normalize(light.color);
         // Calculate attenuation
-        vec3 dist = plight[i].position - vertex.position;
+        vec3 dist = light.position - vertex.position;
         float dist2 = dot(dist, dist);
-        float attenuation = (plight[i].intensity / dist2);
+        float attenuation = (light.intensity / dist2);
+        attenuation *= distance_window(sqrt(dist2), light.radius, light.smoothness);
+        attenuation *= point_shadow(light, vertex.position, normal);
         lighting += diffuse * attenuation;
     }
     for (uint i = 0u; i < directional_light_count; i++) {
         vec3 dir = dlight[i].direction;
         float diff = max(dot(-dir, normal), 0.0);
         vec3 diffuse = diff * dlight[i].color;
-        lighting += diffuse * dlight[i].intensity;
+        lighting += diffuse * dlight[i].intensity * directional_shadow(dlight[i], vertex.position, normal);
+    }
+    uint first_area = cluster.x + cluster.y + cluster.z;
+    for (uint i = first_area; i < first_area + cluster.w; i++) {
+        AreaLight light = alight[light_indices[i]];
+        float diffuse = area_light_diffuse(light, vertex.position, normal);
+        lighting += diffuse * light.color * light.intensity;
     }
     lighting += ambient_color;
     out_color = vec4(lighting * albedo + emission, alpha) * vertex.color;
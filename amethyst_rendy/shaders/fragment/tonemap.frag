@@ -0,0 +1,85 @@
+#version 450
+
+// Maps the HDR image with its bloom to displayable colors, then applies the vignette and the
+// color grading.
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+layout(set = 0, binding = 1) uniform sampler2D bloom;
+// Color grading lookup table: `size` slices of `size` by `size` pixels side by side, the blue
+// component selecting the slice.
+layout(set = 0, binding = 2) uniform sampler2D lut;
+
+layout(std140, set = 0, binding = 3) uniform ToneMapArgs {
+    float exposure;
+    // 0: clamp, 1: Reinhard, 2: extended Reinhard, 3: ACES filmic, 4: Uncharted 2.
+    int tonemapper;
+    float white;
+    float bloom_intensity;
+    float vignette_intensity;
+    float vignette_smoothness;
+    int color_grading;
+};
+
+layout(location = 0) in vec2 tex_coord;
+
+layout(location = 0) out vec4 out_color;
+
+vec3 uncharted2(vec3 x) {
+    const float A = 0.15;
+    const float B = 0.50;
+    const float C = 0.10;
+    const float D = 0.20;
+    const float E = 0.02;
+    const float F = 0.30;
+    return ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F;
+}
+
+vec3 tonemap(vec3 color) {
+    if (tonemapper == 1) {
+        return color / (1.0 + color);
+    } else if (tonemapper == 2) {
+        return color * (1.0 + color / (white * white)) / (1.0 + color);
+    } else if (tonemapper == 3) {
+        return (color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14);
+    } else if (tonemapper == 4) {
+        return uncharted2(color * 2.0) / uncharted2(vec3(white));
+    }
+    return color;
+}
+
+vec3 linear_to_srgb(vec3 color) {
+    vec3 low = color * 12.92;
+    vec3 high = 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055;
+    return mix(high, low, vec3(lessThanEqual(color, vec3(0.0031308))));
+}
+
+// The table is sampled with the sRGB encoded color, and holds sRGB encoded colors which are
+// decoded by the sampler, so a neutral table maps the colors to themselves.
+vec3 grade(vec3 color) {
+    vec2 size = vec2(textureSize(lut, 0));
+    float slices = size.y;
+    vec3 coord = linear_to_srgb(color) * (slices - 1.0);
+    float slice = floor(coord.b);
+    float next = min(slice + 1.0, slices - 1.0);
+    vec2 uv = vec2((coord.r + 0.5) / size.x, (coord.g + 0.5) / size.y);
+    vec3 low = texture(lut, uv + vec2(slice * slices / size.x, 0.0)).rgb;
+    vec3 high = texture(lut, uv + vec2(next * slices / size.x, 0.0)).rgb;
+    return mix(low, high, coord.b - slice);
+}
+
+void main() {
+    vec3 color = texture(source, tex_coord).rgb;
+    color += texture(bloom, tex_coord).rgb * bloom_intensity;
+    color = clamp(tonemap(color * exposure), 0.0, 1.0);
+
+    // Distance to the center, 1 in the corners.
+    float distance = length(tex_coord - 0.5) * 1.41421356;
+    float falloff = smoothstep(1.0 - max(vignette_smoothness, 0.001), 1.0, distance);
+    color *= 1.0 - vignette_intensity * falloff;
+
+    if (color_grading != 0) {
+        color = grade(color);
+    }
+
+    out_color = vec4(color, 1.0);
+}
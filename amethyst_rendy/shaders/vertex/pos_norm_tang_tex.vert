@@ -12,6 +12,7 @@ layout(location = 2) in vec4 tangent;
 layout(location = 3) in vec2 tex_coord;
 layout(location = 4) in mat4 model; // instance rate
 layout(location = 8) in vec4 tint; // instance rate
+layout(location = 10) in float lod_fade; // instance rate
 
 layout(location = 0) out VertexData {
     vec3 position;
@@ -20,6 +21,7 @@ layout(location = 0) out VertexData {
     float tang_handedness;
     vec2 tex_coord;
     vec4 color;
+    float lod_fade;
 } vertex;
 
 void main() {
@@ -30,5 +32,6 @@ void main() {
     vertex.tang_handedness = tangent.w;
     vertex.tex_coord = tex_coord;
     vertex.color = tint;
+    vertex.lod_fade = lod_fade;
     gl_Position = proj_view * vertex_position;
 }
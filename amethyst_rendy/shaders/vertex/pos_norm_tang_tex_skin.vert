@@ -27,6 +27,7 @@ layout(location = 0) out VertexData {
     float tang_handedness;
     vec2 tex_coord;
     vec4 color;
+    float lod_fade;
 } vertex;
 
 void main() {
@@ -44,5 +45,6 @@ void main() {
     vertex.tang_handedness = tangent.w;
     vertex.tex_coord = tex_coord;
     vertex.color = tint;
+    vertex.lod_fade = 1.0;
     gl_Position = proj_view * vertex_position;
 }
@@ -11,12 +11,14 @@ layout(location = 1) in vec3 normal;
 layout(location = 2) in vec2 tex_coord;
 layout(location = 3) in mat4 model; // instance rate
 layout(location = 7) in vec4 tint; // instance rate
+layout(location = 9) in float lod_fade; // instance rate
 
 layout(location = 0) out VertexData {
     vec3 position;
     vec3 normal;
     vec2 tex_coord;
     vec4 color;
+    float lod_fade;
 } vertex;
 
 void main() {
@@ -25,5 +27,6 @@ void main() {
     vertex.normal = mat3(model) * normal;
     vertex.tex_coord = tex_coord;
     vertex.color = tint;
+    vertex.lod_fade = lod_fade;
     gl_Position = proj_view * vertex_position;
 }
@@ -24,6 +24,7 @@ layout(location = 0) out VertexData {
     vec3 normal;
     vec2 tex_coord;
     vec4 color;
+    float lod_fade;
 } vertex;
 
 void main() {
@@ -39,6 +40,7 @@ void main() {
     vertex.normal = mat3_transform * normal;
     vertex.tex_coord = tex_coord;
     vertex.color = tint;
+    vertex.lod_fade = 1.0;
     gl_Position = proj_view * vertex_position;
 
 }
@@ -10,11 +10,13 @@ layout(location = 0) in vec3 position;
 layout(location = 1) in vec2 tex_coord;
 layout(location = 2) in mat4 model; // instance rate
 layout(location = 6) in vec4 tint; // instance rate
+layout(location = 8) in float lod_fade; // instance rate
 
 layout(location = 0) out VertexData {
     vec3 position;
     vec2 tex_coord;
     vec4 color;
+    float lod_fade;
 } vertex;
 
 void main() {
@@ -22,5 +24,6 @@ void main() {
     vertex.position = vertex_position.xyz;
     vertex.tex_coord = tex_coord;
     vertex.color = tint;
+    vertex.lod_fade = lod_fade;
     gl_Position = proj_view * vertex_position;
 }
@@ -22,6 +22,7 @@ layout(location = 0) out VertexData {
     vec3 position;
     vec2 tex_coord;
     vec4 color;
+    float lod_fade;
 } vertex;
 
 void main() {
@@ -35,5 +36,6 @@ void main() {
     vertex.position = vertex_position.xyz;
     vertex.tex_coord = tex_coord;
     vertex.color = tint;
+    vertex.lod_fade = 1.0;
     gl_Position = proj_view * vertex_position;
 }
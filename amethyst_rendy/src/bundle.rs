@@ -238,6 +238,15 @@ impl<B: Backend> RenderPlan<B> {
         Ok(())
     }
 
+    /// Returns `true` if the outputs of a render target have already been defined, e.g. by
+    /// another plugin.
+    #[must_use]
+    pub fn is_defined(&self, target: Target) -> bool {
+        self.targets
+            .get(&target)
+            .map_or(false, |plan| plan.outputs.is_some())
+    }
+
     /// Extend the rendering plan of a render target. Target can be defined in other plugins.
     /// The closure is evaluated only if the target contributes to the rendering result, e.g.
     /// is rendered to a window or is a dependency of other evaluated target.
@@ -253,6 +262,22 @@ impl<B: Backend> RenderPlan<B> {
         target_plan.add_extension(Box::new(closure));
     }
 
+    /// Add nodes reading the images of a render target once it is drawn, e.g. to copy or download
+    /// them. The closure receives the graph builder, the render pass node of the target and its
+    /// color images, and is evaluated right after the target, only if the target is evaluated.
+    pub fn after_target(
+        &mut self,
+        target: Target,
+        closure: impl FnOnce(&mut GraphBuilder<B, GraphAuxData>, NodeId, &[ImageId]) -> Result<(), Error>
+            + 'static,
+    ) {
+        let target_plan = self
+            .targets
+            .entry(target)
+            .or_insert_with(|| TargetPlan::new(target));
+        target_plan.add_reader(Box::new(closure));
+    }
+
     fn build(self, factory: &Factory<B>) -> Result<GraphBuilder<B, GraphAuxData>, Error> {
         let mut ctx = PlanContext {
             target_metadata: self
@@ -577,14 +602,22 @@ struct TargetPlan<B: Backend> {
     key: Target,
     #[derivative(Debug = "ignore")]
     extensions: Vec<Box<dyn FnOnce(&mut TargetPlanContext<'_, B>) -> Result<(), Error> + 'static>>,
+    #[derivative(Debug = "ignore")]
+    readers: Vec<TargetReader<B>>,
     outputs: Option<TargetPlanOutputs<B>>,
 }
 
+type TargetReader<B> = Box<
+    dyn FnOnce(&mut GraphBuilder<B, GraphAuxData>, NodeId, &[ImageId]) -> Result<(), Error>
+        + 'static,
+>;
+
 impl<B: Backend> TargetPlan<B> {
     fn new(key: Target) -> Self {
         Self {
             key,
             extensions: vec![],
+            readers: vec![],
             outputs: None,
         }
     }
@@ -653,6 +686,10 @@ impl<B: Backend> TargetPlan<B> {
         self.extensions.push(extension);
     }
 
+    fn add_reader(&mut self, reader: TargetReader<B>) {
+        self.readers.push(reader);
+    }
+
     fn evaluate(self, ctx: &mut PlanContext<B>) -> Result<(), Error> {
         if self.outputs.is_none() {
             return Err(format_err!(
@@ -690,6 +727,7 @@ impl<B: Backend> TargetPlan<B> {
 
         let mut subpass = SubpassBuilder::new();
         let mut pass = RenderPassNodeBuilder::new();
+        let mut color_images = Vec::new();
 
         actions.sort_by_key(|a| a.0);
         for action in actions.drain(..).map(|a| a.1) {
@@ -710,6 +748,7 @@ impl<B: Backend> TargetPlan<B> {
                     let node = ctx.create_image(&opts);
                     ctx.register_output(TargetImage::Color(self.key, i), node)?;
                     subpass.add_color(node);
+                    color_images.push(node);
                 }
             }
         }
@@ -726,6 +765,11 @@ impl<B: Backend> TargetPlan<B> {
 
         pass.add_subpass(subpass);
         ctx.submit_pass(self.key, pass);
+
+        let node = ctx.get_pass_node_raw(self.key).expect("Just built");
+        for reader in self.readers {
+            reader(ctx.graph(), node, &color_images)?;
+        }
         Ok(())
     }
 }
@@ -815,12 +859,30 @@ pub enum Target {
     /// Usually the one that gets presented to the window.
     Main,
     /// Render target for shadow mapping.
-    /// Builtin plugins use cascaded shadow maps.
+    /// Builtin plugins draw the shadow maps of all lights into tiles of a single depth image,
+    /// see the [shadow](crate::shadow) module.
     ShadowMap,
+    /// High dynamic range render target drawn by the
+    /// [`RenderPostProcess`](crate::post_process::RenderPostProcess) plugin, which presents it
+    /// to `Main` through its post-processing chain.
+    /// Plugins drawing into it render the cameras of `Main`.
+    Hdr,
     /// Custom render target identifier.
     Custom(&'static str),
 }
 
+impl Target {
+    /// Returns the target whose cameras are drawn into this target, which is the target itself
+    /// except for `Hdr`, drawn by the cameras of `Main`.
+    #[must_use]
+    pub fn camera_target(self) -> Target {
+        match self {
+            Target::Hdr => Target::Main,
+            target => target,
+        }
+    }
+}
+
 impl Default for Target {
     fn default() -> Target {
         Target::Main
@@ -3,7 +3,7 @@
 use amethyst_assets::{
     prefab::{
         register_component_type,
-        serde_diff::{ApplyContext, DiffContext},
+        serde_diff::{self, ApplyContext, DiffContext},
         SerdeDiff,
     },
     Asset,
@@ -289,6 +289,268 @@ pub struct ActiveCamera {
     pub entity: Option<Entity>,
 }
 
+/// Set of up to 32 render layers.
+///
+/// On a renderable entity, these are the layers the entity belongs to. Entities without
+/// `RenderLayers` belong to layer 0 only. In a [`CameraView`], these are the layers drawn by the
+/// camera.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+    SerdeDiff,
+    TypeUuid,
+)]
+#[uuid = "6a78842b-976f-4380-bb22-261001f7eb07"]
+pub struct RenderLayers {
+    bits: u32,
+}
+
+register_component_type!(RenderLayers);
+
+impl Default for RenderLayers {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl RenderLayers {
+    /// Number of available layers.
+    pub const COUNT: u8 = 32;
+    /// Only layer 0, the layer of entities without `RenderLayers`.
+    pub const DEFAULT: Self = Self { bits: 1 };
+    /// Every layer.
+    pub const ALL: Self = Self { bits: u32::MAX };
+    /// No layer.
+    pub const NONE: Self = Self { bits: 0 };
+
+    /// Creates a set containing only `layer`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `layer` is not below [`RenderLayers::COUNT`].
+    #[must_use]
+    pub fn layer(layer: u8) -> Self {
+        Self::NONE.with(layer)
+    }
+
+    /// Adds `layer` to the set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `layer` is not below [`RenderLayers::COUNT`].
+    #[must_use]
+    pub fn with(self, layer: u8) -> Self {
+        Self {
+            bits: self.bits | Self::bit(layer),
+        }
+    }
+
+    /// Removes `layer` from the set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `layer` is not below [`RenderLayers::COUNT`].
+    #[must_use]
+    pub fn without(self, layer: u8) -> Self {
+        Self {
+            bits: self.bits & !Self::bit(layer),
+        }
+    }
+
+    /// Returns whether `layer` is in the set.
+    #[must_use]
+    pub fn contains(self, layer: u8) -> bool {
+        layer < Self::COUNT && self.bits & Self::bit(layer) != 0
+    }
+
+    /// Returns whether both sets have a layer in common.
+    #[must_use]
+    pub fn intersects(self, other: Self) -> bool {
+        self.bits & other.bits != 0
+    }
+
+    fn bit(layer: u8) -> u32 {
+        assert!(
+            layer < Self::COUNT,
+            "Render layer {} is out of range, there are {} layers",
+            layer,
+            Self::COUNT
+        );
+        1 << layer
+    }
+}
+
+/// Part of the render target a camera draws to, in fractions of the target size from its top left
+/// corner.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize, SerdeDiff)]
+pub struct Viewport {
+    /// Left edge.
+    pub x: f32,
+    /// Top edge.
+    pub y: f32,
+    /// Width.
+    pub width: f32,
+    /// Height.
+    pub height: f32,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+impl Viewport {
+    /// The whole render target.
+    pub const FULL: Self = Self {
+        x: 0.0,
+        y: 0.0,
+        width: 1.0,
+        height: 1.0,
+    };
+
+    /// Creates a viewport from its top left corner and size, in fractions of the target size.
+    #[must_use]
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Returns the viewport in pixels of a target of the given size, as `(x, y, width, height)`.
+    ///
+    /// Edges are rounded to the nearest pixel so adjacent viewports share their edges, and the
+    /// result is clamped to the target.
+    #[must_use]
+    pub fn to_pixels(&self, target_width: u32, target_height: u32) -> (u32, u32, u32, u32) {
+        let edge =
+            |fraction: f32, size: u32| (fraction.clamp(0.0, 1.0) * size as f32).round() as u32;
+        let left = edge(self.x, target_width);
+        let top = edge(self.y, target_height);
+        let right = edge(self.x + self.width, target_width).max(left);
+        let bottom = edge(self.y + self.height, target_height).max(top);
+        (left, top, right - left, bottom - top)
+    }
+
+    /// Converts a position on a screen of the given size, in pixels, to a position in the
+    /// viewport, returned with the size of the viewport in pixels. These can be passed to
+    /// [`Camera::screen_ray`] for a camera drawing to this viewport.
+    ///
+    /// Returns `None` if the position is outside of the viewport.
+    #[must_use]
+    pub fn screen_to_viewport(
+        &self,
+        position: Point2<f32>,
+        screen_diagonal: Vector2<f32>,
+    ) -> Option<(Point2<f32>, Vector2<f32>)> {
+        let origin = Point2::new(self.x * screen_diagonal.x, self.y * screen_diagonal.y);
+        let size = Vector2::new(
+            self.width * screen_diagonal.x,
+            self.height * screen_diagonal.y,
+        );
+        let local = position - origin;
+        let inside = (0.0..=size.x).contains(&local.x) && (0.0..=size.y).contains(&local.y);
+        inside.then(|| (Point2::from(local), size))
+    }
+}
+
+/// Draws a camera to a part of the render target, in addition to the [`ActiveCamera`].
+///
+/// Every camera with a `CameraView` is rendered each frame, ordered by `order` from the lowest,
+/// so cameras with a higher order are drawn on top. The active camera is always rendered, with
+/// the defaults if it has no `CameraView`: the whole target, order 0 and every layer. This is
+/// enough for split-screen, minimaps or picture-in-picture, for example a minimap drawn over the
+/// main camera:
+///
+/// ```
+/// use amethyst::renderer::camera::{CameraView, RenderLayers, Viewport};
+///
+/// let minimap = CameraView::new(Viewport::new(0.75, 0.0, 0.25, 0.25))
+///     .with_order(1)
+///     .with_layers(RenderLayers::layer(1));
+/// assert!(!minimap.layers.contains(0));
+/// ```
+#[derive(
+    Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize, SerdeDiff, TypeUuid,
+)]
+#[uuid = "880ecf04-5c0d-4bc6-8035-066ff4e7862b"]
+#[serde(default)]
+pub struct CameraView {
+    /// Part of the render target the camera draws to.
+    pub viewport: Viewport,
+    /// Order of the camera among the rendered cameras, lower orders are drawn first.
+    pub order: i32,
+    /// Layers of the entities drawn by the camera.
+    pub layers: RenderLayers,
+}
+
+register_component_type!(CameraView);
+
+impl Default for CameraView {
+    fn default() -> Self {
+        Self::new(Viewport::FULL)
+    }
+}
+
+impl CameraView {
+    /// Creates a view of every layer, drawing to `viewport`.
+    #[must_use]
+    pub fn new(viewport: Viewport) -> Self {
+        Self {
+            viewport,
+            order: 0,
+            layers: RenderLayers::ALL,
+        }
+    }
+
+    /// Sets the order in which the camera is drawn.
+    #[must_use]
+    pub fn with_order(mut self, order: i32) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Sets the layers drawn by the camera.
+    #[must_use]
+    pub fn with_layers(mut self, layers: RenderLayers) -> Self {
+        self.layers = layers;
+        self
+    }
+}
+
+/// Returns the cameras to render in render order, with their views: the active camera, or the
+/// first camera if there is no active one, and every other camera with a [`CameraView`].
+pub fn render_views<'a, I>(active_camera: Option<Entity>, cameras: I) -> Vec<(Entity, CameraView)>
+where
+    I: IntoIterator<Item = (Entity, Option<&'a CameraView>)>,
+{
+    let cameras: Vec<_> = cameras.into_iter().collect();
+    let main = active_camera
+        .filter(|active| cameras.iter().any(|(entity, _)| entity == active))
+        .or_else(|| cameras.first().map(|(entity, _)| *entity));
+    let mut views: Vec<_> = cameras
+        .into_iter()
+        .filter_map(|(entity, view)| {
+            match view {
+                Some(view) => Some((entity, *view)),
+                None if Some(entity) == main => Some((entity, CameraView::default())),
+                None => None,
+            }
+        })
+        .collect();
+    views.sort_by_key(|(_, view)| view.order);
+    views
+}
+
 #[cfg(test)]
 mod tests {
     //! Tests for amethysts camera implementation.
@@ -664,4 +926,64 @@ mod tests {
         assert_ulps_eq!(ray.origin, expected_ray.origin);
         assert_ulps_eq!(ray.direction, expected_ray.direction);
     }
+
+    #[test]
+    fn render_layers() {
+        let layers = RenderLayers::layer(3).with(31);
+        assert!(layers.contains(3) && layers.contains(31));
+        assert!(!layers.contains(0));
+        assert!(!layers.without(3).contains(3));
+        assert!(!layers.intersects(RenderLayers::DEFAULT));
+        assert!(layers.intersects(RenderLayers::ALL));
+        assert!(!RenderLayers::ALL.contains(32));
+    }
+
+    #[test]
+    fn viewport_pixels_share_edges() {
+        let left = Viewport::new(0.0, 0.0, 1.0 / 3.0, 1.0);
+        let right = Viewport::new(1.0 / 3.0, 0.0, 2.0 / 3.0, 1.0);
+        let (x, _, width, height) = left.to_pixels(100, 50);
+        assert_eq!((x, width, height), (0, 33, 50));
+        assert_eq!(right.to_pixels(100, 50), (33, 0, 67, 50));
+        assert_eq!(
+            Viewport::new(0.5, 0.5, 1.0, 1.0).to_pixels(100, 50),
+            (50, 25, 50, 25)
+        );
+    }
+
+    #[test]
+    fn screen_to_viewport() {
+        let viewport = Viewport::new(0.5, 0.0, 0.5, 0.5);
+        let screen = Vector2::new(200.0, 100.0);
+        assert_eq!(
+            viewport.screen_to_viewport(Point2::new(150.0, 25.0), screen),
+            Some((Point2::new(50.0, 25.0), Vector2::new(100.0, 50.0)))
+        );
+        assert_eq!(
+            viewport.screen_to_viewport(Point2::new(50.0, 25.0), screen),
+            None
+        );
+    }
+
+    #[test]
+    fn render_views_order() {
+        let mut world = amethyst_core::ecs::World::default();
+        let main = world.push(());
+        let hidden = world.push(());
+        let minimap = world.push(());
+        let minimap_view = CameraView::new(Viewport::new(0.75, 0.0, 0.25, 0.25)).with_order(1);
+        let background_view = CameraView::default().with_order(-1);
+
+        let views = render_views(
+            Some(main),
+            vec![(hidden, None), (minimap, Some(&minimap_view)), (main, None)],
+        );
+        assert_eq!(
+            views,
+            vec![(main, CameraView::default()), (minimap, minimap_view)]
+        );
+
+        let views = render_views(None, vec![(hidden, Some(&background_view)), (main, None)]);
+        assert_eq!(views, vec![(hidden, background_view)]);
+    }
 }
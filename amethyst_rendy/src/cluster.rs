@@ -0,0 +1,452 @@
+//! Clustered light culling.
+//!
+//! The view of a camera is split into a grid of clusters: [`CLUSTER_COUNT`] tiles of the screen
+//! by slices of the view depth, growing exponentially thicker away from the camera. Every frame,
+//! each point, spot and area light is assigned to the clusters its range overlaps, so the shaders
+//! only evaluate the lights of the cluster of a pixel instead of every light of the scene.
+//! Directional lights reach everything and aren't clustered.
+//!
+//! The clusters are computed on the CPU by the `EnvironmentSub` of the 3D passes, for each
+//! rendered camera, and shared with the shaders in storage buffers.
+
+use std::ops::Range;
+
+use amethyst_core::math::{Matrix4, Point3, Vector3};
+
+use crate::{camera::Camera, visibility::BoundingSphere};
+
+/// Number of clusters along the width and the height of the screen, and the view depth.
+pub const CLUSTER_COUNT: [u32; 3] = [16, 9, 24];
+
+/// Lights of each cluster of the view of a camera.
+///
+/// A cluster is a `(x, y, slice)` triple, numbered `x + width * (y + height * slice)`, where `x`
+/// and `y` are counted from the `-1` corner of normalized device coordinates.
+#[derive(Debug)]
+pub struct LightClusters {
+    /// `(offset, point lights, spot lights, area lights)` of each cluster: the position of its
+    /// first light in `indices` and how many lights of each kind follow.
+    pub clusters: Vec<[u32; 4]>,
+    /// Indices of the lights of all the clusters. Those of a cluster are its point lights, then
+    /// its spot lights, then its area lights.
+    pub indices: Vec<u32>,
+    lists: Vec<[Vec<u32>; 3]>,
+    bounds: Vec<(Vector3<f32>, Vector3<f32>)>,
+    proj_view: Matrix4<f32>,
+    view: Matrix4<f32>,
+    depth_scale: f32,
+    depth_bias: f32,
+}
+
+impl Default for LightClusters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LightClusters {
+    /// Creates clusters without any light.
+    #[must_use]
+    pub fn new() -> Self {
+        let count = (CLUSTER_COUNT[0] * CLUSTER_COUNT[1] * CLUSTER_COUNT[2]) as usize;
+        Self {
+            clusters: vec![[0; 4]; count],
+            indices: Vec::new(),
+            lists: vec![Default::default(); count],
+            bounds: Vec::new(),
+            proj_view: Matrix4::identity(),
+            view: Matrix4::identity(),
+            depth_scale: 0.0,
+            depth_bias: 0.0,
+        }
+    }
+
+    /// Scale of the logarithm of the view depth of a point giving its depth slice.
+    #[must_use]
+    pub fn depth_scale(&self) -> f32 {
+        self.depth_scale
+    }
+
+    /// Bias of the logarithm of the view depth of a point giving its depth slice.
+    #[must_use]
+    pub fn depth_bias(&self) -> f32 {
+        self.depth_bias
+    }
+
+    /// Assigns the lights to the clusters of the view of `camera`, with the view matrix `view`.
+    ///
+    /// The lights are given as their bounding spheres in world space, and are referred to in
+    /// [`LightClusters::indices`] by their position in their slice.
+    pub fn assign(
+        &mut self,
+        camera: &Camera,
+        view: &Matrix4<f32>,
+        points: &[BoundingSphere],
+        spots: &[BoundingSphere],
+        areas: &[BoundingSphere],
+    ) {
+        self.proj_view = camera.matrix * view;
+        self.view = *view;
+        for lists in &mut self.lists {
+            for list in lists.iter_mut() {
+                list.clear();
+            }
+        }
+
+        let near = camera_near(camera);
+        let far = points
+            .iter()
+            .chain(spots)
+            .chain(areas)
+            .map(|light| -view.transform_point(&light.center).z + light.radius)
+            .fold(near * 2.0, f32::max);
+        let slices = CLUSTER_COUNT[2] as f32;
+        self.depth_scale = slices / (far / near).ln();
+        self.depth_bias = -slices * near.ln() / (far / near).ln();
+
+        let depths: Vec<f32> = (0..=CLUSTER_COUNT[2])
+            .map(|slice| near * (far / near).powf(slice as f32 / slices))
+            .collect();
+        self.bounds = cluster_bounds(camera, &depths);
+        for (kind, lights) in [points, spots, areas].iter().enumerate() {
+            for (index, light) in lights.iter().enumerate() {
+                self.assign_light(camera, kind, index as u32, light);
+            }
+        }
+
+        self.indices.clear();
+        for (cluster, lists) in self.clusters.iter_mut().zip(&self.lists) {
+            *cluster = [
+                self.indices.len() as u32,
+                lists[0].len() as u32,
+                lists[1].len() as u32,
+                lists[2].len() as u32,
+            ];
+            for list in lists {
+                self.indices.extend_from_slice(list);
+            }
+        }
+    }
+
+    fn assign_light(&mut self, camera: &Camera, kind: usize, index: u32, light: &BoundingSphere) {
+        let center = self.view.transform_point(&light.center);
+        let radius = light.radius;
+        let depth = -center.z;
+        if depth + radius <= 0.0 {
+            return;
+        }
+        let slices = self.slice(depth - radius)..self.slice(depth + radius) + 1;
+        let (xs, ys) = match screen_tiles(camera, &center, radius) {
+            Some(tiles) => tiles,
+            None => return,
+        };
+        for slice in slices {
+            for y in ys.clone() {
+                for x in xs.clone() {
+                    let cluster = cluster_number(x, y, slice);
+                    let (min, max) = &self.bounds[cluster];
+                    let closest = center.coords.sup(min).inf(max);
+                    if (closest - center.coords).norm_squared() <= radius * radius {
+                        self.lists[cluster][kind].push(index);
+                    }
+                }
+            }
+        }
+    }
+
+    fn slice(&self, depth: f32) -> u32 {
+        let slice = depth.max(1e-6).ln() * self.depth_scale + self.depth_bias;
+        slice.max(0.0).min(CLUSTER_COUNT[2] as f32 - 1.0) as u32
+    }
+
+    /// Returns the cluster containing `position`, in world space, computed like the shaders do.
+    #[must_use]
+    pub fn cluster_of(&self, position: &Point3<f32>) -> usize {
+        let clip = self.proj_view * position.to_homogeneous();
+        let tile = |ndc: f32, count: u32| {
+            ((ndc * 0.5 + 0.5) * count as f32)
+                .max(0.0)
+                .min(count as f32 - 1.0) as u32
+        };
+        let depth = -self.view.transform_point(position).z;
+        cluster_number(
+            tile(clip.x / clip.w, CLUSTER_COUNT[0]),
+            tile(clip.y / clip.w, CLUSTER_COUNT[1]),
+            self.slice(depth),
+        )
+    }
+
+    /// Returns the indices of the point, spot and area lights of `cluster`.
+    #[must_use]
+    pub fn lights(&self, cluster: usize) -> [&[u32]; 3] {
+        let [offset, points, spots, areas] = self.clusters[cluster];
+        let (points, spots, areas) = (
+            offset as usize..(offset + points) as usize,
+            (offset + points) as usize..(offset + points + spots) as usize,
+            (offset + points + spots) as usize..(offset + points + spots + areas) as usize,
+        );
+        [
+            &self.indices[points],
+            &self.indices[spots],
+            &self.indices[areas],
+        ]
+    }
+}
+
+fn cluster_number(x: u32, y: u32, slice: u32) -> usize {
+    (x + CLUSTER_COUNT[0] * (y + CLUSTER_COUNT[1] * slice)) as usize
+}
+
+/// Returns the depth of the near plane of `camera`.
+fn camera_near(camera: &Camera) -> f32 {
+    let near = -camera
+        .inverse
+        .transform_point(&Point3::new(0.0, 0.0, 1.0))
+        .z;
+    near.max(1e-3)
+}
+
+/// Returns the view space bounding box of each cluster, whose slices are between `depths`.
+fn cluster_bounds(camera: &Camera, depths: &[f32]) -> Vec<(Vector3<f32>, Vector3<f32>)> {
+    // Rays through the corners of the tiles, with the depth reversed, 1 on the near plane.
+    let rays: Vec<Vec<(Point3<f32>, Vector3<f32>)>> = (0..=CLUSTER_COUNT[1])
+        .map(|y| {
+            (0..=CLUSTER_COUNT[0])
+                .map(|x| {
+                    let ndc_x = 2.0 * x as f32 / CLUSTER_COUNT[0] as f32 - 1.0;
+                    let ndc_y = 2.0 * y as f32 / CLUSTER_COUNT[1] as f32 - 1.0;
+                    let front = camera
+                        .inverse
+                        .transform_point(&Point3::new(ndc_x, ndc_y, 1.0));
+                    let back = camera
+                        .inverse
+                        .transform_point(&Point3::new(ndc_x, ndc_y, 0.5));
+                    (front, back - front)
+                })
+                .collect()
+        })
+        .collect();
+    let at_depth = |(front, ray): &(Point3<f32>, Vector3<f32>), depth: f32| {
+        (front + ray * ((depth + front.z) / -ray.z)).coords
+    };
+
+    let mut bounds =
+        Vec::with_capacity((CLUSTER_COUNT[0] * CLUSTER_COUNT[1] * CLUSTER_COUNT[2]) as usize);
+    for slice in depths.windows(2) {
+        for y in 0..CLUSTER_COUNT[1] as usize {
+            for x in 0..CLUSTER_COUNT[0] as usize {
+                let mut min = Vector3::repeat(f32::MAX);
+                let mut max = Vector3::repeat(f32::MIN);
+                for ray in &[
+                    rays[y][x],
+                    rays[y][x + 1],
+                    rays[y + 1][x],
+                    rays[y + 1][x + 1],
+                ] {
+                    for depth in slice {
+                        let corner = at_depth(ray, *depth);
+                        min = min.inf(&corner);
+                        max = max.sup(&corner);
+                    }
+                }
+                bounds.push((min, max));
+            }
+        }
+    }
+    bounds
+}
+
+/// Returns the ranges of tiles covered by a sphere at `center` in view space, or `None` if it is
+/// outside of the screen.
+fn screen_tiles(
+    camera: &Camera,
+    center: &Point3<f32>,
+    radius: f32,
+) -> Option<(Range<u32>, Range<u32>)> {
+    let full = (0..CLUSTER_COUNT[0], 0..CLUSTER_COUNT[1]);
+    let mut min = [f32::MAX; 2];
+    let mut max = [f32::MIN; 2];
+    for corner in 0..8 {
+        let offset = Vector3::new(
+            if corner & 1 == 0 { -radius } else { radius },
+            if corner & 2 == 0 { -radius } else { radius },
+            if corner & 4 == 0 { -radius } else { radius },
+        );
+        let clip = camera.matrix * (center + offset).to_homogeneous();
+        // A corner behind the camera projects to the wrong side of the screen.
+        if clip.w <= 0.0 {
+            return Some(full);
+        }
+        for axis in 0..2 {
+            min[axis] = min[axis].min(clip[axis] / clip.w);
+            max[axis] = max[axis].max(clip[axis] / clip.w);
+        }
+    }
+    if min[0] > 1.0 || min[1] > 1.0 || max[0] < -1.0 || max[1] < -1.0 {
+        return None;
+    }
+    let tiles = |axis: usize| {
+        let count = CLUSTER_COUNT[axis] as f32;
+        let tile = |ndc: f32| ((ndc * 0.5 + 0.5) * count).max(0.0).min(count - 1.0) as u32;
+        tile(min[axis])..tile(max[axis]) + 1
+    };
+    Some((tiles(0), tiles(1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::transform::Transform;
+
+    use super::*;
+
+    fn camera() -> (Camera, Matrix4<f32>) {
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(1.0, 2.0, 10.0);
+        transform.append_rotation_y_axis(0.3);
+        transform.copy_local_to_global();
+        (
+            Camera::standard_3d(16.0, 9.0),
+            transform.global_matrix().try_inverse().unwrap(),
+        )
+    }
+
+    #[test]
+    fn lights_are_in_the_clusters_they_reach() {
+        let (camera, view) = camera();
+        let points = [
+            BoundingSphere::new(Point3::new(0.0, 0.0, 0.0), 3.0),
+            BoundingSphere::new(Point3::new(4.0, 1.0, -20.0), 8.0),
+            // Around the camera.
+            BoundingSphere::new(Point3::new(1.0, 2.0, 11.0), 2.0),
+        ];
+        let spots = [BoundingSphere::new(Point3::new(-3.0, 0.0, 2.0), 5.0)];
+        let areas = [BoundingSphere::new(Point3::new(2.0, -1.0, 5.0), 1.5)];
+        let mut clusters = LightClusters::new();
+        clusters.assign(&camera, &view, &points, &spots, &areas);
+
+        let world = view.try_inverse().unwrap();
+        let kinds = [&points[..], &spots[..], &areas[..]];
+        for (kind, lights) in kinds.iter().enumerate() {
+            for (index, light) in lights.iter().enumerate() {
+                // Points inside the light, in front of the camera.
+                let steps = 6;
+                for x in 0..=steps {
+                    for y in 0..=steps {
+                        for z in 0..=steps {
+                            let offset = Vector3::new(x as f32, y as f32, z as f32) / steps as f32
+                                * 2.0
+                                - Vector3::repeat(1.0);
+                            if offset.norm() > 1.0 {
+                                continue;
+                            }
+                            let point = light.center + offset * light.radius * 0.999;
+                            let ndc = (camera.matrix * view).transform_point(&point);
+                            let in_view = -view.transform_point(&point).z > 0.2
+                                && ndc.x.abs() <= 1.0
+                                && ndc.y.abs() <= 1.0;
+                            if !in_view {
+                                continue;
+                            }
+                            let cluster = clusters.cluster_of(&point);
+                            assert!(
+                                clusters.lights(cluster)[kind].contains(&(index as u32)),
+                                "light {} of kind {} is missing from cluster {} at {:?}",
+                                index,
+                                kind,
+                                cluster,
+                                world.transform_point(&view.transform_point(&point)),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn points_are_inside_their_cluster() {
+        let (camera, view) = camera();
+        let world = view.try_inverse().unwrap();
+        let mut clusters = LightClusters::new();
+        clusters.assign(
+            &camera,
+            &view,
+            &[BoundingSphere::new(Point3::new(0.0, 0.0, -40.0), 5.0)],
+            &[],
+            &[],
+        );
+        let depth = |slice: f32| ((slice - clusters.depth_bias()) / clusters.depth_scale()).exp();
+        let (near, far) = (depth(0.0), depth(CLUSTER_COUNT[2] as f32));
+
+        let steps = 20;
+        for x in 0..steps {
+            for y in 0..steps {
+                for z in 0..steps {
+                    let ndc = Point3::new(
+                        (x as f32 + 0.5) / steps as f32 * 2.0 - 1.0,
+                        (y as f32 + 0.5) / steps as f32 * 2.0 - 1.0,
+                        1.0,
+                    );
+                    let ray = camera.inverse.transform_point(&ndc).coords.normalize();
+                    let depth = near * (far / near).powf((z as f32 + 0.5) / steps as f32);
+                    let point = Point3::from(ray * (depth / -ray.z));
+                    let cluster = clusters.cluster_of(&world.transform_point(&point));
+                    let (min, max) = &clusters.bounds[cluster];
+                    let epsilon = Vector3::repeat(1e-3 * depth);
+                    assert!(
+                        point.coords >= min - epsilon && point.coords <= max + epsilon,
+                        "{:?} is outside of {:?}",
+                        point,
+                        (min, max),
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lights_are_only_in_nearby_clusters() {
+        let (camera, view) = camera();
+        let world = view.try_inverse().unwrap();
+        // A small light on the left of the view, and one behind the camera.
+        let left = world.transform_point(&Point3::new(-9.0, 0.0, -10.0));
+        let behind = world.transform_point(&Point3::new(0.0, 0.0, 5.0));
+        let points = [
+            BoundingSphere::new(left, 1.0),
+            BoundingSphere::new(behind, 1.0),
+        ];
+        let mut clusters = LightClusters::new();
+        clusters.assign(&camera, &view, &points, &[], &[]);
+
+        let used = clusters
+            .clusters
+            .iter()
+            .filter(|cluster| cluster[1] > 0)
+            .count();
+        assert!(used > 0 && used < 20, "{} clusters", used);
+        assert!(clusters.indices.iter().all(|index| *index == 0));
+        let right = world.transform_point(&Point3::new(9.0, 0.0, -10.0));
+        assert!(clusters.lights(clusters.cluster_of(&right))[0].is_empty());
+    }
+
+    #[test]
+    fn cluster_lights_are_grouped_by_kind() {
+        let (camera, view) = camera();
+        let world = view.try_inverse().unwrap();
+        let center = world.transform_point(&Point3::new(0.0, 0.0, -10.0));
+        let light = BoundingSphere::new(center, 2.0);
+        let mut clusters = LightClusters::new();
+        clusters.assign(
+            &camera,
+            &view,
+            &[light.clone(), light.clone()],
+            std::slice::from_ref(&light),
+            &[light.clone(), light.clone(), light],
+        );
+
+        let [points, spots, areas] = clusters.lights(clusters.cluster_of(&center));
+        assert_eq!(points, &[0, 1]);
+        assert_eq!(spots, &[0]);
+        assert_eq!(areas, &[0, 1, 2]);
+    }
+}
@@ -19,6 +19,11 @@ use crate::types::TextureData;
 
 /// Image format description newtype wrapper for `ImageTextureConfig` from rendy.
 ///
+/// These are the importer options of PNG, JPEG, TGA and BMP files. The defaults suit pixel art:
+/// sRGB, `Nearest` filtering, no mipmaps and premultiplied alpha. They are changed for a single
+/// texture in the `importer_options` of its `.meta` file, or for a folder in a `png.import_defaults`
+/// file, see the "Import Settings" chapter of the book.
+///
 /// # Example Usage
 /// ```
 /// use amethyst::{
@@ -0,0 +1,233 @@
+//! Comparison of rendered images against reference "golden" images, for rendering regression
+//! tests. You need to enable the `test-support` flag to use this.
+//!
+//! Render the scene with the [`RenderToImage`](crate::RenderToImage) plugin, then compare the
+//! [`CapturedImage`] with a PNG checked into the repository:
+//!
+//! ```no_run
+//! use amethyst::renderer::{
+//!     golden::{compare_golden, Tolerance},
+//!     CapturedImage,
+//! };
+//!
+//! # fn check(captured: &CapturedImage) -> amethyst::Result<()> {
+//! compare_golden(captured, "tests/golden/sprites.png", Tolerance::default())?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Run the tests with the `AMETHYST_UPDATE_GOLDEN` environment variable set to write the golden
+//! images instead of comparing them, after checking the new images are correct.
+
+use std::path::Path;
+
+use amethyst_error::{format_err, Error};
+
+use crate::CapturedImage;
+
+/// Environment variable making [`compare_golden`] write the golden images.
+pub const UPDATE_GOLDEN_VAR: &str = "AMETHYST_UPDATE_GOLDEN";
+
+/// Differences accepted between a rendered image and its golden image, to allow for the small
+/// rasterization differences between drivers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance {
+    /// Largest difference of a color channel for which two pixels are still equal.
+    pub channel: u8,
+    /// Fraction of the pixels which may differ, between 0 and 1.
+    pub pixels: f32,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            channel: 2,
+            pixels: 0.001,
+        }
+    }
+}
+
+impl Tolerance {
+    /// Images must be identical.
+    pub const EXACT: Self = Self {
+        channel: 0,
+        pixels: 0.0,
+    };
+}
+
+/// Differences between two images of the same size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageDiff {
+    /// Number of pixels with a channel differing by more than the channel tolerance.
+    pub mismatched: usize,
+    /// Number of pixels in each image.
+    pub total: usize,
+    /// Largest difference of a channel over all the pixels.
+    pub max_channel_difference: u8,
+}
+
+impl ImageDiff {
+    /// Returns whether the differences are within `tolerance`.
+    #[must_use]
+    pub fn within(&self, tolerance: Tolerance) -> bool {
+        self.mismatched as f32 <= tolerance.pixels * self.total as f32
+    }
+}
+
+/// Compares two RGBA images pixel by pixel, counting the pixels with a channel differing by more
+/// than `channel_tolerance`.
+///
+/// Returns `None` if the images don't have the same size.
+#[must_use]
+pub fn diff_images(
+    expected: &image::RgbaImage,
+    actual: &image::RgbaImage,
+    channel_tolerance: u8,
+) -> Option<ImageDiff> {
+    if expected.dimensions() != actual.dimensions() {
+        return None;
+    }
+    let mut diff = ImageDiff {
+        mismatched: 0,
+        total: expected.pixels().len(),
+        max_channel_difference: 0,
+    };
+    for (expected, actual) in expected.pixels().zip(actual.pixels()) {
+        let difference = pixel_difference(expected.0, actual.0);
+        diff.max_channel_difference = diff.max_channel_difference.max(difference);
+        if difference > channel_tolerance {
+            diff.mismatched += 1;
+        }
+    }
+    Some(diff)
+}
+
+/// Compares `captured` with the golden PNG image at `path`.
+///
+/// If the `AMETHYST_UPDATE_GOLDEN` environment variable is set, the image is written to `path`
+/// instead. When the images differ by more than `tolerance`, the rendered image and an image of
+/// the mismatched pixels in red are written next to the golden image, with the `actual.png` and
+/// `diff.png` extensions, to inspect the failure.
+///
+/// # Errors
+///
+/// Returns an error if the images differ, or if the golden image can't be read or written.
+pub fn compare_golden(
+    captured: &CapturedImage,
+    path: impl AsRef<Path>,
+    tolerance: Tolerance,
+) -> Result<(), Error> {
+    let path = path.as_ref();
+    if std::env::var_os(UPDATE_GOLDEN_VAR).is_some() {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        return captured.save_png(path);
+    }
+
+    let expected = image::open(path)
+        .map_err(|err| {
+            format_err!(
+                "Failed to read golden image {}: {}. Set {} to write it.",
+                path.display(),
+                err,
+                UPDATE_GOLDEN_VAR
+            )
+        })?
+        .to_rgba8();
+    let actual = captured.to_rgba_image();
+    let diff = match diff_images(&expected, &actual, tolerance.channel) {
+        Some(diff) => diff,
+        None => {
+            captured.save_png(path.with_extension("actual.png"))?;
+            return Err(format_err!(
+                "Rendered image is {}x{}, golden image {} is {}x{}",
+                actual.width(),
+                actual.height(),
+                path.display(),
+                expected.width(),
+                expected.height()
+            ));
+        }
+    };
+    if diff.within(tolerance) {
+        return Ok(());
+    }
+
+    captured.save_png(path.with_extension("actual.png"))?;
+    diff_image(&expected, &actual, tolerance.channel).save(path.with_extension("diff.png"))?;
+    Err(format_err!(
+        "Rendered image differs from golden image {}: {} of {} pixels differ, by up to {}",
+        path.display(),
+        diff.mismatched,
+        diff.total,
+        diff.max_channel_difference
+    ))
+}
+
+fn pixel_difference(expected: [u8; 4], actual: [u8; 4]) -> u8 {
+    expected
+        .iter()
+        .zip(&actual)
+        .map(|(expected, actual)| (i16::from(*expected) - i16::from(*actual)).unsigned_abs() as u8)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Image of the mismatched pixels in red over a faded copy of the expected image.
+fn diff_image(
+    expected: &image::RgbaImage,
+    actual: &image::RgbaImage,
+    channel_tolerance: u8,
+) -> image::RgbaImage {
+    image::RgbaImage::from_fn(expected.width(), expected.height(), |x, y| {
+        let expected = expected.get_pixel(x, y).0;
+        if pixel_difference(expected, actual.get_pixel(x, y).0) > channel_tolerance {
+            image::Rgba([255, 0, 0, 255])
+        } else {
+            let [r, g, b, _] = expected;
+            image::Rgba([r / 4, g / 4, b / 4, 255])
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(pixels: &[[u8; 4]]) -> image::RgbaImage {
+        image::RgbaImage::from_raw(pixels.len() as u32, 1, pixels.concat()).unwrap()
+    }
+
+    #[test]
+    fn diff_counts_pixels_over_tolerance() {
+        let expected = image(&[[10, 10, 10, 255], [200, 0, 0, 255], [0, 0, 0, 255]]);
+        let actual = image(&[[12, 9, 10, 255], [100, 0, 0, 255], [0, 0, 0, 255]]);
+
+        let diff = diff_images(&expected, &actual, 2).unwrap();
+        assert_eq!(
+            diff,
+            ImageDiff {
+                mismatched: 1,
+                total: 3,
+                max_channel_difference: 100,
+            }
+        );
+        assert!(!diff.within(Tolerance::default()));
+        assert!(diff.within(Tolerance {
+            channel: 2,
+            pixels: 0.5,
+        }));
+        assert_eq!(diff_images(&expected, &actual, 100).unwrap().mismatched, 0);
+        assert_eq!(diff_images(&expected, &image(&[[0; 4]]), 0), None);
+    }
+
+    #[test]
+    fn diff_image_marks_mismatches() {
+        let expected = image(&[[40, 80, 120, 255], [0, 0, 0, 255]]);
+        let actual = image(&[[40, 80, 120, 255], [0, 50, 0, 255]]);
+        let diff = diff_image(&expected, &actual, 0);
+        assert_eq!(diff.get_pixel(0, 0).0, [10, 20, 30, 255]);
+        assert_eq!(diff.get_pixel(1, 0).0, [255, 0, 0, 255]);
+    }
+}
@@ -22,13 +22,19 @@
 //! * [`RenderingSystem`](crate::system::RenderingSystem)
 //! * [`VisibilitySortingSystem`](crate::visibility::VisibilitySortingSystem)
 //! * [`SpriteVisibilitySortingSystem`](crate::sprite_visibility::SpriteVisibilitySortingSystem)
+//! * [`SpatialIndexSystem`](crate::spatial::SpatialIndexSystem)
+//! * `MousePickingSystem` in [`picking`](crate::picking), with the `window` feature
 //!
 //! ## Components
 //!
 //! * [`Camera`](camera::Camera)
+//! * [`CameraView`](camera::CameraView)
+//! * [`RenderLayers`](camera::RenderLayers)
+//! * [`RenderTexture`](render_texture::RenderTexture)
 //! * [`SpriteVisibility`](sprite_visibility::SpriteVisibility)
 //! * [`Visibility`](visibility::Visibility)
 //! * [`BoundingSphere`](visibility::BoundingSphere)
+//! * [`MeshLod`](lod::MeshLod)
 //! * [`DebugLinesComponent`](debug_drawing::DebugLinesComponent)
 //! * [`Light`](light::Light)
 //! * [`Tint`](resources::Tint)
@@ -64,19 +70,30 @@ pub mod pass;
 pub mod batch;
 pub mod bundle;
 pub mod camera;
+pub mod cluster;
 pub mod debug_drawing;
 pub mod error;
 pub mod formats;
 pub mod light;
+pub mod lod;
+mod ltc;
+pub mod mesh_vertices;
 pub mod mtl;
+pub mod picking;
 pub mod pipeline;
 pub mod plugins;
+pub mod post_process;
+pub mod render_texture;
+pub mod render_to_image;
 pub mod resources;
 pub mod serde_shim;
+pub mod shadow;
 pub mod shape;
 pub mod skinning;
+pub mod spatial;
 pub mod sprite;
 pub mod sprite_visibility;
+pub mod static_batch;
 pub mod submodules;
 pub mod system;
 pub mod transparent;
@@ -86,6 +103,9 @@ pub mod visibility;
 pub mod pod;
 pub mod util;
 
+#[cfg(feature = "test-support")]
+pub mod golden;
+
 /* FIXME
 #[cfg(feature = "test-support")]
 mod render_test_bundle;
@@ -110,6 +130,9 @@ pub use crate::{
     formats::texture::ImageFormat,
     mtl::{Material, MaterialDefaults},
     plugins::*,
+    post_process::{PostProcessSettings, RenderPostProcess},
+    render_texture::{RenderTexture, RenderToTexture},
+    render_to_image::{CapturedImage, RenderToImage},
     sprite::{Sprite, SpriteRender, SpriteSheet},
     system::{GraphCreator, MeshProcessorSystem, TextureProcessorSystem},
     transparent::Transparent,
@@ -17,8 +17,7 @@ use type_uuid::TypeUuid;
 #[uuid = "32cf5344-28c1-41c4-a1f9-ea87de4b1a4f"]
 pub enum Light {
     /// An area light.
-    /// FIXME: Missing implementation!
-    Area,
+    Area(AreaLight),
     /// A directional light.
     Directional(DirectionalLight),
     /// A point light.
@@ -31,7 +30,7 @@ pub enum Light {
 
 impl Default for Light {
     fn default() -> Self {
-        Light::Area
+        Light::Point(PointLight::default())
     }
 }
 
@@ -70,6 +69,8 @@ pub struct DirectionalLight {
     pub intensity: f32,
     /// Direction that the light is pointing.
     pub direction: Vector3<f32>,
+    /// Shadows cast by the light, none by default.
+    pub shadows: Option<ShadowSettings>,
 }
 
 impl Default for DirectionalLight {
@@ -78,6 +79,7 @@ impl Default for DirectionalLight {
             color: palette::rgb::Rgb::default(),
             intensity: 1.0,
             direction: [-1.0, -1.0, -1.0].into(),
+            shadows: None,
         }
     }
 }
@@ -122,6 +124,8 @@ pub struct PointLight {
     /// Smoothness of the light-to-dark transition from the center to the
     /// radius.
     pub smoothness: f32,
+    /// Shadows cast by the light, none by default.
+    pub shadows: Option<ShadowSettings>,
 }
 
 impl Default for PointLight {
@@ -131,6 +135,7 @@ impl Default for PointLight {
             intensity: 10.0,
             radius: 10.0,
             smoothness: 4.0,
+            shadows: None,
         }
     }
 }
@@ -160,6 +165,8 @@ pub struct SpotLight {
     /// Smoothness of the light-to-dark transition from the center to the
     /// radius.
     pub smoothness: f32,
+    /// Shadows cast by the light, none by default.
+    pub shadows: Option<ShadowSettings>,
 }
 
 impl Default for SpotLight {
@@ -171,6 +178,45 @@ impl Default for SpotLight {
             intensity: 10.0,
             range: 10.0,
             smoothness: 4.0,
+            shadows: None,
+        }
+    }
+}
+
+/// Shadow map settings of a light casting shadows.
+///
+/// Shadows are drawn by the 3D plugins created with `with_shadows`, see [`crate::shadow`].
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct ShadowSettings {
+    /// Width and height in pixels of each shadow map of the light, rounded up to a power of two.
+    pub resolution: u32,
+    /// Offset towards the light, in world units, of the surfaces tested against the shadow map.
+    /// Increase it if lit surfaces are covered with shadow stripes.
+    pub bias: f32,
+    /// Offset along the surface normal, in world units, of the surfaces tested against the
+    /// shadow map. Removes the stripes on surfaces almost parallel to the light.
+    pub normal_bias: f32,
+    /// Number of shadow cascades of a directional light, from 1 to 4. Ignored by the other
+    /// lights.
+    pub cascades: u8,
+    /// Distance from the camera covered by the shadow cascades of a directional light. It is
+    /// also how far towards the light shadow casters outside of the view are drawn.
+    pub max_distance: f32,
+    /// Near plane of the shadow maps of point and spot lights. Nothing closer to the light
+    /// casts a shadow.
+    pub near: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        ShadowSettings {
+            resolution: 1024,
+            bias: 0.05,
+            normal_bias: 0.05,
+            cascades: 4,
+            max_distance: 100.0,
+            near: 0.05,
         }
     }
 }
@@ -181,6 +227,86 @@ impl From<SpotLight> for Light {
     }
 }
 
+/// A rectangle or disc shaped light source. Uses the `Transform` set of components for
+/// positioning: the light lies in the local XY plane of its entity and emits towards its local
+/// negative Z axis.
+///
+/// Area lights are shaded with linearly transformed cosines, as described in
+/// [this paper][ltc], which gives soft highlights and lighting matching the shape of the light.
+///
+/// [ltc]: https://eheitzresearch.wordpress.com/415-2/
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct AreaLight {
+    /// Color of the light in SRGB format.
+    #[serde(with = "crate::serde_shim::srgb")]
+    pub color: palette::Srgb,
+    /// Brightness of the light source, in nits.
+    pub intensity: f32,
+    /// Shape and size of the light.
+    pub shape: AreaShape,
+    /// Whether the light also emits from its back face.
+    pub two_sided: bool,
+    /// Maximum distance from the center of the light of its affected area.
+    pub range: f32,
+}
+
+impl Default for AreaLight {
+    fn default() -> Self {
+        AreaLight {
+            color: palette::rgb::Rgb::default(),
+            intensity: 10.0,
+            shape: AreaShape::default(),
+            two_sided: false,
+            range: 10.0,
+        }
+    }
+}
+
+impl AreaLight {
+    /// Returns the half extents of the light along its local X and Y axes.
+    #[must_use]
+    pub fn half_extents(&self) -> (f32, f32) {
+        match self.shape {
+            AreaShape::Rectangle { width, height } => (width / 2.0, height / 2.0),
+            AreaShape::Disc { radius } => (radius, radius),
+        }
+    }
+}
+
+impl From<AreaLight> for Light {
+    fn from(area: AreaLight) -> Self {
+        Light::Area(area)
+    }
+}
+
+/// Shape of an [`AreaLight`], in the local XY plane of the light.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum AreaShape {
+    /// A rectangle centered on the light.
+    Rectangle {
+        /// Size along the local X axis.
+        width: f32,
+        /// Size along the local Y axis.
+        height: f32,
+    },
+    /// A disc centered on the light.
+    Disc {
+        /// Radius of the disc.
+        radius: f32,
+    },
+}
+
+impl Default for AreaShape {
+    fn default() -> Self {
+        AreaShape::Rectangle {
+            width: 1.0,
+            height: 1.0,
+        }
+    }
+}
+
 /// A realistic disk-shaped sun light source.
 #[repr(C)]
 #[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
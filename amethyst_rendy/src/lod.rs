@@ -0,0 +1,366 @@
+//! Mesh level of detail, drawing simpler meshes for the entities which are small on screen.
+//!
+//! An entity with a [`MeshLod`] draws one of its meshes instead of its `Handle<Mesh>`. The level
+//! is selected for each rendered camera by the `VisibilitySortingSystem`, before the meshes are
+//! batched, from either the distance to the camera or the size of the `BoundingSphere` of the
+//! entity on screen. The selection of each camera is in [`ViewVisibility::lods`].
+//!
+//! ```
+//! use amethyst::{
+//!     assets::Handle,
+//!     renderer::{
+//!         lod::{LodMetric, MeshLod},
+//!         Mesh,
+//!     },
+//! };
+//!
+//! # fn example(detailed: Handle<Mesh>, simple: Handle<Mesh>) {
+//! // The detailed mesh down to a tenth of the view height, then the simple one down to a
+//! // hundredth, and nothing below.
+//! let lod = MeshLod::new(LodMetric::ScreenSize)
+//!     .with_level(detailed, 0.1)
+//!     .with_level(simple, 0.01)
+//!     .with_cross_fade(0.25);
+//! # }
+//! ```
+//!
+//! The entity must still have a `Handle<Mesh>`, usually its most detailed level, which is the
+//! one drawn into the shadow maps.
+//!
+//! [`ViewVisibility::lods`]: crate::visibility::ViewVisibility::lods
+
+use amethyst_assets::Handle;
+use amethyst_core::math::{Matrix4, Point3};
+use serde::{Deserialize, Serialize};
+use smallvec::SmallVec;
+
+use crate::{types::Mesh, visibility::BoundingSphere};
+
+/// What the thresholds of a [`MeshLod`] are compared to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LodMetric {
+    /// Distance between the camera and the center of the bounding sphere, in world units. Each
+    /// threshold is the largest distance at which the level is drawn.
+    Distance,
+    /// Fraction of the view height covered by the bounding sphere. Each threshold is the smallest
+    /// screen size at which the level is drawn.
+    ScreenSize,
+}
+
+/// Meshes of an entity at decreasing levels of detail, see the [module documentation](self).
+///
+/// Beyond the threshold of the last level the entity is not drawn at all, a last threshold of
+/// `0.0` screen size or infinite distance always draws it.
+#[derive(Clone, Debug)]
+pub struct MeshLod {
+    /// Meshes of the levels, the most detailed first.
+    pub meshes: Vec<Handle<Mesh>>,
+    /// Threshold of each level, see [`LodMetric`].
+    pub thresholds: Vec<f32>,
+    /// What the thresholds are compared to.
+    pub metric: LodMetric,
+    /// Fraction of the thresholds by which an entity must go past them before its level changes
+    /// again, so the level doesn't flicker when the entity stays near a threshold.
+    pub hysteresis: f32,
+    /// Duration in seconds of the dithered cross-fade between two levels, `None` to switch
+    /// immediately.
+    pub cross_fade: Option<f32>,
+}
+
+impl MeshLod {
+    /// Creates a `MeshLod` without levels, with a hysteresis of 10% and without cross-fade.
+    #[must_use]
+    pub fn new(metric: LodMetric) -> Self {
+        Self {
+            meshes: Vec::new(),
+            thresholds: Vec::new(),
+            metric,
+            hysteresis: 0.1,
+            cross_fade: None,
+        }
+    }
+
+    /// Adds a level less detailed than the previous ones.
+    #[must_use]
+    pub fn with_level(mut self, mesh: Handle<Mesh>, threshold: f32) -> Self {
+        self.meshes.push(mesh);
+        self.thresholds.push(threshold);
+        self
+    }
+
+    /// Sets the hysteresis, see [`MeshLod::hysteresis`].
+    #[must_use]
+    pub fn with_hysteresis(mut self, hysteresis: f32) -> Self {
+        self.hysteresis = hysteresis;
+        self
+    }
+
+    /// Cross-fades between levels for `seconds`.
+    #[must_use]
+    pub fn with_cross_fade(mut self, seconds: f32) -> Self {
+        self.cross_fade = Some(seconds);
+        self
+    }
+
+    /// Returns the number of levels.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.meshes.len().min(self.thresholds.len())
+    }
+
+    /// Returns true if there are no levels.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the value of the metric of `sphere`, in world space, seen by a camera at
+    /// `camera_position` with the `projection` matrix.
+    #[must_use]
+    pub fn metric_value(
+        &self,
+        sphere: &BoundingSphere,
+        camera_position: &Point3<f32>,
+        projection: &Matrix4<f32>,
+    ) -> f32 {
+        let distance = (sphere.center - camera_position).magnitude();
+        match self.metric {
+            LodMetric::Distance => distance,
+            LodMetric::ScreenSize => {
+                let size = sphere.radius * projection[(1, 1)].abs();
+                if projection[(3, 3)].abs() < f32::EPSILON {
+                    // Perspective projections shrink objects with their distance.
+                    size / distance.max(f32::EPSILON)
+                } else {
+                    // Orthographic projections keep the size of objects at any distance.
+                    size
+                }
+            }
+        }
+    }
+
+    /// Returns the level drawn for a metric `value`, `None` beyond the last threshold.
+    ///
+    /// `current` is the level selected in the previous frame, which is kept until the value goes
+    /// past its thresholds by more than the hysteresis.
+    #[must_use]
+    pub fn select_level(&self, value: f32, current: Option<Option<usize>>) -> Option<usize> {
+        let len = self.len();
+        let level = select_level(
+            self.metric,
+            &self.thresholds[..len],
+            self.hysteresis,
+            value,
+            current.map(|level| level.unwrap_or(len)),
+        );
+        if level < len {
+            Some(level)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the selection for a metric `value` following `previous`, the selection of the
+    /// previous frame, `delta_seconds` later.
+    #[must_use]
+    pub fn select(
+        &self,
+        value: f32,
+        previous: Option<&LodSelection>,
+        delta_seconds: f32,
+    ) -> LodSelection {
+        let level = self.select_level(value, previous.map(|previous| previous.level));
+        LodSelection::next(previous, level, self.cross_fade, delta_seconds)
+    }
+}
+
+/// Index of the level drawn for `value`, `thresholds.len()` beyond the last threshold.
+fn select_level(
+    metric: LodMetric,
+    thresholds: &[f32],
+    hysteresis: f32,
+    value: f32,
+    current: Option<usize>,
+) -> usize {
+    // Keys grow with the distance for both metrics, a screen size of zero being infinitely far.
+    let key = |value: f32| {
+        match metric {
+            LodMetric::Distance => value,
+            LodMetric::ScreenSize => value.recip(),
+        }
+    };
+    let bound = |level: usize| thresholds.get(level).map_or(f32::INFINITY, |t| key(*t));
+    let value = key(value);
+
+    if let Some(current) = current.filter(|level| *level <= thresholds.len()) {
+        let upper = bound(current) * (1.0 + hysteresis);
+        let lower = if current == 0 {
+            f32::NEG_INFINITY
+        } else {
+            bound(current - 1) * (1.0 - hysteresis)
+        };
+        if value > lower && value <= upper {
+            return current;
+        }
+    }
+    thresholds
+        .iter()
+        .position(|threshold| value <= key(*threshold))
+        .unwrap_or(thresholds.len())
+}
+
+/// Level of detail of an entity selected for a camera, see [`MeshLod`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LodSelection {
+    /// Level drawn, `None` beyond the last threshold.
+    pub level: Option<usize>,
+    /// Level being faded out while `fade` is below `1.0`.
+    pub previous: Option<usize>,
+    /// Progress of the cross-fade from `previous` to `level`, from `0.0` to `1.0`.
+    pub fade: f32,
+}
+
+impl LodSelection {
+    /// Returns the selection of `level` following `previous`, the selection of the previous frame,
+    /// `delta_seconds` later, cross-fading over `cross_fade` seconds when the level changes.
+    #[must_use]
+    pub fn next(
+        previous: Option<&LodSelection>,
+        level: Option<usize>,
+        cross_fade: Option<f32>,
+        delta_seconds: f32,
+    ) -> Self {
+        let duration = cross_fade.filter(|duration| *duration > 0.0);
+        match (previous, duration) {
+            (Some(previous), Some(duration)) if previous.level != level => {
+                Self {
+                    level,
+                    previous: previous.level,
+                    fade: (delta_seconds / duration).min(1.0),
+                }
+            }
+            (Some(previous), Some(duration)) if previous.fade < 1.0 => {
+                Self {
+                    level,
+                    previous: previous.previous,
+                    fade: (previous.fade + delta_seconds / duration).min(1.0),
+                }
+            }
+            _ => {
+                Self {
+                    level,
+                    previous: None,
+                    fade: 1.0,
+                }
+            }
+        }
+    }
+
+    /// Returns true if no level is drawn.
+    #[must_use]
+    pub fn is_hidden(&self) -> bool {
+        self.level.is_none() && (self.previous.is_none() || self.fade >= 1.0)
+    }
+
+    /// Returns the levels drawn with their cross-fade, see `VertexArgs::lod_fade`.
+    #[must_use]
+    pub fn instances(&self) -> SmallVec<[(usize, f32); 2]> {
+        let mut instances = SmallVec::new();
+        if let Some(level) = self.level {
+            instances.push((level, self.fade));
+        }
+        if let Some(previous) = self.previous.filter(|_| self.fade < 1.0) {
+            instances.push((previous, 1.0 + self.fade));
+        }
+        instances
+    }
+}
+
+/// Returns the meshes drawn for an entity with `mesh`, its optional `MeshLod` and the
+/// `LodSelection` of the view, with their cross-fade.
+pub(crate) fn lod_meshes<'a>(
+    mesh: &'a Handle<Mesh>,
+    lod: Option<&'a MeshLod>,
+    selection: Option<&LodSelection>,
+) -> SmallVec<[(&'a Handle<Mesh>, f32); 2]> {
+    match (lod, selection) {
+        (Some(lod), Some(selection)) => {
+            selection
+                .instances()
+                .into_iter()
+                .filter_map(|(level, fade)| Some((lod.meshes.get(level)?, fade)))
+                .collect()
+        }
+        _ => {
+            let mut meshes = SmallVec::new();
+            meshes.push((mesh, 1.0));
+            meshes
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THRESHOLDS: [f32; 3] = [10.0, 20.0, 40.0];
+
+    #[test]
+    fn selects_first_level_within_threshold() {
+        let select = |value| select_level(LodMetric::Distance, &THRESHOLDS, 0.1, value, None);
+        assert_eq!(select(5.0), 0);
+        assert_eq!(select(10.0), 0);
+        assert_eq!(select(15.0), 1);
+        assert_eq!(select(39.0), 2);
+        assert_eq!(select(41.0), 3);
+
+        let select = |value| select_level(LodMetric::ScreenSize, &[0.5, 0.1], 0.1, value, None);
+        assert_eq!(select(0.8), 0);
+        assert_eq!(select(0.2), 1);
+        assert_eq!(select(0.05), 2);
+        assert_eq!(
+            select_level(LodMetric::ScreenSize, &[0.5, 0.0], 0.1, 0.0, None),
+            1
+        );
+    }
+
+    #[test]
+    fn hysteresis_keeps_current_level() {
+        let select =
+            |value, current| select_level(LodMetric::Distance, &THRESHOLDS, 0.1, value, current);
+        // Moving away, the level changes 10% past the threshold.
+        assert_eq!(select(10.5, Some(0)), 0);
+        assert_eq!(select(11.5, Some(0)), 1);
+        // Coming back, the level changes 10% before the threshold.
+        assert_eq!(select(9.5, Some(1)), 1);
+        assert_eq!(select(8.5, Some(1)), 0);
+        // Culled entities come back 10% before the last threshold.
+        assert_eq!(select(38.0, Some(3)), 3);
+        assert_eq!(select(35.0, Some(3)), 2);
+        // Jumps further than a level ignore the hysteresis.
+        assert_eq!(select(30.0, Some(0)), 2);
+    }
+
+    #[test]
+    fn cross_fade_between_levels() {
+        let first = LodSelection::next(None, Some(0), Some(0.5), 0.1);
+        assert_eq!(first.instances().as_slice(), &[(0, 1.0)]);
+
+        let switched = LodSelection::next(Some(&first), Some(1), Some(0.5), 0.1);
+        assert_eq!(switched.previous, Some(0));
+        assert!((switched.fade - 0.2).abs() < 1e-6);
+        assert_eq!(switched.instances().len(), 2);
+        assert!(!switched.is_hidden());
+
+        let done = LodSelection::next(Some(&switched), Some(1), Some(0.5), 0.5);
+        assert_eq!(done.instances().as_slice(), &[(1, 1.0)]);
+
+        let culled = LodSelection::next(Some(&done), None, Some(0.5), 0.1);
+        assert_eq!(culled.instances().len(), 1);
+        assert!(!culled.is_hidden());
+        assert!(LodSelection::next(Some(&culled), None, Some(0.5), 0.5).is_hidden());
+
+        let immediate = LodSelection::next(Some(&first), Some(1), None, 0.1);
+        assert_eq!(immediate.instances().as_slice(), &[(1, 1.0)]);
+    }
+}
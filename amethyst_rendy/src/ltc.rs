@@ -0,0 +1,390 @@
+//! Linearly transformed cosine tables, used to shade area lights.
+//!
+//! A linearly transformed cosine (LTC) is a cosine distribution whose directions are transformed
+//! by a 3x3 matrix. Its integral over a polygon has a closed form, which is used to integrate the
+//! GGX specular lobe over the shape of area lights, see [this paper][ltc]. The matrices fitted to
+//! GGX for each roughness and view angle are precomputed and sampled by the shaders.
+//!
+//! [ltc]: https://eheitzresearch.wordpress.com/415-2/
+
+/// Width and height in texels of both tables.
+pub(crate) const LTC_SIZE: u32 = 64;
+
+/// Both tables as `Rgba16Sfloat` texels, row after row, the first table followed by the second.
+///
+/// The tables are indexed by `(roughness, sqrt(1 - dot(normal, view)))`, the second table is
+/// indexed by `(z * 0.5 + 0.5, length)` in its last channel, see `header/area_light.frag`:
+///
+/// * the first table holds the entries `(0, 0)`, `(2, 0)`, `(0, 2)` and `(2, 2)` of the inverse
+///   LTC matrix, with its entry `(1, 1)` normalized to 1 and all others zero,
+/// * the first two channels of the second table are the integrals of the BRDF weighted by the
+///   two terms of Schlick's Fresnel approximation, `1 - (1 - VdotH)^5` and `(1 - VdotH)^5`,
+/// * the last channel of the second table is the horizon clipped form factor of a sphere, divided
+///   by its unclipped form factor `length`, for a sphere centered at an elevation cosine `z`.
+static LTC_TABLES: &[u8] = include_bytes!("../compiled/ltc_ggx.bin");
+
+/// Returns the texels of the first table.
+pub(crate) fn ltc_1() -> &'static [u8] {
+    &LTC_TABLES[..LTC_TABLES.len() / 2]
+}
+
+/// Returns the texels of the second table.
+pub(crate) fn ltc_2() -> &'static [u8] {
+    &LTC_TABLES[LTC_TABLES.len() / 2..]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use amethyst_core::math::{Matrix3, Vector3};
+
+    use super::*;
+
+    const SIZE: usize = LTC_SIZE as usize;
+    const SAMPLES: usize = 32;
+
+    fn texel(table: &[u8], x: usize, y: usize) -> [f32; 4] {
+        let mut texel = [0.0; 4];
+        for (channel, value) in texel.iter_mut().enumerate() {
+            let offset = ((y * SIZE + x) * 4 + channel) * 2;
+            *value = half_to_f32(u16::from_le_bytes([table[offset], table[offset + 1]]));
+        }
+        texel
+    }
+
+    fn half_to_f32(half: u16) -> f32 {
+        let sign = if half & 0x8000 == 0 { 1.0 } else { -1.0 };
+        let exponent = i32::from((half >> 10) & 0x1f);
+        let mantissa = f32::from(half & 0x3ff);
+        match exponent {
+            0 => sign * mantissa * 2f32.powi(-24),
+            31 => sign * f32::INFINITY,
+            _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+        }
+    }
+
+    fn f32_to_half(value: f32) -> u16 {
+        let bits = value.to_bits();
+        let sign = ((bits >> 16) & 0x8000) as u16;
+        let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+        let mantissa = bits & 0x7f_ffff;
+        if exponent >= 31 {
+            return sign | 0x7bff;
+        }
+        if exponent <= 0 {
+            if exponent < -10 {
+                return sign;
+            }
+            let mantissa = (mantissa | 0x80_0000) >> (1 - exponent);
+            return sign | ((mantissa + 0x1000) >> 13) as u16;
+        }
+        let half = ((exponent as u32) << 10) | (mantissa >> 13);
+        sign | (half + ((mantissa >> 12) & 1)).min(0x7bff) as u16
+    }
+
+    #[test]
+    fn tables_match_known_values() {
+        assert_eq!(ltc_1().len(), SIZE * SIZE * 8);
+        assert_eq!(ltc_2().len(), SIZE * SIZE * 8);
+
+        // At normal incidence the lobe is symmetric around the normal.
+        for roughness in 0..SIZE {
+            let [m00, m20, m02, _] = texel(ltc_1(), roughness, 0);
+            assert!((m00 - 1.0).abs() < 1e-3 && m20.abs() < 1e-3 && m02.abs() < 1e-3);
+        }
+        // The albedo of GGX with a roughness of 1 at normal incidence is `1 - ln(2)`.
+        let [weighted, fresnel, _, _] = texel(ltc_2(), SIZE - 1, 0);
+        assert!((weighted + fresnel - (1.0 - 2f32.ln())).abs() < 1e-2);
+        // A sphere entirely above the horizon isn't clipped, its scale is `z`.
+        for z in SIZE / 2..SIZE {
+            let sphere = texel(ltc_2(), z, 0)[3];
+            assert!((sphere - (2.0 * z as f32 / (SIZE - 1) as f32 - 1.0)).abs() < 1e-2);
+        }
+        assert!(texel(ltc_2(), 0, SIZE / 2)[3].abs() < 1e-3);
+    }
+
+    /// GGX with Smith's height correlated masking and shadowing, without Fresnel, times the
+    /// cosine of `light`. Returns the value and the pdf of `light` sampled by `sample_ggx`.
+    fn ggx(view: &Vector3<f64>, light: &Vector3<f64>, alpha: f64) -> (f64, f64) {
+        if view.z <= 0.0 {
+            return (0.0, 0.0);
+        }
+        let half = (view + light).normalize();
+        let alpha2 = alpha * alpha;
+        let d = alpha2 / (PI * (half.z * half.z * (alpha2 - 1.0) + 1.0).powi(2));
+        let pdf = d * half.z / (4.0 * view.dot(&half)).max(1e-12);
+        if light.z <= 0.0 {
+            return (0.0, pdf.max(0.0));
+        }
+        let lambda = |cos: f64| {
+            let tan2 = (1.0 - cos * cos).max(0.0) / (cos * cos);
+            ((1.0 + alpha2 * tan2).sqrt() - 1.0) / 2.0
+        };
+        let g2 = 1.0 / (1.0 + lambda(view.z) + lambda(light.z));
+        (d * g2 / (4.0 * view.z), pdf)
+    }
+
+    fn sample_ggx(view: &Vector3<f64>, alpha: f64, u1: f64, u2: f64) -> Vector3<f64> {
+        let phi = 2.0 * PI * u1;
+        let r = alpha * (u2 / (1.0 - u2)).sqrt();
+        let half = Vector3::new(r * phi.cos(), r * phi.sin(), 1.0).normalize();
+        2.0 * half * half.dot(view) - view
+    }
+
+    fn samples(count: usize) -> impl Iterator<Item = (f64, f64)> {
+        (0..count * count).map(move |i| {
+            (
+                ((i % count) as f64 + 0.5) / count as f64,
+                ((i / count) as f64 + 0.5) / count as f64,
+            )
+        })
+    }
+
+    /// Returns the integrals of the BRDF weighted by the Fresnel terms, and its average direction.
+    fn average_terms(view: &Vector3<f64>, alpha: f64) -> (f64, f64, Vector3<f64>) {
+        let count = SAMPLES * 4;
+        let mut weighted = 0.0;
+        let mut fresnel = 0.0;
+        let mut direction = Vector3::zeros();
+        for (u1, u2) in samples(count) {
+            let light = sample_ggx(view, alpha, u1, u2);
+            let (value, pdf) = ggx(view, &light, alpha);
+            if pdf > 0.0 {
+                let weight = value / pdf;
+                let schlick = (1.0 - view.dot(&(view + light).normalize()).max(0.0)).powi(5);
+                weighted += weight * (1.0 - schlick);
+                fresnel += weight * schlick;
+                direction += light * weight;
+            }
+        }
+        direction.y = 0.0;
+        let count = (count * count) as f64;
+        (weighted / count, fresnel / count, direction.normalize())
+    }
+
+    #[derive(Clone, Copy)]
+    struct Ltc {
+        m11: f64,
+        m22: f64,
+        m13: f64,
+        basis: Matrix3<f64>,
+        amplitude: f64,
+    }
+
+    impl Ltc {
+        fn matrix(&self) -> Matrix3<f64> {
+            self.basis * Matrix3::new(self.m11, 0.0, self.m13, 0.0, self.m22, 0.0, 0.0, 0.0, 1.0)
+        }
+
+        fn eval(&self, inverse: &Matrix3<f64>, determinant: f64, light: &Vector3<f64>) -> f64 {
+            let original = inverse * light;
+            let length = original.norm();
+            let cosine = (original.z / length).max(0.0) / PI;
+            self.amplitude * cosine * determinant / (length * length * length)
+        }
+
+        fn sample(&self, matrix: &Matrix3<f64>, u1: f64, u2: f64) -> Vector3<f64> {
+            let (sin, cos) = ((1.0 - u1).sqrt(), u1.sqrt());
+            let phi = 2.0 * PI * u2;
+            (matrix * Vector3::new(sin * phi.cos(), sin * phi.sin(), cos)).normalize()
+        }
+
+        /// Difference with the BRDF, sampling both distributions.
+        fn error(&self, view: &Vector3<f64>, alpha: f64) -> f64 {
+            let matrix = self.matrix();
+            let inverse = match matrix.try_inverse() {
+                Some(inverse) => inverse,
+                None => return f64::INFINITY,
+            };
+            let determinant = inverse.determinant().abs();
+            let mut error = 0.0;
+            for (u1, u2) in samples(SAMPLES) {
+                for light in &[
+                    self.sample(&matrix, u1, u2),
+                    sample_ggx(view, alpha, u1, u2),
+                ] {
+                    let (brdf, brdf_pdf) = ggx(view, light, alpha);
+                    let ltc = self.eval(&inverse, determinant, light);
+                    let ltc_pdf = ltc / self.amplitude;
+                    if ltc_pdf + brdf_pdf > 0.0 {
+                        error += (brdf - ltc).abs().powi(3) / (ltc_pdf + brdf_pdf);
+                    }
+                }
+            }
+            error / (SAMPLES * SAMPLES) as f64
+        }
+    }
+
+    fn nelder_mead(start: &[f64], step: f64, f: impl Fn(&[f64]) -> f64) -> Vec<f64> {
+        let n = start.len();
+        let mut simplex = vec![start.to_vec()];
+        for i in 0..n {
+            let mut point = start.to_vec();
+            point[i] += step;
+            simplex.push(point);
+        }
+        let mut values: Vec<f64> = simplex.iter().map(|point| f(point)).collect();
+        for _ in 0..200 {
+            let mut order: Vec<usize> = (0..=n).collect();
+            order.sort_by(|a, b| values[*a].partial_cmp(&values[*b]).unwrap());
+            simplex = order.iter().map(|i| simplex[*i].clone()).collect();
+            values = order.iter().map(|i| values[*i]).collect();
+            if values[n] - values[0] < 1e-6 * values[0].abs().max(1e-12) {
+                break;
+            }
+            let centroid: Vec<f64> = (0..n)
+                .map(|k| simplex[..n].iter().map(|point| point[k]).sum::<f64>() / n as f64)
+                .collect();
+            let along = |t: f64| -> Vec<f64> {
+                (0..n)
+                    .map(|k| centroid[k] + t * (simplex[n][k] - centroid[k]))
+                    .collect()
+            };
+            let reflected = along(-1.0);
+            let reflected_value = f(&reflected);
+            if reflected_value < values[0] {
+                let expanded = along(-2.0);
+                let expanded_value = f(&expanded);
+                if expanded_value < reflected_value {
+                    simplex[n] = expanded;
+                    values[n] = expanded_value;
+                } else {
+                    simplex[n] = reflected;
+                    values[n] = reflected_value;
+                }
+            } else if reflected_value < values[n - 1] {
+                simplex[n] = reflected;
+                values[n] = reflected_value;
+            } else {
+                let contracted = along(if reflected_value < values[n] {
+                    -0.5
+                } else {
+                    0.5
+                });
+                let contracted_value = f(&contracted);
+                if contracted_value < values[n].min(reflected_value) {
+                    simplex[n] = contracted;
+                    values[n] = contracted_value;
+                } else {
+                    for i in 1..=n {
+                        simplex[i] = (0..n)
+                            .map(|k| simplex[0][k] + 0.5 * (simplex[i][k] - simplex[0][k]))
+                            .collect();
+                        values[i] = f(&simplex[i]);
+                    }
+                }
+            }
+        }
+        let best = (0..=n)
+            .min_by(|a, b| values[*a].partial_cmp(&values[*b]).unwrap())
+            .unwrap();
+        simplex[best].clone()
+    }
+
+    /// Fits the LTC of GGX for `alpha` and `cos_theta`, starting from the parameters of the
+    /// previous entry. Returns the fit and the Fresnel weighted integrals of the BRDF.
+    fn fit(alpha: f64, cos_theta: f64, previous: Option<[f64; 3]>) -> (Ltc, f64, f64) {
+        let view = Vector3::new((1.0 - cos_theta * cos_theta).sqrt(), 0.0, cos_theta);
+        let (weighted, fresnel, direction) = average_terms(&view, alpha);
+        let isotropic = cos_theta > 0.9999;
+        let basis = if isotropic {
+            Matrix3::identity()
+        } else {
+            Matrix3::from_columns(&[
+                Vector3::new(direction.z, 0.0, -direction.x),
+                Vector3::y(),
+                direction,
+            ])
+        };
+        let ltc = |params: &[f64]| {
+            let m11 = params[0].abs().max(1e-5);
+            Ltc {
+                m11,
+                m22: if isotropic {
+                    m11
+                } else {
+                    params[1].abs().max(1e-5)
+                },
+                m13: if isotropic { 0.0 } else { params[2] },
+                basis,
+                amplitude: weighted + fresnel,
+            }
+        };
+        let start = previous.unwrap_or([alpha.max(0.01), alpha.max(0.01), 0.0]);
+        let start = if isotropic { &start[..1] } else { &start[..] };
+        let params = nelder_mead(start, 0.05, |params| ltc(params).error(&view, alpha));
+        (ltc(&params), weighted, fresnel)
+    }
+
+    /// Horizon clipped form factor of a sphere with the unclipped form factor `length`, centered
+    /// at the elevation cosine `z`, divided by `length`.
+    fn sphere_clipped(z: f64, length: f64) -> f64 {
+        if length <= 0.0 {
+            return z.max(0.0);
+        }
+        let cos_radius = (1.0 - length).max(0.0).sqrt();
+        let center = Vector3::new((1.0 - z * z).max(0.0).sqrt(), 0.0, z);
+        let tangent = Vector3::y();
+        let bitangent = center.cross(&tangent);
+        let count = 512;
+        let mut sum = 0.0;
+        for (u1, u2) in samples(count) {
+            let cos = 1.0 - (1.0 - cos_radius) * u1;
+            let sin = (1.0 - cos * cos).max(0.0).sqrt();
+            let phi = 2.0 * PI * u2;
+            let direction =
+                tangent * (sin * phi.cos()) + bitangent * (sin * phi.sin()) + center * cos;
+            sum += direction.z.max(0.0);
+        }
+        let solid_angle = 2.0 * PI * (1.0 - cos_radius);
+        sum / (count * count) as f64 * solid_angle / PI / length
+    }
+
+    /// Fits the tables and writes them to `compiled/ltc_ggx.bin`. Takes a few minutes with
+    /// `cargo test --release -- --ignored regenerate_tables`.
+    #[test]
+    #[ignore]
+    fn regenerate_tables() {
+        let mut ltc_1 = vec![[0.0; 4]; SIZE * SIZE];
+        let mut ltc_2 = vec![[0.0; 4]; SIZE * SIZE];
+        let last = (SIZE - 1) as f64;
+        for theta in 0..SIZE {
+            // Each fit starts from the fit of the rougher entry.
+            let mut previous = None;
+            for roughness in (0..SIZE).rev() {
+                let alpha = (roughness as f64 / last).powi(2).max(1e-4);
+                let cos_theta = (1.0 - (theta as f64 / last).powi(2)).max(1e-4);
+                let (ltc, weighted, fresnel) = fit(alpha, cos_theta, previous);
+                previous = Some([ltc.m11, ltc.m22, ltc.m13]);
+                let inverse = ltc.matrix().try_inverse().unwrap();
+                let inverse = inverse / inverse[(1, 1)];
+                ltc_1[theta * SIZE + roughness] = [
+                    inverse[(0, 0)],
+                    inverse[(2, 0)],
+                    inverse[(0, 2)],
+                    inverse[(2, 2)],
+                ];
+                ltc_2[theta * SIZE + roughness] = [weighted, fresnel, 0.0, 0.0];
+            }
+        }
+        for length in 0..SIZE {
+            for z in 0..SIZE {
+                ltc_2[length * SIZE + z][3] =
+                    sphere_clipped(2.0 * z as f64 / last - 1.0, length as f64 / last);
+            }
+        }
+
+        let bytes: Vec<u8> = ltc_1
+            .iter()
+            .chain(&ltc_2)
+            .flat_map(|texel| texel.iter())
+            .flat_map(|value| f32_to_half(*value as f32).to_le_bytes().to_vec())
+            .collect();
+        std::fs::write(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/compiled/ltc_ggx.bin"),
+            bytes,
+        )
+        .unwrap();
+    }
+}
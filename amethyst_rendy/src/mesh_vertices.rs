@@ -0,0 +1,502 @@
+//! CPU copy of the vertices of a mesh, used for picking and static batching.
+//!
+//! The vertices of a loaded `Mesh` only live on the GPU. A [`MeshVertices`] component keeps the
+//! vertices of the mesh of an entity, or a simplified version of them, for the features which need
+//! them on the CPU: [`picking`](crate::picking) tests its triangles and
+//! [`static_batch`](crate::static_batch) merges it with the meshes of other static entities.
+//!
+//! ```
+//! use amethyst::renderer::{mesh_vertices::MeshVertices, shape::Shape};
+//!
+//! let cube = MeshVertices::from_shape(&Shape::Cube, None);
+//! assert_eq!(cube.triangle_count(), 12);
+//! ```
+//!
+//! The vertices of a loaded mesh are read from its `MeshData` with
+//! [`MeshVertices::from_mesh_data`], and the glTF importer attaches them to the mesh entities of a
+//! scene with its `mesh_vertices` option.
+
+use std::cmp::Ordering;
+
+use amethyst_assets::prefab::{register_component_type, serde_diff, SerdeDiff};
+use amethyst_core::{
+    geometry::Ray,
+    math::{Matrix3, Matrix4, Point3, Vector3, Vector4},
+};
+use amethyst_error::{format_err, Error};
+use rendy::{
+    hal::IndexType,
+    mesh::{AsVertex, MeshBuilder, Normal, Position, Tangent, TexCoord, VertexFormat},
+};
+use serde::{Deserialize, Serialize};
+use type_uuid::TypeUuid;
+
+use crate::{shape::Shape, spatial::Aabb, types::MeshData, visibility::BoundingSphere};
+
+/// Vertices of a mesh in the local space of its entity.
+///
+/// The attribute lists are either empty or as long as `positions`. Without indices, every three
+/// vertices form a triangle.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, TypeUuid, SerdeDiff)]
+#[serde(default)]
+#[uuid = "0d4d5d3e-8a4c-4f57-9c1b-52f7a1e3b6c9"]
+pub struct MeshVertices {
+    /// Positions of the vertices.
+    #[serde_diff(opaque)]
+    pub positions: Vec<[f32; 3]>,
+    /// Normals of the vertices.
+    #[serde_diff(opaque)]
+    pub normals: Vec<[f32; 3]>,
+    /// Tangents of the vertices, with the handedness of the bitangent in `w`.
+    #[serde_diff(opaque)]
+    pub tangents: Vec<[f32; 4]>,
+    /// Texture coordinates of the vertices.
+    #[serde_diff(opaque)]
+    pub tex_coords: Vec<[f32; 2]>,
+    /// Indices of the vertices of the triangles.
+    #[serde_diff(opaque)]
+    pub indices: Vec<u32>,
+}
+
+register_component_type!(MeshVertices);
+
+impl MeshVertices {
+    /// Generates the vertices of `shape`, with all the attributes.
+    #[must_use]
+    pub fn from_shape(shape: &Shape, scale: Option<(f32, f32, f32)>) -> Self {
+        let (positions, normals, tangents, tex_coords) =
+            shape.generate_vertices::<(Vec<Position>, Vec<Normal>, Vec<Tangent>, Vec<TexCoord>)>(
+                scale,
+            );
+        Self {
+            positions: positions.into_iter().map(|position| position.0).collect(),
+            normals: normals.into_iter().map(|normal| normal.0).collect(),
+            tangents: tangents.into_iter().map(|tangent| tangent.0).collect(),
+            tex_coords: tex_coords
+                .into_iter()
+                .map(|tex_coord| tex_coord.0)
+                .collect(),
+            indices: Vec::new(),
+        }
+    }
+
+    /// Reads the positions, normals, tangents, texture coordinates and indices of `builder`.
+    ///
+    /// Each attribute must have its own vertex buffer, as in the meshes of the glTF and OBJ
+    /// importers. Other vertex buffers, like colors or interleaved attributes, are left out.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the mesh has no positions, or an attribute has another number of vertices.
+    pub fn from_mesh_builder(builder: &MeshBuilder<'_>) -> Result<Self, Error> {
+        // The vertex buffers of a `MeshBuilder` are private, they are read from its serialized
+        // form.
+        let mesh: SerializedMesh = bincode::serialize(builder)
+            .and_then(|bytes| bincode::deserialize(&bytes))
+            .map_err(|e| format_err!("Could not read the mesh vertices: {}", e))?;
+
+        let mut vertices = Self::default();
+        for buffer in &mesh.vertices {
+            let values = buffer.floats();
+            if buffer.format == Position::vertex() {
+                vertices.positions = values.chunks_exact(3).map(|v| [v[0], v[1], v[2]]).collect();
+            } else if buffer.format == Normal::vertex() {
+                vertices.normals = values.chunks_exact(3).map(|v| [v[0], v[1], v[2]]).collect();
+            } else if buffer.format == Tangent::vertex() {
+                vertices.tangents = values
+                    .chunks_exact(4)
+                    .map(|v| [v[0], v[1], v[2], v[3]])
+                    .collect();
+            } else if buffer.format == TexCoord::vertex() {
+                vertices.tex_coords = values.chunks_exact(2).map(|v| [v[0], v[1]]).collect();
+            }
+        }
+        if let Some(indices) = &mesh.indices {
+            vertices.indices = match indices.index_type {
+                IndexType::U16 => {
+                    indices
+                        .indices
+                        .chunks_exact(2)
+                        .map(|bytes| u32::from(u16::from_ne_bytes([bytes[0], bytes[1]])))
+                        .collect()
+                }
+                IndexType::U32 => {
+                    indices
+                        .indices
+                        .chunks_exact(4)
+                        .map(|bytes| u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                        .collect()
+                }
+            };
+        }
+
+        let len = vertices.len();
+        if len == 0 {
+            return Err(format_err!("The mesh has no positions"));
+        }
+        let lengths = [
+            vertices.normals.len(),
+            vertices.tangents.len(),
+            vertices.tex_coords.len(),
+        ];
+        if lengths.iter().any(|&n| n != 0 && n != len) {
+            return Err(format_err!(
+                "The attributes of the mesh don't all have {} vertices",
+                len
+            ));
+        }
+        Ok(vertices)
+    }
+
+    /// Reads the vertices of a mesh asset, see [`MeshVertices::from_mesh_builder`].
+    ///
+    /// # Errors
+    ///
+    /// Fails if the mesh has no positions, or an attribute has another number of vertices.
+    pub fn from_mesh_data(data: &MeshData) -> Result<Self, Error> {
+        Self::from_mesh_builder(&data.0)
+    }
+
+    /// Number of vertices.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Returns true if there are no vertices.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Number of triangles.
+    #[must_use]
+    pub fn triangle_count(&self) -> usize {
+        if self.indices.is_empty() {
+            self.len() / 3
+        } else {
+            self.indices.len() / 3
+        }
+    }
+
+    /// Appends the vertices of `other` transformed by `transform`.
+    ///
+    /// The result is indexed. Attributes missing from some of the merged meshes are filled with
+    /// defaults.
+    pub fn append(&mut self, other: &MeshVertices, transform: &Matrix4<f32>) {
+        let base = self.len();
+        if self.indices.is_empty() {
+            self.indices = (0..base as u32).collect();
+        }
+
+        let linear = Matrix3::from_fn(|row, column| transform[(row, column)]);
+        let normal_matrix = linear
+            .try_inverse()
+            .map_or_else(Matrix3::identity, |inverse| inverse.transpose());
+        // Mirroring transforms flip the winding of the triangles and the bitangents.
+        let mirrored = linear.determinant() < 0.0;
+
+        self.positions.extend(other.positions.iter().map(|p| {
+            let position = transform * Vector4::new(p[0], p[1], p[2], 1.0);
+            [position.x, position.y, position.z]
+        }));
+        if !other.normals.is_empty() {
+            pad(&mut self.normals, base, [0.0, 1.0, 0.0]);
+            self.normals.extend(other.normals.iter().map(|n| {
+                let normal = normalize(normal_matrix * Vector3::from(*n));
+                [normal.x, normal.y, normal.z]
+            }));
+        }
+        if !other.tangents.is_empty() {
+            pad(&mut self.tangents, base, [1.0, 0.0, 0.0, 1.0]);
+            self.tangents.extend(other.tangents.iter().map(|t| {
+                let tangent = normalize(linear * Vector3::new(t[0], t[1], t[2]));
+                let handedness = if mirrored { -t[3] } else { t[3] };
+                [tangent.x, tangent.y, tangent.z, handedness]
+            }));
+        }
+        if !other.tex_coords.is_empty() {
+            pad(&mut self.tex_coords, base, [0.0, 0.0]);
+            self.tex_coords.extend_from_slice(&other.tex_coords);
+        }
+
+        let start = self.indices.len();
+        if other.indices.is_empty() {
+            self.indices
+                .extend((0..other.len() as u32).map(|index| base as u32 + index));
+        } else {
+            self.indices
+                .extend(other.indices.iter().map(|index| base as u32 + index));
+        }
+        if mirrored {
+            for triangle in self.indices[start..].chunks_exact_mut(3) {
+                triangle.swap(1, 2);
+            }
+        }
+
+        let len = self.len();
+        pad_present(&mut self.normals, len, [0.0, 1.0, 0.0]);
+        pad_present(&mut self.tangents, len, [1.0, 0.0, 0.0, 1.0]);
+        pad_present(&mut self.tex_coords, len, [0.0, 0.0]);
+    }
+
+    /// Returns the smallest box containing every vertex.
+    #[must_use]
+    pub fn bounds(&self) -> Option<Aabb> {
+        let mut points = self.positions.iter().map(|p| Point3::from(*p));
+        let first = points.next()?;
+        Some(points.fold(Aabb::new(first, first), |aabb, p| {
+            Aabb::new(aabb.min.inf(&p), aabb.max.sup(&p))
+        }))
+    }
+
+    /// Smallest sphere centered on the bounding box of the vertices which contains them.
+    #[must_use]
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+        let bounds = match self.bounds() {
+            Some(bounds) => bounds,
+            None => return BoundingSphere::origin(0.0),
+        };
+        let center = Point3::from((bounds.min.coords + bounds.max.coords) * 0.5);
+        let radius = self
+            .positions
+            .iter()
+            .map(|p| (Point3::from(*p) - center).norm())
+            .fold(0.0, f32::max);
+        BoundingSphere::new(center, radius)
+    }
+
+    /// Returns the distance along `ray` to the nearest triangle it hits, in units of
+    /// `ray.direction`.
+    #[must_use]
+    pub fn ray_distance(&self, ray: &Ray<f32>) -> Option<f32> {
+        let vertex = |i: usize| -> Option<Point3<f32>> {
+            let index = if self.indices.is_empty() {
+                i
+            } else {
+                *self.indices.get(i)? as usize
+            };
+            self.positions.get(index).copied().map(Point3::from)
+        };
+        (0..self.triangle_count())
+            .filter_map(|triangle| {
+                let i = triangle * 3;
+                ray_triangle_distance(ray, [vertex(i)?, vertex(i + 1)?, vertex(i + 2)?])
+            })
+            .min_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+    }
+}
+
+fn normalize(vector: Vector3<f32>) -> Vector3<f32> {
+    vector.try_normalize(1e-12).unwrap_or(vector)
+}
+
+/// Fills `attribute` with `default` up to `len` elements.
+fn pad<T: Copy>(attribute: &mut Vec<T>, len: usize, default: T) {
+    attribute.resize(len.max(attribute.len()), default);
+}
+
+/// Fills `attribute` with `default` up to `len` elements if some meshes have it.
+fn pad_present<T: Copy>(attribute: &mut Vec<T>, len: usize, default: T) {
+    if !attribute.is_empty() {
+        pad(attribute, len, default);
+    }
+}
+
+/// Möller–Trumbore intersection, hitting both sides of the triangle.
+fn ray_triangle_distance(ray: &Ray<f32>, [v0, v1, v2]: [Point3<f32>; 3]) -> Option<f32> {
+    let edge1 = v1 - v0;
+    let edge2 = v2 - v0;
+    let p_vec = ray.direction.cross(&edge2);
+    let determinant = edge1.dot(&p_vec);
+    if determinant.abs() <= f32::EPSILON {
+        return None;
+    }
+    let inverse = 1.0 / determinant;
+    let to_origin = ray.origin - v0;
+    let u = to_origin.dot(&p_vec) * inverse;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q_vec = to_origin.cross(&edge1);
+    let v = ray.direction.dot(&q_vec) * inverse;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let distance = edge2.dot(&q_vec) * inverse;
+    (distance >= 0.0).then_some(distance)
+}
+
+/// Serialized form of a `MeshBuilder`, with the fields in the same order.
+#[derive(Deserialize)]
+struct SerializedMesh {
+    vertices: Vec<SerializedVertices>,
+    indices: Option<SerializedIndices>,
+}
+
+#[derive(Deserialize)]
+struct SerializedVertices {
+    vertices: Vec<u8>,
+    format: VertexFormat,
+}
+
+impl SerializedVertices {
+    fn floats(&self) -> Vec<f32> {
+        self.vertices
+            .chunks_exact(4)
+            .map(|bytes| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect()
+    }
+}
+
+#[derive(Deserialize)]
+struct SerializedIndices {
+    indices: Vec<u8>,
+    index_type: IndexType,
+}
+
+impl From<MeshVertices> for MeshBuilder<'static> {
+    fn from(vertices: MeshVertices) -> Self {
+        let mut builder = MeshBuilder::new().with_vertices(
+            vertices
+                .positions
+                .into_iter()
+                .map(Position)
+                .collect::<Vec<_>>(),
+        );
+        if !vertices.normals.is_empty() {
+            builder =
+                builder.with_vertices(vertices.normals.into_iter().map(Normal).collect::<Vec<_>>());
+        }
+        if !vertices.tangents.is_empty() {
+            builder = builder.with_vertices(
+                vertices
+                    .tangents
+                    .into_iter()
+                    .map(Tangent)
+                    .collect::<Vec<_>>(),
+            );
+        }
+        if !vertices.tex_coords.is_empty() {
+            builder = builder.with_vertices(
+                vertices
+                    .tex_coords
+                    .into_iter()
+                    .map(TexCoord)
+                    .collect::<Vec<_>>(),
+            );
+        }
+        if !vertices.indices.is_empty() {
+            builder = builder.with_indices(vertices.indices);
+        }
+        builder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle() -> MeshVertices {
+        MeshVertices {
+            positions: vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+            normals: vec![[0.0, 0.0, 1.0]; 3],
+            tangents: vec![[1.0, 0.0, 0.0, 1.0]; 3],
+            tex_coords: vec![[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]],
+            indices: Vec::new(),
+        }
+    }
+
+    fn ray(origin: [f32; 3], direction: [f32; 3]) -> Ray<f32> {
+        Ray {
+            origin: Point3::from(origin),
+            direction: Vector3::from(direction).normalize(),
+        }
+    }
+
+    #[test]
+    fn append_transforms_and_offsets_indices() {
+        let mut batch = MeshVertices::default();
+        batch.append(&triangle(), &Matrix4::identity());
+        batch.append(
+            &triangle(),
+            &Matrix4::new_translation(&Vector3::new(0.0, 0.0, 5.0)),
+        );
+
+        assert_eq!(batch.len(), 6);
+        assert_eq!(batch.indices, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(batch.positions[4], [1.0, 0.0, 5.0]);
+        assert_eq!(batch.normals[4], [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn append_mirrored_flips_winding_and_handedness() {
+        let mut batch = MeshVertices::default();
+        batch.append(
+            &triangle(),
+            &Matrix4::new_nonuniform_scaling(&Vector3::new(-1.0, 1.0, 1.0)),
+        );
+
+        assert_eq!(batch.indices, vec![0, 2, 1]);
+        assert_eq!(batch.positions[1], [-1.0, 0.0, 0.0]);
+        assert_eq!(batch.tangents[0], [-1.0, 0.0, 0.0, -1.0]);
+    }
+
+    #[test]
+    fn append_fills_missing_attributes() {
+        let mut bare = triangle();
+        bare.normals.clear();
+        bare.tex_coords.clear();
+        let mut batch = MeshVertices::default();
+        batch.append(&bare, &Matrix4::identity());
+        batch.append(&triangle(), &Matrix4::identity());
+
+        assert_eq!(batch.normals.len(), 6);
+        assert_eq!(batch.normals[0], [0.0, 1.0, 0.0]);
+        assert_eq!(batch.tex_coords.len(), 6);
+    }
+
+    #[test]
+    fn mesh_builders_convert_back() {
+        let mut vertices = triangle();
+        vertices.indices = vec![0, 2, 1];
+        let builder = MeshBuilder::from(vertices.clone());
+        assert_eq!(MeshVertices::from_mesh_builder(&builder).unwrap(), vertices);
+
+        let cube = MeshVertices::from_shape(&Shape::Cube, None);
+        let data = MeshData(MeshBuilder::from(cube.clone()));
+        assert_eq!(MeshVertices::from_mesh_data(&data).unwrap(), cube);
+
+        assert!(MeshVertices::from_mesh_builder(&MeshBuilder::new()).is_err());
+    }
+
+    #[test]
+    fn bounding_sphere_contains_vertices() {
+        let sphere = triangle().bounding_sphere();
+        assert_eq!(sphere.center, Point3::new(0.5, 0.5, 0.0));
+        assert!((sphere.radius - 0.5_f32.sqrt()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn ray_hits_nearest_triangle() {
+        let cube = MeshVertices::from_shape(&Shape::Cube, None);
+        let distance = cube
+            .ray_distance(&ray([0.5, 0.25, 5.0], [0.0, 0.0, -1.0]))
+            .unwrap();
+        assert!((distance - 4.0).abs() < 1e-5);
+        assert_eq!(
+            cube.ray_distance(&ray([0.5, 2.0, 5.0], [0.0, 0.0, -1.0])),
+            None
+        );
+        let bounds = cube.bounds().unwrap();
+        assert_eq!(bounds.min, Point3::new(-1.0, -1.0, -1.0));
+        assert_eq!(bounds.max, Point3::new(1.0, 1.0, 1.0));
+
+        // Indexed triangles use the positions they point to.
+        let mut indexed = MeshVertices::default();
+        indexed.append(&triangle(), &Matrix4::identity());
+        assert!(indexed
+            .ray_distance(&ray([0.25, 0.25, 1.0], [0.0, 0.0, -1.0]))
+            .is_some());
+    }
+}
@@ -8,7 +8,7 @@ use rendy::{
     factory::Factory,
     graph::{
         render::{PrepareResult, RenderGroup, RenderGroupDesc},
-        GraphContext, NodeBuffer, NodeImage,
+        GraphContext, ImageAccess, NodeBuffer, NodeImage,
     },
     hal::{self, device::Device, pso},
     mesh::{AsVertex, VertexFormat},
@@ -17,19 +17,21 @@ use rendy::{
 use smallvec::SmallVec;
 
 use crate::{
-    batch,
     batch::{GroupIterator, OrderedTwoLevelBatch, TwoLevelBatch},
+    bundle::Target,
+    camera::Viewport,
+    lod::{lod_meshes, MeshLod},
     mtl::{FullTextureSet, Material, StaticTextureSet},
     pass,
     pipeline::{PipelineDescBuilder, PipelinesBuilder},
     pod::{SkinnedVertexArgs, VertexArgs},
-    resources::Tint,
+    resources::{InstanceData, Tint},
     skinning::JointTransforms,
     submodules::{DynamicVertexBuffer, EnvironmentSub, MaterialId, MaterialSub, SkinningSub},
     system::GraphAuxData,
     types::{Backend, Mesh},
     util,
-    visibility::Visibility,
+    visibility::{views_for_target, Visibility},
 };
 
 macro_rules! profile_scope_impl {
@@ -73,6 +75,8 @@ pub trait Base3DPassDef: 'static + std::fmt::Debug + Send + Sync {
 #[derivative(Debug(bound = ""), Default(bound = ""))]
 pub struct DrawBase3DDesc<B: Backend, T: Base3DPassDef> {
     skinning: bool,
+    shadows: bool,
+    target: Target,
     marker: PhantomData<(B, T)>,
 }
 
@@ -88,6 +92,8 @@ impl<B: Backend, T: Base3DPassDef> DrawBase3DDesc<B, T> {
     pub fn skinned() -> Self {
         Self {
             skinning: true,
+            shadows: false,
+            target: Target::Main,
             marker: PhantomData,
         }
     }
@@ -98,29 +104,53 @@ impl<B: Backend, T: Base3DPassDef> DrawBase3DDesc<B, T> {
         self.skinning = skinned;
         self
     }
+
+    /// Draw the meshes seen by the cameras rendering to `target`, `Target::Main` by default.
+    #[must_use]
+    pub fn with_target(mut self, target: Target) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// Sample the shadow atlas if true is passed. The depth image of `Target::ShadowMap` must
+    /// then be added to the render group builder with `with_image`.
+    #[must_use]
+    pub fn with_shadows(mut self, shadows: bool) -> Self {
+        self.shadows = shadows;
+        self
+    }
 }
 
 impl<B: Backend, T: Base3DPassDef> RenderGroupDesc<B, GraphAuxData> for DrawBase3DDesc<B, T> {
+    fn images(&self) -> Vec<ImageAccess> {
+        shadow_map_access(self.shadows)
+    }
+
     fn build(
         self,
-        _ctx: &GraphContext<B>,
+        ctx: &GraphContext<B>,
         factory: &mut Factory<B>,
-        _queue: QueueId,
+        queue: QueueId,
         _aux: &GraphAuxData,
         framebuffer_width: u32,
         framebuffer_height: u32,
         subpass: hal::pass::Subpass<'_, B>,
         _buffers: Vec<NodeBuffer>,
-        _images: Vec<NodeImage>,
+        images: Vec<NodeImage>,
     ) -> Result<Box<dyn RenderGroup<B, GraphAuxData>>, pso::CreationError> {
         profile_scope_impl!("build");
 
         let env = EnvironmentSub::new(
             factory,
+            queue,
             [
                 hal::pso::ShaderStageFlags::VERTEX,
                 hal::pso::ShaderStageFlags::FRAGMENT,
             ],
+            images
+                .first()
+                .and_then(|image| ctx.get_image(image.id))
+                .cloned(),
         )?;
         let materials = MaterialSub::new(factory)?;
         let skinning = SkinningSub::new(factory)?;
@@ -148,11 +178,15 @@ impl<B: Backend, T: Base3DPassDef> RenderGroupDesc<B, GraphAuxData> for DrawBase
         vertex_format_skinned.sort();
 
         Ok(Box::new(DrawBase3D::<B, T> {
+            target: self.target,
             pipeline_basic: pipelines.remove(0),
             pipeline_skinned: pipelines.pop(),
             pipeline_layout,
-            static_batches: batch::TwoLevelBatch::default(),
-            skinned_batches: batch::TwoLevelBatch::default(),
+            static_batches: Vec::new(),
+            skinned_batches: Vec::new(),
+            viewports: Vec::new(),
+            framebuffer_width,
+            framebuffer_height,
             vertex_format_base,
             vertex_format_skinned,
             env,
@@ -170,11 +204,15 @@ impl<B: Backend, T: Base3DPassDef> RenderGroupDesc<B, GraphAuxData> for DrawBase
 #[derive(Derivative)]
 #[derivative(Debug(bound = ""))]
 pub struct DrawBase3D<B: Backend, T: Base3DPassDef> {
+    target: Target,
     pipeline_basic: B::GraphicsPipeline,
     pipeline_skinned: Option<B::GraphicsPipeline>,
     pipeline_layout: B::PipelineLayout,
-    static_batches: TwoLevelBatch<MaterialId, LoadHandle, SmallVec<[VertexArgs; 4]>>,
-    skinned_batches: TwoLevelBatch<MaterialId, LoadHandle, SmallVec<[SkinnedVertexArgs; 4]>>,
+    static_batches: Vec<TwoLevelBatch<MaterialId, LoadHandle, SmallVec<[VertexArgs; 4]>>>,
+    skinned_batches: Vec<TwoLevelBatch<MaterialId, LoadHandle, SmallVec<[SkinnedVertexArgs; 4]>>>,
+    viewports: Vec<Viewport>,
+    framebuffer_width: u32,
+    framebuffer_height: u32,
     vertex_format_base: Vec<VertexFormat>,
     vertex_format_skinned: Vec<VertexFormat>,
     env: EnvironmentSub<B>,
@@ -200,117 +238,163 @@ impl<B: Backend, T: Base3DPassDef> RenderGroup<B, GraphAuxData> for DrawBase3D<B
 
         let visibility = resources.get::<Visibility>().unwrap();
         let mesh_storage = resources.get::<AssetStorage<Mesh>>().unwrap();
+        let views = views_for_target(&visibility.views, self.target);
 
         // Prepare environment
-        self.env.process(factory, index, world, resources);
+        self.env.process_views(
+            factory,
+            index,
+            world,
+            resources,
+            views.iter().map(|view| view.camera),
+        );
         self.materials.maintain();
 
-        self.static_batches.clear_inner();
-        self.skinned_batches.clear_inner();
+        self.viewports.clear();
+        self.viewports
+            .extend(views.iter().map(|view| view.viewport));
+        self.static_batches
+            .resize_with(views.len(), TwoLevelBatch::default);
+        self.skinned_batches
+            .resize_with(views.len(), TwoLevelBatch::default);
 
+        let skinning_enabled = self.pipeline_skinned.is_some();
         let materials_ref = &mut self.materials;
         let skinning_ref = &mut self.skinning;
-        let statics_ref = &mut self.static_batches;
-        let skinned_ref = &mut self.skinned_batches;
 
+        for ((statics_ref, skinned_ref), view) in self
+            .static_batches
+            .iter_mut()
+            .zip(self.skinned_batches.iter_mut())
+            .zip(&views)
         {
-            profile_scope_impl!("prepare");
-            let mut query =
-                <(&Handle<Material>, &Handle<Mesh>, &Transform, Option<&Tint>)>::query();
+            statics_ref.clear_inner();
+            skinned_ref.clear_inner();
 
-            visibility
-                .visible_unordered
-                .iter()
-                .filter_map(|entity| Some((entity, query.get(*world, *entity).ok()?)))
-                .map(|(entity, (mat, mesh, tform, tint))| {
-                    // log::debug!("(entity, (mat, mesh, tform, tint))");
-                    if let Some(tint) = tint {
-                        (
-                            (mat, mesh.load_handle()),
-                            VertexArgs::from_object_data(tform, Some(tint)),
-                        )
-                    } else {
-                        (
-                            (mat, mesh.load_handle()),
-                            VertexArgs::from_object_data(tform, None),
-                        )
-                    }
-                })
-                .for_each_group(|(mat, mesh_id), data| {
-                    // log::debug!("mesh_id: {:?}, mat_id: {:?}", mesh_id, mat);
-                    if mesh_storage.contains(mesh_id) {
-                        // log::debug!("if mesh_storage.contains(mesh_id)");
-                        if let Some((mat, _)) = materials_ref.insert(factory, resources, mat) {
-                            // log::debug!("statics_ref.insert(mat, mesh_id, data.drain(..))");
-                            statics_ref.insert(mat, mesh_id, data.drain(..));
+            {
+                profile_scope_impl!("prepare");
+                let mut query = <(
+                    &Handle<Material>,
+                    &Handle<Mesh>,
+                    &Transform,
+                    Option<&Tint>,
+                    Option<&InstanceData>,
+                    Option<&MeshLod>,
+                )>::query();
+
+                // Sorted by material and mesh, so all the instances sharing both are drawn in
+                // one instanced draw call.
+                let mut instances: Vec<_> = view
+                    .visible_unordered
+                    .iter()
+                    .filter_map(|entity| Some((entity, query.get(*world, *entity).ok()?)))
+                    .flat_map(|(entity, (mat, mesh, tform, tint, data, lod))| {
+                        let args =
+                            VertexArgs::from_object_data(tform, tint).with_instance_data(data);
+                        lod_meshes(mesh, lod, view.lods.get(entity))
+                            .into_iter()
+                            .map(move |(mesh, fade)| {
+                                ((mat, mesh.load_handle()), args.with_lod_fade(fade))
+                            })
+                    })
+                    .collect();
+                instances.sort_by_key(|((mat, mesh_id), _)| (mat.load_handle().0, mesh_id.0));
+
+                instances
+                    .into_iter()
+                    .for_each_group(|(mat, mesh_id), data| {
+                        // log::debug!("mesh_id: {:?}, mat_id: {:?}", mesh_id, mat);
+                        if mesh_storage.contains(mesh_id) {
+                            // log::debug!("if mesh_storage.contains(mesh_id)");
+                            if let Some((mat, _)) = materials_ref.insert(factory, resources, mat) {
+                                // log::debug!("statics_ref.insert(mat, mesh_id, data.drain(..))");
+                                statics_ref.insert(mat, mesh_id, data.drain(..));
+                            }
                         }
-                    }
-                });
-        }
-
-        if self.pipeline_skinned.is_some() {
-            profile_scope_impl!("prepare_skinning");
-
-            let mut query = <(
-                &Handle<Material>,
-                &Handle<Mesh>,
-                &Transform,
-                Option<&Tint>,
-                &JointTransforms,
-            )>::query();
+                    });
+            }
 
-            visibility
-                .visible_unordered
-                .iter()
-                .filter_map(|entity| Some((entity, query.get(*world, *entity).ok()?)))
-                .map(|(_, (mat, mesh, tform, tint, joints))| {
-                    if let Some(tint) = tint {
-                        (
-                            (mat, mesh.load_handle()),
-                            SkinnedVertexArgs::from_object_data(
-                                tform,
-                                Some(tint),
-                                skinning_ref.insert(joints),
-                            ),
-                        )
-                    } else {
-                        (
-                            (mat, mesh.load_handle()),
-                            SkinnedVertexArgs::from_object_data(
-                                tform,
-                                None,
-                                skinning_ref.insert(joints),
-                            ),
-                        )
-                    }
-                })
-                .for_each_group(|(mat, mesh_id), data| {
-                    if mesh_storage.contains(mesh_id) {
-                        if let Some((mat, _)) = materials_ref.insert(factory, resources, mat) {
-                            skinned_ref.insert(mat, mesh_id, data.drain(..));
+            if skinning_enabled {
+                profile_scope_impl!("prepare_skinning");
+
+                let mut query = <(
+                    &Handle<Material>,
+                    &Handle<Mesh>,
+                    &Transform,
+                    Option<&Tint>,
+                    &JointTransforms,
+                    Option<&MeshLod>,
+                )>::query();
+
+                view.visible_unordered
+                    .iter()
+                    .filter_map(|entity| {
+                        // Skinned meshes switch level without cross-fade.
+                        let (mat, mesh, tform, tint, joints, lod) =
+                            query.get(*world, *entity).ok()?;
+                        let (mesh, _) = *lod_meshes(mesh, lod, view.lods.get(entity)).first()?;
+                        Some((mat, mesh, tform, tint, joints))
+                    })
+                    .map(|(mat, mesh, tform, tint, joints)| {
+                        if let Some(tint) = tint {
+                            (
+                                (mat, mesh.load_handle()),
+                                SkinnedVertexArgs::from_object_data(
+                                    tform,
+                                    Some(tint),
+                                    skinning_ref.insert(joints),
+                                ),
+                            )
+                        } else {
+                            (
+                                (mat, mesh.load_handle()),
+                                SkinnedVertexArgs::from_object_data(
+                                    tform,
+                                    None,
+                                    skinning_ref.insert(joints),
+                                ),
+                            )
                         }
-                    }
-                });
-        };
+                    })
+                    .for_each_group(|(mat, mesh_id), data| {
+                        if mesh_storage.contains(mesh_id) {
+                            if let Some((mat, _)) = materials_ref.insert(factory, resources, mat) {
+                                skinned_ref.insert(mat, mesh_id, data.drain(..));
+                            }
+                        }
+                    });
+            };
+
+            statics_ref.prune();
+            skinned_ref.prune();
+        }
 
         {
             profile_scope_impl!("write");
 
-            self.static_batches.prune();
-            self.skinned_batches.prune();
-
+            // Instances of every view share one buffer, laid out view after view.
             self.models.write(
                 factory,
                 index,
-                self.static_batches.count() as u64,
-                self.static_batches.data(),
+                self.static_batches
+                    .iter()
+                    .map(TwoLevelBatch::count)
+                    .sum::<usize>() as u64,
+                self.static_batches
+                    .iter()
+                    .flat_map(|batches| batches.data()),
             );
 
             self.skinned_models.write(
                 factory,
                 index,
-                self.skinned_batches.count() as u64,
-                self.skinned_batches.data(),
+                self.skinned_batches
+                    .iter()
+                    .map(TwoLevelBatch::count)
+                    .sum::<usize>() as u64,
+                self.skinned_batches
+                    .iter()
+                    .flat_map(|batches| batches.data()),
             );
             self.skinning.commit(factory, index);
         }
@@ -330,48 +414,23 @@ impl<B: Backend, T: Base3DPassDef> RenderGroup<B, GraphAuxData> for DrawBase3D<B
         let models_loc = self.vertex_format_base.len() as u32;
         let skin_models_loc = self.vertex_format_skinned.len() as u32;
 
-        encoder.bind_graphics_pipeline(&self.pipeline_basic);
-        self.env.bind(index, &self.pipeline_layout, 0, &mut encoder);
-
-        if self.models.bind(index, models_loc, 0, &mut encoder) {
-            let mut instances_drawn = 0;
-            for (&mat_id, batches) in self.static_batches.iter() {
-                if self.materials.loaded(mat_id) {
-                    self.materials
-                        .bind(&self.pipeline_layout, 1, mat_id, &mut encoder);
-                    for (mesh_id, batch_data) in batches {
-                        debug_assert!(mesh_storage.contains(*mesh_id));
-                        if let Some(mesh) = B::unwrap_mesh({
-                            mesh_storage
-                                .get_for_load_handle(*mesh_id)
-                                .expect("Could not get mesh.")
-                        }) {
-                            mesh.bind_and_draw(
-                                0,
-                                &self.vertex_format_base,
-                                instances_drawn..instances_drawn + batch_data.len() as u32,
-                                &mut encoder,
-                            )
-                            .unwrap();
-                        }
-                        instances_drawn += batch_data.len() as u32;
-                    }
-                }
-            }
-        }
-
-        if let Some(pipeline_skinned) = self.pipeline_skinned.as_ref() {
-            encoder.bind_graphics_pipeline(pipeline_skinned);
-
-            if self
-                .skinned_models
-                .bind(index, skin_models_loc, 0, &mut encoder)
-            {
-                self.skinning
-                    .bind(index, &self.pipeline_layout, 2, &mut encoder);
-
-                let mut instances_drawn = 0;
-                for (&mat_id, batches) in self.skinned_batches.iter() {
+        let mut static_offset = 0;
+        let mut skinned_offset = 0;
+        for (view, viewport) in self.viewports.iter().enumerate() {
+            util::set_viewport(
+                &mut encoder,
+                viewport,
+                self.framebuffer_width,
+                self.framebuffer_height,
+            );
+            encoder.bind_graphics_pipeline(&self.pipeline_basic);
+            self.env
+                .bind_view(index, view, &self.pipeline_layout, 0, &mut encoder);
+
+            let static_batches = &self.static_batches[view];
+            if self.models.bind(index, models_loc, 0, &mut encoder) {
+                let mut instances_drawn = static_offset;
+                for (&mat_id, batches) in static_batches.iter() {
                     if self.materials.loaded(mat_id) {
                         self.materials
                             .bind(&self.pipeline_layout, 1, mat_id, &mut encoder);
@@ -384,7 +443,7 @@ impl<B: Backend, T: Base3DPassDef> RenderGroup<B, GraphAuxData> for DrawBase3D<B
                             }) {
                                 mesh.bind_and_draw(
                                     0,
-                                    &self.vertex_format_skinned,
+                                    &self.vertex_format_base,
                                     instances_drawn..instances_drawn + batch_data.len() as u32,
                                     &mut encoder,
                                 )
@@ -395,6 +454,46 @@ impl<B: Backend, T: Base3DPassDef> RenderGroup<B, GraphAuxData> for DrawBase3D<B
                     }
                 }
             }
+            static_offset += static_batches.count() as u32;
+
+            if let Some(pipeline_skinned) = self.pipeline_skinned.as_ref() {
+                encoder.bind_graphics_pipeline(pipeline_skinned);
+
+                let skinned_batches = &self.skinned_batches[view];
+                if self
+                    .skinned_models
+                    .bind(index, skin_models_loc, 0, &mut encoder)
+                {
+                    self.skinning
+                        .bind(index, &self.pipeline_layout, 2, &mut encoder);
+
+                    let mut instances_drawn = skinned_offset;
+                    for (&mat_id, batches) in skinned_batches.iter() {
+                        if self.materials.loaded(mat_id) {
+                            self.materials
+                                .bind(&self.pipeline_layout, 1, mat_id, &mut encoder);
+                            for (mesh_id, batch_data) in batches {
+                                debug_assert!(mesh_storage.contains(*mesh_id));
+                                if let Some(mesh) = B::unwrap_mesh({
+                                    mesh_storage
+                                        .get_for_load_handle(*mesh_id)
+                                        .expect("Could not get mesh.")
+                                }) {
+                                    mesh.bind_and_draw(
+                                        0,
+                                        &self.vertex_format_skinned,
+                                        instances_drawn..instances_drawn + batch_data.len() as u32,
+                                        &mut encoder,
+                                    )
+                                    .unwrap();
+                                }
+                                instances_drawn += batch_data.len() as u32;
+                            }
+                        }
+                    }
+                }
+                skinned_offset += skinned_batches.count() as u32;
+            }
         }
     }
 
@@ -419,6 +518,8 @@ impl<B: Backend, T: Base3DPassDef> RenderGroup<B, GraphAuxData> for DrawBase3D<B
 #[derivative(Debug(bound = ""), Default(bound = ""))]
 pub struct DrawBase3DTransparentDesc<B: Backend, T: Base3DPassDef> {
     skinning: bool,
+    shadows: bool,
+    target: Target,
     marker: PhantomData<(B, T)>,
 }
 
@@ -428,6 +529,8 @@ impl<B: Backend, T: Base3DPassDef> DrawBase3DTransparentDesc<B, T> {
     pub fn new() -> Self {
         Self {
             skinning: false,
+            shadows: false,
+            target: Target::Main,
             marker: PhantomData,
         }
     }
@@ -437,6 +540,8 @@ impl<B: Backend, T: Base3DPassDef> DrawBase3DTransparentDesc<B, T> {
     pub fn skinned() -> Self {
         Self {
             skinning: true,
+            shadows: false,
+            target: Target::Main,
             marker: PhantomData,
         }
     }
@@ -447,29 +552,53 @@ impl<B: Backend, T: Base3DPassDef> DrawBase3DTransparentDesc<B, T> {
         self.skinning = skinned;
         self
     }
+
+    /// Draw the meshes seen by the cameras rendering to `target`, `Target::Main` by default.
+    #[must_use]
+    pub fn with_target(mut self, target: Target) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// Sample the shadow atlas if true is passed. The depth image of `Target::ShadowMap` must
+    /// then be added to the render group builder with `with_image`.
+    #[must_use]
+    pub fn with_shadows(mut self, shadows: bool) -> Self {
+        self.shadows = shadows;
+        self
+    }
 }
 
 impl<B: Backend, T: Base3DPassDef> RenderGroupDesc<B, GraphAuxData>
     for DrawBase3DTransparentDesc<B, T>
 {
+    fn images(&self) -> Vec<ImageAccess> {
+        shadow_map_access(self.shadows)
+    }
+
     fn build(
         self,
-        _ctx: &GraphContext<B>,
+        ctx: &GraphContext<B>,
         factory: &mut Factory<B>,
-        _queue: QueueId,
+        queue: QueueId,
         _aux: &GraphAuxData,
         framebuffer_width: u32,
         framebuffer_height: u32,
         subpass: hal::pass::Subpass<'_, B>,
         _buffers: Vec<NodeBuffer>,
-        _images: Vec<NodeImage>,
+        images: Vec<NodeImage>,
     ) -> Result<Box<dyn RenderGroup<B, GraphAuxData>>, pso::CreationError> {
         let env = EnvironmentSub::new(
             factory,
+            queue,
             [
                 hal::pso::ShaderStageFlags::VERTEX,
                 hal::pso::ShaderStageFlags::FRAGMENT,
             ],
+            images
+                .first()
+                .and_then(|image| ctx.get_image(image.id))
+                .cloned(),
         )?;
 
         let materials = MaterialSub::new(factory)?;
@@ -498,11 +627,15 @@ impl<B: Backend, T: Base3DPassDef> RenderGroupDesc<B, GraphAuxData>
         vertex_format_skinned.sort();
 
         Ok(Box::new(DrawBase3DTransparent::<B, T> {
+            target: self.target,
             pipeline_basic: pipelines.remove(0),
             pipeline_skinned: pipelines.pop(),
             pipeline_layout,
-            static_batches: batch::OrderedTwoLevelBatch::default(),
-            skinned_batches: batch::OrderedTwoLevelBatch::default(),
+            static_batches: Vec::new(),
+            skinned_batches: Vec::new(),
+            viewports: Vec::new(),
+            framebuffer_width,
+            framebuffer_height,
             vertex_format_base,
             vertex_format_skinned,
             env,
@@ -520,11 +653,15 @@ impl<B: Backend, T: Base3DPassDef> RenderGroupDesc<B, GraphAuxData>
 #[derive(Derivative)]
 #[derivative(Debug(bound = ""))]
 pub struct DrawBase3DTransparent<B: Backend, T: Base3DPassDef> {
+    target: Target,
     pipeline_basic: B::GraphicsPipeline,
     pipeline_skinned: Option<B::GraphicsPipeline>,
     pipeline_layout: B::PipelineLayout,
-    static_batches: OrderedTwoLevelBatch<MaterialId, LoadHandle, VertexArgs>,
-    skinned_batches: OrderedTwoLevelBatch<MaterialId, LoadHandle, SkinnedVertexArgs>,
+    static_batches: Vec<OrderedTwoLevelBatch<MaterialId, LoadHandle, VertexArgs>>,
+    skinned_batches: Vec<OrderedTwoLevelBatch<MaterialId, LoadHandle, SkinnedVertexArgs>>,
+    viewports: Vec<Viewport>,
+    framebuffer_width: u32,
+    framebuffer_height: u32,
     vertex_format_base: Vec<VertexFormat>,
     vertex_format_skinned: Vec<VertexFormat>,
     env: EnvironmentSub<B>,
@@ -551,124 +688,164 @@ impl<B: Backend, T: Base3DPassDef> RenderGroup<B, GraphAuxData> for DrawBase3DTr
 
         let visibility = resources.get::<Visibility>().unwrap();
         let mesh_storage = resources.get::<AssetStorage<Mesh>>().unwrap();
+        let views = views_for_target(&visibility.views, self.target);
 
         // Prepare environment
-        self.env.process(factory, index, world, resources);
+        self.env.process_views(
+            factory,
+            index,
+            world,
+            resources,
+            views.iter().map(|view| view.camera),
+        );
         self.materials.maintain();
 
-        self.static_batches.swap_clear();
-        self.skinned_batches.swap_clear();
-
+        let mut changed = self.viewports.len() != views.len()
+            || self
+                .viewports
+                .iter()
+                .zip(&views)
+                .any(|(viewport, view)| *viewport != view.viewport);
+        self.viewports.clear();
+        self.viewports
+            .extend(views.iter().map(|view| view.viewport));
+        self.static_batches
+            .resize_with(views.len(), OrderedTwoLevelBatch::default);
+        self.skinned_batches
+            .resize_with(views.len(), OrderedTwoLevelBatch::default);
+
+        let skinning_enabled = self.pipeline_skinned.is_some();
         let materials_ref = &mut self.materials;
         let skinning_ref = &mut self.skinning;
-        let statics_ref = &mut self.static_batches;
-        let skinned_ref = &mut self.skinned_batches;
-        let mut changed = false;
 
+        for ((statics_ref, skinned_ref), view) in self
+            .static_batches
+            .iter_mut()
+            .zip(self.skinned_batches.iter_mut())
+            .zip(&views)
         {
-            profile_scope_impl!("prepare");
+            statics_ref.swap_clear();
+            skinned_ref.swap_clear();
 
-            let mut query =
-                <(&Handle<Material>, &Handle<Mesh>, &Transform, Option<&Tint>)>::query();
-
-            visibility
-                .visible_ordered
-                .iter()
-                .filter_map(|entity| Some((entity, query.get(*world, *entity).ok()?)))
-                .map(|(entity, (mat, mesh, tform, tint))| {
-                    if let Some(tint) = tint {
-                        (
-                            (mat, mesh.load_handle()),
-                            VertexArgs::from_object_data(tform, Some(tint)),
-                        )
-                    } else {
-                        (
-                            (mat, mesh.load_handle()),
-                            VertexArgs::from_object_data(tform, None),
-                        )
-                    }
-                })
-                .for_each_group(|(mat, mesh_id), data| {
-                    if mesh_storage.contains(mesh_id) {
-                        if let Some((mat, this_changed)) =
-                            materials_ref.insert(factory, resources, mat)
-                        {
-                            changed = changed || this_changed;
-                            statics_ref.insert(mat, mesh_id, data.drain(..));
+            {
+                profile_scope_impl!("prepare");
+
+                let mut query = <(
+                    &Handle<Material>,
+                    &Handle<Mesh>,
+                    &Transform,
+                    Option<&Tint>,
+                    Option<&InstanceData>,
+                    Option<&MeshLod>,
+                )>::query();
+
+                view.visible_ordered
+                    .iter()
+                    .filter_map(|entity| Some((entity, query.get(*world, *entity).ok()?)))
+                    .flat_map(|(entity, (mat, mesh, tform, tint, data, lod))| {
+                        let args =
+                            VertexArgs::from_object_data(tform, tint).with_instance_data(data);
+                        lod_meshes(mesh, lod, view.lods.get(entity))
+                            .into_iter()
+                            .map(move |(mesh, fade)| {
+                                ((mat, mesh.load_handle()), args.with_lod_fade(fade))
+                            })
+                    })
+                    .for_each_group(|(mat, mesh_id), data| {
+                        if mesh_storage.contains(mesh_id) {
+                            if let Some((mat, this_changed)) =
+                                materials_ref.insert(factory, resources, mat)
+                            {
+                                changed = changed || this_changed;
+                                statics_ref.insert(mat, mesh_id, data.drain(..));
+                            }
+                        } else {
+                            log::error!("Gathered mesh with invalid mesh ID for rendering");
                         }
-                    } else {
-                        log::error!("Gathered mesh with invalid mesh ID for rendering");
-                    }
-                });
-        }
-
-        if self.pipeline_skinned.is_some() {
-            profile_scope_impl!("prepare_skinning");
-
-            let mut query = <(
-                &Handle<Material>,
-                &Handle<Mesh>,
-                &Transform,
-                Option<&Tint>,
-                &JointTransforms,
-            )>::query();
+                    });
+            }
 
-            visibility
-                .visible_unordered
-                .iter()
-                .filter_map(|entity| Some((entity, query.get(*world, *entity).ok()?)))
-                .map(|(_, (mat, mesh, tform, tint, joints))| {
-                    if let Some(tint) = tint {
-                        (
-                            (mat, mesh.load_handle()),
-                            SkinnedVertexArgs::from_object_data(
-                                tform,
-                                Some(tint),
-                                skinning_ref.insert(joints),
-                            ),
-                        )
-                    } else {
-                        (
-                            (mat, mesh.load_handle()),
-                            SkinnedVertexArgs::from_object_data(
-                                tform,
-                                None,
-                                skinning_ref.insert(joints),
-                            ),
-                        )
-                    }
-                })
-                .for_each_group(|(mat, mesh_id), data| {
-                    if mesh_storage.contains(mesh_id) {
-                        if let Some((mat, this_changed)) =
-                            materials_ref.insert(factory, resources, mat)
-                        {
-                            changed = changed || this_changed;
-                            skinned_ref.insert(mat, mesh_id, data.drain(..));
+            if skinning_enabled {
+                profile_scope_impl!("prepare_skinning");
+
+                let mut query = <(
+                    &Handle<Material>,
+                    &Handle<Mesh>,
+                    &Transform,
+                    Option<&Tint>,
+                    &JointTransforms,
+                    Option<&MeshLod>,
+                )>::query();
+
+                view.visible_unordered
+                    .iter()
+                    .filter_map(|entity| {
+                        // Skinned meshes switch level without cross-fade.
+                        let (mat, mesh, tform, tint, joints, lod) =
+                            query.get(*world, *entity).ok()?;
+                        let (mesh, _) = *lod_meshes(mesh, lod, view.lods.get(entity)).first()?;
+                        Some((mat, mesh, tform, tint, joints))
+                    })
+                    .map(|(mat, mesh, tform, tint, joints)| {
+                        if let Some(tint) = tint {
+                            (
+                                (mat, mesh.load_handle()),
+                                SkinnedVertexArgs::from_object_data(
+                                    tform,
+                                    Some(tint),
+                                    skinning_ref.insert(joints),
+                                ),
+                            )
+                        } else {
+                            (
+                                (mat, mesh.load_handle()),
+                                SkinnedVertexArgs::from_object_data(
+                                    tform,
+                                    None,
+                                    skinning_ref.insert(joints),
+                                ),
+                            )
                         }
-                    }
-                });
-        };
+                    })
+                    .for_each_group(|(mat, mesh_id), data| {
+                        if mesh_storage.contains(mesh_id) {
+                            if let Some((mat, this_changed)) =
+                                materials_ref.insert(factory, resources, mat)
+                            {
+                                changed = changed || this_changed;
+                                skinned_ref.insert(mat, mesh_id, data.drain(..));
+                            }
+                        }
+                    });
+            };
+
+            changed = changed || statics_ref.changed();
+            changed = changed || skinned_ref.changed();
+        }
 
+        // Instances of every view share one buffer, laid out view after view.
         self.models.write(
             factory,
             index,
-            self.static_batches.count() as u64,
-            Some(self.static_batches.data()),
+            self.static_batches
+                .iter()
+                .map(OrderedTwoLevelBatch::count)
+                .sum::<usize>() as u64,
+            self.static_batches.iter().map(OrderedTwoLevelBatch::data),
         );
 
         self.skinned_models.write(
             factory,
             index,
-            self.skinned_batches.count() as u64,
-            Some(self.skinned_batches.data()),
+            self.skinned_batches
+                .iter()
+                .map(OrderedTwoLevelBatch::count)
+                .sum::<usize>() as u64,
+            self.skinned_batches.iter().map(OrderedTwoLevelBatch::data),
         );
 
         self.skinning.commit(factory, index);
 
-        changed = changed || self.static_batches.changed();
-        changed = changed || self.skinned_batches.changed();
-
         self.change.prepare_result(index, changed)
     }
 
@@ -688,45 +865,21 @@ impl<B: Backend, T: Base3DPassDef> RenderGroup<B, GraphAuxData> for DrawBase3DTr
         let models_loc = self.vertex_format_base.len() as u32;
         let skin_models_loc = self.vertex_format_skinned.len() as u32;
 
-        encoder.bind_graphics_pipeline(&self.pipeline_basic);
-        self.env.bind(index, layout, 0, encoder);
-
-        if self.models.bind(index, models_loc, 0, encoder) {
-            for (&mat, batches) in self.static_batches.iter() {
-                if self.materials.loaded(mat) {
-                    self.materials.bind(layout, 1, mat, encoder);
-                    for (mesh, range) in batches {
-                        debug_assert!(mesh_storage.contains(*mesh));
-                        if let Some(mesh) = B::unwrap_mesh({
-                            mesh_storage
-                                .get_for_load_handle(*mesh)
-                                .expect("Could not get mesh.")
-                        }) {
-                            if let Err(error) = mesh.bind_and_draw(
-                                0,
-                                &self.vertex_format_base,
-                                range.clone(),
-                                encoder,
-                            ) {
-                                log::warn!(
-                                    "Trying to draw a mesh that lacks {:?} vertex attributes. Pass {} requires attributes {:?}.",
-                                    error.not_found.attributes,
-                                    T::NAME,
-                                    T::base_format(),
-                                );
-                            }
-                        }
-                    }
-                }
-            }
-        }
-
-        if let Some(pipeline_skinned) = self.pipeline_skinned.as_ref() {
-            encoder.bind_graphics_pipeline(pipeline_skinned);
+        let mut static_offset = 0;
+        let mut skinned_offset = 0;
+        for (view, viewport) in self.viewports.iter().enumerate() {
+            util::set_viewport(
+                encoder,
+                viewport,
+                self.framebuffer_width,
+                self.framebuffer_height,
+            );
+            encoder.bind_graphics_pipeline(&self.pipeline_basic);
+            self.env.bind_view(index, view, layout, 0, encoder);
 
-            if self.skinned_models.bind(index, skin_models_loc, 0, encoder) {
-                self.skinning.bind(index, layout, 2, encoder);
-                for (&mat, batches) in self.skinned_batches.iter() {
+            let static_batches = &self.static_batches[view];
+            if self.models.bind(index, models_loc, 0, encoder) {
+                for (&mat, batches) in static_batches.iter() {
                     if self.materials.loaded(mat) {
                         self.materials.bind(layout, 1, mat, encoder);
                         for (mesh, range) in batches {
@@ -738,15 +891,15 @@ impl<B: Backend, T: Base3DPassDef> RenderGroup<B, GraphAuxData> for DrawBase3DTr
                             }) {
                                 if let Err(error) = mesh.bind_and_draw(
                                     0,
-                                    &self.vertex_format_skinned,
-                                    range.clone(),
+                                    &self.vertex_format_base,
+                                    range.start + static_offset..range.end + static_offset,
                                     encoder,
                                 ) {
                                     log::warn!(
-                                        "Trying to draw a skinned mesh that lacks {:?} vertex attributes. Pass {} requires attributes {:?}.",
+                                        "Trying to draw a mesh that lacks {:?} vertex attributes. Pass {} requires attributes {:?}.",
                                         error.not_found.attributes,
                                         T::NAME,
-                                        T::skinned_format(),
+                                        T::base_format(),
                                     );
                                 }
                             }
@@ -754,6 +907,44 @@ impl<B: Backend, T: Base3DPassDef> RenderGroup<B, GraphAuxData> for DrawBase3DTr
                     }
                 }
             }
+            static_offset += static_batches.count() as u32;
+
+            if let Some(pipeline_skinned) = self.pipeline_skinned.as_ref() {
+                encoder.bind_graphics_pipeline(pipeline_skinned);
+
+                let skinned_batches = &self.skinned_batches[view];
+                if self.skinned_models.bind(index, skin_models_loc, 0, encoder) {
+                    self.skinning.bind(index, layout, 2, encoder);
+                    for (&mat, batches) in skinned_batches.iter() {
+                        if self.materials.loaded(mat) {
+                            self.materials.bind(layout, 1, mat, encoder);
+                            for (mesh, range) in batches {
+                                debug_assert!(mesh_storage.contains(*mesh));
+                                if let Some(mesh) = B::unwrap_mesh({
+                                    mesh_storage
+                                        .get_for_load_handle(*mesh)
+                                        .expect("Could not get mesh.")
+                                }) {
+                                    if let Err(error) = mesh.bind_and_draw(
+                                        0,
+                                        &self.vertex_format_skinned,
+                                        range.start + skinned_offset..range.end + skinned_offset,
+                                        encoder,
+                                    ) {
+                                        log::warn!(
+                                            "Trying to draw a skinned mesh that lacks {:?} vertex attributes. Pass {} requires attributes {:?}.",
+                                            error.not_found.attributes,
+                                            T::NAME,
+                                            T::skinned_format(),
+                                        );
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                skinned_offset += skinned_batches.count() as u32;
+            }
         }
     }
 
@@ -772,6 +963,20 @@ impl<B: Backend, T: Base3DPassDef> RenderGroup<B, GraphAuxData> for DrawBase3DTr
     }
 }
 
+/// Access of the 3D passes to the shadow atlas, sampled by the fragment shaders.
+fn shadow_map_access(shadows: bool) -> Vec<ImageAccess> {
+    if shadows {
+        vec![ImageAccess {
+            access: hal::image::Access::SHADER_READ,
+            usage: hal::image::Usage::SAMPLED,
+            layout: hal::image::Layout::ShaderReadOnlyOptimal,
+            stages: pso::PipelineStage::FRAGMENT_SHADER,
+        }]
+    } else {
+        Vec::new()
+    }
+}
+
 fn build_pipelines<B: Backend, T: Base3DPassDef>(
     factory: &Factory<B>,
     subpass: hal::pass::Subpass<'_, B>,
@@ -809,6 +1014,7 @@ fn build_pipelines<B: Backend, T: Base3DPassDef>(
         .with_layout(&pipeline_layout)
         .with_subpass(subpass)
         .with_framebuffer_size(framebuffer_width, framebuffer_height)
+        .with_dynamic_viewport()
         .with_face_culling(pso::Face::BACK)
         .with_depth_test(pso::DepthTest {
             fun: pso::Comparison::Greater,
@@ -16,11 +16,12 @@ use rendy::{
 use thread_profiler::profile_scope;
 
 use crate::{
+    bundle::Target,
+    camera::Viewport,
     debug_drawing::{DebugLine, DebugLines, DebugLinesComponent, DebugLinesParams},
     pass,
     pipeline::{PipelineDescBuilder, PipelinesBuilder},
-    pod::ViewArgs,
-    submodules::{gather::CameraGatherer, DynamicUniform, DynamicVertexBuffer},
+    submodules::{gather::CameraGatherer, DynamicUniform, DynamicVertexBuffer, FlatEnvironmentSub},
     system::GraphAuxData,
     types::Backend,
     util,
@@ -34,7 +35,9 @@ struct DebugLinesArgs {
 /// Draw opaque sprites without lighting.
 #[derive(Clone, Debug, PartialEq, Derivative)]
 #[derivative(Default(bound = ""))]
-pub struct DrawDebugLinesDesc;
+pub struct DrawDebugLinesDesc {
+    target: Target,
+}
 
 impl DrawDebugLinesDesc {
     /// Create instance of `DrawDebugLines` render group
@@ -42,6 +45,13 @@ impl DrawDebugLinesDesc {
     pub fn new() -> Self {
         pass::debug_lines::DrawDebugLinesDesc::default()
     }
+
+    /// Draw the lines seen by the cameras rendering to `target`, `Target::Main` by default.
+    #[must_use]
+    pub fn with_target(mut self, target: Target) -> Self {
+        self.target = target;
+        self
+    }
 }
 
 impl<B: Backend> RenderGroupDesc<B, GraphAuxData> for DrawDebugLinesDesc {
@@ -60,7 +70,7 @@ impl<B: Backend> RenderGroupDesc<B, GraphAuxData> for DrawDebugLinesDesc {
         #[cfg(feature = "profiler")]
         profile_scope!("build");
 
-        let env = DynamicUniform::new(factory, pso::ShaderStageFlags::VERTEX)?;
+        let env = FlatEnvironmentSub::new(factory)?;
         let args = DynamicUniform::new(factory, pso::ShaderStageFlags::VERTEX)?;
         let vertex = DynamicVertexBuffer::new();
 
@@ -73,13 +83,15 @@ impl<B: Backend> RenderGroupDesc<B, GraphAuxData> for DrawDebugLinesDesc {
         )?;
 
         Ok(Box::new(DrawDebugLines::<B> {
+            target: self.target,
             pipeline,
             pipeline_layout,
             env,
-            args,
+            args: vec![args],
             vertex,
-            framebuffer_width: framebuffer_width as f32,
-            framebuffer_height: framebuffer_height as f32,
+            framebuffer_width,
+            framebuffer_height,
+            viewports: Vec::new(),
             lines: Vec::new(),
             change: util::ChangeDetection::default(),
         }))
@@ -89,13 +101,15 @@ impl<B: Backend> RenderGroupDesc<B, GraphAuxData> for DrawDebugLinesDesc {
 /// Draws debug lines
 #[derive(Debug)]
 pub struct DrawDebugLines<B: Backend> {
+    target: Target,
     pipeline: B::GraphicsPipeline,
     pipeline_layout: B::PipelineLayout,
-    env: DynamicUniform<B, ViewArgs>,
-    args: DynamicUniform<B, DebugLinesArgs>,
+    env: FlatEnvironmentSub<B>,
+    args: Vec<DynamicUniform<B, DebugLinesArgs>>,
     vertex: DynamicVertexBuffer<B, DebugLine>,
-    framebuffer_width: f32,
-    framebuffer_height: f32,
+    framebuffer_width: u32,
+    framebuffer_height: u32,
+    viewports: Vec<Viewport>,
     lines: Vec<DebugLine>,
     change: util::ChangeDetection,
 }
@@ -124,24 +138,54 @@ impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawDebugLines<B> {
             self.lines.extend(lines_res.drain());
         };
 
-        let cam = CameraGatherer::gather(world, resources);
+        let views: Vec<_> = CameraGatherer::gather_views(world, resources)
+            .into_iter()
+            .filter(|(_, _, target)| *target == self.target.camera_target())
+            .collect();
         let line_width = resources
             .get::<DebugLinesParams>()
             .map_or(DebugLinesParams::default().line_width, |p| p.line_width);
 
-        self.env.write(factory, index, cam.projview);
-        self.args.write(
+        self.env.process_views(
             factory,
             index,
-            DebugLinesArgs {
-                screen_space_thickness: [
-                    (line_width * 2.0) / self.framebuffer_width,
-                    (line_width * 2.0) / self.framebuffer_height,
-                ]
-                .into(),
-            }
-            .std140(),
+            world,
+            views.iter().map(|(camera, _, _)| *camera),
         );
+        for (view, (_, camera_view, _)) in views.iter().enumerate() {
+            if view == self.args.len() {
+                let args = self.args[0].with_same_layout();
+                self.args.push(args);
+            }
+            // Lines have the same thickness in pixels in every viewport.
+            let (_, _, width, height) = camera_view
+                .viewport
+                .to_pixels(self.framebuffer_width, self.framebuffer_height);
+            self.args[view].write(
+                factory,
+                index,
+                DebugLinesArgs {
+                    screen_space_thickness: [
+                        (line_width * 2.0) / width.max(1) as f32,
+                        (line_width * 2.0) / height.max(1) as f32,
+                    ]
+                    .into(),
+                }
+                .std140(),
+            );
+        }
+
+        let mut changed = old_len != self.lines.len();
+        if !self
+            .viewports
+            .iter()
+            .eq(views.iter().map(|(_, view, _)| &view.viewport))
+        {
+            self.viewports.clear();
+            self.viewports
+                .extend(views.iter().map(|(_, view, _)| view.viewport));
+            changed = true;
+        }
 
         {
             #[cfg(feature = "profiler")]
@@ -150,7 +194,6 @@ impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawDebugLines<B> {
                 .write(factory, index, self.lines.len() as u64, Some(&self.lines));
         }
 
-        let changed = old_len != self.lines.len();
         self.change.prepare_result(index, changed)
     }
 
@@ -170,11 +213,19 @@ impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawDebugLines<B> {
 
         let layout = &self.pipeline_layout;
         encoder.bind_graphics_pipeline(&self.pipeline);
-        self.env.bind(index, layout, 0, &mut encoder);
-        self.args.bind(index, layout, 1, &mut encoder);
         self.vertex.bind(index, 0, 0, &mut encoder);
-        unsafe {
-            encoder.draw(0..4, 0..self.lines.len() as u32);
+        for (view, viewport) in self.viewports.iter().enumerate() {
+            util::set_viewport(
+                &mut encoder,
+                viewport,
+                self.framebuffer_width,
+                self.framebuffer_height,
+            );
+            self.env.bind_view(index, view, layout, 0, &mut encoder);
+            self.args[view].bind(index, layout, 1, &mut encoder);
+            unsafe {
+                encoder.draw(0..4, 0..self.lines.len() as u32);
+            }
         }
     }
 
@@ -216,6 +267,7 @@ fn build_lines_pipeline<B: Backend>(
                 .with_layout(&pipeline_layout)
                 .with_subpass(subpass)
                 .with_framebuffer_size(framebuffer_width, framebuffer_height)
+                .with_dynamic_viewport()
                 .with_blend_targets(vec![pso::ColorBlendDesc {
                     mask: pso::ColorMask::ALL,
                     blend: Some(pso::BlendState::ALPHA),
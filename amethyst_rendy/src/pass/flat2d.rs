@@ -19,8 +19,9 @@ use rendy::{
 use thread_profiler::profile_scope;
 
 use crate::{
-    batch,
     batch::{GroupIterator, OneLevelBatch, OrderedOneLevelBatch},
+    bundle::Target,
+    camera::Viewport,
     pass,
     pipeline::{PipelineDescBuilder, PipelinesBuilder},
     pod::SpriteArgs,
@@ -31,12 +32,15 @@ use crate::{
     system::GraphAuxData,
     types::{Backend, Texture},
     util,
+    visibility::views_for_target,
 };
 
 /// Draw opaque sprites without lighting.
 #[derive(Clone, Debug, PartialEq, Derivative)]
 #[derivative(Default(bound = ""))]
-pub struct DrawFlat2DDesc;
+pub struct DrawFlat2DDesc {
+    target: Target,
+}
 
 impl DrawFlat2DDesc {
     /// Create instance of `DrawFlat2D` render group
@@ -44,6 +48,13 @@ impl DrawFlat2DDesc {
     pub fn new() -> Self {
         pass::flat2d::DrawFlat2DDesc::default()
     }
+
+    /// Draw the sprites seen by the cameras rendering to `target`, `Target::Main` by default.
+    #[must_use]
+    pub fn with_target(mut self, target: Target) -> Self {
+        self.target = target;
+        self
+    }
 }
 
 impl<B: Backend> RenderGroupDesc<B, GraphAuxData> for DrawFlat2DDesc {
@@ -76,12 +87,16 @@ impl<B: Backend> RenderGroupDesc<B, GraphAuxData> for DrawFlat2DDesc {
         )?;
 
         Ok(Box::new(DrawFlat2D::<B> {
+            target: self.target,
             pipeline,
             pipeline_layout,
             env,
             textures,
             vertex,
-            sprites: batch::OneLevelBatch::default(),
+            sprites: Vec::new(),
+            viewports: Vec::new(),
+            framebuffer_width,
+            framebuffer_height,
         }))
     }
 }
@@ -89,12 +104,16 @@ impl<B: Backend> RenderGroupDesc<B, GraphAuxData> for DrawFlat2DDesc {
 /// Draws opaque 2D sprites to the screen without lighting.
 #[derive(Debug)]
 pub struct DrawFlat2D<B: Backend> {
+    target: Target,
     pipeline: B::GraphicsPipeline,
     pipeline_layout: B::PipelineLayout,
     env: FlatEnvironmentSub<B>,
     textures: TextureSub<B>,
     vertex: DynamicVertexBuffer<B, SpriteArgs>,
-    sprites: OneLevelBatch<TextureId, SpriteArgs>,
+    sprites: Vec<OneLevelBatch<TextureId, SpriteArgs>>,
+    viewports: Vec<Viewport>,
+    framebuffer_width: u32,
+    framebuffer_height: u32,
 }
 
 impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawFlat2D<B> {
@@ -119,21 +138,26 @@ impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawFlat2D<B> {
                 Read<SpriteVisibility>,
             )>::fetch(resources);
 
-        self.env.process(factory, index, world, resources);
+        let views = views_for_target(&visibility.views, self.target);
+        self.env
+            .process_views(factory, index, world, views.iter().map(|view| view.camera));
+        self.viewports.clear();
+        self.viewports
+            .extend(views.iter().map(|view| view.viewport));
+        self.sprites
+            .resize_with(views.len(), OneLevelBatch::default);
 
-        let sprites_ref = &mut self.sprites;
         let textures_ref = &mut self.textures;
 
-        sprites_ref.clear_inner();
-
-        {
+        for (sprites_ref, view) in self.sprites.iter_mut().zip(&views) {
             #[cfg(feature = "profiler")]
             profile_scope!("gather_visibility");
 
+            sprites_ref.clear_inner();
+
             let mut query = <(&SpriteRender, &Transform, Option<&Tint>)>::query();
 
-            visibility
-                .visible_unordered
+            view.visible_unordered
                 .iter()
                 .filter_map(|entity| Some((entity, query.get(*world, *entity).ok()?)))
                 .filter_map(|(entity, (sprite_render, global, tint))| {
@@ -155,6 +179,8 @@ impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawFlat2D<B> {
                 .for_each_group(|tex_id, batch_data| {
                     sprites_ref.insert(tex_id, batch_data.drain(..));
                 });
+
+            sprites_ref.prune();
         }
 
         self.textures.maintain(factory, resources);
@@ -163,12 +189,11 @@ impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawFlat2D<B> {
             #[cfg(feature = "profiler")]
             profile_scope!("write");
 
-            sprites_ref.prune();
             self.vertex.write(
                 factory,
                 index,
-                self.sprites.count() as u64,
-                self.sprites.data(),
+                self.sprites.iter().map(OneLevelBatch::count).sum::<usize>() as u64,
+                self.sprites.iter().flat_map(|sprites| sprites.data()),
             );
         }
 
@@ -187,15 +212,25 @@ impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawFlat2D<B> {
 
         let layout = &self.pipeline_layout;
         encoder.bind_graphics_pipeline(&self.pipeline);
-        self.env.bind(index, layout, 0, &mut encoder);
         self.vertex.bind(index, 0, 0, &mut encoder);
-        for (&tex, range) in self.sprites.iter() {
-            if self.textures.loaded(tex) {
-                self.textures.bind(layout, 1, tex, &mut encoder);
-                unsafe {
-                    encoder.draw(0..4, range);
+        let mut offset = 0;
+        for (view, (sprites, viewport)) in self.sprites.iter().zip(&self.viewports).enumerate() {
+            util::set_viewport(
+                &mut encoder,
+                viewport,
+                self.framebuffer_width,
+                self.framebuffer_height,
+            );
+            self.env.bind_view(index, view, layout, 0, &mut encoder);
+            for (&tex, range) in sprites.iter() {
+                if self.textures.loaded(tex) {
+                    self.textures.bind(layout, 1, tex, &mut encoder);
+                    unsafe {
+                        encoder.draw(0..4, range.start + offset..range.end + offset);
+                    }
                 }
             }
+            offset += sprites.count() as u32;
         }
     }
 
@@ -211,7 +246,9 @@ impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawFlat2D<B> {
 /// Describes drawing transparent sprites without lighting.
 #[derive(Clone, Debug, PartialEq, Derivative)]
 #[derivative(Default(bound = ""))]
-pub struct DrawFlat2DTransparentDesc;
+pub struct DrawFlat2DTransparentDesc {
+    target: Target,
+}
 
 impl DrawFlat2DTransparentDesc {
     /// Create instance of `DrawFlat2D` render group
@@ -219,6 +256,13 @@ impl DrawFlat2DTransparentDesc {
     pub fn new() -> Self {
         pass::flat2d::DrawFlat2DTransparentDesc::default()
     }
+
+    /// Draw the sprites seen by the cameras rendering to `target`, `Target::Main` by default.
+    #[must_use]
+    pub fn with_target(mut self, target: Target) -> Self {
+        self.target = target;
+        self
+    }
 }
 
 impl<B: Backend> RenderGroupDesc<B, GraphAuxData> for DrawFlat2DTransparentDesc {
@@ -251,12 +295,16 @@ impl<B: Backend> RenderGroupDesc<B, GraphAuxData> for DrawFlat2DTransparentDesc
         )?;
 
         Ok(Box::new(DrawFlat2DTransparent::<B> {
+            target: self.target,
             pipeline,
             pipeline_layout,
             env,
             textures,
             vertex,
-            sprites: batch::OrderedOneLevelBatch::default(),
+            sprites: Vec::new(),
+            viewports: Vec::new(),
+            framebuffer_width,
+            framebuffer_height,
             change: util::ChangeDetection::default(),
         }))
     }
@@ -265,12 +313,16 @@ impl<B: Backend> RenderGroupDesc<B, GraphAuxData> for DrawFlat2DTransparentDesc
 /// Draws transparent sprites without lighting.
 #[derive(Debug)]
 pub struct DrawFlat2DTransparent<B: Backend> {
+    target: Target,
     pipeline: B::GraphicsPipeline,
     pipeline_layout: B::PipelineLayout,
     env: FlatEnvironmentSub<B>,
     textures: TextureSub<B>,
     vertex: DynamicVertexBuffer<B, SpriteArgs>,
-    sprites: OrderedOneLevelBatch<TextureId, SpriteArgs>,
+    sprites: Vec<OrderedOneLevelBatch<TextureId, SpriteArgs>>,
+    viewports: Vec<Viewport>,
+    framebuffer_width: u32,
+    framebuffer_height: u32,
     change: util::ChangeDetection,
 }
 
@@ -296,22 +348,32 @@ impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawFlat2DTransparent<B> {
                 Read<SpriteVisibility>,
             )>::fetch(resources);
 
-        self.env.process(factory, index, world, resources);
+        let views = views_for_target(&visibility.views, self.target);
+        self.env
+            .process_views(factory, index, world, views.iter().map(|view| view.camera));
+        let mut changed = self.viewports.len() != views.len()
+            || self
+                .viewports
+                .iter()
+                .zip(&views)
+                .any(|(viewport, view)| *viewport != view.viewport);
+        self.viewports.clear();
+        self.viewports
+            .extend(views.iter().map(|view| view.viewport));
+        self.sprites
+            .resize_with(views.len(), OrderedOneLevelBatch::default);
 
-        let sprites_ref = &mut self.sprites;
         let textures_ref = &mut self.textures;
 
-        sprites_ref.swap_clear();
-        let mut changed = false;
-
-        {
+        for (sprites_ref, view) in self.sprites.iter_mut().zip(&views) {
             #[cfg(feature = "profiler")]
             profile_scope!("gather_visibility");
 
+            sprites_ref.swap_clear();
+
             let mut query = <(&SpriteRender, &Transform, Option<&Tint>)>::query();
 
-            visibility
-                .visible_ordered
+            view.visible_ordered
                 .iter()
                 .filter_map(|entity| Some((entity, query.get(*world, *entity).ok()?)))
                 .filter_map(|(entity, (sprite_render, global, tint))| {
@@ -335,10 +397,11 @@ impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawFlat2DTransparent<B> {
                 .for_each_group(|tex_id, batch_data| {
                     sprites_ref.insert(tex_id, batch_data.drain(..));
                 });
+
+            changed = changed || sprites_ref.changed();
         }
 
         self.textures.maintain(factory, resources);
-        changed = changed || sprites_ref.changed();
 
         {
             #[cfg(feature = "profiler")]
@@ -347,8 +410,11 @@ impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawFlat2DTransparent<B> {
             self.vertex.write(
                 factory,
                 index,
-                self.sprites.count() as u64,
-                Some(self.sprites.data()),
+                self.sprites
+                    .iter()
+                    .map(OrderedOneLevelBatch::count)
+                    .sum::<usize>() as u64,
+                self.sprites.iter().map(OrderedOneLevelBatch::data),
             );
         }
 
@@ -367,15 +433,25 @@ impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawFlat2DTransparent<B> {
 
         let layout = &self.pipeline_layout;
         encoder.bind_graphics_pipeline(&self.pipeline);
-        self.env.bind(index, layout, 0, &mut encoder);
         self.vertex.bind(index, 0, 0, &mut encoder);
-        for (&tex, range) in self.sprites.iter() {
-            if self.textures.loaded(tex) {
-                self.textures.bind(layout, 1, tex, &mut encoder);
-                unsafe {
-                    encoder.draw(0..4, range);
+        let mut offset = 0;
+        for (view, (sprites, viewport)) in self.sprites.iter().zip(&self.viewports).enumerate() {
+            util::set_viewport(
+                &mut encoder,
+                viewport,
+                self.framebuffer_width,
+                self.framebuffer_height,
+            );
+            self.env.bind_view(index, view, layout, 0, &mut encoder);
+            for (&tex, range) in sprites.iter() {
+                if self.textures.loaded(tex) {
+                    self.textures.bind(layout, 1, tex, &mut encoder);
+                    unsafe {
+                        encoder.draw(0..4, range.start + offset..range.end + offset);
+                    }
                 }
             }
+            offset += sprites.count() as u32;
         }
     }
 
@@ -418,6 +494,7 @@ fn build_sprite_pipeline<B: Backend>(
                 .with_layout(&pipeline_layout)
                 .with_subpass(subpass)
                 .with_framebuffer_size(framebuffer_width, framebuffer_height)
+                .with_dynamic_viewport()
                 .with_blend_targets(vec![pso::ColorBlendDesc {
                     mask: pso::ColorMask::ALL,
                     blend: if transparent {
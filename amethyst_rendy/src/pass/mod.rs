@@ -5,12 +5,17 @@ mod debug_lines;
 mod flat;
 mod flat2d;
 mod pbr;
+mod post_effect;
 mod shaded;
+mod shadow;
 mod skybox;
 
 use rendy::{hal::pso::ShaderStageFlags, shader::SpirvShader};
 
-pub use self::{base_3d::*, debug_lines::*, flat::*, flat2d::*, pbr::*, shaded::*, skybox::*};
+pub use self::{
+    base_3d::*, debug_lines::*, flat::*, flat2d::*, pbr::*, post_effect::*, shaded::*, shadow::*,
+    skybox::*,
+};
 
 lazy_static::lazy_static! {
     static ref POS_TEX_VERTEX: SpirvShader = SpirvShader::from_bytes(
@@ -91,6 +96,12 @@ lazy_static::lazy_static! {
         "main",
     ).unwrap();
 
+    static ref SHADOW_VERTEX: SpirvShader = SpirvShader::from_bytes(
+        include_bytes!("../../compiled/vertex/shadow.vert.spv"),
+        ShaderStageFlags::VERTEX,
+        "main",
+    ).unwrap();
+
     static ref DEBUG_LINES_VERTEX: SpirvShader = SpirvShader::from_bytes(
         include_bytes!("../../compiled/vertex/debug_lines.vert.spv"),
         ShaderStageFlags::VERTEX,
@@ -102,4 +113,34 @@ lazy_static::lazy_static! {
         ShaderStageFlags::FRAGMENT,
         "main",
     ).unwrap();
+
+    pub(crate) static ref FULLSCREEN_VERTEX: SpirvShader = SpirvShader::from_bytes(
+        include_bytes!("../../compiled/vertex/fullscreen.vert.spv"),
+        ShaderStageFlags::VERTEX,
+        "main",
+    ).unwrap();
+
+    pub(crate) static ref BLOOM_PREFILTER_FRAGMENT: SpirvShader = SpirvShader::from_bytes(
+        include_bytes!("../../compiled/fragment/bloom_prefilter.frag.spv"),
+        ShaderStageFlags::FRAGMENT,
+        "main",
+    ).unwrap();
+
+    pub(crate) static ref BLOOM_BLUR_FRAGMENT: SpirvShader = SpirvShader::from_bytes(
+        include_bytes!("../../compiled/fragment/bloom_blur.frag.spv"),
+        ShaderStageFlags::FRAGMENT,
+        "main",
+    ).unwrap();
+
+    pub(crate) static ref TONEMAP_FRAGMENT: SpirvShader = SpirvShader::from_bytes(
+        include_bytes!("../../compiled/fragment/tonemap.frag.spv"),
+        ShaderStageFlags::FRAGMENT,
+        "main",
+    ).unwrap();
+
+    pub(crate) static ref FXAA_FRAGMENT: SpirvShader = SpirvShader::from_bytes(
+        include_bytes!("../../compiled/fragment/fxaa.frag.spv"),
+        ShaderStageFlags::FRAGMENT,
+        "main",
+    ).unwrap();
 }
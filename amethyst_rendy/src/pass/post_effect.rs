@@ -0,0 +1,534 @@
+use std::sync::Arc;
+
+use amethyst_assets::{AssetStorage, Handle};
+use amethyst_core::ecs::{Resources, World};
+use derivative::Derivative;
+use glsl_layout::Uniform;
+use rendy::{
+    command::{QueueId, RenderPassEncoder},
+    factory::{Factory, ImageState},
+    graph::{
+        render::{PrepareResult, RenderGroup, RenderGroupDesc},
+        GraphContext, ImageAccess, NodeBuffer, NodeImage,
+    },
+    hal::{
+        self,
+        device::Device,
+        format::{Aspects, Format, Swizzle},
+        image::{Filter, Kind, Layout, SamplerDesc, SubresourceRange, ViewKind, WrapMode},
+        pso,
+    },
+    memory::Write as _,
+    resource::{
+        Buffer, BufferInfo, DescriptorSet, DescriptorSetLayout, Escape, Handle as RendyHandle,
+        ImageView, ImageViewInfo, Sampler, SubRange,
+    },
+    shader::SpirvShader,
+    texture::TextureBuilder,
+};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{
+    pipeline::{PipelineDescBuilder, PipelinesBuilder},
+    system::GraphAuxData,
+    types::{Backend, Texture},
+    util,
+};
+
+/// Uniform block of a post effect, written every frame from the world.
+#[derive(Clone, Derivative)]
+#[derivative(Debug)]
+pub struct EffectUniform {
+    size: usize,
+    #[derivative(Debug = "ignore")]
+    write: Arc<dyn Fn(&World, &Resources) -> Vec<u8> + Send + Sync>,
+}
+
+impl EffectUniform {
+    /// Creates a uniform block holding the `U` returned by `uniform` every frame.
+    pub fn new<U>(uniform: impl Fn(&World, &Resources) -> U + Send + Sync + 'static) -> Self
+    where
+        U: Uniform,
+        U::Std140: Sized,
+    {
+        Self {
+            size: std::mem::size_of::<U::Std140>(),
+            write: Arc::new(move |world, resources| {
+                util::slice_as_bytes(&[uniform(world, resources).std140()]).to_vec()
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum InputDesc {
+    Image,
+    Texture(Option<Handle<Texture>>),
+}
+
+/// Draws a triangle covering the whole target with a fragment shader sampling other images, for
+/// post-processing.
+///
+/// The shader receives the texture coordinates of the pixel at location 0. Its set 0 holds one
+/// combined image sampler per input, in the order they are added, followed by the uniform block
+/// if there is one.
+#[derive(Clone, Debug)]
+pub struct DrawPostEffectDesc {
+    shader: SpirvShader,
+    inputs: Vec<InputDesc>,
+    uniform: Option<EffectUniform>,
+    depth: bool,
+}
+
+impl DrawPostEffectDesc {
+    /// Create a post effect drawn with the `fragment` shader.
+    #[must_use]
+    pub fn new(fragment: SpirvShader) -> Self {
+        Self {
+            shader: fragment,
+            inputs: Vec::new(),
+            uniform: None,
+            depth: false,
+        }
+    }
+
+    /// Add an input sampling the next image passed to the group builder with `with_image`.
+    #[must_use]
+    pub fn with_image_input(mut self) -> Self {
+        self.inputs.push(InputDesc::Image);
+        self
+    }
+
+    /// Add an input sampling `texture`, or a black texture while it is not loaded or if it is
+    /// `None`.
+    #[must_use]
+    pub fn with_texture_input(mut self, texture: Option<Handle<Texture>>) -> Self {
+        self.inputs.push(InputDesc::Texture(texture));
+        self
+    }
+
+    /// Set the uniform block bound after the inputs.
+    #[must_use]
+    pub fn with_uniform(mut self, uniform: EffectUniform) -> Self {
+        self.uniform = Some(uniform);
+        self
+    }
+
+    /// Set whether the target drawn into has a depth image, which is not used.
+    #[must_use]
+    pub fn with_depth(mut self, depth: bool) -> Self {
+        self.depth = depth;
+        self
+    }
+}
+
+impl<B: Backend> RenderGroupDesc<B, GraphAuxData> for DrawPostEffectDesc {
+    fn images(&self) -> Vec<ImageAccess> {
+        self.inputs
+            .iter()
+            .filter(|input| matches!(input, InputDesc::Image))
+            .map(|_| {
+                ImageAccess {
+                    access: hal::image::Access::SHADER_READ,
+                    usage: hal::image::Usage::SAMPLED,
+                    layout: Layout::ShaderReadOnlyOptimal,
+                    stages: pso::PipelineStage::FRAGMENT_SHADER,
+                }
+            })
+            .collect()
+    }
+
+    fn depth(&self) -> bool {
+        self.depth
+    }
+
+    fn build(
+        self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        queue: QueueId,
+        _aux: &GraphAuxData,
+        framebuffer_width: u32,
+        framebuffer_height: u32,
+        subpass: hal::pass::Subpass<'_, B>,
+        _buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<Box<dyn RenderGroup<B, GraphAuxData>>, pso::CreationError> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("build");
+
+        use rendy::hal::pso::{
+            BufferDescriptorFormat, BufferDescriptorType, DescriptorType, ImageDescriptorType,
+        };
+
+        let stages = pso::ShaderStageFlags::FRAGMENT;
+        let mut bindings = vec![(
+            self.inputs.len() as u32,
+            DescriptorType::Image {
+                ty: ImageDescriptorType::Sampled { with_sampler: true },
+            },
+            stages,
+        )];
+        if self.uniform.is_some() {
+            bindings.push((
+                1,
+                DescriptorType::Buffer {
+                    ty: BufferDescriptorType::Uniform,
+                    format: BufferDescriptorFormat::Structured {
+                        dynamic_offset: false,
+                    },
+                },
+                stages,
+            ));
+        }
+        let layout: RendyHandle<DescriptorSetLayout<B>> = factory
+            .create_descriptor_set_layout(util::set_layout_bindings(bindings))?
+            .into();
+
+        let empty = TextureBuilder::new()
+            .with_kind(Kind::D2(1, 1, 1, 1))
+            .with_view_kind(ViewKind::D2)
+            .with_data_width(1)
+            .with_data_height(1)
+            .with_raw_data(vec![0_u8, 0, 0, 255], Format::Rgba8Unorm)
+            .with_sampler_info(SamplerDesc::new(Filter::Nearest, WrapMode::Clamp))
+            .build(
+                ImageState {
+                    queue,
+                    stage: pso::PipelineStage::FRAGMENT_SHADER,
+                    access: hal::image::Access::SHADER_READ,
+                    layout: Layout::ShaderReadOnlyOptimal,
+                },
+                factory,
+            )
+            .map_err(|_| pso::CreationError::Other)?;
+
+        let mut images = images.into_iter();
+        let inputs = self
+            .inputs
+            .into_iter()
+            .map(|input| {
+                match input {
+                    InputDesc::Image => {
+                        let image = images
+                            .next()
+                            .and_then(|image| ctx.get_image(image.id))
+                            .ok_or(pso::CreationError::Other)?;
+                        image_input(factory, image.clone())
+                    }
+                    InputDesc::Texture(texture) => Ok(Input::Texture(texture)),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (pipeline, pipeline_layout) = build_post_effect_pipeline(
+            factory,
+            subpass,
+            framebuffer_width,
+            framebuffer_height,
+            &self.shader,
+            vec![layout.raw()],
+        )?;
+
+        Ok(Box::new(DrawPostEffect::<B> {
+            pipeline,
+            pipeline_layout,
+            layout,
+            inputs,
+            empty,
+            uniform: self.uniform,
+            per_image: Vec::new(),
+        }))
+    }
+}
+
+fn image_input<B: Backend>(
+    factory: &Factory<B>,
+    image: RendyHandle<rendy::resource::Image<B>>,
+) -> Result<Input<B>, pso::CreationError> {
+    let format = image.format();
+    // Depth images are sampled without filtering, which they may not support.
+    let (aspects, filter) = if format.is_depth() {
+        (Aspects::DEPTH, Filter::Nearest)
+    } else {
+        (Aspects::COLOR, Filter::Linear)
+    };
+    let view = factory
+        .create_image_view(
+            image,
+            ImageViewInfo {
+                view_kind: ViewKind::D2,
+                format,
+                swizzle: Swizzle::NO,
+                range: SubresourceRange {
+                    aspects,
+                    levels: 0..1,
+                    layers: 0..1,
+                },
+            },
+        )
+        .map_err(|_| pso::CreationError::Other)?;
+    let sampler = factory
+        .create_sampler(SamplerDesc::new(filter, WrapMode::Clamp))
+        .map_err(|_| pso::CreationError::Other)?;
+    Ok(Input::Image { view, sampler })
+}
+
+#[derive(Debug)]
+enum Input<B: Backend> {
+    Image {
+        view: Escape<ImageView<B>>,
+        sampler: Escape<Sampler<B>>,
+    },
+    Texture(Option<Handle<Texture>>),
+}
+
+/// Draws a post effect, see [`DrawPostEffectDesc`].
+#[derive(Debug)]
+pub struct DrawPostEffect<B: Backend> {
+    pipeline: B::GraphicsPipeline,
+    pipeline_layout: B::PipelineLayout,
+    layout: RendyHandle<DescriptorSetLayout<B>>,
+    inputs: Vec<Input<B>>,
+    empty: rendy::texture::Texture<B>,
+    uniform: Option<EffectUniform>,
+    per_image: Vec<PerImagePostEffect<B>>,
+}
+
+#[derive(Debug)]
+struct PerImagePostEffect<B: Backend> {
+    set: Escape<DescriptorSet<B>>,
+    buffer: Option<Escape<Buffer<B>>>,
+    /// Version of the texture bound to each texture input, `None` for the empty texture.
+    versions: Vec<Option<u32>>,
+}
+
+impl<B: Backend> DrawPostEffect<B> {
+    fn empty_descriptor(&self) -> pso::Descriptor<'_, B> {
+        pso::Descriptor::CombinedImageSampler(
+            self.empty.view().raw(),
+            Layout::ShaderReadOnlyOptimal,
+            self.empty.sampler().raw(),
+        )
+    }
+
+    fn new_per_image(&self, factory: &Factory<B>) -> PerImagePostEffect<B> {
+        let set = factory.create_descriptor_set(self.layout.clone()).unwrap();
+        let buffer = self.uniform.as_ref().map(|uniform| {
+            factory
+                .create_buffer(
+                    BufferInfo {
+                        size: uniform.size as u64,
+                        usage: hal::buffer::Usage::UNIFORM,
+                    },
+                    rendy::memory::Dynamic,
+                )
+                .unwrap()
+        });
+
+        let mut writes: Vec<_> = self
+            .inputs
+            .iter()
+            .map(|input| {
+                match input {
+                    Input::Image { view, sampler } => {
+                        pso::Descriptor::CombinedImageSampler(
+                            view.raw(),
+                            Layout::ShaderReadOnlyOptimal,
+                            sampler.raw(),
+                        )
+                    }
+                    Input::Texture(_) => self.empty_descriptor(),
+                }
+            })
+            .collect();
+        if let Some(buffer) = &buffer {
+            writes.push(pso::Descriptor::Buffer(buffer.raw(), SubRange::WHOLE));
+        }
+        unsafe {
+            let raw = set.raw();
+            factory.write_descriptor_sets(
+                writes
+                    .into_iter()
+                    .enumerate()
+                    .map(|(binding, descriptor)| util::desc_write(raw, binding as u32, descriptor)),
+            );
+        }
+
+        PerImagePostEffect {
+            set,
+            buffer,
+            versions: vec![None; self.inputs.len()],
+        }
+    }
+}
+
+impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawPostEffect<B> {
+    fn prepare(
+        &mut self,
+        factory: &Factory<B>,
+        _queue: QueueId,
+        index: usize,
+        _subpass: hal::pass::Subpass<'_, B>,
+        aux: &GraphAuxData,
+    ) -> PrepareResult {
+        #[cfg(feature = "profiler")]
+        profile_scope!("prepare");
+
+        let mut changed = false;
+        while self.per_image.len() <= index {
+            let per_image = self.new_per_image(factory);
+            self.per_image.push(per_image);
+            changed = true;
+        }
+        let per_image = &mut self.per_image[index];
+
+        let storage = aux.resources.get::<AssetStorage<Texture>>();
+        for (binding, input) in self.inputs.iter().enumerate() {
+            let texture = match input {
+                Input::Texture(Some(handle)) => {
+                    storage
+                        .as_ref()
+                        .and_then(|storage| storage.get_asset_with_version(handle))
+                        .and_then(|(texture, version)| {
+                            util::texture_desc(texture, Layout::ShaderReadOnlyOptimal)
+                                .map(|descriptor| (descriptor, version))
+                        })
+                }
+                _ => continue,
+            };
+            let version = texture.as_ref().map(|(_, version)| *version);
+            if per_image.versions[binding] == version {
+                continue;
+            }
+            let descriptor = match texture {
+                Some((descriptor, _)) => descriptor,
+                None => {
+                    pso::Descriptor::CombinedImageSampler(
+                        self.empty.view().raw(),
+                        Layout::ShaderReadOnlyOptimal,
+                        self.empty.sampler().raw(),
+                    )
+                }
+            };
+            unsafe {
+                factory.write_descriptor_sets(Some(util::desc_write(
+                    per_image.set.raw(),
+                    binding as u32,
+                    descriptor,
+                )));
+            }
+            per_image.versions[binding] = version;
+            changed = true;
+        }
+
+        if let (Some(uniform), Some(buffer)) = (&self.uniform, per_image.buffer.as_mut()) {
+            let bytes = (uniform.write)(aux.world, aux.resources);
+            let range = 0..buffer.size();
+            let mut mapped = buffer.map(factory.device(), range).unwrap();
+            let mut writer = unsafe {
+                mapped
+                    .write::<u8>(factory.device(), 0..bytes.len() as u64)
+                    .unwrap()
+            };
+            unsafe { writer.slice() }.copy_from_slice(&bytes);
+        }
+
+        if changed {
+            PrepareResult::DrawRecord
+        } else {
+            PrepareResult::DrawReuse
+        }
+    }
+
+    fn draw_inline(
+        &mut self,
+        mut encoder: RenderPassEncoder<'_, B>,
+        index: usize,
+        _subpass: hal::pass::Subpass<'_, B>,
+        _aux: &GraphAuxData,
+    ) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("draw");
+
+        encoder.bind_graphics_pipeline(&self.pipeline);
+        unsafe {
+            encoder.bind_graphics_descriptor_sets(
+                &self.pipeline_layout,
+                0,
+                Some(self.per_image[index].set.raw()),
+                std::iter::empty(),
+            );
+            encoder.draw(0..3, 0..1);
+        }
+    }
+
+    fn dispose(self: Box<Self>, factory: &mut Factory<B>, _aux: &GraphAuxData) {
+        unsafe {
+            factory.device().destroy_graphics_pipeline(self.pipeline);
+            factory
+                .device()
+                .destroy_pipeline_layout(self.pipeline_layout);
+        }
+    }
+}
+
+fn build_post_effect_pipeline<B: Backend>(
+    factory: &Factory<B>,
+    subpass: hal::pass::Subpass<'_, B>,
+    framebuffer_width: u32,
+    framebuffer_height: u32,
+    fragment: &SpirvShader,
+    layouts: Vec<&B::DescriptorSetLayout>,
+) -> Result<(B::GraphicsPipeline, B::PipelineLayout), pso::CreationError> {
+    let pipeline_layout = unsafe {
+        factory
+            .device()
+            .create_pipeline_layout(layouts, None as Option<(_, _)>)
+    }?;
+
+    let shader_vertex = unsafe { super::FULLSCREEN_VERTEX.module(factory).unwrap() };
+    let shader_fragment = match unsafe { fragment.module(factory) } {
+        Ok(module) => module,
+        Err(_) => {
+            unsafe {
+                factory.destroy_shader_module(shader_vertex);
+                factory.device().destroy_pipeline_layout(pipeline_layout);
+            }
+            return Err(pso::CreationError::Other);
+        }
+    };
+
+    let pipes = PipelinesBuilder::new()
+        .with_pipeline(
+            PipelineDescBuilder::new()
+                .with_shaders(util::simple_shader_set(
+                    &shader_vertex,
+                    Some(&shader_fragment),
+                ))
+                .with_layout(&pipeline_layout)
+                .with_subpass(subpass)
+                .with_framebuffer_size(framebuffer_width, framebuffer_height)
+                .with_blend_targets(vec![pso::ColorBlendDesc {
+                    mask: pso::ColorMask::ALL,
+                    blend: None,
+                }]),
+        )
+        .build(factory, None);
+
+    unsafe {
+        factory.destroy_shader_module(shader_vertex);
+        factory.destroy_shader_module(shader_fragment);
+    }
+
+    match pipes {
+        Err(e) => {
+            unsafe {
+                factory.device().destroy_pipeline_layout(pipeline_layout);
+            }
+            Err(e)
+        }
+        Ok(mut pipes) => Ok((pipes.remove(0), pipeline_layout)),
+    }
+}
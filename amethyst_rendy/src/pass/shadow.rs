@@ -0,0 +1,288 @@
+use amethyst_assets::{AssetHandle, AssetStorage, Handle, LoadHandle};
+use amethyst_core::{ecs::IntoQuery, transform::Transform};
+use derivative::Derivative;
+use glsl_layout::Uniform;
+use rendy::{
+    command::{QueueId, RenderPassEncoder},
+    factory::Factory,
+    graph::{
+        render::{PrepareResult, RenderGroup, RenderGroupDesc},
+        GraphContext, NodeBuffer, NodeImage,
+    },
+    hal::{self, device::Device, pso},
+    mesh::{AsVertex, Position},
+    shader::Shader,
+};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{
+    batch::OneLevelBatch,
+    camera::Viewport,
+    pipeline::{PipelineDescBuilder, PipelinesBuilder},
+    pod::{VertexArgs, ViewArgs},
+    shadow::ShadowMaps,
+    skinning::JointTransforms,
+    submodules::{DynamicUniform, DynamicVertexBuffer},
+    system::GraphAuxData,
+    types::{Backend, Mesh},
+    util,
+};
+
+/// Describes drawing the shadow casters of every view of [`ShadowMaps`] into their tiles of the
+/// shadow atlas, the depth output of `Target::ShadowMap`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DrawShadowsDesc;
+
+impl DrawShadowsDesc {
+    /// Create instance of [`DrawShadows`] render group
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<B: Backend> RenderGroupDesc<B, GraphAuxData> for DrawShadowsDesc {
+    fn colors(&self) -> usize {
+        0
+    }
+
+    fn build(
+        self,
+        _ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        _queue: QueueId,
+        _aux: &GraphAuxData,
+        framebuffer_width: u32,
+        framebuffer_height: u32,
+        subpass: hal::pass::Subpass<'_, B>,
+        _buffers: Vec<NodeBuffer>,
+        _images: Vec<NodeImage>,
+    ) -> Result<Box<dyn RenderGroup<B, GraphAuxData>>, pso::CreationError> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("build");
+
+        let view = DynamicUniform::new(factory, pso::ShaderStageFlags::VERTEX)?;
+        let (pipeline, pipeline_layout) = build_shadow_pipeline(
+            factory,
+            subpass,
+            framebuffer_width,
+            framebuffer_height,
+            vec![view.raw_layout()],
+        )?;
+
+        Ok(Box::new(DrawShadows::<B> {
+            pipeline,
+            pipeline_layout,
+            views: vec![view],
+            tiles: Vec::new(),
+            batches: Vec::new(),
+            models: DynamicVertexBuffer::new(),
+            framebuffer_width,
+            framebuffer_height,
+        }))
+    }
+}
+
+/// Draws the depth of the shadow casters into the shadow atlas.
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""))]
+pub struct DrawShadows<B: Backend> {
+    pipeline: B::GraphicsPipeline,
+    pipeline_layout: B::PipelineLayout,
+    views: Vec<DynamicUniform<B, ViewArgs>>,
+    tiles: Vec<Viewport>,
+    batches: Vec<OneLevelBatch<LoadHandle, VertexArgs>>,
+    models: DynamicVertexBuffer<B, VertexArgs>,
+    framebuffer_width: u32,
+    framebuffer_height: u32,
+}
+
+impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawShadows<B> {
+    fn prepare(
+        &mut self,
+        factory: &Factory<B>,
+        _queue: QueueId,
+        index: usize,
+        _subpass: hal::pass::Subpass<'_, B>,
+        aux: &GraphAuxData,
+    ) -> PrepareResult {
+        #[cfg(feature = "profiler")]
+        profile_scope!("prepare");
+
+        let GraphAuxData { world, resources } = aux;
+
+        self.tiles.clear();
+        let shadow_maps = match resources.get::<ShadowMaps>() {
+            Some(shadow_maps) => shadow_maps,
+            None => return PrepareResult::DrawRecord,
+        };
+        let mesh_storage = resources.get::<AssetStorage<Mesh>>().unwrap();
+
+        self.batches
+            .resize_with(shadow_maps.views.len(), OneLevelBatch::default);
+        let mut query = <(&Handle<Mesh>, &Transform, Option<&JointTransforms>)>::query();
+        for (view, (shadow_view, batch)) in shadow_maps
+            .views
+            .iter()
+            .zip(self.batches.iter_mut())
+            .enumerate()
+        {
+            if view == self.views.len() {
+                let uniform = self.views[0].with_same_layout();
+                self.views.push(uniform);
+            }
+            let proj: [[f32; 4]; 4] = shadow_view.proj.into();
+            let view_matrix: [[f32; 4]; 4] = shadow_view.view.into();
+            let proj_view: [[f32; 4]; 4] = (shadow_view.proj * shadow_view.view).into();
+            self.views[view].write(
+                factory,
+                index,
+                ViewArgs {
+                    proj: proj.into(),
+                    view: view_matrix.into(),
+                    proj_view: proj_view.into(),
+                }
+                .std140(),
+            );
+            self.tiles.push(shadow_view.tile);
+
+            batch.clear_inner();
+            for entity in &shadow_view.casters {
+                let (mesh, transform) = match query.get(*world, *entity) {
+                    Ok((mesh, transform, None)) => (mesh, transform),
+                    _ => continue,
+                };
+                let mesh_id = mesh.load_handle();
+                if mesh_storage.contains(mesh_id) {
+                    batch.insert(mesh_id, Some(VertexArgs::from_object_data(transform, None)));
+                }
+            }
+            batch.prune();
+        }
+
+        // Instances of every shadow view share one buffer, laid out view after view.
+        self.models.write(
+            factory,
+            index,
+            self.batches.iter().map(OneLevelBatch::count).sum::<usize>() as u64,
+            self.batches.iter().flat_map(OneLevelBatch::data),
+        );
+
+        PrepareResult::DrawRecord
+    }
+
+    fn draw_inline(
+        &mut self,
+        mut encoder: RenderPassEncoder<'_, B>,
+        index: usize,
+        _subpass: hal::pass::Subpass<'_, B>,
+        aux: &GraphAuxData,
+    ) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("draw");
+
+        if self.tiles.is_empty() {
+            return;
+        }
+        let mesh_storage = aux.resources.get::<AssetStorage<Mesh>>().unwrap();
+        let vertex_format = [Position::vertex()];
+
+        encoder.bind_graphics_pipeline(&self.pipeline);
+        if !self.models.bind(index, 1, 0, &mut encoder) {
+            return;
+        }
+
+        let mut offset = 0;
+        for (view, tile) in self.tiles.iter().enumerate() {
+            util::set_viewport(
+                &mut encoder,
+                tile,
+                self.framebuffer_width,
+                self.framebuffer_height,
+            );
+            self.views[view].bind(index, &self.pipeline_layout, 0, &mut encoder);
+
+            let batch = &self.batches[view];
+            for (mesh_id, range) in batch.iter() {
+                if let Some(mesh) = B::unwrap_mesh(
+                    mesh_storage
+                        .get_for_load_handle(*mesh_id)
+                        .expect("Could not get mesh."),
+                ) {
+                    if let Err(error) = mesh.bind_and_draw(
+                        0,
+                        &vertex_format,
+                        range.start + offset..range.end + offset,
+                        &mut encoder,
+                    ) {
+                        log::warn!(
+                            "Trying to draw a shadow of a mesh that lacks {:?} vertex attributes.",
+                            error.not_found.attributes,
+                        );
+                    }
+                }
+            }
+            offset += batch.count() as u32;
+        }
+    }
+
+    fn dispose(self: Box<Self>, factory: &mut Factory<B>, _aux: &GraphAuxData) {
+        unsafe {
+            factory.device().destroy_graphics_pipeline(self.pipeline);
+            factory
+                .device()
+                .destroy_pipeline_layout(self.pipeline_layout);
+        }
+    }
+}
+
+fn build_shadow_pipeline<B: Backend>(
+    factory: &Factory<B>,
+    subpass: hal::pass::Subpass<'_, B>,
+    framebuffer_width: u32,
+    framebuffer_height: u32,
+    layouts: Vec<&B::DescriptorSetLayout>,
+) -> Result<(B::GraphicsPipeline, B::PipelineLayout), pso::CreationError> {
+    let pipeline_layout = unsafe {
+        factory
+            .device()
+            .create_pipeline_layout(layouts, None as Option<(_, _)>)
+    }?;
+
+    let shader_vertex = unsafe { super::SHADOW_VERTEX.module(factory).unwrap() };
+
+    // Only depth is written, both faces are drawn so thin and open meshes cast shadows.
+    let pipes = PipelinesBuilder::new()
+        .with_pipeline(
+            PipelineDescBuilder::new()
+                .with_vertex_desc(&[
+                    (Position::vertex(), pso::VertexInputRate::Vertex),
+                    (VertexArgs::vertex(), pso::VertexInputRate::Instance(1)),
+                ])
+                .with_shaders(util::simple_shader_set(&shader_vertex, None))
+                .with_layout(&pipeline_layout)
+                .with_subpass(subpass)
+                .with_framebuffer_size(framebuffer_width, framebuffer_height)
+                .with_dynamic_viewport()
+                .with_depth_test(pso::DepthTest {
+                    fun: pso::Comparison::Greater,
+                    write: true,
+                }),
+        )
+        .build(factory, None);
+
+    unsafe {
+        factory.destroy_shader_module(shader_vertex);
+    }
+
+    match pipes {
+        Err(e) => {
+            unsafe {
+                factory.device().destroy_pipeline_layout(pipeline_layout);
+            }
+            Err(e)
+        }
+        Ok(mut pipes) => Ok((pipes.remove(0), pipeline_layout)),
+    }
+}
@@ -15,12 +15,14 @@ use rendy::{
 use thread_profiler::profile_scope;
 
 use crate::{
+    bundle::Target,
+    camera::Viewport,
     palette::Srgb,
     pass,
     pipeline::{PipelineDescBuilder, PipelinesBuilder},
     pod::IntoPod,
     shape::Shape,
-    submodules::{DynamicUniform, FlatEnvironmentSub},
+    submodules::{gather::CameraGatherer, DynamicUniform, FlatEnvironmentSub},
     system::GraphAuxData,
     types::Backend,
     util,
@@ -62,6 +64,7 @@ impl SkyboxSettings {
 #[derivative(Default(bound = ""))]
 pub struct DrawSkyboxDesc {
     default_settings: SkyboxSettings,
+    target: Target,
 }
 
 impl DrawSkyboxDesc {
@@ -79,8 +82,16 @@ impl DrawSkyboxDesc {
                 nadir_color,
                 zenith_color,
             },
+            target: Target::Main,
         }
     }
+
+    /// Draw the skybox behind the cameras rendering to `target`, `Target::Main` by default.
+    #[must_use]
+    pub fn with_target(mut self, target: Target) -> Self {
+        self.target = target;
+        self
+    }
 }
 
 impl<B: Backend> RenderGroupDesc<B, GraphAuxData> for DrawSkyboxDesc {
@@ -120,12 +131,16 @@ impl<B: Backend> RenderGroupDesc<B, GraphAuxData> for DrawSkyboxDesc {
         )?;
 
         Ok(Box::new(DrawSkybox::<B> {
+            target: self.target,
             pipeline,
             pipeline_layout,
             env,
             colors,
             mesh,
             default_settings: self.default_settings,
+            viewports: Vec::new(),
+            framebuffer_width,
+            framebuffer_height,
         }))
     }
 }
@@ -133,12 +148,16 @@ impl<B: Backend> RenderGroupDesc<B, GraphAuxData> for DrawSkyboxDesc {
 /// Draw a skybox around the camera view
 #[derive(Debug)]
 pub struct DrawSkybox<B: Backend> {
+    target: Target,
     pipeline: B::GraphicsPipeline,
     pipeline_layout: B::PipelineLayout,
     env: FlatEnvironmentSub<B>,
     colors: DynamicUniform<B, SkyboxUniform>,
     mesh: Mesh<B>,
     default_settings: SkyboxSettings,
+    viewports: Vec<Viewport>,
+    framebuffer_width: u32,
+    framebuffer_height: u32,
 }
 
 impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawSkybox<B> {
@@ -158,8 +177,27 @@ impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawSkybox<B> {
             .get::<SkyboxSettings>()
             .map_or_else(|| self.default_settings.uniform(), |s| s.uniform());
 
-        self.env.process(factory, index, aux.world, aux.resources);
-        let changed = self.colors.write(factory, index, settings);
+        let views: Vec<_> = CameraGatherer::gather_views(aux.world, aux.resources)
+            .into_iter()
+            .filter(|(_, _, target)| *target == self.target.camera_target())
+            .collect();
+        self.env.process_views(
+            factory,
+            index,
+            aux.world,
+            views.iter().map(|(camera, _, _)| *camera),
+        );
+        let mut changed = self.colors.write(factory, index, settings);
+        if !self
+            .viewports
+            .iter()
+            .eq(views.iter().map(|(_, view, _)| &view.viewport))
+        {
+            self.viewports.clear();
+            self.viewports
+                .extend(views.iter().map(|(_, view, _)| view.viewport));
+            changed = true;
+        }
 
         if changed {
             PrepareResult::DrawRecord
@@ -178,14 +216,23 @@ impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawSkybox<B> {
         #[cfg(feature = "profiler")]
         profile_scope!("draw");
         encoder.bind_graphics_pipeline(&self.pipeline);
-        self.env.bind(index, &self.pipeline_layout, 0, &mut encoder);
         self.colors
             .bind(index, &self.pipeline_layout, 1, &mut encoder);
         self.mesh
             .bind(0, &[PosTex::vertex()], &mut encoder)
             .unwrap();
-        unsafe {
-            encoder.draw(0..self.mesh.len(), 0..1);
+        for (view, viewport) in self.viewports.iter().enumerate() {
+            util::set_viewport(
+                &mut encoder,
+                viewport,
+                self.framebuffer_width,
+                self.framebuffer_height,
+            );
+            self.env
+                .bind_view(index, view, &self.pipeline_layout, 0, &mut encoder);
+            unsafe {
+                encoder.draw(0..self.mesh.len(), 0..1);
+            }
         }
     }
 
@@ -226,6 +273,7 @@ fn build_skybox_pipeline<B: Backend>(
                 .with_layout(&pipeline_layout)
                 .with_subpass(subpass)
                 .with_framebuffer_size(framebuffer_width, framebuffer_height)
+                .with_dynamic_viewport()
                 .with_depth_test(pso::DepthTest {
                     fun: pso::Comparison::GreaterEqual,
                     write: false,
@@ -0,0 +1,494 @@
+//! Picking of the entities hit by a ray, such as the one under the mouse cursor.
+use std::{cmp::Ordering, collections::HashMap};
+
+use amethyst_assets::{AssetHandle, AssetStorage, Handle, LoadHandle};
+use amethyst_core::{
+    ecs::{world::EntryRef, Entity, EntityStore},
+    geometry::Ray,
+    math::Point3,
+    transform::Transform,
+};
+#[cfg(feature = "window")]
+pub use window::{MousePick, MousePickingBundle, MousePickingSystem};
+
+use crate::{
+    camera::Camera,
+    mesh_vertices::MeshVertices,
+    spatial::{Aabb, SpatialIndex},
+    sprite::{Sprite, SpriteRender, SpriteSheet, Sprites},
+    static_batch::StaticBatch,
+    types::Mesh,
+    visibility::BoundingSphere,
+};
+
+/// An entity hit by a ray.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PickHit {
+    /// The entity which was hit.
+    pub entity: Entity,
+    /// Distance along the ray to the hit.
+    pub distance: f32,
+    /// Point hit in world space.
+    pub point: Point3<f32>,
+}
+
+/// Bounds of the mesh of an entity in its local space, which picking tests instead of the
+/// `BoundingSphere` when the entity has no [`MeshVertices`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshBounds(pub Aabb);
+
+/// Returns the entities hit by `ray` within `max_distance`, the nearest first.
+///
+/// Candidates are the entities whose bounding sphere in the [`SpatialIndex`] is hit, and which
+/// have a mesh, a `BoundingSphere`, [`MeshVertices`] or [`MeshBounds`]. Entities with
+/// `MeshVertices` are then tested against their triangles, entities with `MeshBounds` against their
+/// box, and the others are hit on their bounding sphere. Cameras and static batches are never hit,
+/// and sprites only by [`pick_sprites`].
+///
+/// `ray.direction` must be normalized, like the rays of `Camera::screen_ray`. When called from a
+/// system, `world` must have read access to `Transform`, `MeshVertices`, `MeshBounds`,
+/// `BoundingSphere`, `Handle<Mesh>`, `Camera`, `SpriteRender` and `StaticBatch`.
+pub fn pick<W>(world: &W, index: &SpatialIndex, ray: &Ray<f32>, max_distance: f32) -> Vec<PickHit>
+where
+    W: EntityStore + ?Sized,
+{
+    let mut hits: Vec<PickHit> = index
+        .ray_cast(ray, max_distance)
+        .into_iter()
+        .filter_map(|candidate| {
+            let entry = world.entry_ref(candidate.entity).ok()?;
+            if !is_pickable(&entry) {
+                return None;
+            }
+            let mesh = entry.get_component::<MeshVertices>().ok();
+            let bounds = entry.get_component::<MeshBounds>().ok();
+            let distance = if mesh.is_some() || bounds.is_some() {
+                let local = local_ray(entry.get_component::<Transform>().ok()?, ray)?;
+                match mesh {
+                    Some(mesh) => mesh.ray_distance(&local)?,
+                    None => bounds?.0.ray_distance(&local)?,
+                }
+            } else {
+                candidate.distance
+            };
+            (distance <= max_distance).then(|| {
+                PickHit {
+                    entity: candidate.entity,
+                    distance,
+                    point: ray.at_distance(distance),
+                }
+            })
+        })
+        .collect();
+    sort_hits(&mut hits);
+    hits
+}
+
+/// Entities in the spatial index without a shape, like cameras, lights and the parents of other
+/// entities, only have the default bounding sphere and can't be hit. Static batches are picked
+/// through the entities merged into them.
+fn is_pickable(entry: &EntryRef<'_>) -> bool {
+    let has_shape = entry.get_component::<Handle<Mesh>>().is_ok()
+        || entry.get_component::<BoundingSphere>().is_ok()
+        || entry.get_component::<MeshVertices>().is_ok()
+        || entry.get_component::<MeshBounds>().is_ok();
+    has_shape
+        && entry.get_component::<Camera>().is_err()
+        && entry.get_component::<SpriteRender>().is_err()
+        && entry.get_component::<StaticBatch>().is_err()
+}
+
+/// Returns the sprites hit by `ray` within `max_distance`, the nearest first.
+///
+/// Each sprite is a rectangle of the size of its sprite in the sprite sheet, on the XY plane of
+/// its entity. Sprites whose sprite sheet isn't loaded can't be hit, and hidden sprites should be
+/// left out of `sprites`.
+pub fn pick_sprites<'a, I>(
+    sprites: I,
+    sprite_sheets: &AssetStorage<SpriteSheet>,
+    sprite_lists: &AssetStorage<Sprites>,
+    ray: &Ray<f32>,
+    max_distance: f32,
+) -> Vec<PickHit>
+where
+    I: IntoIterator<Item = (Entity, &'a SpriteRender, &'a Transform)>,
+{
+    // The sprites of each sheet are only built for the first of its sprites.
+    let mut sheets: HashMap<LoadHandle, Option<Vec<Sprite>>> = HashMap::new();
+    let mut hits: Vec<PickHit> = sprites
+        .into_iter()
+        .filter_map(|(entity, render, transform)| {
+            let sheet_sprites = sheets
+                .entry(render.sprite_sheet.load_handle())
+                .or_insert_with(|| {
+                    let sheet = sprite_sheets.get(&render.sprite_sheet)?;
+                    Some(sprite_lists.get(&sheet.sprites)?.build_sprites())
+                });
+            let sprite = sheet_sprites.as_ref()?.get(render.sprite_number)?;
+
+            let local = local_ray(transform, ray)?;
+            if local.direction.z.abs() <= f32::EPSILON {
+                return None;
+            }
+            let distance = -local.origin.z / local.direction.z;
+            if distance < 0.0 || distance > max_distance {
+                return None;
+            }
+            // Sprites are centered on their entity, shifted by their offsets.
+            let point = local.at_distance(distance);
+            let x = point.x + sprite.offsets[0];
+            let y = point.y + sprite.offsets[1];
+            let inside = x.abs() <= sprite.width * 0.5 && y.abs() <= sprite.height * 0.5;
+            inside.then(|| {
+                PickHit {
+                    entity,
+                    distance,
+                    point: ray.at_distance(distance),
+                }
+            })
+        })
+        .collect();
+    sort_hits(&mut hits);
+    hits
+}
+
+fn sort_hits(hits: &mut [PickHit]) {
+    hits.sort_by(|a, b| {
+        a.distance
+            .partial_cmp(&b.distance)
+            .unwrap_or(Ordering::Equal)
+    });
+}
+
+/// Returns `ray` in the local space of `transform`, distances along both rays are the same.
+fn local_ray(transform: &Transform, ray: &Ray<f32>) -> Option<Ray<f32>> {
+    let inverse = transform.global_matrix().try_inverse()?;
+    Some(Ray {
+        origin: inverse.transform_point(&ray.origin),
+        direction: inverse.transform_vector(&ray.direction),
+    })
+}
+
+#[cfg(feature = "window")]
+mod window {
+    use amethyst_assets::{AssetStorage, Handle};
+    use amethyst_core::{
+        ecs::{
+            component, DispatcherBuilder, Entity, EntityStore, IntoQuery, ParallelRunnable,
+            Resources, System, SystemBuilder, SystemBundle, World,
+        },
+        geometry::Ray,
+        math::{Point2, Vector2},
+        transform::Transform,
+        Hidden, HiddenPropagate,
+    };
+    use amethyst_error::Error;
+    use amethyst_input::InputHandler;
+    use amethyst_window::ScreenDimensions;
+    #[cfg(feature = "profiler")]
+    use thread_profiler::profile_scope;
+
+    use super::{pick, pick_sprites, sort_hits, MeshBounds, PickHit};
+    use crate::{
+        bundle::Target,
+        camera::{ActiveCamera, Camera, CameraView, RenderLayers},
+        mesh_vertices::MeshVertices,
+        render_texture::{self, RenderTexture},
+        spatial::SpatialIndex,
+        sprite::{SpriteRender, SpriteSheet, Sprites},
+        static_batch::StaticBatch,
+        types::Mesh,
+        visibility::{entity_layers, BoundingSphere},
+    };
+
+    /// Resource with the entities under the mouse cursor, updated by the
+    /// [`MousePickingSystem`].
+    #[derive(Debug, Default, Clone)]
+    pub struct MousePick {
+        /// Ray through the mouse cursor from the topmost camera whose viewport contains it,
+        /// `None` if there is no mouse position or no such camera.
+        pub ray: Option<Ray<f32>>,
+        /// Entities and sprites hit by the ray on the layers seen by the camera, the nearest
+        /// first.
+        pub hits: Vec<PickHit>,
+    }
+
+    impl MousePick {
+        /// Returns the nearest entity under the mouse cursor.
+        #[must_use]
+        pub fn first(&self) -> Option<&PickHit> {
+            self.hits.first()
+        }
+    }
+
+    /// Picks the entities under the mouse position of the `InputHandler`, as seen by the topmost
+    /// rendered camera whose `CameraView` contains it, and writes them to the [`MousePick`]
+    /// resource.
+    #[derive(Debug)]
+    pub struct MousePickingSystem {
+        max_distance: f32,
+    }
+
+    impl System for MousePickingSystem {
+        fn build(self) -> Box<dyn ParallelRunnable> {
+            let max_distance = self.max_distance;
+            Box::new(
+                SystemBuilder::new("MousePickingSystem")
+                    .read_resource::<InputHandler>()
+                    .read_resource::<ActiveCamera>()
+                    .read_resource::<ScreenDimensions>()
+                    .read_resource::<SpatialIndex>()
+                    .read_resource::<AssetStorage<SpriteSheet>>()
+                    .read_resource::<AssetStorage<Sprites>>()
+                    .write_resource::<MousePick>()
+                    .read_component::<Transform>()
+                    .read_component::<MeshVertices>()
+                    .read_component::<MeshBounds>()
+                    .read_component::<BoundingSphere>()
+                    .read_component::<Handle<Mesh>>()
+                    .read_component::<Camera>()
+                    .read_component::<SpriteRender>()
+                    .read_component::<StaticBatch>()
+                    .read_component::<RenderLayers>()
+                    .with_query(<(&Camera, &Transform)>::query())
+                    .with_query(<(
+                        Entity,
+                        &Camera,
+                        Option<&CameraView>,
+                        Option<&RenderTexture>,
+                    )>::query())
+                    .with_query(
+                        <(Entity, &SpriteRender, &Transform)>::query()
+                            .filter(!component::<Hidden>() & !component::<HiddenPropagate>()),
+                    )
+                    .build(
+                        move |_,
+                              world,
+                              (
+                            input,
+                            active_camera,
+                            screen,
+                            index,
+                            sprite_sheets,
+                            sprite_lists,
+                            mouse_pick,
+                        ),
+                              (camera_query, view_query, sprite_query)| {
+                            #[cfg(feature = "profiler")]
+                            profile_scope!("mouse_picking_system");
+
+                            mouse_pick.hits.clear();
+                            mouse_pick.ray = None;
+
+                            let (x, y) = match input.mouse_position() {
+                                Some(position) => position,
+                                None => return,
+                            };
+                            let screen_size = Vector2::new(screen.width(), screen.height());
+                            // The camera drawn last on top of the cursor is the one seen there,
+                            // cameras drawing to a render texture are not on the screen.
+                            let views = render_texture::target_views(
+                                active_camera.entity,
+                                view_query.iter(world).map(|(entity, _, view, texture)| {
+                                    (*entity, view, texture.map(RenderTexture::target))
+                                }),
+                            );
+                            let picked = views
+                                .iter()
+                                .rev()
+                                .filter(|(_, _, target)| *target == Target::Main)
+                                .find_map(|(entity, view, _)| {
+                                    let (position, size) = view
+                                        .viewport
+                                        .screen_to_viewport(Point2::new(x, y), screen_size)?;
+                                    let (camera, transform) =
+                                        camera_query.get(world, *entity).ok()?;
+                                    Some((
+                                        camera.screen_ray(position, size, transform),
+                                        view.layers,
+                                    ))
+                                });
+                            let (ray, layers) = match picked {
+                                Some(picked) => picked,
+                                None => return,
+                            };
+
+                            mouse_pick.hits = pick(world, index, &ray, max_distance);
+                            mouse_pick.hits.extend(pick_sprites(
+                                sprite_query.iter(world).map(|(e, s, t)| (*e, s, t)),
+                                sprite_sheets,
+                                sprite_lists,
+                                &ray,
+                                max_distance,
+                            ));
+                            mouse_pick.hits.retain(|hit| {
+                                world
+                                    .entry_ref(hit.entity)
+                                    .map_or(false, |entry| layers.intersects(entity_layers(&entry)))
+                            });
+                            sort_hits(&mut mouse_pick.hits);
+                            mouse_pick.ray = Some(ray);
+                        },
+                    ),
+            )
+        }
+    }
+
+    /// Inserts the [`MousePick`] resource and adds the [`MousePickingSystem`].
+    ///
+    /// Requires the `InputBundle`, the `WindowBundle` and a `RenderBase3D` or `RenderFlat2D`
+    /// plugin, which maintain the `SpatialIndex`.
+    #[derive(Debug)]
+    pub struct MousePickingBundle {
+        max_distance: f32,
+    }
+
+    impl Default for MousePickingBundle {
+        fn default() -> Self {
+            Self {
+                max_distance: f32::INFINITY,
+            }
+        }
+    }
+
+    impl MousePickingBundle {
+        /// Ignores the entities further than `max_distance` from the camera.
+        #[must_use]
+        pub fn with_max_distance(mut self, max_distance: f32) -> Self {
+            self.max_distance = max_distance;
+            self
+        }
+    }
+
+    impl SystemBundle for MousePickingBundle {
+        fn load(
+            &mut self,
+            _world: &mut World,
+            resources: &mut Resources,
+            builder: &mut DispatcherBuilder,
+        ) -> Result<(), Error> {
+            resources.insert(MousePick::default());
+            builder.add_system(MousePickingSystem {
+                max_distance: self.max_distance,
+            });
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::Vector3;
+
+    use super::*;
+    use crate::shape::Shape;
+
+    fn ray(origin: [f32; 3], direction: [f32; 3]) -> Ray<f32> {
+        Ray {
+            origin: Point3::from(origin),
+            direction: Vector3::from(direction).normalize(),
+        }
+    }
+
+    #[test]
+    fn local_ray_keeps_distances() {
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(10.0, 0.0, 0.0);
+        transform.set_scale(Vector3::new(2.0, 2.0, 2.0));
+        transform.copy_local_to_global();
+
+        let world_ray = ray([10.0, 0.0, 10.0], [0.0, 0.0, -1.0]);
+        let local = local_ray(&transform, &world_ray).unwrap();
+        let mesh = MeshVertices::from_shape(&Shape::Cube, None);
+        // The cube is scaled to a half size of 2 around x = 10.
+        let distance = mesh.ray_distance(&local).unwrap();
+        assert!((distance - 8.0).abs() < 1e-5);
+        assert!((world_ray.at_distance(distance) - Point3::new(10.0, 0.0, 2.0)).norm() < 1e-5);
+    }
+
+    #[cfg(feature = "window")]
+    #[test]
+    #[allow(deprecated)] // `modifiers` of `CursorMoved` is deprecated but still mandatory.
+    fn mouse_pick_ignores_camera_batches_and_entities_without_shape() {
+        use amethyst_assets::{DefaultLoader, Loader};
+        use amethyst_core::{
+            ecs::{DispatcherBuilder, Resources, World},
+            shrev::EventChannel,
+        };
+        use amethyst_input::InputHandler;
+        use amethyst_window::ScreenDimensions;
+        use winit::{
+            dpi::PhysicalPosition,
+            event::{DeviceId, Event, ModifiersState, WindowEvent},
+            window::WindowId,
+        };
+
+        use crate::{camera::ActiveCamera, spatial::world_sphere};
+
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        let at = |z: f32| {
+            let mut transform = Transform::default();
+            transform.set_translation_xyz(0.0, 0.0, z);
+            transform.copy_local_to_global();
+            transform
+        };
+        let camera = world.push((
+            Camera::perspective(1.0, std::f32::consts::FRAC_PI_3, 0.1),
+            at(5.0),
+        ));
+        // Between the camera and the target, with the default bounding sphere.
+        let pivot = world.push((at(0.0),));
+        let target = world.push((at(-2.0), BoundingSphere::origin(1.0)));
+        // Picked through the entities merged into it.
+        let batch = world.push((
+            at(-2.0),
+            BoundingSphere::origin(3.0),
+            StaticBatch {
+                entities: vec![target],
+            },
+        ));
+
+        let mut index = SpatialIndex::default();
+        for entity in &[camera, pivot, target, batch] {
+            let entry = world.entry_ref(*entity).unwrap();
+            let sphere = world_sphere(
+                entry.get_component::<Transform>().unwrap(),
+                entry.get_component::<BoundingSphere>().ok(),
+            );
+            index.insert(*entity, sphere);
+        }
+
+        let mut input = InputHandler::new();
+        input.send_event(
+            &Event::WindowEvent {
+                window_id: unsafe { WindowId::dummy() },
+                event: WindowEvent::CursorMoved {
+                    device_id: unsafe { DeviceId::dummy() },
+                    position: PhysicalPosition::new(50.0, 50.0),
+                    modifiers: ModifiersState::default(),
+                },
+            },
+            &mut EventChannel::new(),
+        );
+        resources.insert(input);
+        resources.insert(ScreenDimensions::new(100, 100));
+        resources.insert(ActiveCamera {
+            entity: Some(camera),
+        });
+        resources.insert(index);
+        DefaultLoader::default().init_world(&mut resources);
+
+        let mut dispatcher = DispatcherBuilder::default()
+            .add_bundle(MousePickingBundle::default())
+            .build(&mut world, &mut resources)
+            .unwrap();
+        dispatcher.execute(&mut world, &mut resources);
+
+        let mouse_pick = resources.get::<MousePick>().unwrap();
+        assert!(mouse_pick.ray.is_some());
+        let hits: Vec<Entity> = mouse_pick.hits.iter().map(|hit| hit.entity).collect();
+        assert_eq!(hits, vec![target]);
+        assert!((mouse_pick.hits[0].point - Point3::new(0.0, 0.0, -1.0)).norm() < 1e-3);
+    }
+}
@@ -226,6 +226,21 @@ impl<'a, B: Backend> PipelineDescBuilder<'a, B> {
         });
     }
 
+    /// Build with the viewport and scissor set while drawing, with [`util::set_viewport`], so
+    /// the pipeline can draw to the viewport of each rendered camera.
+    ///
+    /// [`util::set_viewport`]: crate::util::set_viewport
+    #[must_use]
+    pub fn with_dynamic_viewport(mut self) -> Self {
+        self.set_dynamic_viewport();
+        self
+    }
+    /// Set to use the viewport and scissor set while drawing.
+    pub fn set_dynamic_viewport(&mut self) {
+        self.baked_states.viewport = None;
+        self.baked_states.scissor = None;
+    }
+
     /// Build with the provided `DepthTest`
     #[must_use]
     pub fn with_depth_test(mut self, depth_test: DepthTest) -> Self {
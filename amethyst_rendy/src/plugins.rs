@@ -3,20 +3,27 @@
 use amethyst_core::ecs::{DispatcherBuilder, Resources, World};
 use amethyst_error::Error;
 use palette::Srgb;
-use rendy::graph::render::RenderGroupDesc;
+use rendy::{
+    graph::render::RenderGroupDesc,
+    hal::command::{ClearDepthStencil, ClearValue},
+};
 #[cfg(feature = "window")]
 pub use window::RenderToWindow;
 
 use crate::{
     bundle,
-    bundle::{RenderOrder, RenderPlan, RenderPlugin, Target},
+    bundle::{
+        ImageOptions, RenderOrder, RenderPlan, RenderPlugin, Target, TargetImage, TargetPlanOutputs,
+    },
     pass::{
         Base3DPassDef, DrawBase3DDesc, DrawBase3DTransparentDesc, DrawDebugLinesDesc,
-        DrawFlat2DDesc, DrawFlat2DTransparentDesc, DrawSkyboxDesc,
+        DrawFlat2DDesc, DrawFlat2DTransparentDesc, DrawShadowsDesc, DrawSkyboxDesc,
     },
+    shadow::{ShadowMaps, ShadowSystem},
+    spatial::add_spatial_index,
     sprite_visibility::{SpriteVisibility, SpriteVisibilitySortingSystem},
     visibility::{Visibility, VisibilitySortingSystem},
-    Backend, Factory,
+    Backend, Factory, Format, Kind,
 };
 
 #[cfg(feature = "window")]
@@ -187,6 +194,7 @@ pub type RenderPbr3D = RenderBase3D<crate::pass::PbrPassDef>;
 pub struct RenderBase3D<D: Base3DPassDef> {
     target: Target,
     skinning: bool,
+    shadow_atlas_size: Option<u32>,
     marker: std::marker::PhantomData<D>,
 }
 
@@ -206,6 +214,17 @@ impl<D: Base3DPassDef> RenderBase3D<D> {
         self.skinning = true;
         self
     }
+
+    /// Enable shadows of the lights with [`ShadowSettings`](crate::light::ShadowSettings),
+    /// drawn into a shadow atlas of `atlas_size` by `atlas_size` pixels, see [`crate::shadow`].
+    ///
+    /// The atlas is shared by the 3D plugins of every target, the first plugin with shadows
+    /// sets its size.
+    #[must_use]
+    pub fn with_shadows(mut self, atlas_size: u32) -> Self {
+        self.shadow_atlas_size = Some(atlas_size);
+        self
+    }
 }
 
 impl<B: Backend, D: Base3DPassDef> RenderPlugin<B> for RenderBase3D<D> {
@@ -215,8 +234,18 @@ impl<B: Backend, D: Base3DPassDef> RenderPlugin<B> for RenderBase3D<D> {
         resources: &mut Resources,
         builder: &mut DispatcherBuilder,
     ) -> Result<(), Error> {
-        resources.insert(Visibility::default());
-        builder.add_system(VisibilitySortingSystem::default());
+        add_spatial_index(world, resources, builder);
+        // Shared by the 3D plugins of every target.
+        if !resources.contains::<Visibility>() {
+            resources.insert(Visibility::default());
+            builder.add_system(VisibilitySortingSystem::default());
+        }
+        if let Some(atlas_size) = self.shadow_atlas_size {
+            if !resources.contains::<ShadowMaps>() {
+                resources.insert(ShadowMaps::new(atlas_size));
+                builder.add_system(ShadowSystem::default());
+            }
+        }
         Ok(())
     }
 
@@ -225,22 +254,62 @@ impl<B: Backend, D: Base3DPassDef> RenderPlugin<B> for RenderBase3D<D> {
         plan: &mut RenderPlan<B>,
         _factory: &mut Factory<B>,
         _world: &World,
-        _resources: &Resources,
+        resources: &Resources,
     ) -> Result<(), Error> {
+        let shadows = self.shadow_atlas_size.is_some();
+        if shadows && !plan.is_defined(Target::ShadowMap) {
+            // Sized by the plugin which inserted the shadow maps.
+            let atlas_size = resources.get::<ShadowMaps>().map_or_else(
+                || ShadowMaps::new(self.shadow_atlas_size.unwrap_or(0)).atlas_size(),
+                |shadow_maps| shadow_maps.atlas_size(),
+            );
+            plan.define_pass(
+                Target::ShadowMap,
+                TargetPlanOutputs {
+                    colors: vec![],
+                    depth: Some(ImageOptions {
+                        kind: Kind::D2(atlas_size, atlas_size, 1, 1),
+                        levels: 1,
+                        format: Format::D32Sfloat,
+                        clear: Some(ClearValue {
+                            depth_stencil: ClearDepthStencil {
+                                depth: 0.0,
+                                stencil: 0,
+                            },
+                        }),
+                    }),
+                },
+            )?;
+            plan.extend_target(Target::ShadowMap, |ctx| {
+                ctx.add(RenderOrder::Opaque, DrawShadowsDesc::new().builder())?;
+                Ok(())
+            });
+        }
+
         let skinning = self.skinning;
+        let target = self.target;
         plan.extend_target(self.target, move |ctx| {
-            ctx.add(
-                RenderOrder::Opaque,
-                DrawBase3DDesc::<B, D>::new()
-                    .with_skinning(skinning)
-                    .builder(),
-            )?;
-            ctx.add(
-                RenderOrder::Transparent,
-                DrawBase3DTransparentDesc::<B, D>::new()
-                    .with_skinning(skinning)
-                    .builder(),
-            )?;
+            let shadow_map = if shadows {
+                Some(ctx.get_image(TargetImage::Depth(Target::ShadowMap))?)
+            } else {
+                None
+            };
+            let mut opaque = DrawBase3DDesc::<B, D>::new()
+                .with_skinning(skinning)
+                .with_shadows(shadows)
+                .with_target(target)
+                .builder();
+            let mut transparent = DrawBase3DTransparentDesc::<B, D>::new()
+                .with_skinning(skinning)
+                .with_shadows(shadows)
+                .with_target(target)
+                .builder();
+            if let Some(shadow_map) = shadow_map {
+                opaque = opaque.with_image(shadow_map);
+                transparent = transparent.with_image(shadow_map);
+            }
+            ctx.add(RenderOrder::Opaque, opaque)?;
+            ctx.add(RenderOrder::Transparent, transparent)?;
             Ok(())
         });
         Ok(())
@@ -270,8 +339,12 @@ impl<B: Backend> RenderPlugin<B> for RenderFlat2D {
         resources: &mut Resources,
         builder: &mut DispatcherBuilder,
     ) -> Result<(), Error> {
-        resources.insert(SpriteVisibility::default());
-        builder.add_system(SpriteVisibilitySortingSystem);
+        add_spatial_index(world, resources, builder);
+        // Shared by the 2D plugins of every target.
+        if !resources.contains::<SpriteVisibility>() {
+            resources.insert(SpriteVisibility::default());
+            builder.add_system(SpriteVisibilitySortingSystem);
+        }
         Ok(())
     }
 
@@ -282,11 +355,17 @@ impl<B: Backend> RenderPlugin<B> for RenderFlat2D {
         _world: &World,
         _resources: &Resources,
     ) -> Result<(), Error> {
-        plan.extend_target(self.target, |ctx| {
-            ctx.add(RenderOrder::Opaque, DrawFlat2DDesc::new().builder())?;
+        let target = self.target;
+        plan.extend_target(self.target, move |ctx| {
+            ctx.add(
+                RenderOrder::Opaque,
+                DrawFlat2DDesc::new().with_target(target).builder(),
+            )?;
             ctx.add(
                 RenderOrder::Transparent,
-                DrawFlat2DTransparentDesc::new().builder(),
+                DrawFlat2DTransparentDesc::new()
+                    .with_target(target)
+                    .builder(),
             )?;
             Ok(())
         });
@@ -318,10 +397,11 @@ impl<B: Backend> RenderPlugin<B> for RenderDebugLines {
         _world: &World,
         _resources: &Resources,
     ) -> Result<(), Error> {
-        plan.extend_target(self.target, |ctx| {
+        let target = self.target;
+        plan.extend_target(self.target, move |ctx| {
             ctx.add(
                 RenderOrder::BeforeTransparent,
-                DrawDebugLinesDesc::new().builder(),
+                DrawDebugLinesDesc::new().with_target(target).builder(),
             )?;
             Ok(())
         });
@@ -363,14 +443,18 @@ impl<B: Backend> RenderPlugin<B> for RenderSkybox {
         _resources: &Resources,
     ) -> Result<(), Error> {
         let colors = self.colors;
+        let target = self.target;
         plan.extend_target(self.target, move |ctx| {
             let group = if let Some((nadir, zenith)) = colors {
-                DrawSkyboxDesc::with_colors(nadir, zenith).builder()
+                DrawSkyboxDesc::with_colors(nadir, zenith)
             } else {
-                DrawSkyboxDesc::new().builder()
+                DrawSkyboxDesc::new()
             };
 
-            ctx.add(RenderOrder::AfterOpaque, group)?;
+            ctx.add(
+                RenderOrder::AfterOpaque,
+                group.with_target(target).builder(),
+            )?;
             Ok(())
         });
         Ok(())
@@ -4,13 +4,17 @@ use amethyst_core::{
     math::{convert, Matrix4, Vector4},
     transform::Transform,
 };
-use glsl_layout::{float, int, mat4, vec2, vec3, vec4, Uniform};
+use glsl_layout::{float, int, mat4, uvec3, vec2, vec3, vec4, Uniform};
 use rendy::{
     hal::format::Format,
     mesh::{AsAttribute, AsVertex, Model, VertexFormat},
 };
 
-use crate::{mtl, resources::Tint as TintComponent, Sprite};
+use crate::{
+    mtl,
+    resources::{InstanceData as InstanceDataComponent, Tint as TintComponent},
+    Sprite,
+};
 
 /// `TextureOffset`
 /// ```glsl
@@ -74,18 +78,57 @@ impl AsAttribute for Tint {
     const FORMAT: Format = Format::Rgba32Sfloat;
 }
 
+/// Custom instance data
+/// ```glsl
+/// vec4 instance_data;
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Uniform)]
+#[repr(C, align(16))]
+pub struct InstanceData {
+    /// Instance data as `Rgba32Sfloat`
+    pub instance_data: vec4,
+}
+
+impl AsAttribute for InstanceData {
+    const NAME: &'static str = "instance_data";
+    const FORMAT: Format = Format::Rgba32Sfloat;
+}
+
+/// Level of detail cross-fade
+/// ```glsl
+/// float lod_fade;
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Uniform)]
+#[repr(C, align(4))]
+pub struct LodFade {
+    /// Cross-fade as `R32Sfloat`, see [`VertexArgs::lod_fade`]
+    pub lod_fade: float,
+}
+
+impl AsAttribute for LodFade {
+    const NAME: &'static str = "lod_fade";
+    const FORMAT: Format = Format::R32Sfloat;
+}
+
 /// Instance-rate vertex arguments
 /// ```glsl
 ///  mat4 model;
 ///  vec4 tint;
+///  vec4 instance_data;
+///  float lod_fade;
 /// ```
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
-#[repr(C, align(16))]
+#[repr(C)]
 pub struct VertexArgs {
     /// Instance-rate model matrix
     pub model: mat4,
     /// Instance-rate model `Tint`
     pub tint: vec4,
+    /// Instance-rate custom data, see [`InstanceDataComponent`]
+    pub instance_data: vec4,
+    /// Instance-rate level of detail cross-fade. `1.0` draws the whole mesh, a fade `t` below
+    /// it dithers the incoming level in and `1.0 + t` dithers the outgoing level out.
+    pub lod_fade: float,
 }
 
 impl VertexArgs {
@@ -102,13 +145,36 @@ impl VertexArgs {
                 let (r, g, b, a) = t.0.into_linear().into_components();
                 [r, g, b, a].into()
             }),
+            instance_data: [0.0; 4].into(),
+            lod_fade: 1.0,
         }
     }
+
+    /// Sets the custom data of the instance from its `InstanceDataComponent`, zero without one.
+    #[inline]
+    #[must_use]
+    pub fn with_instance_data(mut self, data: Option<&InstanceDataComponent>) -> Self {
+        self.instance_data = data.map_or([0.0; 4], |data| data.0).into();
+        self
+    }
+
+    /// Sets the level of detail cross-fade of the instance, see [`VertexArgs::lod_fade`].
+    #[inline]
+    #[must_use]
+    pub fn with_lod_fade(mut self, fade: f32) -> Self {
+        self.lod_fade = fade;
+        self
+    }
 }
 
 impl AsVertex for VertexArgs {
     fn vertex() -> VertexFormat {
-        VertexFormat::new((Model::vertex(), Tint::vertex()))
+        VertexFormat::new((
+            Model::vertex(),
+            Tint::vertex(),
+            InstanceData::vertex(),
+            LodFade::vertex(),
+        ))
     }
 }
 
@@ -179,6 +245,9 @@ impl SkinnedVertexArgs {
 ///    vec3 position;
 ///    vec3 color;
 ///    float intensity;
+///    float radius;
+///    float smoothness;
+///    int shadow;
 /// };
 /// ```
 #[derive(Clone, Copy, Debug, Uniform)]
@@ -189,6 +258,12 @@ pub struct PointLight {
     pub color: vec3,
     /// Light intensity (0 - infinity)
     pub intensity: float,
+    /// Distance from the light beyond which nothing is lit
+    pub radius: float,
+    /// Smoothness of the falloff at the radius
+    pub smoothness: float,
+    /// Index of the first of the 6 shadow views of the light, or -1 without shadows
+    pub shadow: int,
 }
 
 /// directional light struct
@@ -197,6 +272,8 @@ pub struct PointLight {
 ///    vec3 color;
 ///    float intensity;
 ///    vec3 direction;
+///    int shadow;
+///    int cascades;
 /// };
 /// ```
 #[derive(Clone, Copy, Debug, Uniform)]
@@ -207,6 +284,10 @@ pub struct DirectionalLight {
     pub intensity: float,
     /// light cast direction vector
     pub direction: vec3,
+    /// Index of the shadow view of the first cascade, or -1 without shadows
+    pub shadow: int,
+    /// Number of shadow cascades
+    pub cascades: int,
 }
 
 /// spot light struct
@@ -219,6 +300,7 @@ pub struct DirectionalLight {
 ///    float intensity;
 ///    float range;
 ///    float smoothness;
+///    int shadow;
 /// };
 /// ```
 #[derive(Clone, Copy, Debug, Uniform)]
@@ -237,6 +319,62 @@ pub struct SpotLight {
     pub range: float,
     /// Spotlight smoothness
     pub smoothness: float,
+    /// Index of the shadow view of the light, or -1 without shadows
+    pub shadow: int,
+}
+
+/// area light struct
+/// ```glsl
+/// struct AreaLight {
+///    vec3 position;
+///    vec3 color;
+///    vec3 right;
+///    vec3 up;
+///    float intensity;
+///    float range;
+///    int shape;
+///    int two_sided;
+/// };
+/// ```
+#[derive(Clone, Copy, Debug, Uniform)]
+pub struct AreaLight {
+    /// Light world position
+    pub position: vec3,
+    /// Light color
+    pub color: vec3,
+    /// Half extent of the light along its local X axis, in world space
+    pub right: vec3,
+    /// Half extent of the light along its local Y axis, in world space
+    pub up: vec3,
+    /// Light intensity (0 - infinity)
+    pub intensity: float,
+    /// Distance from the light beyond which nothing is lit
+    pub range: float,
+    /// 0 for a rectangle, 1 for a disc
+    pub shape: int,
+    /// 1 if the light emits from both faces
+    pub two_sided: int,
+}
+
+/// shadow view struct
+/// ```glsl
+/// struct ShadowView {
+///    mat4 projview;
+///    vec4 tile;
+///    float bias;
+///    float normal_bias;
+/// };
+/// ```
+#[derive(Clone, Copy, Debug, Uniform)]
+pub struct ShadowView {
+    /// Premultiplied Proj-View matrix of the light
+    pub projview: mat4,
+    /// Tile of the shadow atlas as `(x, y, width, height)`, in fractions of the atlas size
+    pub tile: vec4,
+    /// Offset towards the light, in world units
+    pub bias: float,
+    /// Offset along the surface normal, in world units
+    pub normal_bias: float,
 }
 
 /// Environment Uniform
@@ -247,6 +385,10 @@ pub struct SpotLight {
 ///    int point_light_count;
 ///    int directional_light_count;
 ///    int spot_light_count;
+///    int area_light_count;
+///    uvec3 cluster_count;
+///    float cluster_depth_scale;
+///    float cluster_depth_bias;
 /// };
 /// ```
 #[derive(Clone, Copy, Debug, Uniform)]
@@ -261,6 +403,14 @@ pub struct Environment {
     pub directional_light_count: int,
     /// Number of spot lights
     pub spot_light_count: int,
+    /// Number of area lights
+    pub area_light_count: int,
+    /// Number of light clusters along the screen width, height and the depth
+    pub cluster_count: uvec3,
+    /// Scale of the logarithm of the view depth giving the depth slice of a cluster
+    pub cluster_depth_scale: float,
+    /// Bias of the logarithm of the view depth giving the depth slice of a cluster
+    pub cluster_depth_bias: float,
 }
 
 /// Material Uniform
@@ -0,0 +1,739 @@
+//! High dynamic range rendering and the post-processing chain presenting it to the window.
+//!
+//! The [`RenderPostProcess`] plugin defines [`Target::Hdr`], a floating point render target the
+//! 3D plugins draw into when added with `with_target(Target::Hdr)`, and draws it into
+//! `Target::Main` through a chain of full-screen passes:
+//!
+//! 1. the user effects of the [`PostStage::Linear`] stage, on linear HDR colors,
+//! 2. bloom, blurring the parts brighter than a threshold at half resolution,
+//! 3. tonemapping, mapping the HDR colors to displayable ones with the exposure and the
+//!    [`Tonemapper`], then applying the vignette and the color grading,
+//! 4. FXAA anti-aliasing,
+//! 5. the user effects of the [`PostStage::Display`] stage, on tonemapped colors.
+//!
+//! The chain is configured with the [`PostProcessSettings`] resource. Toggling bloom, FXAA or the
+//! color grading rebuilds the render graph, the other settings are applied every frame.
+//!
+//! ```no_run
+//! use amethyst::{
+//!     renderer::{
+//!         bundle::Target,
+//!         plugins::{RenderPbr3D, RenderToWindow},
+//!         post_process::{PostProcessSettings, RenderPostProcess, Tonemapper},
+//!         types::DefaultBackend,
+//!         RenderingBundle,
+//!     },
+//!     window::DisplayConfig,
+//! };
+//!
+//! let bundle = RenderingBundle::<DefaultBackend>::new()
+//!     .with_plugin(RenderToWindow::from_config(DisplayConfig::default()))
+//!     .with_plugin(RenderPostProcess::new())
+//!     .with_plugin(RenderPbr3D::default().with_target(Target::Hdr));
+//!
+//! let settings = PostProcessSettings {
+//!     exposure: 1.5,
+//!     tonemapper: Tonemapper::Uncharted2,
+//!     ..PostProcessSettings::default()
+//! };
+//! ```
+//!
+//! A custom effect is a fragment shader drawn over the whole target by a [`PostEffect`]. It
+//! receives the texture coordinates of the pixel at location 0 and writes the color at location
+//! 0. The image of the previous pass is bound at binding 0 of set 0, followed by the inputs added
+//! with [`PostEffect::with_image`] and [`PostEffect::with_texture`] in the order they are added,
+//! and by the uniform block of [`PostEffect::with_uniform`].
+//!
+//! ```glsl
+//! #version 450
+//!
+//! layout(set = 0, binding = 0) uniform sampler2D source;
+//! layout(std140, set = 0, binding = 1) uniform GrayscaleArgs {
+//!     float amount;
+//! };
+//!
+//! layout(location = 0) in vec2 tex_coord;
+//! layout(location = 0) out vec4 out_color;
+//!
+//! void main() {
+//!     vec3 color = texture(source, tex_coord).rgb;
+//!     float gray = dot(color, vec3(0.2126, 0.7152, 0.0722));
+//!     out_color = vec4(mix(color, vec3(gray), amount), 1.0);
+//! }
+//! ```
+
+use amethyst_assets::{AssetStorage, Handle};
+use amethyst_core::ecs::{DispatcherBuilder, Resources, World};
+use amethyst_error::{format_err, Error};
+use glsl_layout::{float, int, vec2, Uniform};
+use rendy::{
+    factory::Factory,
+    graph::render::RenderGroupDesc,
+    hal::command::{ClearColor, ClearDepthStencil, ClearValue},
+    shader::SpirvShader,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bundle::{
+        ImageOptions, OutputColor, RenderOrder, RenderPlan, RenderPlugin, Target, TargetImage,
+        TargetPlanOutputs,
+    },
+    pass::{
+        DrawPostEffectDesc, EffectUniform, BLOOM_BLUR_FRAGMENT, BLOOM_PREFILTER_FRAGMENT,
+        FXAA_FRAGMENT, TONEMAP_FRAGMENT,
+    },
+    types::{Backend, Texture},
+    Format, Kind,
+};
+
+const BLOOM_PREFILTER: Target = Target::Custom("post_bloom_prefilter");
+const BLOOM_BLUR_X: Target = Target::Custom("post_bloom_blur_x");
+const BLOOM_BLUR_Y: Target = Target::Custom("post_bloom_blur_y");
+const TONEMAP: Target = Target::Custom("post_tonemap");
+const FXAA: Target = Target::Custom("post_fxaa");
+
+/// Operator mapping the HDR colors to the displayable range.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Tonemapper {
+    /// Colors are clamped to 1.
+    None,
+    /// Reinhard operator, `color / (1 + color)`.
+    Reinhard,
+    /// Reinhard operator mapping `white` and brighter colors to 1.
+    ReinhardExtended {
+        /// Smallest color value mapped to 1.
+        white: f32,
+    },
+    /// Approximation of the ACES filmic curve by Krzysztof Narkowicz.
+    AcesFilmic,
+    /// Filmic curve by John Hable, used in Uncharted 2.
+    Uncharted2,
+}
+
+impl Tonemapper {
+    /// Operator index and white point read by the tonemapping shader.
+    fn args(self) -> (i32, f32) {
+        match self {
+            Tonemapper::None => (0, 1.0),
+            Tonemapper::Reinhard => (1, 1.0),
+            Tonemapper::ReinhardExtended { white } => (2, white),
+            Tonemapper::AcesFilmic => (3, 1.0),
+            Tonemapper::Uncharted2 => (4, 11.2),
+        }
+    }
+}
+
+impl Default for Tonemapper {
+    fn default() -> Self {
+        Tonemapper::AcesFilmic
+    }
+}
+
+/// Settings of the bloom pass of [`RenderPostProcess`].
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BloomSettings {
+    /// Brightness above which colors bloom, before the exposure is applied.
+    pub threshold: f32,
+    /// Width of the brightness range below the threshold fading into the bloom.
+    pub knee: f32,
+    /// Factor of the blurred colors added to the image.
+    pub intensity: f32,
+    /// Distance in half resolution pixels between the taps of the blur.
+    pub radius: f32,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        BloomSettings {
+            threshold: 1.0,
+            knee: 0.5,
+            intensity: 0.3,
+            radius: 1.0,
+        }
+    }
+}
+
+/// Settings of the vignette darkening the corners of the image.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct VignetteSettings {
+    /// Darkening of the corners, from 0 to 1.
+    pub intensity: f32,
+    /// Fraction of the distance from the corners to the center over which the darkening fades.
+    pub smoothness: f32,
+}
+
+impl Default for VignetteSettings {
+    fn default() -> Self {
+        VignetteSettings {
+            intensity: 0.4,
+            smoothness: 0.5,
+        }
+    }
+}
+
+/// Resource configuring the [`RenderPostProcess`] plugin, inserted with default values by the
+/// plugin if missing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PostProcessSettings {
+    /// Factor of the HDR colors before tonemapping.
+    pub exposure: f32,
+    /// Operator mapping the HDR colors to the displayable range.
+    pub tonemapper: Tonemapper,
+    /// Bloom settings, `None` to disable bloom.
+    pub bloom: Option<BloomSettings>,
+    /// Vignette settings, `None` to disable the vignette.
+    pub vignette: Option<VignetteSettings>,
+    /// Color grading lookup table, `None` to disable color grading.
+    ///
+    /// The texture is a strip of `size` slices of `size` by `size` pixels side by side, where
+    /// `size` is the height of the texture. The red component of the graded color selects the
+    /// column in a slice, the green component the row and the blue component the slice, all
+    /// encoded in sRGB. A table loaded in sRGB with the color of each pixel equal to its
+    /// coordinates leaves the colors unchanged.
+    pub color_grading: Option<Handle<Texture>>,
+    /// Whether to smooth the edges with FXAA.
+    pub fxaa: bool,
+}
+
+impl Default for PostProcessSettings {
+    fn default() -> Self {
+        PostProcessSettings {
+            exposure: 1.0,
+            tonemapper: Tonemapper::default(),
+            bloom: Some(BloomSettings::default()),
+            vignette: None,
+            color_grading: None,
+            fxaa: true,
+        }
+    }
+}
+
+impl PostProcessSettings {
+    fn tonemap_args(&self, color_grading: bool) -> ToneMapArgs {
+        let (tonemapper, white) = self.tonemapper.args();
+        let vignette = self.vignette.unwrap_or(VignetteSettings {
+            intensity: 0.0,
+            smoothness: 1.0,
+        });
+        ToneMapArgs {
+            exposure: self.exposure,
+            tonemapper,
+            white,
+            bloom_intensity: self.bloom.map_or(0.0, |bloom| bloom.intensity),
+            vignette_intensity: vignette.intensity,
+            vignette_smoothness: vignette.smoothness,
+            color_grading: color_grading.into(),
+        }
+    }
+}
+
+/// Settings of the frame, or the defaults if the resource is missing.
+fn read_settings<T>(resources: &Resources, read: impl FnOnce(&PostProcessSettings) -> T) -> T {
+    match resources.get::<PostProcessSettings>() {
+        Some(settings) => read(&settings),
+        None => read(&PostProcessSettings::default()),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Uniform)]
+struct ToneMapArgs {
+    exposure: float,
+    tonemapper: int,
+    white: float,
+    bloom_intensity: float,
+    vignette_intensity: float,
+    vignette_smoothness: float,
+    color_grading: int,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Uniform)]
+struct BloomArgs {
+    threshold: float,
+    knee: float,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Uniform)]
+struct BlurArgs {
+    direction: vec2,
+}
+
+/// Stage of the post-processing chain a [`PostEffect`] is drawn in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PostStage {
+    /// Before bloom and tonemapping, reading and writing linear HDR colors.
+    Linear,
+    /// After tonemapping and FXAA, reading and writing displayable colors.
+    Display,
+}
+
+#[derive(Clone, Debug)]
+enum EffectInput {
+    Image(TargetImage),
+    Texture(Handle<Texture>),
+}
+
+/// A custom full-screen pass of the [`RenderPostProcess`] chain.
+///
+/// See the [module documentation](self) for the bindings of the shader.
+#[derive(Clone, Debug)]
+pub struct PostEffect {
+    name: &'static str,
+    shader: SpirvShader,
+    stage: PostStage,
+    inputs: Vec<EffectInput>,
+    uniform: Option<EffectUniform>,
+}
+
+impl PostEffect {
+    /// Create an effect drawn with the `fragment` shader in `stage`. It draws into the render
+    /// target `Target::Custom(name)`, so the name must be unique among the render targets.
+    #[must_use]
+    pub fn new(name: &'static str, fragment: SpirvShader, stage: PostStage) -> Self {
+        Self {
+            name,
+            shader: fragment,
+            stage,
+            inputs: Vec::new(),
+            uniform: None,
+        }
+    }
+
+    /// Add an input sampling an image of another render target, e.g. the depth of
+    /// `Target::Hdr`.
+    #[must_use]
+    pub fn with_image(mut self, image: TargetImage) -> Self {
+        self.inputs.push(EffectInput::Image(image));
+        self
+    }
+
+    /// Add an input sampling `texture`, black while it is not loaded.
+    #[must_use]
+    pub fn with_texture(mut self, texture: Handle<Texture>) -> Self {
+        self.inputs.push(EffectInput::Texture(texture));
+        self
+    }
+
+    /// Set the uniform block of the shader, holding the `U` returned by `uniform` every frame.
+    #[must_use]
+    pub fn with_uniform<U>(
+        mut self,
+        uniform: impl Fn(&World, &Resources) -> U + Send + Sync + 'static,
+    ) -> Self
+    where
+        U: Uniform,
+        U::Std140: Sized,
+    {
+        self.uniform = Some(EffectUniform::new(uniform));
+        self
+    }
+
+    /// Render target the effect draws into, unless it is the last pass of the chain.
+    #[must_use]
+    pub fn target(&self) -> Target {
+        Target::Custom(self.name)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Pass {
+    Effect(usize),
+    BloomPrefilter,
+    BloomBlurX,
+    BloomBlurY,
+    ToneMap,
+    Fxaa,
+}
+
+/// A pass of the chain, drawing `source` into `target`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Stage {
+    pass: Pass,
+    source: Target,
+    target: Target,
+}
+
+impl Stage {
+    fn order(&self, effects: &[PostEffect]) -> RenderOrder {
+        match self.pass {
+            Pass::Effect(index) if effects[index].stage == PostStage::Linear => {
+                RenderOrder::LinearPostEffects
+            }
+            Pass::BloomPrefilter | Pass::BloomBlurX | Pass::BloomBlurY => {
+                RenderOrder::LinearPostEffects
+            }
+            Pass::ToneMap => RenderOrder::ToneMap,
+            Pass::Effect(_) | Pass::Fxaa => RenderOrder::DisplayPostEffects,
+        }
+    }
+}
+
+/// Options of the chain requiring to rebuild the render graph when they change.
+#[derive(Clone, Debug, PartialEq)]
+struct ChainKey {
+    width: u32,
+    height: u32,
+    bloom: bool,
+    fxaa: bool,
+    color_grading: Option<Handle<Texture>>,
+}
+
+/// A [`RenderPlugin`] defining the HDR render target [`Target::Hdr`] and drawing it into
+/// `Target::Main` through bloom, tonemapping, FXAA and user [`PostEffect`]s, see the
+/// [module documentation](self).
+///
+/// The last pass of the chain overwrites `Target::Main`, so only overlays such as the UI should
+/// still be drawn into it. `Target::Main` must be defined by another plugin, e.g.
+/// `RenderToWindow`.
+#[derive(Debug, Default)]
+pub struct RenderPostProcess {
+    size: Option<(u32, u32)>,
+    effects: Vec<PostEffect>,
+    planned: Option<ChainKey>,
+}
+
+impl RenderPostProcess {
+    /// Create the plugin, sized like the window.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the size of the HDR target, which must be the size of `Target::Main`. Required
+    /// without the `window` feature.
+    #[must_use]
+    pub fn with_size(mut self, width: u32, height: u32) -> Self {
+        self.size = Some((width, height));
+        self
+    }
+
+    /// Add a custom effect to the chain, drawn after the effects of the same stage already
+    /// added.
+    #[must_use]
+    pub fn with_effect(mut self, effect: PostEffect) -> Self {
+        self.effects.push(effect);
+        self
+    }
+
+    fn key(&self, resources: &Resources) -> Option<ChainKey> {
+        let (width, height) = self.size.or_else(|| screen_size(resources))?;
+        Some(read_settings(resources, |settings| {
+            ChainKey {
+                width,
+                height,
+                bloom: settings.bloom.is_some(),
+                fxaa: settings.fxaa,
+                color_grading: settings.color_grading.clone(),
+            }
+        }))
+    }
+
+    /// Passes of the chain from `Target::Hdr` to `Target::Main`.
+    fn chain(&self, bloom: bool, fxaa: bool) -> Vec<Stage> {
+        let mut stages = Vec::new();
+        let mut source = Target::Hdr;
+        let push = |stages: &mut Vec<Stage>, pass, target, source| {
+            stages.push(Stage {
+                pass,
+                source,
+                target,
+            });
+        };
+
+        for (index, effect) in self.effects.iter().enumerate() {
+            if effect.stage == PostStage::Linear {
+                push(&mut stages, Pass::Effect(index), effect.target(), source);
+                source = effect.target();
+            }
+        }
+        if bloom {
+            push(&mut stages, Pass::BloomPrefilter, BLOOM_PREFILTER, source);
+            push(&mut stages, Pass::BloomBlurX, BLOOM_BLUR_X, BLOOM_PREFILTER);
+            push(&mut stages, Pass::BloomBlurY, BLOOM_BLUR_Y, BLOOM_BLUR_X);
+        }
+        push(&mut stages, Pass::ToneMap, TONEMAP, source);
+        source = TONEMAP;
+        if fxaa {
+            push(&mut stages, Pass::Fxaa, FXAA, source);
+            source = FXAA;
+        }
+        for (index, effect) in self.effects.iter().enumerate() {
+            if effect.stage == PostStage::Display {
+                push(&mut stages, Pass::Effect(index), effect.target(), source);
+                source = effect.target();
+            }
+        }
+
+        if let Some(last) = stages.last_mut() {
+            last.target = Target::Main;
+        }
+        stages
+    }
+
+    /// Draw group of the pass of `stage`, with the images it samples besides its source.
+    fn stage_desc(&self, stage: &Stage, key: &ChainKey) -> (DrawPostEffectDesc, Vec<TargetImage>) {
+        let mut images = vec![TargetImage::Color(stage.source, 0)];
+        let desc = match stage.pass {
+            Pass::Effect(index) => {
+                let effect = &self.effects[index];
+                let mut desc = DrawPostEffectDesc::new(effect.shader.clone()).with_image_input();
+                for input in &effect.inputs {
+                    desc = match input {
+                        EffectInput::Image(image) => {
+                            images.push(*image);
+                            desc.with_image_input()
+                        }
+                        EffectInput::Texture(texture) => {
+                            desc.with_texture_input(Some(texture.clone()))
+                        }
+                    };
+                }
+                match &effect.uniform {
+                    Some(uniform) => desc.with_uniform(uniform.clone()),
+                    None => desc,
+                }
+            }
+            Pass::BloomPrefilter => {
+                DrawPostEffectDesc::new(BLOOM_PREFILTER_FRAGMENT.clone())
+                    .with_image_input()
+                    .with_uniform(EffectUniform::new(|_world, resources| {
+                        let bloom =
+                            read_settings(resources, |settings| settings.bloom.unwrap_or_default());
+                        BloomArgs {
+                            threshold: bloom.threshold,
+                            knee: bloom.knee,
+                        }
+                    }))
+            }
+            Pass::BloomBlurX | Pass::BloomBlurY => {
+                let axis = if stage.pass == Pass::BloomBlurX {
+                    [1.0, 0.0]
+                } else {
+                    [0.0, 1.0]
+                };
+                DrawPostEffectDesc::new(BLOOM_BLUR_FRAGMENT.clone())
+                    .with_image_input()
+                    .with_uniform(EffectUniform::new(move |_world, resources| {
+                        let radius = read_settings(resources, |settings| {
+                            settings.bloom.unwrap_or_default().radius
+                        });
+                        BlurArgs {
+                            direction: [axis[0] * radius, axis[1] * radius].into(),
+                        }
+                    }))
+            }
+            Pass::ToneMap => {
+                let desc = DrawPostEffectDesc::new(TONEMAP_FRAGMENT.clone()).with_image_input();
+                let desc = if key.bloom {
+                    images.push(TargetImage::Color(BLOOM_BLUR_Y, 0));
+                    desc.with_image_input()
+                } else {
+                    desc.with_texture_input(None)
+                };
+                desc.with_texture_input(key.color_grading.clone())
+                    .with_uniform(EffectUniform::new(|_world, resources| {
+                        read_settings(resources, |settings| {
+                            let color_grading =
+                                settings.color_grading.as_ref().map_or(false, |lut| {
+                                    resources
+                                        .get::<AssetStorage<Texture>>()
+                                        .map_or(false, |storage| storage.get(lut).is_some())
+                                });
+                            settings.tonemap_args(color_grading)
+                        })
+                    }))
+            }
+            Pass::Fxaa => DrawPostEffectDesc::new(FXAA_FRAGMENT.clone()).with_image_input(),
+        };
+        (desc, images)
+    }
+}
+
+#[cfg(feature = "window")]
+fn screen_size(resources: &Resources) -> Option<(u32, u32)> {
+    resources
+        .get::<amethyst_window::ScreenDimensions>()
+        .map(|dimensions| (dimensions.width() as u32, dimensions.height() as u32))
+}
+
+#[cfg(not(feature = "window"))]
+fn screen_size(_resources: &Resources) -> Option<(u32, u32)> {
+    None
+}
+
+impl<B: Backend> RenderPlugin<B> for RenderPostProcess {
+    fn on_build(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        _builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        if !resources.contains::<PostProcessSettings>() {
+            resources.insert(PostProcessSettings::default());
+        }
+        Ok(())
+    }
+
+    fn should_rebuild(&mut self, _world: &World, resources: &Resources) -> bool {
+        self.planned != self.key(resources)
+    }
+
+    fn on_plan(
+        &mut self,
+        plan: &mut RenderPlan<B>,
+        _factory: &mut Factory<B>,
+        _world: &World,
+        resources: &Resources,
+    ) -> Result<(), Error> {
+        let key = self.key(resources).ok_or_else(|| {
+            format_err!("The size of the post-processing targets is unknown, set it with_size")
+        })?;
+        let kind = Kind::D2(key.width, key.height, 1, 1);
+        let half_kind = Kind::D2((key.width / 2).max(1), (key.height / 2).max(1), 1, 1);
+
+        plan.define_pass(
+            Target::Hdr,
+            TargetPlanOutputs {
+                colors: vec![OutputColor::Image(ImageOptions {
+                    kind,
+                    levels: 1,
+                    format: Format::Rgba16Sfloat,
+                    clear: Some(ClearValue {
+                        color: ClearColor {
+                            float32: [0.0, 0.0, 0.0, 1.0],
+                        },
+                    }),
+                })],
+                depth: Some(ImageOptions {
+                    kind,
+                    levels: 1,
+                    format: Format::D32Sfloat,
+                    clear: Some(ClearValue {
+                        depth_stencil: ClearDepthStencil {
+                            depth: 0.0,
+                            stencil: 0,
+                        },
+                    }),
+                }),
+            },
+        )?;
+
+        for stage in self.chain(key.bloom, key.fxaa) {
+            let order = stage.order(&self.effects);
+            if stage.target != Target::Main {
+                let (kind, format) = match stage.pass {
+                    Pass::BloomPrefilter | Pass::BloomBlurX | Pass::BloomBlurY => {
+                        (half_kind, Format::Rgba16Sfloat)
+                    }
+                    Pass::Effect(index) if self.effects[index].stage == PostStage::Linear => {
+                        (kind, Format::Rgba16Sfloat)
+                    }
+                    _ => (kind, Format::Rgba8Srgb),
+                };
+                plan.define_pass(
+                    stage.target,
+                    TargetPlanOutputs {
+                        colors: vec![OutputColor::Image(ImageOptions {
+                            kind,
+                            levels: 1,
+                            format,
+                            clear: None,
+                        })],
+                        depth: None,
+                    },
+                )?;
+            }
+
+            let (desc, images) = self.stage_desc(&stage, &key);
+            plan.extend_target(stage.target, move |ctx| {
+                let mut group = desc.with_depth(ctx.depth()).builder();
+                for image in images {
+                    group = group.with_image(ctx.get_image(image)?);
+                }
+                ctx.add(order, group)?;
+                Ok(())
+            });
+        }
+
+        self.planned = Some(key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn effect(name: &'static str, stage: PostStage) -> PostEffect {
+        PostEffect::new(name, FXAA_FRAGMENT.clone(), stage)
+    }
+
+    #[test]
+    fn chain_without_options_only_tonemaps() {
+        let plugin = RenderPostProcess::new();
+        assert_eq!(
+            plugin.chain(false, false),
+            vec![Stage {
+                pass: Pass::ToneMap,
+                source: Target::Hdr,
+                target: Target::Main,
+            }]
+        );
+    }
+
+    #[test]
+    fn chain_orders_effects_around_tonemapping() {
+        let plugin = RenderPostProcess::new()
+            .with_effect(effect("grain", PostStage::Display))
+            .with_effect(effect("fog", PostStage::Linear));
+        let stages = plugin.chain(true, true);
+
+        let passes: Vec<_> = stages.iter().map(|stage| stage.pass).collect();
+        assert_eq!(
+            passes,
+            vec![
+                Pass::Effect(1),
+                Pass::BloomPrefilter,
+                Pass::BloomBlurX,
+                Pass::BloomBlurY,
+                Pass::ToneMap,
+                Pass::Fxaa,
+                Pass::Effect(0),
+            ]
+        );
+        // Bloom and tonemapping both read the output of the linear effects.
+        assert_eq!(stages[1].source, Target::Custom("fog"));
+        assert_eq!(stages[4].source, Target::Custom("fog"));
+        assert_eq!(stages[6].source, FXAA);
+        assert_eq!(stages[6].target, Target::Main);
+        assert_eq!(
+            i32::from(stages[6].order(&plugin.effects)),
+            i32::from(RenderOrder::DisplayPostEffects)
+        );
+        assert_eq!(
+            i32::from(stages[0].order(&plugin.effects)),
+            i32::from(RenderOrder::LinearPostEffects)
+        );
+    }
+
+    #[test]
+    fn tonemap_args_disable_missing_effects() {
+        let settings = PostProcessSettings {
+            exposure: 2.0,
+            tonemapper: Tonemapper::ReinhardExtended { white: 4.0 },
+            bloom: None,
+            vignette: None,
+            color_grading: None,
+            fxaa: false,
+        };
+        let args = settings.tonemap_args(false);
+        assert_eq!(args.exposure, 2.0);
+        assert_eq!(args.tonemapper, 2);
+        assert_eq!(args.white, 4.0);
+        assert_eq!(args.bloom_intensity, 0.0);
+        assert_eq!(args.vignette_intensity, 0.0);
+        assert_eq!(args.color_grading, 0);
+    }
+}
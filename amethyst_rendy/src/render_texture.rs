@@ -0,0 +1,544 @@
+//! Offscreen render targets that cameras draw into, sampled as textures by materials and UI.
+//!
+//! A camera entity with a [`RenderTexture`] renders into the texture instead of the window. The
+//! texture is drawn by the plugins added with `with_target(render_texture.target())`, and the
+//! [`RenderToTexture`] plugin copies it to [`RenderTexture::texture`] once per frame, before the
+//! main target is drawn, so the handle can be used in a `Material` or a `UiImage` like any other
+//! texture.
+//!
+//! ```no_run
+//! use amethyst::{
+//!     assets::{DefaultLoader, ProcessingQueue},
+//!     core::transform::Transform,
+//!     ecs::{Resources, World},
+//!     renderer::{
+//!         bundle::Target,
+//!         camera::Camera,
+//!         plugins::RenderPbr3D,
+//!         render_texture::RenderTexture,
+//!         types::{DefaultBackend, TextureData},
+//!         RenderToTexture, RenderingBundle,
+//!     },
+//! };
+//!
+//! # fn example(world: &mut World, resources: &Resources) {
+//! let monitor = {
+//!     let loader = resources.get::<DefaultLoader>().unwrap();
+//!     let queue = resources.get::<ProcessingQueue<TextureData>>().unwrap();
+//!     RenderTexture::builder("security_monitor", 256, 256).build(&*loader, &queue)
+//! };
+//! // Sample `monitor.texture()` in the material of the monitor screen.
+//! let screen_texture = monitor.texture().clone();
+//! world.push((
+//!     Camera::standard_3d(256.0, 256.0),
+//!     Transform::default(),
+//!     monitor,
+//! ));
+//!
+//! let bundle = RenderingBundle::<DefaultBackend>::new()
+//!     .with_plugin(RenderToTexture::default())
+//!     .with_plugin(RenderPbr3D::default())
+//!     .with_plugin(RenderPbr3D::default().with_target(Target::Custom("security_monitor")));
+//! # }
+//! ```
+
+use amethyst_assets::{AssetStorage, Handle, Loader, ProcessingQueue};
+use amethyst_core::ecs::{Entity, IntoQuery, Resources, World};
+use amethyst_error::Error;
+use rendy::{
+    command::{
+        CommandBuffer, CommandPool, Family, Fence, Graphics, IndividualReset, OneShot,
+        PendingOnceState, PrimaryLevel, Queue, QueueType, Submission,
+    },
+    factory::Factory,
+    frame::Frames,
+    graph::{
+        gfx_acquire_barriers, gfx_release_barriers, GraphContext, ImageAccess, Node, NodeBuffer,
+        NodeBuildError, NodeDesc, NodeImage,
+    },
+    hal::{
+        self,
+        command::{ClearColor, ClearDepthStencil, ClearValue},
+        format::{Aspects, Format},
+        image::{
+            Access, Filter, Kind, Layout, SamplerDesc, SubresourceLayers, SubresourceRange,
+            ViewKind, WrapMode,
+        },
+        pso::PipelineStage,
+    },
+    texture::TextureBuilder,
+};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{
+    bundle::{
+        ImageOptions, OutputColor, RenderPlan, RenderPlugin, Target, TargetImage, TargetPlanOutputs,
+    },
+    camera::{render_views, CameraView},
+    system::GraphAuxData,
+    types::{Backend, Texture, TextureData},
+};
+
+/// Offscreen image rendered by the camera it is attached to, instead of the window.
+///
+/// The image is drawn into the render target [`RenderTexture::target`] and copied every frame to
+/// [`RenderTexture::texture`], which can be used wherever a `Handle<Texture>` is expected. The
+/// [`RenderToTexture`] plugin must be added to the `RenderingBundle`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderTexture {
+    name: &'static str,
+    width: u32,
+    height: u32,
+    format: Format,
+    clear: [f32; 4],
+    texture: Handle<Texture>,
+}
+
+impl RenderTexture {
+    /// Starts building a render texture of `width` by `height` pixels, drawn into the render
+    /// target `Target::Custom(name)`.
+    ///
+    /// The name must be unique among the render textures.
+    #[must_use]
+    pub fn builder(name: &'static str, width: u32, height: u32) -> RenderTextureBuilder {
+        RenderTextureBuilder {
+            name,
+            width,
+            height,
+            format: Format::Rgba8Srgb,
+            clear: [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+
+    /// Render target the camera draws into, which the drawing plugins must be added to.
+    #[must_use]
+    pub fn target(&self) -> Target {
+        Target::Custom(self.name)
+    }
+
+    /// Texture holding the last rendered image.
+    #[must_use]
+    pub fn texture(&self) -> &Handle<Texture> {
+        &self.texture
+    }
+
+    /// Width in pixels.
+    #[must_use]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height in pixels.
+    #[must_use]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Format of the image and the texture.
+    #[must_use]
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Linear RGBA color the image is cleared to before drawing.
+    #[must_use]
+    pub fn clear_color(&self) -> [f32; 4] {
+        self.clear
+    }
+
+    fn kind(&self) -> Kind {
+        Kind::D2(self.width, self.height, 1, 1)
+    }
+}
+
+/// Builder of a [`RenderTexture`], created by [`RenderTexture::builder`].
+#[derive(Debug, Clone)]
+pub struct RenderTextureBuilder {
+    name: &'static str,
+    width: u32,
+    height: u32,
+    format: Format,
+    clear: [f32; 4],
+}
+
+impl RenderTextureBuilder {
+    /// Sets the format of the image, `Rgba8Srgb` by default. It must be usable as a color
+    /// attachment and as a sampled image.
+    #[must_use]
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets the linear RGBA color the image is cleared to, opaque black by default.
+    #[must_use]
+    pub fn with_clear(mut self, clear: [f32; 4]) -> Self {
+        self.clear = clear;
+        self
+    }
+
+    /// Creates the texture the image is copied to and returns the render texture.
+    pub fn build(
+        self,
+        loader: &impl Loader,
+        queue: &ProcessingQueue<TextureData>,
+    ) -> RenderTexture {
+        let bytes_per_pixel = usize::from(self.format.surface_desc().bits / 8);
+        let pixels = vec![0; self.width as usize * self.height as usize * bytes_per_pixel];
+        let builder = TextureBuilder::new()
+            .with_kind(Kind::D2(self.width, self.height, 1, 1))
+            .with_view_kind(ViewKind::D2)
+            .with_data_width(self.width)
+            .with_data_height(self.height)
+            .with_sampler_info(SamplerDesc::new(Filter::Linear, WrapMode::Clamp))
+            .with_raw_data(pixels, self.format);
+
+        RenderTexture {
+            name: self.name,
+            width: self.width,
+            height: self.height,
+            format: self.format,
+            clear: self.clear,
+            texture: loader.load_from_data(TextureData(builder), (), queue),
+        }
+    }
+}
+
+/// Returns the cameras to render in render order, with their views and render targets.
+///
+/// `cameras` yields each camera with its view and the target of its [`RenderTexture`], if any.
+/// The cameras without a render texture are drawn to `Target::Main`, filtered by
+/// [`render_views`], and every camera with a render texture is drawn to its target.
+pub fn target_views<'a, I>(
+    active_camera: Option<Entity>,
+    cameras: I,
+) -> Vec<(Entity, CameraView, Target)>
+where
+    I: IntoIterator<Item = (Entity, Option<&'a CameraView>, Option<Target>)>,
+{
+    let mut main_cameras = Vec::new();
+    let mut views = Vec::new();
+    for (entity, view, target) in cameras {
+        match target {
+            Some(target) => views.push((entity, view.copied().unwrap_or_default(), target)),
+            None => main_cameras.push((entity, view)),
+        }
+    }
+    views.extend(
+        render_views(active_camera, main_cameras)
+            .into_iter()
+            .map(|(entity, view)| (entity, view, Target::Main)),
+    );
+    views.sort_by_key(|(_, view, _)| view.order);
+    views
+}
+
+/// A [`RenderPlugin`] drawing the cameras with a [`RenderTexture`] into their texture.
+///
+/// It defines the render target of each `RenderTexture`, with a color and a depth image, and
+/// copies the color image to the texture before the target of the plugin is drawn, `Target::Main`
+/// by default. The render graph is rebuilt when a render texture is added, removed or resized.
+///
+/// Only the plugins added with `with_target(render_texture.target())` draw into a render texture.
+#[derive(Default, Debug)]
+pub struct RenderToTexture {
+    target: Target,
+    planned: Vec<RenderTexture>,
+}
+
+impl RenderToTexture {
+    /// Set the target drawn after the render textures are updated, usually the one sampling
+    /// them.
+    #[must_use]
+    pub fn with_target(mut self, target: Target) -> Self {
+        self.target = target;
+        self
+    }
+
+    fn render_textures(world: &World) -> Vec<RenderTexture> {
+        let mut textures: Vec<_> = <&RenderTexture>::query().iter(world).cloned().collect();
+        textures.sort_by_key(|texture| texture.name);
+        textures.dedup_by_key(|texture| texture.name);
+        textures
+    }
+}
+
+impl<B: Backend> RenderPlugin<B> for RenderToTexture {
+    fn should_rebuild(&mut self, world: &World, _resources: &Resources) -> bool {
+        self.planned != Self::render_textures(world)
+    }
+
+    fn on_plan(
+        &mut self,
+        plan: &mut RenderPlan<B>,
+        _factory: &mut Factory<B>,
+        world: &World,
+        _resources: &Resources,
+    ) -> Result<(), Error> {
+        self.planned = Self::render_textures(world);
+
+        for render_texture in &self.planned {
+            let target = render_texture.target();
+            plan.define_pass(
+                target,
+                TargetPlanOutputs {
+                    colors: vec![OutputColor::Image(ImageOptions {
+                        kind: render_texture.kind(),
+                        levels: 1,
+                        format: render_texture.format,
+                        clear: Some(ClearValue {
+                            color: ClearColor {
+                                float32: render_texture.clear,
+                            },
+                        }),
+                    })],
+                    depth: Some(ImageOptions {
+                        kind: render_texture.kind(),
+                        levels: 1,
+                        format: Format::D32Sfloat,
+                        clear: Some(ClearValue {
+                            depth_stencil: ClearDepthStencil {
+                                depth: 0.0,
+                                stencil: 0,
+                            },
+                        }),
+                    }),
+                },
+            )?;
+
+            let copy = CopyToTextureDesc {
+                texture: render_texture.texture.clone(),
+                width: render_texture.width,
+                height: render_texture.height,
+            };
+            plan.extend_target(self.target, move |ctx| {
+                let image = ctx.get_image(TargetImage::Color(target, 0))?;
+                let pass = ctx.get_node(target)?;
+                let node = ctx
+                    .graph()
+                    .add_node(copy.builder().with_image(image).with_dependency(pass));
+                ctx.add_dep(node);
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Render graph node copying its image to a texture.
+#[derive(Debug)]
+struct CopyToTextureDesc {
+    texture: Handle<Texture>,
+    width: u32,
+    height: u32,
+}
+
+impl<B: Backend> NodeDesc<B, GraphAuxData> for CopyToTextureDesc {
+    type Node = CopyToTexture<B>;
+
+    fn images(&self) -> Vec<ImageAccess> {
+        vec![ImageAccess {
+            access: Access::TRANSFER_READ,
+            usage: hal::image::Usage::TRANSFER_SRC,
+            layout: Layout::TransferSrcOptimal,
+            stages: PipelineStage::TRANSFER,
+        }]
+    }
+
+    fn build<'a>(
+        self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        family: &mut Family<B>,
+        _queue: usize,
+        _aux: &GraphAuxData,
+        _buffers: Vec<NodeBuffer>,
+        mut images: Vec<NodeImage>,
+    ) -> Result<Self::Node, NodeBuildError> {
+        let pool = factory
+            .create_command_pool(family)
+            .map_err(NodeBuildError::OutOfMemory)?;
+
+        Ok(CopyToTexture {
+            pool,
+            submitted: (0..ctx.frames_in_flight).map(|_| None).collect(),
+            image: images.remove(0),
+            texture: self.texture,
+            width: self.width,
+            height: self.height,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct CopyToTexture<B: Backend> {
+    pool: CommandPool<B, QueueType, IndividualReset>,
+    submitted:
+        Vec<Option<CommandBuffer<B, QueueType, PendingOnceState, PrimaryLevel, IndividualReset>>>,
+    image: NodeImage,
+    texture: Handle<Texture>,
+    width: u32,
+    height: u32,
+}
+
+impl<B: Backend> Node<B, GraphAuxData> for CopyToTexture<B> {
+    type Capability = Graphics;
+
+    unsafe fn run<'a>(
+        &mut self,
+        ctx: &GraphContext<B>,
+        _factory: &Factory<B>,
+        queue: &mut Queue<B>,
+        aux: &GraphAuxData,
+        frames: &Frames<B>,
+        waits: &[(&'a B::Semaphore, PipelineStage)],
+        signals: &[&'a B::Semaphore],
+        fence: Option<&mut Fence<B>>,
+    ) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("copy_to_texture");
+
+        let storage = aux.resources.get::<AssetStorage<Texture>>().unwrap();
+        let texture = match storage.get(&self.texture).and_then(B::unwrap_texture) {
+            Some(texture) => texture,
+            None => {
+                // The texture is not processed yet, only signal the rest of the graph.
+                queue.submit(
+                    Some(
+                        Submission::new()
+                            .wait(waits.iter().cloned())
+                            .signal(signals.iter().cloned()),
+                    ),
+                    fence,
+                );
+                return;
+            }
+        };
+
+        // The frame which last used this buffer is complete, the graph waits for it before
+        // running a frame `frames_in_flight` later.
+        let slot = (frames.next().index() % u64::from(ctx.frames_in_flight)) as usize;
+        let buffer = match self.submitted[slot].take() {
+            Some(buffer) => buffer.mark_complete().reset(),
+            None => self.pool.allocate_buffers(1).remove(0),
+        };
+        let mut buffer = buffer.begin(OneShot, ());
+        {
+            let mut encoder = buffer.encoder();
+            let source = ctx.get_image(self.image.id).expect("Image does not exist");
+            let destination = texture.image().raw();
+            let color = SubresourceRange {
+                aspects: Aspects::COLOR,
+                levels: 0..1,
+                layers: 0..1,
+            };
+            let layers = SubresourceLayers {
+                aspects: Aspects::COLOR,
+                level: 0,
+                layers: 0..1,
+            };
+            let shader_stages = PipelineStage::VERTEX_SHADER | PipelineStage::FRAGMENT_SHADER;
+
+            let (stages, barriers) = gfx_acquire_barriers(ctx, None, Some(&self.image));
+            encoder.pipeline_barrier(stages, hal::memory::Dependencies::empty(), barriers);
+            // The whole texture is overwritten, its previous content is discarded.
+            encoder.pipeline_barrier(
+                shader_stages..PipelineStage::TRANSFER,
+                hal::memory::Dependencies::empty(),
+                Some(hal::memory::Barrier::Image {
+                    states: (Access::empty(), Layout::Undefined)
+                        ..(Access::TRANSFER_WRITE, Layout::TransferDstOptimal),
+                    target: destination,
+                    families: None,
+                    range: color.clone(),
+                }),
+            );
+            encoder.copy_image(
+                source.raw(),
+                Layout::TransferSrcOptimal,
+                destination,
+                Layout::TransferDstOptimal,
+                Some(hal::command::ImageCopy {
+                    src_subresource: layers.clone(),
+                    src_offset: hal::image::Offset::ZERO,
+                    dst_subresource: layers,
+                    dst_offset: hal::image::Offset::ZERO,
+                    extent: hal::image::Extent {
+                        width: self.width,
+                        height: self.height,
+                        depth: 1,
+                    },
+                }),
+            );
+            encoder.pipeline_barrier(
+                PipelineStage::TRANSFER..shader_stages,
+                hal::memory::Dependencies::empty(),
+                Some(hal::memory::Barrier::Image {
+                    states: (Access::TRANSFER_WRITE, Layout::TransferDstOptimal)
+                        ..(Access::SHADER_READ, Layout::ShaderReadOnlyOptimal),
+                    target: destination,
+                    families: None,
+                    range: color,
+                }),
+            );
+            let (stages, barriers) = gfx_release_barriers(ctx, None, Some(&self.image));
+            encoder.pipeline_barrier(stages, hal::memory::Dependencies::empty(), barriers);
+        }
+
+        let (submit, buffer) = buffer.finish().submit_once();
+        queue.submit(
+            Some(
+                Submission::new()
+                    .submits(Some(submit))
+                    .wait(waits.iter().cloned())
+                    .signal(signals.iter().cloned()),
+            ),
+            fence,
+        );
+        self.submitted[slot] = Some(buffer);
+    }
+
+    unsafe fn dispose(mut self, factory: &mut Factory<B>, _aux: &GraphAuxData) {
+        let buffers: Vec<_> = self
+            .submitted
+            .drain(..)
+            .flatten()
+            .map(|buffer| buffer.mark_complete())
+            .collect();
+        self.pool.free_buffers(buffers);
+        factory.destroy_command_pool(self.pool);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::ecs::World;
+
+    use super::*;
+    use crate::camera::Viewport;
+
+    #[test]
+    fn texture_cameras_are_drawn_to_their_target() {
+        let mut world = World::default();
+        let main = world.push(());
+        let monitor = world.push(());
+        let overlay = CameraView::new(Viewport::new(0.0, 0.0, 0.5, 0.5)).with_order(1);
+
+        // The texture camera is active, yet the window shows the first other camera.
+        let views = target_views(
+            Some(monitor),
+            vec![
+                (monitor, Some(&overlay), Some(Target::Custom("monitor"))),
+                (main, None, None),
+            ],
+        );
+        assert_eq!(
+            views,
+            vec![
+                (main, CameraView::default(), Target::Main),
+                (monitor, overlay, Target::Custom("monitor")),
+            ]
+        );
+    }
+}
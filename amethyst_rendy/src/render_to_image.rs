@@ -0,0 +1,513 @@
+//! Headless rendering of a render target into a CPU-side image.
+//!
+//! [`RenderToImage`] replaces `RenderToWindow`: the target is drawn into an offscreen image, so no
+//! window or surface is needed and a software Vulkan driver is enough. Every frame the image is
+//! downloaded into the [`CapturedImage`] resource, which can be saved as a PNG or compared to a
+//! golden image with the `golden` module of the `test-support` feature.
+//!
+//! ```no_run
+//! use amethyst::{
+//!     ecs::Resources,
+//!     renderer::{
+//!         plugins::RenderFlat2D, types::DefaultBackend, CapturedImage, RenderToImage,
+//!         RenderingBundle,
+//!     },
+//! };
+//!
+//! let bundle = RenderingBundle::<DefaultBackend>::new()
+//!     .with_plugin(RenderToImage::new(320, 240))
+//!     .with_plugin(RenderFlat2D::default());
+//!
+//! // After a few frames have been dispatched:
+//! # fn save(resources: &Resources) -> amethyst::Result<()> {
+//! let captured = resources.get::<CapturedImage>().unwrap();
+//! if captured.frame().is_some() {
+//!     captured.save_png("frame.png")?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::Path;
+
+use amethyst_core::ecs::{DispatcherBuilder, Resources, World};
+use amethyst_error::{format_err, Error};
+use rendy::{
+    command::{
+        CommandBuffer, CommandPool, Family, Fence, Graphics, IndividualReset, OneShot,
+        PendingOnceState, PrimaryLevel, Queue, QueueType, Submission,
+    },
+    factory::Factory,
+    frame::Frames,
+    graph::{
+        gfx_acquire_barriers, gfx_release_barriers, GraphContext, ImageAccess, Node, NodeBuffer,
+        NodeBuildError, NodeDesc, NodeImage,
+    },
+    hal::{
+        self,
+        buffer::Usage,
+        command::{ClearColor, ClearDepthStencil, ClearValue},
+        format::{Aspects, Format},
+        image::{Access, Kind, Layout, SubresourceLayers},
+        pso::PipelineStage,
+    },
+    resource::{Buffer, Escape},
+};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{
+    bundle::{ImageOptions, OutputColor, RenderPlan, RenderPlugin, Target, TargetPlanOutputs},
+    system::GraphAuxData,
+    types::Backend,
+    util,
+};
+
+/// Image downloaded from the render target of the [`RenderToImage`] plugin.
+///
+/// Pixels are stored row by row from the top left corner, as 8-bit sRGB RGBA.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CapturedImage {
+    width: u32,
+    height: u32,
+    frame: Option<u64>,
+    pixels: Vec<u8>,
+}
+
+impl CapturedImage {
+    /// Creates an image from its size and RGBA pixels, e.g. to compare it with a captured one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are not `width * height * 4` bytes of pixels.
+    #[must_use]
+    pub fn new(width: u32, height: u32, pixels: Vec<u8>) -> Self {
+        assert_eq!(
+            pixels.len(),
+            width as usize * height as usize * 4,
+            "Expected {}x{} RGBA pixels",
+            width,
+            height
+        );
+        Self {
+            width,
+            height,
+            frame: None,
+            pixels,
+        }
+    }
+
+    /// Width of the image in pixels.
+    #[must_use]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the image in pixels.
+    #[must_use]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Index of the frame the image was rendered in, `None` until a frame has been downloaded.
+    ///
+    /// A frame is only downloaded once the GPU is done with it, which is a few frames after it
+    /// has been dispatched.
+    #[must_use]
+    pub fn frame(&self) -> Option<u64> {
+        self.frame
+    }
+
+    /// RGBA bytes of the image.
+    #[must_use]
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Returns the RGBA color of the pixel at `x`, `y` from the top left corner.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pixel is outside of the image.
+    #[must_use]
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        assert!(
+            x < self.width && y < self.height,
+            "Pixel {}, {} is outside of the {}x{} image",
+            x,
+            y,
+            self.width,
+            self.height
+        );
+        let start = (y as usize * self.width as usize + x as usize) * 4;
+        let mut pixel = [0; 4];
+        pixel.copy_from_slice(&self.pixels[start..start + 4]);
+        pixel
+    }
+
+    /// Converts the image for use with the `image` crate.
+    #[must_use]
+    pub fn to_rgba_image(&self) -> image::RgbaImage {
+        image::RgbaImage::from_raw(self.width, self.height, self.pixels.clone())
+            .expect("Pixels match the image size")
+    }
+
+    /// Writes the image to a PNG file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if nothing has been captured or the file can't be written.
+    pub fn save_png(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        if self.pixels.is_empty() {
+            return Err(format_err!("No image has been captured yet"));
+        }
+        self.to_rgba_image()
+            .save_with_format(path, image::ImageFormat::Png)?;
+        Ok(())
+    }
+
+    fn update(&mut self, width: u32, height: u32, frame: u64, pixels: &[u8]) {
+        self.width = width;
+        self.height = height;
+        self.frame = Some(frame);
+        self.pixels.clear();
+        self.pixels.extend_from_slice(pixels);
+    }
+}
+
+/// A [`RenderPlugin`] drawing a render target offscreen and downloading it every frame into the
+/// [`CapturedImage`] resource, for headless rendering and screenshots in tests.
+///
+/// It defines the target, `Target::Main` by default, so it can't be combined with a
+/// `RenderToWindow` plugin presenting the same target.
+#[derive(Debug)]
+pub struct RenderToImage {
+    target: Target,
+    width: u32,
+    height: u32,
+    clear: Option<ClearColor>,
+}
+
+impl RenderToImage {
+    /// Render into an image of `width` by `height` pixels.
+    ///
+    /// With the `window` feature, a `ScreenDimensions` resource of that size is added if there is
+    /// none, for the systems depending on the screen size.
+    #[must_use]
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            target: Target::Main,
+            width,
+            height,
+            clear: None,
+        }
+    }
+
+    /// Select the render target drawn into the image.
+    #[must_use]
+    pub fn with_target(mut self, target: Target) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// Clear the image with the specified linear RGBA color every frame.
+    #[must_use]
+    pub fn with_clear(mut self, clear: impl Into<ClearColor>) -> Self {
+        self.clear = Some(clear.into());
+        self
+    }
+}
+
+impl<B: Backend> RenderPlugin<B> for RenderToImage {
+    fn on_build(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        _builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        resources.insert(CapturedImage::default());
+        #[cfg(feature = "window")]
+        {
+            if !resources.contains::<amethyst_window::ScreenDimensions>() {
+                resources.insert(amethyst_window::ScreenDimensions::new(
+                    self.width,
+                    self.height,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn on_plan(
+        &mut self,
+        plan: &mut RenderPlan<B>,
+        _factory: &mut Factory<B>,
+        _world: &World,
+        _resources: &Resources,
+    ) -> Result<(), Error> {
+        let kind = Kind::D2(self.width, self.height, 1, 1);
+
+        plan.add_root(self.target);
+        plan.define_pass(
+            self.target,
+            TargetPlanOutputs {
+                colors: vec![OutputColor::Image(ImageOptions {
+                    kind,
+                    levels: 1,
+                    format: Format::Rgba8Srgb,
+                    clear: self.clear.map(|color| ClearValue { color }),
+                })],
+                depth: Some(ImageOptions {
+                    kind,
+                    levels: 1,
+                    format: Format::D32Sfloat,
+                    clear: Some(ClearValue {
+                        depth_stencil: ClearDepthStencil {
+                            depth: 0.0,
+                            stencil: 0,
+                        },
+                    }),
+                }),
+            },
+        )?;
+
+        let download = DownloadImageDesc {
+            width: self.width,
+            height: self.height,
+        };
+        plan.after_target(self.target, move |graph, pass, images| {
+            graph.add_node(
+                download
+                    .builder()
+                    .with_image(images[0])
+                    .with_dependency(pass),
+            );
+            Ok(())
+        });
+        Ok(())
+    }
+}
+
+/// Render graph node downloading its image to the [`CapturedImage`] resource.
+#[derive(Debug)]
+struct DownloadImageDesc {
+    width: u32,
+    height: u32,
+}
+
+impl<B: Backend> NodeDesc<B, GraphAuxData> for DownloadImageDesc {
+    type Node = DownloadImage<B>;
+
+    fn images(&self) -> Vec<ImageAccess> {
+        vec![ImageAccess {
+            access: Access::TRANSFER_READ,
+            usage: hal::image::Usage::TRANSFER_SRC,
+            layout: Layout::TransferSrcOptimal,
+            stages: PipelineStage::TRANSFER,
+        }]
+    }
+
+    fn build<'a>(
+        self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        family: &mut Family<B>,
+        _queue: usize,
+        _aux: &GraphAuxData,
+        _buffers: Vec<NodeBuffer>,
+        mut images: Vec<NodeImage>,
+    ) -> Result<Self::Node, NodeBuildError> {
+        let pool = factory
+            .create_command_pool(family)
+            .map_err(NodeBuildError::OutOfMemory)?;
+        let slots = ctx.frames_in_flight as usize;
+
+        Ok(DownloadImage {
+            pool,
+            submitted: (0..slots).map(|_| None).collect(),
+            buffers: (0..slots).map(|_| None).collect(),
+            image: images.remove(0),
+            width: self.width,
+            height: self.height,
+        })
+    }
+}
+
+/// Copy of a frame being downloaded, with the index of the frame.
+type PendingDownload<B> = (
+    u64,
+    CommandBuffer<B, QueueType, PendingOnceState, PrimaryLevel, IndividualReset>,
+);
+
+#[derive(Debug)]
+struct DownloadImage<B: Backend> {
+    pool: CommandPool<B, QueueType, IndividualReset>,
+    submitted: Vec<Option<PendingDownload<B>>>,
+    buffers: Vec<Option<Escape<Buffer<B>>>>,
+    image: NodeImage,
+    width: u32,
+    height: u32,
+}
+
+impl<B: Backend> DownloadImage<B> {
+    fn size(&self) -> u64 {
+        u64::from(self.width) * u64::from(self.height) * 4
+    }
+}
+
+impl<B: Backend> Node<B, GraphAuxData> for DownloadImage<B> {
+    type Capability = Graphics;
+
+    unsafe fn run<'a>(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &Factory<B>,
+        queue: &mut Queue<B>,
+        aux: &GraphAuxData,
+        frames: &Frames<B>,
+        waits: &[(&'a B::Semaphore, PipelineStage)],
+        signals: &[&'a B::Semaphore],
+        fence: Option<&mut Fence<B>>,
+    ) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("download_image");
+
+        let size = self.size();
+        // The frame which last used this slot is complete, the graph waits for it before running
+        // a frame `frames_in_flight` later, so its copy can be read.
+        let slot = (frames.next().index() % u64::from(ctx.frames_in_flight)) as usize;
+        if let Err(err) = util::ensure_buffer(
+            factory,
+            &mut self.buffers[slot],
+            Usage::TRANSFER_DST,
+            rendy::memory::Download,
+            size,
+        ) {
+            log::error!("Failed to allocate the image download buffer: {:?}", err);
+        }
+        let staging = match self.buffers[slot].as_mut() {
+            Some(staging) => staging,
+            None => {
+                queue.submit(
+                    Some(
+                        Submission::new()
+                            .wait(waits.iter().cloned())
+                            .signal(signals.iter().cloned()),
+                    ),
+                    fence,
+                );
+                return;
+            }
+        };
+
+        let buffer = match self.submitted[slot].take() {
+            Some((frame, buffer)) => {
+                // A frame whose copy can't be read isn't captured, like one without a buffer.
+                match staging.map(factory.device(), 0..size) {
+                    Ok(mut mapped) => {
+                        match mapped.read::<u8>(factory.device(), 0..size) {
+                            Ok(pixels) => {
+                                if let Some(mut captured) = aux.resources.get_mut::<CapturedImage>()
+                                {
+                                    captured.update(self.width, self.height, frame, pixels);
+                                }
+                            }
+                            Err(err) => {
+                                log::error!("Failed to read the image download buffer: {:?}", err);
+                            }
+                        }
+                    }
+                    Err(err) => log::error!("Failed to map the image download buffer: {:?}", err),
+                }
+                buffer.mark_complete().reset()
+            }
+            None => self.pool.allocate_buffers(1).remove(0),
+        };
+        let staging = staging.raw();
+
+        let mut buffer = buffer.begin(OneShot, ());
+        {
+            let mut encoder = buffer.encoder();
+            let source = ctx.get_image(self.image.id).expect("Image does not exist");
+
+            let (stages, barriers) = gfx_acquire_barriers(ctx, None, Some(&self.image));
+            encoder.pipeline_barrier(stages, hal::memory::Dependencies::empty(), barriers);
+            encoder.copy_image_to_buffer(
+                source.raw(),
+                Layout::TransferSrcOptimal,
+                staging,
+                Some(hal::command::BufferImageCopy {
+                    buffer_offset: 0,
+                    buffer_width: self.width,
+                    buffer_height: self.height,
+                    image_layers: SubresourceLayers {
+                        aspects: Aspects::COLOR,
+                        level: 0,
+                        layers: 0..1,
+                    },
+                    image_offset: hal::image::Offset::ZERO,
+                    image_extent: hal::image::Extent {
+                        width: self.width,
+                        height: self.height,
+                        depth: 1,
+                    },
+                }),
+            );
+            // Make the copy visible to the host once the frame fence is signaled.
+            encoder.pipeline_barrier(
+                PipelineStage::TRANSFER..PipelineStage::HOST,
+                hal::memory::Dependencies::empty(),
+                Some(hal::memory::Barrier::Buffer {
+                    states: hal::buffer::Access::TRANSFER_WRITE..hal::buffer::Access::HOST_READ,
+                    target: staging,
+                    families: None,
+                    range: rendy::resource::SubRange::WHOLE,
+                }),
+            );
+            let (stages, barriers) = gfx_release_barriers(ctx, None, Some(&self.image));
+            encoder.pipeline_barrier(stages, hal::memory::Dependencies::empty(), barriers);
+        }
+
+        let (submit, buffer) = buffer.finish().submit_once();
+        queue.submit(
+            Some(
+                Submission::new()
+                    .submits(Some(submit))
+                    .wait(waits.iter().cloned())
+                    .signal(signals.iter().cloned()),
+            ),
+            fence,
+        );
+        self.submitted[slot] = Some((frames.next().index(), buffer));
+    }
+
+    unsafe fn dispose(mut self, factory: &mut Factory<B>, _aux: &GraphAuxData) {
+        let buffers: Vec<_> = self
+            .submitted
+            .drain(..)
+            .flatten()
+            .map(|(_, buffer)| buffer.mark_complete())
+            .collect();
+        self.pool.free_buffers(buffers);
+        factory.destroy_command_pool(self.pool);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captured_pixels() {
+        let image = CapturedImage::new(2, 1, vec![255, 0, 0, 255, 0, 0, 255, 128]);
+        assert_eq!(image.frame(), None);
+        assert_eq!(image.pixel(1, 0), [0, 0, 255, 128]);
+        assert_eq!(image.to_rgba_image().get_pixel(0, 0).0, [255, 0, 0, 255]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn captured_pixels_size() {
+        let _ = CapturedImage::new(2, 2, vec![0; 4]);
+    }
+}
@@ -20,3 +20,11 @@ impl From<Tint> for [f32; 4] {
         [r, g, b, a]
     }
 }
+
+/// Custom data of an instance of a mesh, read by custom 3D shaders from the `instance_data`
+/// instance-rate attribute following `tint`.
+///
+/// Entities sharing a mesh and a material are drawn in one instanced draw call, each with its
+/// own transform, tint and data. The builtin shaders ignore it, and skinned meshes don't have it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct InstanceData(pub [f32; 4]);
@@ -0,0 +1,645 @@
+//! Shadow mapping for directional, spot and point lights.
+//!
+//! Lights with [`ShadowSettings`](crate::light::ShadowSettings) cast shadows when the 3D plugin
+//! drawing them is created with `with_shadows`, e.g. `RenderPbr3D::default().with_shadows(4096)`:
+//!
+//! ```
+//! use amethyst::renderer::light::{DirectionalLight, Light, ShadowSettings};
+//!
+//! let sun: Light = DirectionalLight {
+//!     shadows: Some(ShadowSettings {
+//!         cascades: 3,
+//!         max_distance: 50.0,
+//!         ..ShadowSettings::default()
+//!     }),
+//!     ..DirectionalLight::default()
+//! }
+//! .into();
+//! ```
+//!
+//! The shadow maps of all the lights are tiles of a single depth image, the shadow atlas, drawn
+//! into the `Target::ShadowMap` render target. Each light has a tile per shadow view: one per
+//! cascade for a directional light, each cascade covering a further part of the view of the
+//! active camera, one for a spot light and six for a point light, one per face of a cube.
+//!
+//! The [`ShadowSystem`] places the shadow views in the atlas every frame, the lights with the
+//! largest resolution first. The lights which don't fit in the atlas don't cast shadows.
+//! Skinned meshes don't cast shadows.
+
+use std::ops::Range;
+
+use amethyst_core::{
+    ecs::{systems::ParallelRunnable, Entity, IntoQuery, System, SystemBuilder},
+    math::{Matrix4, Point3, Vector3},
+    transform::Transform,
+};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{
+    camera::{ActiveCamera, Camera, Viewport},
+    light::Light,
+    spatial::SpatialIndex,
+    visibility::Frustum,
+};
+
+/// Maximum number of shadow views, the size of the shadow view array of the shaders.
+pub const MAX_SHADOW_VIEWS: usize = 64;
+
+/// Maximum number of cascades of a directional light.
+pub const MAX_CASCADES: u8 = 4;
+
+/// Weight of the logarithmic split against the uniform split of the cascades.
+const CASCADE_SPLIT_LAMBDA: f32 = 0.75;
+
+/// Directions of the faces of the shadow views of a point light, in the order the shaders
+/// expect them.
+const CUBE_FACES: [[f32; 3]; 6] = [
+    [1.0, 0.0, 0.0],
+    [-1.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0],
+    [0.0, -1.0, 0.0],
+    [0.0, 0.0, 1.0],
+    [0.0, 0.0, -1.0],
+];
+
+/// A shadow map of a light, drawn into a tile of the shadow atlas.
+#[derive(Debug, Clone)]
+pub struct ShadowView {
+    /// Light entity casting the shadow.
+    pub light: Entity,
+    /// Projection matrix of the shadow map.
+    pub proj: Matrix4<f32>,
+    /// View matrix of the shadow map.
+    pub view: Matrix4<f32>,
+    /// Tile of the shadow atlas the shadow map is drawn to.
+    pub tile: Viewport,
+    /// Offset towards the light, see [`ShadowSettings::bias`].
+    pub bias: f32,
+    /// Offset along the surface normal, see [`ShadowSettings::normal_bias`].
+    pub normal_bias: f32,
+    /// Entities which may cast a shadow in the shadow map.
+    pub casters: Vec<Entity>,
+}
+
+/// Resource holding the shadow views of the current frame, updated by the [`ShadowSystem`].
+///
+/// The views of a light are contiguous, see [`ShadowMaps::light_views`].
+#[derive(Debug)]
+pub struct ShadowMaps {
+    atlas_size: u32,
+    /// Shadow views of all the lights casting shadows.
+    pub views: Vec<ShadowView>,
+}
+
+impl ShadowMaps {
+    /// Creates an empty set of shadow views for an atlas of `atlas_size` by `atlas_size` pixels,
+    /// rounded up to a power of two.
+    #[must_use]
+    pub fn new(atlas_size: u32) -> Self {
+        Self {
+            atlas_size: atlas_size.next_power_of_two(),
+            views: Vec::new(),
+        }
+    }
+
+    /// Width and height in pixels of the shadow atlas.
+    #[must_use]
+    pub fn atlas_size(&self) -> u32 {
+        self.atlas_size
+    }
+
+    /// Returns the range of [`ShadowMaps::views`] of `light`, or `None` if it casts no shadow.
+    #[must_use]
+    pub fn light_views(&self, light: Entity) -> Option<Range<usize>> {
+        let start = self.views.iter().position(|view| view.light == light)?;
+        let count = self.views[start..]
+            .iter()
+            .take_while(|view| view.light == light)
+            .count();
+        Some(start..start + count)
+    }
+
+    /// Places the shadow views of `lights` and finds their shadow casters in `index`.
+    ///
+    /// `camera` is the projection and global matrix of the camera which the cascades of
+    /// directional lights are fitted to. Directional lights cast no shadow without it.
+    pub fn update<'a>(
+        &mut self,
+        camera: Option<(&Camera, &Matrix4<f32>)>,
+        lights: impl IntoIterator<Item = (Entity, &'a Light, Option<&'a Transform>)>,
+        index: &SpatialIndex,
+    ) {
+        self.views.clear();
+
+        let mut requests = Vec::new();
+        for (entity, light, transform) in lights {
+            let (settings, count) = match light {
+                Light::Directional(light) => {
+                    match (&light.shadows, camera) {
+                        (Some(settings), Some(_)) => {
+                            (
+                                settings,
+                                usize::from(settings.cascades.clamp(1, MAX_CASCADES)),
+                            )
+                        }
+                        _ => continue,
+                    }
+                }
+                Light::Spot(light) => {
+                    match &light.shadows {
+                        Some(settings) => (settings, 1),
+                        None => continue,
+                    }
+                }
+                Light::Point(light) => {
+                    match &light.shadows {
+                        Some(settings) => (settings, CUBE_FACES.len()),
+                        None => continue,
+                    }
+                }
+                _ => continue,
+            };
+            let position = transform.map_or_else(Point3::origin, |transform| {
+                transform.global_matrix().transform_point(&Point3::origin())
+            });
+            requests.push((entity, light, position, *settings, count));
+        }
+
+        let tiles = pack_tiles(
+            self.atlas_size,
+            &requests
+                .iter()
+                .map(|(_, _, _, settings, count)| (settings.resolution.next_power_of_two(), *count))
+                .collect::<Vec<_>>(),
+        );
+
+        let atlas_size = self.atlas_size as f32;
+        for ((entity, light, position, settings, count), tiles) in requests.into_iter().zip(tiles) {
+            let tiles = match tiles {
+                Some(tiles) if self.views.len() + count <= MAX_SHADOW_VIEWS => tiles,
+                _ => continue,
+            };
+            let resolution = settings.resolution.next_power_of_two();
+            let (matrices, range) = match (light, camera) {
+                (Light::Directional(light), Some((camera, camera_matrix))) => {
+                    let near = camera_near(camera);
+                    let far = settings.max_distance.max(near * 2.0);
+                    let mut start = near;
+                    let cascades = cascade_splits(near, far, count as u8)
+                        .into_iter()
+                        .map(|end| {
+                            let matrices = directional_view(
+                                &light.direction,
+                                camera,
+                                camera_matrix,
+                                start..end,
+                                settings.max_distance,
+                                resolution,
+                            );
+                            start = end;
+                            matrices
+                        })
+                        .collect();
+                    (cascades, None)
+                }
+                (Light::Spot(light), _) => {
+                    (
+                        vec![spot_view(
+                            &position,
+                            &light.direction,
+                            light.angle,
+                            settings.near,
+                        )],
+                        Some(light.range),
+                    )
+                }
+                (Light::Point(light), _) => {
+                    (point_views(&position, settings.near), Some(light.radius))
+                }
+                _ => continue,
+            };
+
+            for ((proj, view), (x, y)) in matrices.into_iter().zip(tiles) {
+                let frustum = Frustum::new(proj * view);
+                let mut casters = Vec::new();
+                match range {
+                    Some(range) => {
+                        index.query_sphere(&position, range, |entity, sphere| {
+                            if frustum.check_sphere(&sphere.center, sphere.radius) {
+                                casters.push(entity);
+                            }
+                        })
+                    }
+                    None => index.query_frustum(&frustum, |entity, _| casters.push(entity)),
+                }
+                self.views.push(ShadowView {
+                    light: entity,
+                    proj,
+                    view,
+                    tile: Viewport::new(
+                        x as f32 / atlas_size,
+                        y as f32 / atlas_size,
+                        resolution as f32 / atlas_size,
+                        resolution as f32 / atlas_size,
+                    ),
+                    bias: settings.bias,
+                    normal_bias: settings.normal_bias,
+                    casters,
+                });
+            }
+        }
+    }
+}
+
+/// Places square tiles with power of two sizes in an atlas of `atlas_size` pixels, also a power
+/// of two. `requests` are `(size, count)` pairs of tiles which must all fit, the largest ones are
+/// placed first.
+///
+/// Returns the positions in pixels of the tiles of each request, in the order of `requests`, or
+/// `None` for the requests which don't fit.
+pub(crate) fn pack_tiles(
+    atlas_size: u32,
+    requests: &[(u32, usize)],
+) -> Vec<Option<Vec<(u32, u32)>>> {
+    let mut order: Vec<_> = (0..requests.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(requests[i].0));
+
+    let capacity = u64::from(atlas_size) * u64::from(atlas_size);
+    let mut used = 0;
+    let mut placed = vec![None; requests.len()];
+    for i in order {
+        let (size, count) = requests[i];
+        let area = u64::from(size) * u64::from(size);
+        if size == 0 || size > atlas_size || used + area * count as u64 > capacity {
+            continue;
+        }
+        // Tiles follow a Z-order curve. As the larger tiles are placed first, `used` is a
+        // multiple of the area of the tile, which is aligned on its size.
+        placed[i] = Some(
+            (0..count as u64)
+                .map(|tile| {
+                    let (x, y) = morton_decode(used / area + tile);
+                    (x * size, y * size)
+                })
+                .collect(),
+        );
+        used += area * count as u64;
+    }
+    placed
+}
+
+fn morton_decode(code: u64) -> (u32, u32) {
+    let mut x = 0;
+    let mut y = 0;
+    for bit in 0..32 {
+        x |= ((code >> (2 * bit)) & 1) << bit;
+        y |= ((code >> (2 * bit + 1)) & 1) << bit;
+    }
+    (x as u32, y as u32)
+}
+
+/// Returns the far depth of each of the `cascades` slices of a view from `near` to `far`,
+/// blending logarithmic and uniform splits so the near cascades are more detailed.
+#[must_use]
+pub fn cascade_splits(near: f32, far: f32, cascades: u8) -> Vec<f32> {
+    let count = f32::from(cascades);
+    (1..=cascades)
+        .map(|i| {
+            let fraction = f32::from(i) / count;
+            let logarithmic = near * (far / near).powf(fraction);
+            let uniform = near + (far - near) * fraction;
+            CASCADE_SPLIT_LAMBDA * logarithmic + (1.0 - CASCADE_SPLIT_LAMBDA) * uniform
+        })
+        .collect()
+}
+
+/// Returns the projection and view matrices of a shadow cascade of a directional light, covering
+/// the view of `camera` between the depths of `depths`.
+///
+/// The cascade is fitted to the bounding sphere of that part of the view, so its size doesn't
+/// change when the camera rotates, and moves by whole texels of the shadow map so the edges of
+/// the shadows don't shimmer when the camera moves. Casters up to `caster_distance` towards the
+/// light from the sphere are drawn.
+#[must_use]
+pub fn directional_view(
+    direction: &Vector3<f32>,
+    camera: &Camera,
+    camera_matrix: &Matrix4<f32>,
+    depths: Range<f32>,
+    caster_distance: f32,
+    resolution: u32,
+) -> (Matrix4<f32>, Matrix4<f32>) {
+    let corners = frustum_slice(camera, depths);
+    let center = corners
+        .iter()
+        .fold(Vector3::zeros(), |sum, corner| sum + corner.coords)
+        / corners.len() as f32;
+    let radius = corners
+        .iter()
+        .map(|corner| (corner.coords - center).norm())
+        .fold(0.0, f32::max);
+    // Rounded up, so rounding errors don't change the size of the cascade.
+    let radius = ((radius * 16.0).ceil() / 16.0).max(1.0 / 16.0);
+
+    // Snapping moves the center by up to a texel, which the extent leaves room for.
+    let resolution = resolution.max(4) as f32;
+    let extent = radius * resolution / (resolution - 2.0);
+    let texel = 2.0 * extent / resolution;
+
+    let rotation = look_at(&Point3::origin(), direction);
+    let mut center =
+        rotation.transform_point(&camera_matrix.transform_point(&Point3::from(center)));
+    center.x = (center.x / texel).floor() * texel;
+    center.y = (center.y / texel).floor() * texel;
+
+    let eye = Vector3::new(center.x, center.y, center.z + radius + caster_distance);
+    let view = Matrix4::new_translation(&-eye) * rotation;
+    let proj = Camera::orthographic(
+        -extent,
+        extent,
+        -extent,
+        extent,
+        0.0,
+        2.0 * radius + caster_distance,
+    )
+    .matrix;
+    (proj, view)
+}
+
+/// Returns the projection and view matrices of the shadow map of a spot light at `position`.
+#[must_use]
+pub fn spot_view(
+    position: &Point3<f32>,
+    direction: &Vector3<f32>,
+    angle: f32,
+    near: f32,
+) -> (Matrix4<f32>, Matrix4<f32>) {
+    let fov = (2.0 * angle).clamp(0.01, std::f32::consts::PI - 0.01);
+    (
+        Camera::perspective(1.0, fov, near).matrix,
+        look_at(position, direction),
+    )
+}
+
+/// Returns the projection and view matrices of the six shadow maps of a point light at
+/// `position`, one per face of a cube.
+#[must_use]
+pub fn point_views(position: &Point3<f32>, near: f32) -> Vec<(Matrix4<f32>, Matrix4<f32>)> {
+    let proj = Camera::perspective(1.0, std::f32::consts::FRAC_PI_2, near).matrix;
+    CUBE_FACES
+        .iter()
+        .map(|face| (proj, look_at(position, &Vector3::from(*face))))
+        .collect()
+}
+
+/// Returns the depth of the near plane of `camera`.
+fn camera_near(camera: &Camera) -> f32 {
+    -camera
+        .inverse
+        .transform_point(&Point3::new(0.0, 0.0, 1.0))
+        .z
+}
+
+/// Returns the corners of the part of the view of `camera` between the depths of `depths`, in
+/// view space.
+fn frustum_slice(camera: &Camera, depths: Range<f32>) -> [Point3<f32>; 8] {
+    let mut corners = [Point3::origin(); 8];
+    for (i, (x, y)) in [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)]
+        .iter()
+        .enumerate()
+    {
+        // Depth is reversed, 1 on the near plane, and 0 at infinity for perspective cameras.
+        let front = camera.inverse.transform_point(&Point3::new(*x, *y, 1.0));
+        let back = camera.inverse.transform_point(&Point3::new(*x, *y, 0.5));
+        let ray = back - front;
+        let at_depth = |depth: f32| front + ray * ((depth + front.z) / -ray.z);
+        corners[i] = at_depth(depths.start);
+        corners[i + 4] = at_depth(depths.end);
+    }
+    corners
+}
+
+/// Returns the view matrix of an eye at `eye` looking along `direction`.
+fn look_at(eye: &Point3<f32>, direction: &Vector3<f32>) -> Matrix4<f32> {
+    let up = if direction.normalize().y.abs() > 0.99 {
+        Vector3::z()
+    } else {
+        Vector3::y()
+    };
+    Matrix4::look_at_rh(eye, &(eye + direction), &up)
+}
+
+/// Updates the [`ShadowMaps`] with the shadow views of the lights casting shadows, fitting the
+/// cascades of directional lights to the `ActiveCamera`.
+///
+/// Shadow casters are found with the [`SpatialIndex`], so this should run after the
+/// `SpatialIndexSystem`.
+#[derive(Debug, Default)]
+pub struct ShadowSystem;
+
+impl System for ShadowSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("ShadowSystem")
+                .read_resource::<ActiveCamera>()
+                .read_resource::<SpatialIndex>()
+                .write_resource::<ShadowMaps>()
+                .with_query(<(Entity, &Light, Option<&Transform>)>::query())
+                .with_query(<(Entity, &Camera, &Transform)>::query())
+                .build(
+                    |_, world, (active_camera, index, shadow_maps), (lights, cameras)| {
+                        #[cfg(feature = "profiler")]
+                        profile_scope!("shadow_system");
+
+                        let cameras: Vec<_> = cameras
+                            .iter(world)
+                            .map(|(entity, camera, transform)| {
+                                (*entity, camera, *transform.global_matrix())
+                            })
+                            .collect();
+                        let camera = cameras
+                            .iter()
+                            .find(|(entity, _, _)| Some(*entity) == active_camera.entity)
+                            .or_else(|| cameras.first())
+                            .map(|(_, camera, matrix)| (*camera, matrix));
+
+                        shadow_maps.update(
+                            camera,
+                            lights
+                                .iter(world)
+                                .map(|(entity, light, transform)| (*entity, light, transform)),
+                            index,
+                        );
+                    },
+                ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::ecs::World;
+
+    use super::*;
+    use crate::{
+        light::{PointLight, SpotLight},
+        visibility::BoundingSphere,
+    };
+
+    fn project(proj: &Matrix4<f32>, view: &Matrix4<f32>, point: &Point3<f32>) -> Point3<f32> {
+        (proj * view).transform_point(point)
+    }
+
+    fn inside(ndc: &Point3<f32>) -> bool {
+        let epsilon = 1e-4;
+        ndc.x.abs() <= 1.0 + epsilon
+            && ndc.y.abs() <= 1.0 + epsilon
+            && (-epsilon..=1.0 + epsilon).contains(&ndc.z)
+    }
+
+    #[test]
+    fn tiles_dont_overlap() {
+        let requests = [(256, 1), (512, 2), (256, 6), (2048, 1), (128, 4)];
+        let placed = pack_tiles(1024, &requests);
+        assert!(placed[3].is_none());
+
+        let mut tiles = Vec::new();
+        for (request, placed) in requests.iter().zip(&placed) {
+            if request.0 > 1024 {
+                continue;
+            }
+            let placed = placed.as_ref().unwrap();
+            assert_eq!(placed.len(), request.1);
+            for &(x, y) in placed {
+                assert_eq!((x % request.0, y % request.0), (0, 0));
+                assert!(x + request.0 <= 1024 && y + request.0 <= 1024);
+                tiles.push((x, y, request.0));
+            }
+        }
+        for (i, a) in tiles.iter().enumerate() {
+            for b in &tiles[i + 1..] {
+                let apart =
+                    a.0 + a.2 <= b.0 || b.0 + b.2 <= a.0 || a.1 + a.2 <= b.1 || b.1 + b.2 <= a.1;
+                assert!(apart, "{:?} overlaps {:?}", a, b);
+            }
+        }
+
+        // The atlas is full, the last tile doesn't fit.
+        let placed = pack_tiles(512, &[(256, 3), (128, 4), (128, 1)]);
+        assert!(placed[0].is_some() && placed[1].is_some());
+        assert!(placed[2].is_none());
+    }
+
+    #[test]
+    fn cascades_split_the_distance() {
+        let splits = cascade_splits(0.1, 100.0, 4);
+        assert_eq!(splits.len(), 4);
+        assert!(splits.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!((splits[3] - 100.0).abs() < 1e-3);
+        assert!(splits[0] < 25.0);
+    }
+
+    #[test]
+    fn cascade_contains_its_slice() {
+        let camera = Camera::standard_3d(16.0, 9.0);
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(3.0, 2.0, 10.0);
+        transform.append_rotation_y_axis(0.5);
+        transform.copy_local_to_global();
+        let camera_matrix = *transform.global_matrix();
+        let direction = Vector3::new(-1.0, -2.0, -0.5);
+
+        let (proj, view) =
+            directional_view(&direction, &camera, &camera_matrix, 2.0..12.0, 20.0, 1024);
+        for corner in &frustum_slice(&camera, 2.0..12.0) {
+            let world = camera_matrix.transform_point(corner);
+            assert!(inside(&project(&proj, &view, &world)), "{:?}", world);
+            // Casters towards the light are drawn.
+            let caster = world - direction.normalize() * 15.0;
+            assert!(inside(&project(&proj, &view, &caster)), "{:?}", caster);
+        }
+    }
+
+    #[test]
+    fn point_light_faces_cover_their_axis() {
+        let position = Point3::new(1.0, 2.0, 3.0);
+        let views = point_views(&position, 0.05);
+        assert_eq!(views.len(), 6);
+        for (face, (proj, view)) in CUBE_FACES.iter().zip(&views) {
+            let axis = Vector3::from(*face);
+            let side = if axis.y == 0.0 {
+                Vector3::y()
+            } else {
+                Vector3::x()
+            };
+            for offset in &[axis * 2.0, axis * 2.0 + side * 1.9, axis * 2.0 - side * 1.9] {
+                assert!(inside(&project(proj, view, &(position + offset))));
+            }
+            assert!(!inside(&project(proj, view, &(position - axis * 2.0))));
+        }
+
+        let (proj, view) = spot_view(&position, &Vector3::new(0.0, -1.0, 0.0), 0.5, 0.05);
+        let below = project(&proj, &view, &(position - Vector3::y() * 4.0));
+        assert!(inside(&below) && below.x.abs() < 1e-4 && below.y.abs() < 1e-4);
+    }
+
+    #[test]
+    fn shadow_views_of_lights() {
+        let mut world = World::default();
+        let caster = world.push(());
+        let far_caster = world.push(());
+        let mut index = SpatialIndex::default();
+        index.insert(caster, BoundingSphere::new(Point3::new(0.0, 0.0, 0.0), 0.5));
+        index.insert(
+            far_caster,
+            BoundingSphere::new(Point3::new(0.0, -50.0, 0.0), 0.5),
+        );
+
+        let settings = ShadowSettings {
+            resolution: 1000,
+            ..ShadowSettings::default()
+        };
+        let spot = Light::Spot(SpotLight {
+            shadows: Some(settings),
+            ..SpotLight::default()
+        });
+        let point = Light::Point(PointLight {
+            shadows: Some(settings),
+            ..PointLight::default()
+        });
+        let unshadowed = Light::Point(PointLight::default());
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(0.0, 5.0, 0.0);
+        transform.copy_local_to_global();
+        let lights = [world.push(()), world.push(()), world.push(())];
+
+        let mut shadow_maps = ShadowMaps::new(3000);
+        assert_eq!(shadow_maps.atlas_size(), 4096);
+        shadow_maps.update(
+            None,
+            vec![
+                (lights[0], &spot, Some(&transform)),
+                (lights[1], &unshadowed, Some(&transform)),
+                (lights[2], &point, Some(&transform)),
+            ],
+            &index,
+        );
+
+        assert_eq!(shadow_maps.views.len(), 7);
+        assert_eq!(shadow_maps.light_views(lights[0]), Some(0..1));
+        assert_eq!(shadow_maps.light_views(lights[1]), None);
+        assert_eq!(shadow_maps.light_views(lights[2]), Some(1..7));
+        assert_eq!(shadow_maps.views[0].tile.width, 0.25);
+
+        // The spot light points down on the caster, the other one is out of range.
+        assert_eq!(shadow_maps.views[0].casters, vec![caster]);
+        // Only the face of the point light looking down sees the caster.
+        let below = shadow_maps.views[1..]
+            .iter()
+            .filter(|view| view.casters.contains(&caster))
+            .count();
+        assert_eq!(below, 1);
+        assert!(shadow_maps.views[4].casters.contains(&caster));
+    }
+}
@@ -0,0 +1,860 @@
+//! Spatial index of the bounding spheres of entities, used for culling and picking.
+use std::cmp::Ordering;
+
+use amethyst_core::{
+    ecs::{
+        component, maybe_changed, world::Event, DispatcherBuilder, Entity, EntityStore, IntoQuery,
+        ParallelRunnable, Resources, System, SystemBuilder, World,
+    },
+    geometry::Ray,
+    math::{Point3, Vector3, Vector4},
+    transform::Transform,
+    Hidden, HiddenPropagate,
+};
+use crossbeam_channel::Receiver;
+use fnv::FnvHashMap;
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::visibility::{BoundingSphere, Frustum};
+
+const NULL: usize = usize::MAX;
+
+/// Fraction of the radius added around each sphere in the tree, so that small moves don't
+/// require updating the tree.
+const FAT_MARGIN: f32 = 0.25;
+
+/// Axis aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    /// Corner with the smallest coordinates.
+    pub min: Point3<f32>,
+    /// Corner with the largest coordinates.
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    /// Creates a box from its two corners.
+    #[must_use]
+    pub fn new(min: Point3<f32>, max: Point3<f32>) -> Self {
+        Self { min, max }
+    }
+
+    /// Creates the smallest box containing the sphere.
+    #[must_use]
+    pub fn from_sphere(center: &Point3<f32>, radius: f32) -> Self {
+        let extent = Vector3::repeat(radius);
+        Self {
+            min: center - extent,
+            max: center + extent,
+        }
+    }
+
+    /// Returns the smallest box containing both boxes.
+    #[must_use]
+    pub fn union(&self, other: &Aabb) -> Self {
+        Self {
+            min: self.min.inf(&other.min),
+            max: self.max.sup(&other.max),
+        }
+    }
+
+    /// Returns `true` if `other` is completely inside this box.
+    #[must_use]
+    pub fn contains(&self, other: &Aabb) -> bool {
+        (0..3).all(|axis| self.min[axis] <= other.min[axis] && other.max[axis] <= self.max[axis])
+    }
+
+    /// Returns `true` if the boxes overlap.
+    #[must_use]
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x
+            && other.min.x <= self.max.x
+            && self.min.y <= other.max.y
+            && other.min.y <= self.max.y
+            && self.min.z <= other.max.z
+            && other.min.z <= self.max.z
+    }
+
+    /// Returns the squared distance from `point` to the box, 0 if the point is inside.
+    #[must_use]
+    pub fn distance_squared(&self, point: &Point3<f32>) -> f32 {
+        let closest = point.sup(&self.min).inf(&self.max);
+        (point - closest).norm_squared()
+    }
+
+    /// Returns the distance along `ray` at which it enters the box, 0 if it starts inside, or
+    /// `None` if it misses the box.
+    #[must_use]
+    pub fn ray_distance(&self, ray: &Ray<f32>) -> Option<f32> {
+        let mut near = 0.0_f32;
+        let mut far = f32::INFINITY;
+        for axis in 0..3 {
+            let inverse = 1.0 / ray.direction[axis];
+            let mut t0 = (self.min[axis] - ray.origin[axis]) * inverse;
+            let mut t1 = (self.max[axis] - ray.origin[axis]) * inverse;
+            if inverse < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // `max` and `min` ignore the NaN of a ray parallel to a face of the box.
+            near = near.max(t0);
+            far = far.min(t1);
+            if far < near {
+                return None;
+            }
+        }
+        Some(near)
+    }
+
+    fn area(&self) -> f32 {
+        let size = self.max - self.min;
+        size.x * size.y + size.y * size.z + size.z * size.x
+    }
+
+    /// Returns `true` if the box is completely behind one of the planes.
+    fn outside_planes(&self, planes: &[Vector4<f32>]) -> bool {
+        planes.iter().any(|plane| {
+            // The corner furthest along the normal of the plane.
+            let pick = |normal: f32, max: f32, min: f32| if normal >= 0.0 { max } else { min };
+            let corner = Point3::new(
+                pick(plane.x, self.max.x, self.min.x),
+                pick(plane.y, self.max.y, self.min.y),
+                pick(plane.z, self.max.z, self.min.z),
+            );
+            plane.xyz().dot(&corner.coords) + plane.w < 0.0
+        })
+    }
+}
+
+/// An entity hit by [`SpatialIndex::ray_cast`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    /// The entity whose bounding sphere was hit.
+    pub entity: Entity,
+    /// Distance along the ray to the bounding sphere.
+    pub distance: f32,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    /// Bounds of the children, or the sphere with a margin for leaves.
+    aabb: Aabb,
+    parent: usize,
+    children: [usize; 2],
+    /// 0 for leaves.
+    height: u32,
+    /// The entity and its sphere in world space, for leaves.
+    leaf: Option<(Entity, BoundingSphere)>,
+}
+
+impl Node {
+    fn is_leaf(&self) -> bool {
+        self.children[0] == NULL
+    }
+}
+
+/// Bounding volume hierarchy of the visible entities with a `Transform`, using their
+/// `BoundingSphere` in world space.
+///
+/// The tree is updated incrementally by the system added with [`RenderBase3D`] or
+/// [`RenderFlat2D`], only for the entities which moved, so large scenes of static objects cost
+/// nothing to maintain. Entities without a `BoundingSphere` use the default one, a unit sphere.
+/// Hidden entities are not in the index.
+///
+/// It is used for frustum culling by the visibility systems and can be queried by game systems,
+/// for example for picking with [`SpatialIndex::ray_cast`].
+///
+/// [`RenderBase3D`]: crate::plugins::RenderBase3D
+/// [`RenderFlat2D`]: crate::plugins::RenderFlat2D
+#[derive(Debug, Clone)]
+pub struct SpatialIndex {
+    nodes: Vec<Node>,
+    free: Vec<usize>,
+    root: usize,
+    leaves: FnvHashMap<Entity, usize>,
+}
+
+impl Default for SpatialIndex {
+    fn default() -> Self {
+        Self {
+            nodes: Vec::new(),
+            free: Vec::new(),
+            root: NULL,
+            leaves: FnvHashMap::default(),
+        }
+    }
+}
+
+impl SpatialIndex {
+    /// Returns the number of entities in the index.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// Returns `true` if the index has no entity.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// Returns `true` if `entity` is in the index.
+    #[must_use]
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.leaves.contains_key(&entity)
+    }
+
+    /// Returns the bounding sphere of `entity` in world space.
+    #[must_use]
+    pub fn get(&self, entity: Entity) -> Option<&BoundingSphere> {
+        let node = *self.leaves.get(&entity)?;
+        self.nodes[node].leaf.as_ref().map(|(_, sphere)| sphere)
+    }
+
+    /// Inserts `entity` with a bounding sphere in world space, or moves it if it is already in
+    /// the index.
+    pub fn insert(&mut self, entity: Entity, sphere: BoundingSphere) {
+        if let Some(&node) = self.leaves.get(&entity) {
+            let tight = Aabb::from_sphere(&sphere.center, sphere.radius);
+            let moved = !self.nodes[node].aabb.contains(&tight);
+            if moved {
+                self.remove_leaf(node);
+                self.nodes[node].aabb = fat_aabb(&sphere);
+            }
+            self.nodes[node].leaf = Some((entity, sphere));
+            if moved {
+                self.insert_leaf(node);
+            }
+            return;
+        }
+
+        let node = self.allocate(Node {
+            aabb: fat_aabb(&sphere),
+            parent: NULL,
+            children: [NULL; 2],
+            height: 0,
+            leaf: Some((entity, sphere)),
+        });
+        self.insert_leaf(node);
+        self.leaves.insert(entity, node);
+    }
+
+    /// Removes `entity` from the index, returns `false` if it wasn't in it.
+    pub fn remove(&mut self, entity: Entity) -> bool {
+        match self.leaves.remove(&entity) {
+            Some(node) => {
+                self.remove_leaf(node);
+                self.deallocate(node);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes every entity.
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Calls `f` with the entities whose bounding sphere is inside or intersects the frustum.
+    pub fn query_frustum<F>(&self, frustum: &Frustum, mut f: F)
+    where
+        F: FnMut(Entity, &BoundingSphere),
+    {
+        self.query_planes(&frustum.planes, |entity, sphere| {
+            if frustum.check_sphere(&sphere.center, sphere.radius) {
+                f(entity, sphere);
+            }
+        });
+    }
+
+    /// Calls `f` with the entities whose bounding sphere may be in front of all `planes`.
+    ///
+    /// Planes are given as `(normal, distance)` with the front side where
+    /// `normal.dot(point) + distance` is positive. This is conservative: a sphere close to the
+    /// intersection of two planes can be reported while being behind both.
+    pub fn query_planes<F>(&self, planes: &[Vector4<f32>], mut f: F)
+    where
+        F: FnMut(Entity, &BoundingSphere),
+    {
+        self.traverse(
+            |aabb| !aabb.outside_planes(planes),
+            |entity, sphere| {
+                let outside = planes
+                    .iter()
+                    .any(|plane| plane.xyz().dot(&sphere.center.coords) + plane.w < -sphere.radius);
+                if !outside {
+                    f(entity, sphere);
+                }
+            },
+        );
+    }
+
+    /// Calls `f` with the entities whose bounding sphere intersects `aabb`.
+    pub fn query_aabb<F>(&self, aabb: &Aabb, mut f: F)
+    where
+        F: FnMut(Entity, &BoundingSphere),
+    {
+        self.traverse(
+            |node| node.intersects(aabb),
+            |entity, sphere| {
+                if aabb.distance_squared(&sphere.center) <= sphere.radius * sphere.radius {
+                    f(entity, sphere);
+                }
+            },
+        );
+    }
+
+    /// Calls `f` with the entities whose bounding sphere intersects the sphere.
+    pub fn query_sphere<F>(&self, center: &Point3<f32>, radius: f32, mut f: F)
+    where
+        F: FnMut(Entity, &BoundingSphere),
+    {
+        self.traverse(
+            |node| node.distance_squared(center) <= radius * radius,
+            |entity, sphere| {
+                let reach = radius + sphere.radius;
+                if (sphere.center - center).norm_squared() <= reach * reach {
+                    f(entity, sphere);
+                }
+            },
+        );
+    }
+
+    /// Returns the entities whose bounding sphere is hit by `ray` within `max_distance`, the
+    /// nearest first.
+    ///
+    /// `ray.direction` must be normalized for the distances to be in world units.
+    #[must_use]
+    pub fn ray_cast(&self, ray: &Ray<f32>, max_distance: f32) -> Vec<RayHit> {
+        let mut hits = Vec::new();
+        self.traverse(
+            |aabb| aabb.ray_distance(ray).is_some_and(|d| d <= max_distance),
+            |entity, sphere| {
+                if let Some(distance) = ray_sphere_distance(ray, sphere) {
+                    if distance <= max_distance {
+                        hits.push(RayHit { entity, distance });
+                    }
+                }
+            },
+        );
+        hits.sort_by(|a, b| {
+            a.distance
+                .partial_cmp(&b.distance)
+                .unwrap_or(Ordering::Equal)
+        });
+        hits
+    }
+
+    /// Returns the nearest entity whose bounding sphere is hit by `ray` within `max_distance`.
+    #[must_use]
+    pub fn ray_cast_first(&self, ray: &Ray<f32>, max_distance: f32) -> Option<RayHit> {
+        let mut best: Option<RayHit> = None;
+        let mut stack = self.stack();
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            let limit = best.map_or(max_distance, |hit| hit.distance);
+            match node.aabb.ray_distance(ray) {
+                Some(distance) if distance <= limit => {}
+                _ => continue,
+            }
+            if let Some((entity, sphere)) = &node.leaf {
+                if let Some(distance) = ray_sphere_distance(ray, sphere) {
+                    if distance <= limit {
+                        best = Some(RayHit {
+                            entity: *entity,
+                            distance,
+                        });
+                    }
+                }
+            } else {
+                stack.extend_from_slice(&node.children);
+            }
+        }
+        best
+    }
+
+    /// Returns the entity whose bounding sphere is the nearest to `point` within
+    /// `max_distance`, with the distance to its surface, 0 if the point is inside the sphere.
+    #[must_use]
+    pub fn nearest(&self, point: &Point3<f32>, max_distance: f32) -> Option<(Entity, f32)> {
+        let mut best: Option<(Entity, f32)> = None;
+        let mut stack = self.stack();
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            let limit = best.map_or(max_distance, |(_, distance)| distance);
+            if node.aabb.distance_squared(point) > limit * limit {
+                continue;
+            }
+            if let Some((entity, sphere)) = &node.leaf {
+                let distance = ((sphere.center - point).norm() - sphere.radius).max(0.0);
+                if distance <= limit {
+                    best = Some((*entity, distance));
+                }
+            } else {
+                // Visit the closest child first, it is more likely to shrink the search.
+                let [a, b] = node.children;
+                if self.nodes[a].aabb.distance_squared(point)
+                    < self.nodes[b].aabb.distance_squared(point)
+                {
+                    stack.extend_from_slice(&[b, a]);
+                } else {
+                    stack.extend_from_slice(&[a, b]);
+                }
+            }
+        }
+        best
+    }
+
+    fn stack(&self) -> Vec<usize> {
+        let mut stack = Vec::with_capacity(64);
+        if self.root != NULL {
+            stack.push(self.root);
+        }
+        stack
+    }
+
+    /// Visits the leaves under the nodes accepted by `enter`.
+    fn traverse<E, F>(&self, mut enter: E, mut f: F)
+    where
+        E: FnMut(&Aabb) -> bool,
+        F: FnMut(Entity, &BoundingSphere),
+    {
+        let mut stack = self.stack();
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !enter(&node.aabb) {
+                continue;
+            }
+            match &node.leaf {
+                Some((entity, sphere)) => f(*entity, sphere),
+                None => stack.extend_from_slice(&node.children),
+            }
+        }
+    }
+
+    fn allocate(&mut self, node: Node) -> usize {
+        if let Some(index) = self.free.pop() {
+            self.nodes[index] = node;
+            index
+        } else {
+            self.nodes.push(node);
+            self.nodes.len() - 1
+        }
+    }
+
+    fn deallocate(&mut self, index: usize) {
+        self.nodes[index].leaf = None;
+        self.free.push(index);
+    }
+
+    fn insert_leaf(&mut self, leaf: usize) {
+        if self.root == NULL {
+            self.root = leaf;
+            self.nodes[leaf].parent = NULL;
+            return;
+        }
+
+        // Find the sibling which grows the tree the least, by surface area.
+        let leaf_aabb = self.nodes[leaf].aabb;
+        let mut index = self.root;
+        while !self.nodes[index].is_leaf() {
+            let node = &self.nodes[index];
+            let area = node.aabb.area();
+            let combined = node.aabb.union(&leaf_aabb).area();
+            // Cost of making a new parent for this node and the leaf.
+            let cost = 2.0 * combined;
+            // Minimum cost of pushing the leaf further down the tree.
+            let inheritance = 2.0 * (combined - area);
+            let child_cost = |child: &Node| {
+                let grown = child.aabb.union(&leaf_aabb).area();
+                if child.is_leaf() {
+                    grown + inheritance
+                } else {
+                    grown - child.aabb.area() + inheritance
+                }
+            };
+            let [left, right] = node.children;
+            let cost_left = child_cost(&self.nodes[left]);
+            let cost_right = child_cost(&self.nodes[right]);
+            if cost < cost_left && cost < cost_right {
+                break;
+            }
+            index = if cost_left < cost_right { left } else { right };
+        }
+
+        let sibling = index;
+        let old_parent = self.nodes[sibling].parent;
+        let new_parent = self.allocate(Node {
+            aabb: leaf_aabb.union(&self.nodes[sibling].aabb),
+            parent: old_parent,
+            children: [sibling, leaf],
+            height: self.nodes[sibling].height + 1,
+            leaf: None,
+        });
+        if old_parent == NULL {
+            self.root = new_parent;
+        } else {
+            let children = &mut self.nodes[old_parent].children;
+            let slot = usize::from(children[0] != sibling);
+            children[slot] = new_parent;
+        }
+        self.nodes[sibling].parent = new_parent;
+        self.nodes[leaf].parent = new_parent;
+
+        self.refit(new_parent);
+    }
+
+    fn remove_leaf(&mut self, leaf: usize) {
+        if leaf == self.root {
+            self.root = NULL;
+            return;
+        }
+
+        let parent = self.nodes[leaf].parent;
+        let grand_parent = self.nodes[parent].parent;
+        let [left, right] = self.nodes[parent].children;
+        let sibling = if left == leaf { right } else { left };
+
+        self.nodes[parent].leaf = None;
+        self.free.push(parent);
+        if grand_parent == NULL {
+            self.root = sibling;
+            self.nodes[sibling].parent = NULL;
+        } else {
+            let children = &mut self.nodes[grand_parent].children;
+            let slot = usize::from(children[0] != parent);
+            children[slot] = sibling;
+            self.nodes[sibling].parent = grand_parent;
+            self.refit(grand_parent);
+        }
+    }
+
+    /// Balances and updates the bounds and heights of `index` and its ancestors.
+    fn refit(&mut self, mut index: usize) {
+        while index != NULL {
+            index = self.balance(index);
+            let [left, right] = self.nodes[index].children;
+            let height = 1 + self.nodes[left].height.max(self.nodes[right].height);
+            let aabb = self.nodes[left].aabb.union(&self.nodes[right].aabb);
+            let node = &mut self.nodes[index];
+            node.height = height;
+            node.aabb = aabb;
+            index = node.parent;
+        }
+    }
+
+    /// Rotates the subtree of `a` if one child is more than one level deeper than the other,
+    /// returns the new root of the subtree.
+    fn balance(&mut self, a: usize) -> usize {
+        if self.nodes[a].is_leaf() || self.nodes[a].height < 2 {
+            return a;
+        }
+        let [b, c] = self.nodes[a].children;
+        let balance = i64::from(self.nodes[c].height) - i64::from(self.nodes[b].height);
+        if balance > 1 {
+            self.rotate(a, c, 1)
+        } else if balance < -1 {
+            self.rotate(a, b, 0)
+        } else {
+            a
+        }
+    }
+
+    /// Moves `child`, the deeper child of `a` at `slot`, up in place of `a`.
+    fn rotate(&mut self, a: usize, child: usize, slot: usize) -> usize {
+        let other = self.nodes[a].children[1 - slot];
+        let [f, g] = self.nodes[child].children;
+
+        // `child` takes the place of `a`.
+        let parent = self.nodes[a].parent;
+        self.nodes[child].parent = parent;
+        self.nodes[a].parent = child;
+        if parent == NULL {
+            self.root = child;
+        } else {
+            let children = &mut self.nodes[parent].children;
+            let index = usize::from(children[0] != a);
+            children[index] = child;
+        }
+
+        // The deeper grandchild stays under `child`, the other one moves under `a`.
+        let (keep, moved) = if self.nodes[f].height > self.nodes[g].height {
+            (f, g)
+        } else {
+            (g, f)
+        };
+        self.nodes[child].children = [a, keep];
+        self.nodes[a].children[slot] = moved;
+        self.nodes[moved].parent = a;
+
+        let a_aabb = self.nodes[other].aabb.union(&self.nodes[moved].aabb);
+        let a_height = 1 + self.nodes[other].height.max(self.nodes[moved].height);
+        self.nodes[a].aabb = a_aabb;
+        self.nodes[a].height = a_height;
+        self.nodes[child].aabb = a_aabb.union(&self.nodes[keep].aabb);
+        self.nodes[child].height = 1 + a_height.max(self.nodes[keep].height);
+        child
+    }
+}
+
+fn fat_aabb(sphere: &BoundingSphere) -> Aabb {
+    Aabb::from_sphere(&sphere.center, sphere.radius * (1.0 + FAT_MARGIN))
+}
+
+/// Returns the distance along `ray` to the sphere, 0 if the ray starts inside it.
+fn ray_sphere_distance(ray: &Ray<f32>, sphere: &BoundingSphere) -> Option<f32> {
+    let to_center = sphere.center - ray.origin;
+    let length_squared = ray.direction.norm_squared();
+    let projection = to_center.dot(&ray.direction) / length_squared;
+    let distance_squared = to_center.norm_squared() - projection * projection * length_squared;
+    let radius_squared = sphere.radius * sphere.radius;
+    if distance_squared > radius_squared {
+        return None;
+    }
+    let half_chord = ((radius_squared - distance_squared) / length_squared).sqrt();
+    if projection + half_chord < 0.0 {
+        return None;
+    }
+    Some((projection - half_chord).max(0.0))
+}
+
+/// Returns the bounding sphere of an entity in world space.
+pub(crate) fn world_sphere(
+    transform: &Transform,
+    sphere: Option<&BoundingSphere>,
+) -> BoundingSphere {
+    let matrix = transform.global_matrix();
+    let center = sphere.map_or_else(Point3::origin, |s| s.center);
+    BoundingSphere {
+        center: matrix.transform_point(&center),
+        radius: sphere.map_or(1.0, |s| s.radius)
+            * matrix[(0, 0)].max(matrix[(1, 1)]).max(matrix[(2, 2)]),
+    }
+}
+
+/// Keeps the [`SpatialIndex`] up to date with the `Transform` and `BoundingSphere` of entities.
+///
+/// Added by [`RenderBase3D`] and [`RenderFlat2D`], after the `TransformSystem`.
+///
+/// [`RenderBase3D`]: crate::plugins::RenderBase3D
+/// [`RenderFlat2D`]: crate::plugins::RenderFlat2D
+#[derive(Debug)]
+pub struct SpatialIndexSystem {
+    events: Receiver<Event>,
+}
+
+impl SpatialIndexSystem {
+    /// Creates the system, subscribing to the entities of `world` which enter or leave the
+    /// index.
+    pub fn new(world: &mut World) -> Self {
+        let (sender, events) = crossbeam_channel::unbounded();
+        world.subscribe(
+            sender,
+            component::<Transform>() & !component::<Hidden>() & !component::<HiddenPropagate>(),
+        );
+        Self { events }
+    }
+}
+
+impl System for SpatialIndexSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        let events = self.events;
+        let mut inserted = Vec::new();
+
+        Box::new(
+            SystemBuilder::new("SpatialIndexSystem")
+                .write_resource::<SpatialIndex>()
+                .read_component::<Transform>()
+                .read_component::<BoundingSphere>()
+                .with_query(
+                    <(Entity, &Transform, Option<&BoundingSphere>)>::query().filter(
+                        (maybe_changed::<Transform>() | maybe_changed::<BoundingSphere>())
+                            & !component::<Hidden>()
+                            & !component::<HiddenPropagate>(),
+                    ),
+                )
+                .build(move |_, world, index, query| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("spatial_index_system");
+
+                    // Adding or removing a component moves an entity to another archetype,
+                    // which sends both events in order.
+                    for event in events.try_iter() {
+                        match event {
+                            Event::EntityInserted(entity, _) => inserted.push(entity),
+                            Event::EntityRemoved(entity, _) => {
+                                inserted.retain(|e| *e != entity);
+                                index.remove(entity);
+                            }
+                            _ => {}
+                        }
+                    }
+                    for entity in inserted.drain(..) {
+                        if let Ok(entry) = world.entry_ref(entity) {
+                            if let Ok(transform) = entry.get_component::<Transform>() {
+                                let sphere = entry.get_component::<BoundingSphere>().ok();
+                                index.insert(entity, world_sphere(transform, sphere));
+                            }
+                        }
+                    }
+
+                    query.for_each(world, |(entity, transform, sphere)| {
+                        index.insert(*entity, world_sphere(transform, sphere));
+                    });
+                }),
+        )
+    }
+}
+
+/// Inserts the [`SpatialIndex`] and adds its system, unless another plugin did it already.
+pub(crate) fn add_spatial_index(
+    world: &mut World,
+    resources: &mut Resources,
+    builder: &mut DispatcherBuilder,
+) {
+    if !resources.contains::<SpatialIndex>() {
+        resources.insert(SpatialIndex::default());
+        builder.add_system(SpatialIndexSystem::new(world));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::{
+        ecs::World,
+        math::{Matrix4, Vector3},
+    };
+
+    use super::*;
+
+    /// Spheres on a grid, with the entities in the same order.
+    fn grid(world: &mut World, index: &mut SpatialIndex) -> Vec<(Entity, BoundingSphere)> {
+        let mut spheres = Vec::new();
+        for x in 0..20_u16 {
+            for y in 0..20_u16 {
+                let entity = world.push((x, y));
+                let sphere = BoundingSphere::new(
+                    Point3::new(f32::from(x) * 3.0, f32::from(y) * 3.0, f32::from(x * y % 7)),
+                    0.5 + f32::from((x + y) % 3) * 0.25,
+                );
+                index.insert(entity, sphere.clone());
+                spheres.push((entity, sphere));
+            }
+        }
+        spheres
+    }
+
+    fn sorted(mut entities: Vec<Entity>) -> Vec<Entity> {
+        entities.sort_by_key(|e| format!("{:?}", e));
+        entities
+    }
+
+    #[test]
+    fn queries_match_brute_force() {
+        let mut world = World::default();
+        let mut index = SpatialIndex::default();
+        let spheres = grid(&mut world, &mut index);
+        assert_eq!(index.len(), 400);
+
+        let frustum = Frustum::new(
+            Matrix4::new_perspective(1.0, 1.0, 0.1, 100.0)
+                * Matrix4::look_at_rh(
+                    &Point3::new(30.0, 30.0, 40.0),
+                    &Point3::new(20.0, 10.0, 0.0),
+                    &Vector3::y(),
+                ),
+        );
+        let mut found = Vec::new();
+        index.query_frustum(&frustum, |entity, _| found.push(entity));
+        let expected: Vec<_> = spheres
+            .iter()
+            .filter(|(_, s)| frustum.check_sphere(&s.center, s.radius))
+            .map(|(e, _)| *e)
+            .collect();
+        assert!(!expected.is_empty() && expected.len() < spheres.len());
+        assert_eq!(sorted(found), sorted(expected));
+
+        let center = Point3::new(10.0, 10.0, 2.0);
+        let mut found = Vec::new();
+        index.query_sphere(&center, 5.0, |entity, _| found.push(entity));
+        let expected: Vec<_> = spheres
+            .iter()
+            .filter(|(_, s)| (s.center - center).norm() <= 5.0 + s.radius)
+            .map(|(e, _)| *e)
+            .collect();
+        assert_eq!(sorted(found), sorted(expected));
+
+        let (nearest, distance) = index
+            .nearest(&Point3::new(31.0, 29.0, 100.0), 1000.0)
+            .unwrap();
+        let expected = spheres
+            .iter()
+            .map(|(e, s)| {
+                (
+                    *e,
+                    (s.center - Point3::new(31.0, 29.0, 100.0)).norm() - s.radius,
+                )
+            })
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .unwrap();
+        assert_eq!(nearest, expected.0);
+        assert!((distance - expected.1).abs() < 1e-4);
+    }
+
+    #[test]
+    fn ray_cast_returns_hits_nearest_first() {
+        let mut world = World::default();
+        let mut index = SpatialIndex::default();
+        let spheres = grid(&mut world, &mut index);
+
+        let ray = Ray {
+            origin: Point3::new(-10.0, 0.0, 0.0),
+            direction: Vector3::x(),
+        };
+        let hits = index.ray_cast(&ray, 1000.0);
+        // The spheres of the row at `y = 0` and `z = 0`.
+        assert_eq!(hits.len(), 20);
+        assert_eq!(hits[0].entity, spheres[0].0);
+        assert!((hits[0].distance - 9.5).abs() < 1e-4);
+        assert!(hits.windows(2).all(|w| w[0].distance <= w[1].distance));
+        assert_eq!(index.ray_cast_first(&ray, 1000.0), Some(hits[0]));
+        assert_eq!(index.ray_cast_first(&ray, 5.0), None);
+    }
+
+    #[test]
+    fn moved_and_removed_entities_are_updated() {
+        let mut world = World::default();
+        let mut index = SpatialIndex::default();
+        let spheres = grid(&mut world, &mut index);
+
+        let (moved, _) = spheres[0];
+        index.insert(
+            moved,
+            BoundingSphere::new(Point3::new(500.0, 0.0, 0.0), 1.0),
+        );
+        assert_eq!(index.len(), 400);
+        assert_eq!(
+            index.nearest(&Point3::new(510.0, 0.0, 0.0), 100.0),
+            Some((moved, 9.0))
+        );
+
+        for (entity, _) in &spheres[..200] {
+            assert!(index.remove(*entity));
+        }
+        assert!(!index.remove(moved));
+        assert_eq!(index.len(), 200);
+        let mut found = Vec::new();
+        index.query_aabb(
+            &Aabb::new(
+                Point3::new(-100.0, -100.0, -100.0),
+                Point3::new(1e3, 1e3, 1e3),
+            ),
+            |entity, _| found.push(entity),
+        );
+        assert_eq!(
+            sorted(found),
+            sorted(spheres[200..].iter().map(|(e, _)| *e).collect())
+        );
+    }
+}
@@ -2,28 +2,28 @@
 use std::cmp::Ordering;
 
 use amethyst_core::{
-    ecs::{component, Entity, IntoQuery, ParallelRunnable, System, SystemBuilder},
-    math::{Point3, Vector3},
+    ecs::{Entity, EntityStore, IntoQuery, ParallelRunnable, System, SystemBuilder},
+    math::{Point3, Vector3, Vector4},
     transform::Transform,
-    Hidden, HiddenPropagate,
 };
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
 
 use crate::{
-    camera::{ActiveCamera, Camera},
+    camera::{ActiveCamera, Camera, CameraView, RenderLayers},
+    render_texture::{self, RenderTexture},
+    spatial::SpatialIndex,
     sprite::SpriteRender,
     transparent::Transparent,
+    visibility::{entity_layers, reset_views, ViewVisibility},
 };
 
-/// Resource for controlling what entities should be rendered, and whether to draw them ordered or
-/// not, which is useful for transparent surfaces.
+/// Resource for controlling what sprites should be rendered by each camera, and whether to draw
+/// them ordered or not, which is useful for transparent surfaces.
 #[derive(Default, Debug)]
 pub struct SpriteVisibility {
-    /// Visible entities that can be drawn in any order
-    pub visible_unordered: Vec<Entity>,
-    /// Visible entities that need to be drawn in the given order
-    pub visible_ordered: Vec<Entity>,
+    /// Sprites visible to each rendered camera, in render order.
+    pub views: Vec<ViewVisibility>,
 }
 
 #[derive(Debug, Clone)]
@@ -34,14 +34,18 @@ struct Internals {
     from_camera: Vector3<f32>,
 }
 
-/// Determines what entities to be drawn. Will also sort transparent entities back to front based on
-/// position on the Z axis.
+/// Determines what entities to be drawn by each rendered camera. Will also sort transparent
+/// entities back to front based on position on the Z axis.
 ///
 /// The sprite render pass should draw all sprites without semi-transparent pixels, then draw the
 /// sprites with semi-transparent pixels from far to near.
 ///
-/// Note that this should run after `Transform` has been updated for the current frame, and
-/// before rendering occurs.
+/// The rendered cameras are the `ActiveCamera`, the cameras with a [`CameraView`] and the cameras
+/// with a [`RenderTexture`], each of which only sees the sprites on one of its [`RenderLayers`].
+///
+/// Sprites in front of the camera are found with the [`SpatialIndex`], which must be up to date
+/// with the `Transform` of the current frame, so this should run after the `SpatialIndexSystem`
+/// and before rendering occurs.
 #[derive(Debug)]
 pub struct SpriteVisibilitySortingSystem;
 
@@ -52,92 +56,97 @@ impl System for SpriteVisibilitySortingSystem {
         Box::new(
             SystemBuilder::<()>::new("SpriteVisibilitySortingSystem")
                 .read_resource::<ActiveCamera>()
+                .read_resource::<SpatialIndex>()
                 .write_resource::<SpriteVisibility>()
-                .with_query(<(&Camera, &Transform)>::query())
-                .with_query(<(Entity, &Camera, &Transform)>::query())
-                .with_query(
-                    <(Entity, &Transform, &SpriteRender, &Transparent)>::query()
-                        .filter(!component::<Hidden>() & !component::<HiddenPropagate>()),
-                )
-                .with_query(<(Entity, &Transform, &SpriteRender)>::query().filter(
-                    !component::<Transparent>()
-                        & !component::<Hidden>()
-                        & !component::<HiddenPropagate>(),
-                ))
+                .read_component::<Camera>()
+                .read_component::<Transform>()
+                .read_component::<SpriteRender>()
+                .read_component::<Transparent>()
+                .read_component::<RenderLayers>()
+                .with_query(<(
+                    Entity,
+                    &Camera,
+                    Option<&CameraView>,
+                    Option<&RenderTexture>,
+                )>::query())
                 .build(
-                    move |commands,
-                          world,
-                          (active_camera, visibility),
-                          (
-                        camera_query1,
-                        camera_query2,
-                        transparent_query,
-                        non_transparent_query,
-                    )| {
+                    move |commands, world, (active_camera, index, visibility), camera_query| {
                         #[cfg(feature = "profiler")]
                         profile_scope!("sprite_visibility_system");
 
-                        transparent_centroids.clear();
-                        visibility.visible_ordered.clear();
-                        visibility.visible_unordered.clear();
+                        let cameras = render_texture::target_views(
+                            active_camera.entity,
+                            camera_query.iter(world).map(|(entity, _, view, texture)| {
+                                (*entity, view, texture.map(RenderTexture::target))
+                            }),
+                        );
+                        reset_views(&mut visibility.views, &cameras);
 
                         let origin = Point3::origin();
 
-                        let (camera, camera_transform) = match active_camera.entity.map_or_else(
-                            || camera_query1.iter(world).next(),
-                            |e| {
-                                camera_query2
-                                    .iter(world)
-                                    .find(|(camera_entity, _, _)| **camera_entity == e)
-                                    .map(|(_entity, camera, camera_transform)| {
-                                        (camera, camera_transform)
-                                    })
-                            },
-                        ) {
-                            Some(r) => r,
-                            None => return,
-                        };
+                        for (view_visibility, (camera_entity, camera_view, _)) in
+                            visibility.views.iter_mut().zip(&cameras)
+                        {
+                            transparent_centroids.clear();
+
+                            let camera_transform = match world
+                                .entry_ref(*camera_entity)
+                                .ok()
+                                .and_then(|entry| entry.into_component::<Transform>().ok())
+                            {
+                                Some(transform) => transform,
+                                None => continue,
+                            };
 
-                        let camera_backward = camera_transform.global_matrix().column(2).xyz();
-                        let camera_centroid =
-                            camera_transform.global_matrix().transform_point(&origin);
+                            let camera_backward =
+                                camera_transform.global_matrix().column(2).xyz().normalize();
+                            let camera_centroid =
+                                camera_transform.global_matrix().transform_point(&origin);
+                            let camera_plane = Vector4::new(
+                                -camera_backward.x,
+                                -camera_backward.y,
+                                -camera_backward.z,
+                                camera_backward.dot(&camera_centroid.coords),
+                            );
 
-                        transparent_centroids.extend(
-                            transparent_query
-                                .iter(world)
-                                .map(|(e, t, _, _)| {
-                                    (*e, t.global_matrix().transform_point(&origin))
-                                })
+                            let visible_unordered = &mut view_visibility.visible_unordered;
+                            index.query_planes(&[camera_plane], |entity, sphere| {
+                                let centroid = sphere.center;
                                 // filter entities behind the camera
-                                .filter(|(_, c)| (c - camera_centroid).dot(&camera_backward) < 0.0)
-                                .map(|(entity, centroid)| {
-                                    Internals {
+                                if (centroid - camera_centroid).dot(&camera_backward) >= 0.0 {
+                                    return;
+                                }
+                                let entry = match world.entry_ref(entity) {
+                                    Ok(entry) if entry.get_component::<SpriteRender>().is_ok() => {
+                                        entry
+                                    }
+                                    _ => return,
+                                };
+                                if !camera_view.layers.intersects(entity_layers(&entry)) {
+                                    return;
+                                }
+                                if entry.get_component::<Transparent>().is_ok() {
+                                    transparent_centroids.push(Internals {
                                         entity,
                                         centroid,
                                         camera_distance: (centroid.z - camera_centroid.z).abs(),
                                         from_camera: centroid - camera_centroid,
-                                    }
-                                }),
-                        );
-
-                        transparent_centroids.sort_by(|a, b| {
-                            b.camera_distance
-                                .partial_cmp(&a.camera_distance)
-                                .unwrap_or(Ordering::Equal)
-                        });
+                                    });
+                                } else {
+                                    visible_unordered.insert(entity);
+                                }
+                            });
 
-                        visibility
-                            .visible_ordered
-                            .extend(transparent_centroids.iter().map(|c| c.entity));
+                            transparent_centroids.sort_by(|a, b| {
+                                b.camera_distance
+                                    .partial_cmp(&a.camera_distance)
+                                    .unwrap_or(Ordering::Equal)
+                            });
 
-                        visibility.visible_unordered.extend(
-                            non_transparent_query
-                                .iter(world)
-                                .map(|(e, t, _)| (e, t.global_matrix().transform_point(&origin)))
-                                // filter entities behind the camera
-                                .filter(|(_, c)| (c - camera_centroid).dot(&camera_backward) < 0.0)
-                                .map(|(entity, _)| entity),
-                        );
+                            view_visibility
+                                .visible_ordered
+                                .extend(transparent_centroids.iter().map(|c| c.entity));
+                        }
                     },
                 ),
         )
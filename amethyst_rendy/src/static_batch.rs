@@ -0,0 +1,171 @@
+//! Static batching, merging the meshes of entities which never move into one mesh per material.
+//!
+//! Entities sharing a mesh and a material are already drawn with one instanced draw call. Static
+//! batching also removes the draw calls of entities with different meshes: the entities tagged
+//! [`Static`] with the CPU copy of their mesh in a [`MeshVertices`] component are merged by
+//! [`build_static_batches`], their vertices transformed into world space, and replaced by one
+//! entity per material drawing the merged mesh. The merged entities keep their `MeshVertices`, so
+//! they are still picked one by one.
+//!
+//! ```no_run
+//! use amethyst::{
+//!     assets::{DefaultLoader, Handle, ProcessingQueue},
+//!     core::transform::Transform,
+//!     ecs::{Resources, World},
+//!     renderer::{
+//!         mesh_vertices::MeshVertices,
+//!         shape::Shape,
+//!         static_batch::{build_static_batches, Static},
+//!         types::MeshData,
+//!         Material,
+//!     },
+//! };
+//!
+//! # fn example(world: &mut World, resources: &Resources, material: Handle<Material>) {
+//! let cube = MeshVertices::from_shape(&Shape::Cube, None);
+//! for x in 0..100 {
+//!     let mut transform = Transform::default();
+//!     transform.set_translation_x(x as f32 * 3.0);
+//!     world.push((Static, cube.clone(), material.clone(), transform));
+//! }
+//!
+//! // Once the global matrices of the transforms have been computed:
+//! let loader = resources.get::<DefaultLoader>().unwrap();
+//! let queue = resources.get::<ProcessingQueue<MeshData>>().unwrap();
+//! let batches = build_static_batches(world, &*loader, &queue);
+//! # }
+//! ```
+
+use amethyst_assets::{Handle, Loader, ProcessingQueue};
+use amethyst_core::{
+    ecs::{component, Entity, IntoQuery, World},
+    math::{convert, Matrix4},
+    transform::Transform,
+};
+use fnv::FnvHashSet;
+use rendy::mesh::MeshBuilder;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    camera::RenderLayers,
+    lod::MeshLod,
+    mesh_vertices::MeshVertices,
+    mtl::Material,
+    resources::Tint,
+    skinning::JointTransforms,
+    transparent::Transparent,
+    types::{Mesh, MeshData},
+};
+
+/// Marks an entity which never moves, whose mesh can be merged into a static batch.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Static;
+
+/// Component of the entities drawing a static batch, with the entities merged into it.
+///
+/// Picking ignores these entities and hits the merged ones instead.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StaticBatch {
+    /// Entities whose meshes are merged into the batch.
+    pub entities: Vec<Entity>,
+}
+
+/// Entities drawn by the same static batch.
+#[derive(Debug, PartialEq)]
+struct BatchKey {
+    material: Handle<Material>,
+    tint: Option<Tint>,
+    layers: Option<RenderLayers>,
+    transparent: bool,
+}
+
+/// Merges the meshes of the entities with [`Static`], [`MeshVertices`], a `Handle<Material>` and
+/// a `Transform` into one mesh per material, and returns the entities drawing the merged meshes,
+/// which have a [`StaticBatch`] component.
+///
+/// Entities are only merged if they also have the same `Tint`, `RenderLayers` and transparency.
+/// The merged entities keep their components except their `Handle<Mesh>`, so they are no longer
+/// drawn. Skinned entities, entities with a [`MeshLod`] and the entities already merged into a
+/// batch are not merged.
+///
+/// The vertices are transformed with the global matrix of the `Transform`, so this must be
+/// called after the transforms of the entities have been updated.
+pub fn build_static_batches(
+    world: &mut World,
+    loader: &impl Loader,
+    queue: &ProcessingQueue<MeshData>,
+) -> Vec<Entity> {
+    let batched: FnvHashSet<Entity> = <&StaticBatch>::query()
+        .iter(world)
+        .flat_map(|batch| batch.entities.iter().copied())
+        .collect();
+    let mut batches: Vec<(BatchKey, MeshVertices, Vec<Entity>)> = Vec::new();
+
+    let mut query = <(
+        Entity,
+        &MeshVertices,
+        &Handle<Material>,
+        &Transform,
+        Option<&Tint>,
+        Option<&RenderLayers>,
+        Option<&Transparent>,
+    )>::query()
+    .filter(component::<Static>() & !component::<JointTransforms>() & !component::<MeshLod>());
+    for (entity, vertices, material, transform, tint, layers, transparent) in query.iter(world) {
+        if batched.contains(entity) {
+            continue;
+        }
+        let key = BatchKey {
+            material: material.clone(),
+            tint: tint.copied(),
+            layers: layers.copied(),
+            transparent: transparent.is_some(),
+        };
+        let matrix = convert::<_, Matrix4<f32>>(*transform.global_matrix());
+        match batches.iter_mut().find(|(batch, _, _)| *batch == key) {
+            Some((_, batch, entities)) => {
+                batch.append(vertices, &matrix);
+                entities.push(*entity);
+            }
+            None => {
+                let mut batch = MeshVertices::default();
+                batch.append(vertices, &matrix);
+                batches.push((key, batch, vec![*entity]));
+            }
+        }
+    }
+
+    batches
+        .into_iter()
+        .map(|(key, vertices, entities)| {
+            for entity in &entities {
+                if let Some(mut entry) = world.entry(*entity) {
+                    entry.remove_component::<Handle<Mesh>>();
+                }
+            }
+            let sphere = vertices.bounding_sphere();
+            let mesh =
+                loader.load_from_data(MeshData::from(MeshBuilder::from(vertices)), (), queue);
+            let entity = world.push((
+                Static,
+                StaticBatch { entities },
+                mesh,
+                key.material,
+                Transform::default(),
+                sphere,
+            ));
+            if let Some(mut entry) = world.entry(entity) {
+                if let Some(tint) = key.tint {
+                    entry.add_component(tint);
+                }
+                if let Some(layers) = key.layers {
+                    entry.add_component(layers);
+                }
+                if key.transparent {
+                    entry.add_component(Transparent);
+                }
+            }
+            entity
+        })
+        .collect()
+}
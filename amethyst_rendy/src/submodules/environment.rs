@@ -1,7 +1,9 @@
 //! Environment submodule for shared environmental descriptor set data.
 //! Fetches and sets projection and lighting descriptor set information.
+use std::ops::Range;
+
 use amethyst_core::{
-    ecs::{IntoQuery, Read, Resources, World},
+    ecs::{Entity, IntoQuery, Read, Resources, World},
     math::{convert, Vector3},
     transform::Transform,
 };
@@ -11,36 +13,61 @@ use thread_profiler::profile_scope;
 use util::{usize_range, write_into_slice};
 
 use crate::{
-    light::Light,
+    cluster::{LightClusters, CLUSTER_COUNT},
+    light::{AreaShape, Light},
+    ltc::{self, LTC_SIZE},
     pod::{self, IntoPod},
     rendy::{
-        command::RenderPassEncoder,
-        factory::Factory,
+        command::{QueueId, RenderPassEncoder},
+        factory::{Factory, ImageState},
         hal::{
             self,
             adapter::PhysicalDevice,
             device::Device,
-            pso::{CreationError, Descriptor},
+            format::{Aspects, Format, Swizzle},
+            image::{Filter, Kind, Layout, SamplerDesc, SubresourceRange, ViewKind, WrapMode},
+            pso::{Comparison, CreationError, Descriptor},
         },
         memory::Write as _,
-        resource::{Buffer, DescriptorSet, DescriptorSetLayout, Escape, Handle as RendyHandle},
+        resource::{
+            Buffer, DescriptorSet, DescriptorSetLayout, Escape, Handle as RendyHandle, Image,
+            ImageView, ImageViewInfo, Sampler,
+        },
+        texture::{Texture, TextureBuilder},
     },
+    shadow::{ShadowMaps, MAX_SHADOW_VIEWS},
     submodules::gather::{AmbientGatherer, CameraGatherer},
     types::Backend,
     util::{self, TapCountIter},
+    visibility::BoundingSphere,
 };
 
-const MAX_POINT_LIGHTS: usize = 128;
 const MAX_DIR_LIGHTS: usize = 16;
-const MAX_SPOT_LIGHTS: usize = 128;
 
 /// Submodule for loading and binding descriptor sets for a 3D, lit environment.
 /// This also abstracts away the need for handling multiple images in flight, as it provides
 /// per-image submissions.
+///
+/// Each rendered camera has its own sets, see [`EnvironmentSub::process_views`]. The point, spot
+/// and area lights are assigned to the clusters of the view of the camera, see
+/// [`crate::cluster`].
 #[derive(Debug)]
 pub struct EnvironmentSub<B: Backend> {
     layout: RendyHandle<DescriptorSetLayout<B>>,
-    per_image: Vec<PerImageEnvironmentSub<B>>,
+    shadow_map: ShadowMap<B>,
+    ltc: [Texture<B>; 2],
+    views: Vec<Vec<PerImageEnvironmentSub<B>>>,
+}
+
+/// Shadow atlas sampled by the environment sets, or an empty depth image when the pass draws no
+/// shadows.
+#[derive(Debug)]
+enum ShadowMap<B: Backend> {
+    Atlas {
+        view: Escape<ImageView<B>>,
+        sampler: Escape<Sampler<B>>,
+    },
+    Empty(Texture<B>),
 }
 
 /// Submodule for loading and binding descriptor sets for a 3D, lit environment.
@@ -49,46 +76,65 @@ pub struct EnvironmentSub<B: Backend> {
 #[derive(Debug)]
 struct PerImageEnvironmentSub<B: Backend> {
     buffer: Option<Escape<Buffer<B>>>,
+    ranges: Vec<Range<u64>>,
+    clusters: LightClusters,
     set: Escape<DescriptorSet<B>>,
 }
 
 impl<B: Backend> EnvironmentSub<B> {
     /// Create and allocate a new `EnvironmentSub` with the provided rendy `Factory`
     /// Allocate to the supplied shader.
+    ///
+    /// `shadow_map` is the shadow atlas drawn into `Target::ShadowMap`, or `None` if the lights
+    /// cast no shadow in this pass.
     pub fn new(
-        factory: &Factory<B>,
+        factory: &mut Factory<B>,
+        queue: QueueId,
         flags: [hal::pso::ShaderStageFlags; 2],
+        shadow_map: Option<RendyHandle<Image<B>>>,
     ) -> Result<Self, CreationError> {
-        use rendy::hal::pso::{BufferDescriptorFormat, BufferDescriptorType, DescriptorType};
+        use rendy::hal::pso::{
+            BufferDescriptorFormat, BufferDescriptorType, DescriptorType, ImageDescriptorType,
+        };
 
+        let uniform = DescriptorType::Buffer {
+            ty: BufferDescriptorType::Uniform,
+            format: BufferDescriptorFormat::Structured {
+                dynamic_offset: false,
+            },
+        };
+        let storage = DescriptorType::Buffer {
+            ty: BufferDescriptorType::Storage { read_only: true },
+            format: BufferDescriptorFormat::Structured {
+                dynamic_offset: false,
+            },
+        };
+        let sampler = DescriptorType::Image {
+            ty: ImageDescriptorType::Sampled { with_sampler: true },
+        };
+        // The fragment stage finds the light cluster of a pixel with the view matrices.
         let layout = factory
             .create_descriptor_set_layout(util::set_layout_bindings(vec![
-                (
-                    1,
-                    DescriptorType::Buffer {
-                        ty: BufferDescriptorType::Uniform,
-                        format: BufferDescriptorFormat::Structured {
-                            dynamic_offset: false,
-                        },
-                    },
-                    flags[0],
-                ),
-                (
-                    4,
-                    DescriptorType::Buffer {
-                        ty: BufferDescriptorType::Uniform,
-                        format: BufferDescriptorFormat::Structured {
-                            dynamic_offset: false,
-                        },
-                    },
-                    flags[1],
-                ),
+                (1, uniform, flags[0] | flags[1]),
+                (1, uniform, flags[1]),
+                (1, storage, flags[1]),
+                (1, uniform, flags[1]),
+                (1, storage, flags[1]),
+                (1, uniform, flags[1]),
+                (1, sampler, flags[1]),
+                (3, storage, flags[1]),
+                (2, sampler, flags[1]),
             ]))?
             .into();
 
         Ok(Self {
             layout,
-            per_image: Vec::new(),
+            shadow_map: ShadowMap::new(factory, queue, shadow_map)?,
+            ltc: [
+                ltc_texture(factory, queue, ltc::ltc_1())?,
+                ltc_texture(factory, queue, ltc::ltc_2())?,
+            ],
+            views: Vec::new(),
         })
     }
 
@@ -109,14 +155,54 @@ impl<B: Backend> EnvironmentSub<B> {
         #[cfg(feature = "profiler")]
         profile_scope!("process");
 
-        let this_image = {
-            while self.per_image.len() <= index {
-                self.per_image
-                    .push(PerImageEnvironmentSub::new(factory, &self.layout));
-            }
-            &mut self.per_image[index]
-        };
-        this_image.process(factory, world, resources)
+        let camera = CameraGatherer::gather_camera_entity(world, resources);
+        self.process_view(factory, index, 0, world, resources, camera)
+    }
+
+    /// Writes one environment set for each of `cameras`, which are bound with
+    /// [`EnvironmentSub::bind_view`] by their position in `cameras`.
+    pub fn process_views(
+        &mut self,
+        factory: &Factory<B>,
+        index: usize,
+        world: &World,
+        resources: &Resources,
+        cameras: impl IntoIterator<Item = Entity>,
+    ) -> bool {
+        #[cfg(feature = "profiler")]
+        profile_scope!("process_views");
+
+        let mut changed = false;
+        for (view, camera) in cameras.into_iter().enumerate() {
+            changed |= self.process_view(factory, index, view, world, resources, Some(camera));
+        }
+        changed
+    }
+
+    fn process_view(
+        &mut self,
+        factory: &Factory<B>,
+        index: usize,
+        view: usize,
+        world: &World,
+        resources: &Resources,
+        camera: Option<Entity>,
+    ) -> bool {
+        while self.views.len() <= view {
+            self.views.push(Vec::new());
+        }
+        let per_image = &mut self.views[view];
+        while per_image.len() <= index {
+            per_image.push(PerImageEnvironmentSub::new(factory, &self.layout));
+        }
+        per_image[index].process(
+            factory,
+            world,
+            resources,
+            camera,
+            &self.shadow_map,
+            &self.ltc,
+        )
     }
 
     /// Binds this environment set for all images.
@@ -128,7 +214,121 @@ impl<B: Backend> EnvironmentSub<B> {
         set_id: u32,
         encoder: &mut RenderPassEncoder<'_, B>,
     ) {
-        self.per_image[index].bind(pipeline_layout, set_id, encoder);
+        self.bind_view(index, 0, pipeline_layout, set_id, encoder);
+    }
+
+    /// Binds the environment set of a camera written by [`EnvironmentSub::process_views`].
+    #[inline]
+    pub fn bind_view(
+        &self,
+        index: usize,
+        view: usize,
+        pipeline_layout: &B::PipelineLayout,
+        set_id: u32,
+        encoder: &mut RenderPassEncoder<'_, B>,
+    ) {
+        self.views[view][index].bind(pipeline_layout, set_id, encoder);
+    }
+}
+
+fn ltc_texture<B: Backend>(
+    factory: &mut Factory<B>,
+    queue: QueueId,
+    data: &'static [u8],
+) -> Result<Texture<B>, CreationError> {
+    TextureBuilder::new()
+        .with_kind(Kind::D2(LTC_SIZE, LTC_SIZE, 1, 1))
+        .with_view_kind(ViewKind::D2)
+        .with_data_width(LTC_SIZE)
+        .with_data_height(LTC_SIZE)
+        .with_raw_data(data, Format::Rgba16Sfloat)
+        .with_sampler_info(SamplerDesc::new(Filter::Linear, WrapMode::Clamp))
+        .build(
+            ImageState {
+                queue,
+                stage: hal::pso::PipelineStage::FRAGMENT_SHADER,
+                access: hal::image::Access::SHADER_READ,
+                layout: Layout::ShaderReadOnlyOptimal,
+            },
+            factory,
+        )
+        .map_err(|_| CreationError::Other)
+}
+
+impl<B: Backend> ShadowMap<B> {
+    fn new(
+        factory: &mut Factory<B>,
+        queue: QueueId,
+        image: Option<RendyHandle<Image<B>>>,
+    ) -> Result<Self, CreationError> {
+        // Depth is reversed, points closer to the light than the shadow map are lit.
+        let sampler = SamplerDesc {
+            comparison: Some(Comparison::GreaterEqual),
+            ..SamplerDesc::new(Filter::Linear, WrapMode::Clamp)
+        };
+        match image {
+            Some(image) => {
+                Ok(ShadowMap::Atlas {
+                    view: factory
+                        .create_image_view(
+                            image,
+                            ImageViewInfo {
+                                view_kind: ViewKind::D2,
+                                format: Format::D32Sfloat,
+                                swizzle: Swizzle::NO,
+                                range: SubresourceRange {
+                                    aspects: Aspects::DEPTH,
+                                    levels: 0..1,
+                                    layers: 0..1,
+                                },
+                            },
+                        )
+                        .map_err(|_| CreationError::Other)?,
+                    sampler: factory
+                        .create_sampler(sampler)
+                        .map_err(|_| CreationError::Other)?,
+                })
+            }
+            None => {
+                TextureBuilder::new()
+                    .with_kind(Kind::D2(1, 1, 1, 1))
+                    .with_view_kind(ViewKind::D2)
+                    .with_data_width(1)
+                    .with_data_height(1)
+                    .with_raw_data(vec![0; 4], Format::D32Sfloat)
+                    .with_sampler_info(sampler)
+                    .build(
+                        ImageState {
+                            queue,
+                            stage: hal::pso::PipelineStage::FRAGMENT_SHADER,
+                            access: hal::image::Access::SHADER_READ,
+                            layout: Layout::ShaderReadOnlyOptimal,
+                        },
+                        factory,
+                    )
+                    .map(ShadowMap::Empty)
+                    .map_err(|_| CreationError::Other)
+            }
+        }
+    }
+
+    fn descriptor(&self) -> Descriptor<'_, B> {
+        match self {
+            ShadowMap::Atlas { view, sampler } => {
+                Descriptor::CombinedImageSampler(
+                    view.raw(),
+                    Layout::ShaderReadOnlyOptimal,
+                    sampler.raw(),
+                )
+            }
+            ShadowMap::Empty(texture) => {
+                Descriptor::CombinedImageSampler(
+                    texture.view().raw(),
+                    Layout::ShaderReadOnlyOptimal,
+                    texture.sampler().raw(),
+                )
+            }
+        }
     }
 }
 
@@ -136,6 +336,8 @@ impl<B: Backend> PerImageEnvironmentSub<B> {
     fn new(factory: &Factory<B>, layout: &RendyHandle<DescriptorSetLayout<B>>) -> Self {
         Self {
             buffer: None,
+            ranges: Vec::new(),
+            clusters: LightClusters::new(),
             set: factory.create_descriptor_set(layout.clone()).unwrap(),
         }
     }
@@ -157,61 +359,197 @@ impl<B: Backend> PerImageEnvironmentSub<B> {
         }
     }
 
-    fn process(&mut self, factory: &Factory<B>, world: &World, resources: &Resources) -> bool {
-        let align = factory
-            .physical()
-            .limits()
-            .min_uniform_buffer_offset_alignment;
+    fn process(
+        &mut self,
+        factory: &Factory<B>,
+        world: &World,
+        resources: &Resources,
+        camera: Option<Entity>,
+        shadow_map: &ShadowMap<B>,
+        ltc: &[Texture<B>; 2],
+    ) -> bool {
+        // Lights only cast shadows in passes sampling the shadow atlas.
+        let shadow_maps = match shadow_map {
+            ShadowMap::Atlas { .. } => resources.get::<ShadowMaps>(),
+            ShadowMap::Empty(_) => None,
+        };
+        let shadow_views = |entity: &Entity| {
+            shadow_maps
+                .as_ref()
+                .and_then(|shadow_maps| shadow_maps.light_views(*entity))
+                .filter(|views| views.end <= MAX_SHADOW_VIEWS)
+                .unwrap_or(0..0)
+        };
+        let first_view = |views: &Range<usize>| {
+            if views.is_empty() {
+                -1
+            } else {
+                views.start as i32
+            }
+        };
+
+        let mut point_lights = Vec::new();
+        let mut spot_lights = Vec::new();
+        let mut area_lights = Vec::new();
+        let mut point_bounds = Vec::new();
+        let mut spot_bounds = Vec::new();
+        let mut area_bounds = Vec::new();
+        let mut lights_query = <(Entity, Read<Light>, Read<Transform>)>::query();
+        for (entity, light, transform) in lights_query.iter(world) {
+            let matrix = transform.global_matrix();
+            let position = convert::<_, Vector3<f32>>(matrix.column(3).xyz());
+            match &*light {
+                Light::Point(light) => {
+                    point_lights.push(
+                        pod::PointLight {
+                            position: position.into_pod(),
+                            color: light.color.into_pod(),
+                            intensity: light.intensity,
+                            radius: light.radius,
+                            smoothness: light.smoothness,
+                            shadow: first_view(&shadow_views(entity)),
+                        }
+                        .std140(),
+                    );
+                    point_bounds.push(BoundingSphere::new(position.into(), light.radius));
+                }
+                Light::Spot(light) => {
+                    spot_lights.push(
+                        pod::SpotLight {
+                            position: position.into_pod(),
+                            color: light.color.into_pod(),
+                            direction: light.direction.into_pod(),
+                            angle: light.angle.cos(),
+                            intensity: light.intensity,
+                            range: light.range,
+                            smoothness: light.smoothness,
+                            shadow: first_view(&shadow_views(entity)),
+                        }
+                        .std140(),
+                    );
+                    spot_bounds.push(BoundingSphere::new(position.into(), light.range));
+                }
+                Light::Area(light) => {
+                    let (half_width, half_height) = light.half_extents();
+                    let right = convert::<_, Vector3<f32>>(matrix.column(0).xyz()) * half_width;
+                    let up = convert::<_, Vector3<f32>>(matrix.column(1).xyz()) * half_height;
+                    area_lights.push(
+                        pod::AreaLight {
+                            position: position.into_pod(),
+                            color: light.color.into_pod(),
+                            right: right.into_pod(),
+                            up: up.into_pod(),
+                            intensity: light.intensity,
+                            range: light.range,
+                            shape: match light.shape {
+                                AreaShape::Rectangle { .. } => 0,
+                                AreaShape::Disc { .. } => 1,
+                            },
+                            two_sided: light.two_sided.into(),
+                        }
+                        .std140(),
+                    );
+                    area_bounds.push(BoundingSphere::new(position.into(), light.range));
+                }
+                _ => {}
+            }
+        }
+
+        let (view_camera, view_matrix) = CameraGatherer::gather_camera_matrices(world, camera);
+        self.clusters.assign(
+            &view_camera,
+            &view_matrix,
+            &point_bounds,
+            &spot_bounds,
+            &area_bounds,
+        );
+
+        let limits = factory.physical().limits();
+        let align = limits
+            .min_uniform_buffer_offset_alignment
+            .max(limits.min_storage_buffer_offset_alignment);
+        // Light arrays grow by powers of two, so their descriptors are rarely rewritten.
+        let capacity = |count: usize| count.max(1).next_power_of_two();
 
         let projview_size = util::align_size::<pod::ViewArgs>(align, 1);
         let env_buf_size = util::align_size::<pod::Environment>(align, 1);
-        let plight_buf_size = util::align_size::<pod::PointLight>(align, MAX_POINT_LIGHTS);
+        let plight_buf_size =
+            util::align_size::<pod::PointLight>(align, capacity(point_lights.len()));
         let dlight_buf_size = util::align_size::<pod::DirectionalLight>(align, MAX_DIR_LIGHTS);
-        let slight_buf_size = util::align_size::<pod::SpotLight>(align, MAX_SPOT_LIGHTS);
+        let slight_buf_size =
+            util::align_size::<pod::SpotLight>(align, capacity(spot_lights.len()));
+        let shadow_buf_size = util::align_size::<pod::ShadowView>(align, MAX_SHADOW_VIEWS);
+        let alight_buf_size =
+            util::align_size::<pod::AreaLight>(align, capacity(area_lights.len()));
+        let cluster_buf_size = util::align_size::<u32>(align, 4 * self.clusters.clusters.len());
+        let index_buf_size = util::align_size::<u32>(align, capacity(self.clusters.indices.len()));
 
         let projview_range = 0..projview_size;
         let env_range = util::next_range(&projview_range, env_buf_size);
         let plight_range = util::next_range(&env_range, plight_buf_size);
         let dlight_range = util::next_range(&plight_range, dlight_buf_size);
         let slight_range = util::next_range(&dlight_range, slight_buf_size);
+        let shadow_range = util::next_range(&slight_range, shadow_buf_size);
+        let alight_range = util::next_range(&shadow_range, alight_buf_size);
+        let cluster_range = util::next_range(&alight_range, cluster_buf_size);
+        let index_range = util::next_range(&cluster_range, index_buf_size);
 
-        let whole_range = 0..slight_range.end;
+        let whole_range = 0..index_range.end;
+        let ranges = vec![
+            projview_range.clone(),
+            env_range.clone(),
+            plight_range.clone(),
+            dlight_range.clone(),
+            slight_range.clone(),
+            shadow_range.clone(),
+            alight_range.clone(),
+            cluster_range.clone(),
+            index_range.clone(),
+        ];
 
         let new_buffer = util::ensure_buffer(
             factory,
             &mut self.buffer,
-            hal::buffer::Usage::UNIFORM,
+            hal::buffer::Usage::UNIFORM | hal::buffer::Usage::STORAGE,
             rendy::memory::Dynamic,
             whole_range.end,
         )
         .unwrap();
+        let rewrite = new_buffer || self.ranges != ranges;
         if let Some(buffer) = self.buffer.as_mut() {
-            if new_buffer {
+            if rewrite {
                 use util::{desc_write, sub_range};
                 let buffer = buffer.raw();
                 let env_set = self.set.raw();
 
-                let desc_projview = Descriptor::Buffer(buffer, sub_range(projview_range.clone()));
-                let desc_env = Descriptor::Buffer(buffer, sub_range(env_range.clone()));
-                let desc_plight = Descriptor::Buffer(buffer, sub_range(plight_range.clone()));
-                let desc_dlight = Descriptor::Buffer(buffer, sub_range(dlight_range.clone()));
-                let desc_slight = Descriptor::Buffer(buffer, sub_range(slight_range.clone()));
+                let mut writes: Vec<_> = ranges
+                    .iter()
+                    .map(|range| Descriptor::Buffer(buffer, sub_range(range.clone())))
+                    .collect();
+                // Bindings 0 to 5 are before the shadow map, the others after it.
+                let after_shadow_map = writes.split_off(6);
+                writes.push(shadow_map.descriptor());
+                writes.extend(after_shadow_map);
+                writes.extend(ltc.iter().map(|texture| {
+                    Descriptor::CombinedImageSampler(
+                        texture.view().raw(),
+                        Layout::ShaderReadOnlyOptimal,
+                        texture.sampler().raw(),
+                    )
+                }));
 
                 unsafe {
-                    factory.write_descriptor_sets(vec![
-                        desc_write(env_set, 0, desc_projview),
-                        desc_write(env_set, 1, desc_env),
-                        desc_write(env_set, 2, desc_plight),
-                        desc_write(env_set, 3, desc_dlight),
-                        desc_write(env_set, 4, desc_slight),
-                    ]);
+                    factory.write_descriptor_sets(writes.into_iter().enumerate().map(
+                        |(binding, descriptor)| desc_write(env_set, binding as u32, descriptor),
+                    ));
                 }
+                self.ranges = ranges;
             }
 
             let CameraGatherer {
                 camera_position,
                 projview,
-            } = CameraGatherer::gather(world, resources);
+            } = CameraGatherer::gather_camera(world, camera);
 
             let mut mapped = buffer.map(factory, whole_range.clone()).unwrap();
             let mut writer = unsafe { mapped.write::<u8>(factory, whole_range).unwrap() };
@@ -220,46 +558,30 @@ impl<B: Backend> PerImageEnvironmentSub<B> {
             let mut env = pod::Environment {
                 ambient_color: AmbientGatherer::gather(resources),
                 camera_position,
-                point_light_count: 0,
+                point_light_count: point_lights.len() as i32,
                 directional_light_count: 0,
-                spot_light_count: 0,
+                spot_light_count: spot_lights.len() as i32,
+                area_light_count: area_lights.len() as i32,
+                cluster_count: CLUSTER_COUNT.into(),
+                cluster_depth_scale: self.clusters.depth_scale(),
+                cluster_depth_bias: self.clusters.depth_bias(),
             }
             .std140();
 
-            let mut point_lights_query = <(Read<Light>, Read<Transform>)>::query();
-            let point_lights = point_lights_query
-                .iter(world)
-                .filter_map(|(light, transform)| {
-                    match &*light {
-                        Light::Point(light) => {
-                            Some(
-                                pod::PointLight {
-                                    position: convert::<_, Vector3<f32>>(
-                                        transform.global_matrix().column(3).xyz(),
-                                    )
-                                    .into_pod(),
-                                    color: light.color.into_pod(),
-                                    intensity: light.intensity,
-                                }
-                                .std140(),
-                            )
-                        }
-                        _ => None,
-                    }
-                })
-                .take(MAX_POINT_LIGHTS);
-
-            let mut dir_lights_query = <Read<Light>>::query();
+            let mut dir_lights_query = <(Entity, Read<Light>)>::query();
             let dir_lights = dir_lights_query
                 .iter(world)
-                .filter_map(|light| {
+                .filter_map(|(entity, light)| {
                     match &*light {
                         Light::Directional(light) => {
+                            let cascades = shadow_views(entity);
                             Some(
                                 pod::DirectionalLight {
                                     color: light.color.into_pod(),
                                     intensity: light.intensity,
                                     direction: light.direction.into_pod(),
+                                    shadow: first_view(&cascades),
+                                    cascades: cascades.len() as i32,
                                 }
                                 .std140(),
                             )
@@ -269,49 +591,41 @@ impl<B: Backend> PerImageEnvironmentSub<B> {
                 })
                 .take(MAX_DIR_LIGHTS);
 
-            let mut spot_lights_query = <(Read<Light>, Read<Transform>)>::query();
-            let spot_lights = spot_lights_query
-                .iter(world)
-                .filter_map(|(light, transform)| {
-                    match &*light {
-                        Light::Spot(light) => {
-                            Some(
-                                pod::SpotLight {
-                                    position: convert::<_, Vector3<f32>>(
-                                        transform.global_matrix().column(3).xyz(),
-                                    )
-                                    .into_pod(),
-                                    color: light.color.into_pod(),
-                                    direction: light.direction.into_pod(),
-                                    angle: light.angle.cos(),
-                                    intensity: light.intensity,
-                                    range: light.range,
-                                    smoothness: light.smoothness,
-                                }
-                                .std140(),
-                            )
-                        }
-                        _ => None,
-                    }
-                })
-                .take(MAX_SPOT_LIGHTS);
-
-            write_into_slice(
-                &mut dst_slice[usize_range(plight_range)],
-                point_lights.tap_count(&mut env.point_light_count),
-            );
+            write_into_slice(&mut dst_slice[usize_range(plight_range)], point_lights);
             write_into_slice(
                 &mut dst_slice[usize_range(dlight_range)],
                 dir_lights.tap_count(&mut env.directional_light_count),
             );
+            write_into_slice(&mut dst_slice[usize_range(slight_range)], spot_lights);
+            write_into_slice(&mut dst_slice[usize_range(alight_range)], area_lights);
             write_into_slice(
-                &mut dst_slice[usize_range(slight_range)],
-                spot_lights.tap_count(&mut env.spot_light_count),
+                &mut dst_slice[usize_range(cluster_range)],
+                self.clusters.clusters.iter().copied(),
             );
+            write_into_slice(
+                &mut dst_slice[usize_range(index_range)],
+                self.clusters.indices.iter().copied(),
+            );
+            if let Some(shadow_maps) = shadow_maps.as_ref() {
+                write_into_slice(
+                    &mut dst_slice[usize_range(shadow_range)],
+                    shadow_maps.views.iter().take(MAX_SHADOW_VIEWS).map(|view| {
+                        let projview: [[f32; 4]; 4] = (view.proj * view.view).into();
+                        pod::ShadowView {
+                            projview: projview.into(),
+                            tile: [view.tile.x, view.tile.y, view.tile.width, view.tile.height]
+                                .into(),
+                            bias: view.bias,
+                            normal_bias: view.normal_bias,
+                        }
+                        .std140()
+                    }),
+                );
+            }
             write_into_slice(&mut dst_slice[usize_range(projview_range)], Some(projview));
             write_into_slice(&mut dst_slice[usize_range(env_range)], Some(env));
         }
 
-        new_buffer
+        rewrite
     }
 }
@@ -1,6 +1,6 @@
 //! Environment submodule for shared environmental descriptor set data.
 //! Fetches and sets projection set information for a flat pass.
-use amethyst_core::ecs::{Resources, World};
+use amethyst_core::ecs::{Entity, Resources, World};
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
 
@@ -14,23 +14,28 @@ use crate::{
 /// Submodule for loading and binding descriptor sets for a flat, unlit environment.
 /// This also abstracts away the need for handling multiple images in flight, as it provides
 /// per-image submissions.
+///
+/// Each rendered camera has its own set, see [`FlatEnvironmentSub::process_views`].
 #[derive(Debug)]
 pub struct FlatEnvironmentSub<B: Backend> {
-    uniform: DynamicUniform<B, ViewArgs>,
+    views: Vec<DynamicUniform<B, ViewArgs>>,
 }
 
 impl<B: Backend> FlatEnvironmentSub<B> {
     /// Create and allocate a new `EnvironmentSub` with the provided rendy `Factory`
     pub fn new(factory: &Factory<B>) -> Result<Self, rendy::hal::pso::CreationError> {
         Ok(Self {
-            uniform: DynamicUniform::new(factory, rendy::hal::pso::ShaderStageFlags::VERTEX)?,
+            views: vec![DynamicUniform::new(
+                factory,
+                rendy::hal::pso::ShaderStageFlags::VERTEX,
+            )?],
         })
     }
 
     /// Returns the raw `DescriptorSetLayout` for this environment
     #[must_use]
     pub fn raw_layout(&self) -> &B::DescriptorSetLayout {
-        self.uniform.raw_layout()
+        self.views[0].raw_layout()
     }
 
     /// Performs any re-allocation and GPU memory writing required for this environment set.
@@ -44,7 +49,28 @@ impl<B: Backend> FlatEnvironmentSub<B> {
         #[cfg(feature = "profiler")]
         profile_scope!("process");
         let projview = CameraGatherer::gather(world, resources).projview;
-        self.uniform.write(factory, index, projview);
+        self.views[0].write(factory, index, projview);
+    }
+
+    /// Writes one environment set for each of `cameras`, which are bound with
+    /// [`FlatEnvironmentSub::bind_view`] by their position in `cameras`.
+    pub fn process_views(
+        &mut self,
+        factory: &Factory<B>,
+        index: usize,
+        world: &World,
+        cameras: impl IntoIterator<Item = Entity>,
+    ) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("process_views");
+        for (view, camera) in cameras.into_iter().enumerate() {
+            if view == self.views.len() {
+                let uniform = self.views[0].with_same_layout();
+                self.views.push(uniform);
+            }
+            let projview = CameraGatherer::gather_camera(world, Some(camera)).projview;
+            self.views[view].write(factory, index, projview);
+        }
     }
 
     /// Binds this environment set for all images.
@@ -56,6 +82,19 @@ impl<B: Backend> FlatEnvironmentSub<B> {
         set_id: u32,
         encoder: &mut RenderPassEncoder<'_, B>,
     ) {
-        self.uniform.bind(index, pipeline_layout, set_id, encoder);
+        self.bind_view(index, 0, pipeline_layout, set_id, encoder);
+    }
+
+    /// Binds the environment set of a camera written by [`FlatEnvironmentSub::process_views`].
+    #[inline]
+    pub fn bind_view(
+        &self,
+        index: usize,
+        view: usize,
+        pipeline_layout: &B::PipelineLayout,
+        set_id: u32,
+        encoder: &mut RenderPassEncoder<'_, B>,
+    ) {
+        self.views[view].bind(index, pipeline_layout, set_id, encoder);
     }
 }
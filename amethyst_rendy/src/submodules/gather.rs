@@ -9,8 +9,10 @@ use glsl_layout::{vec3, Uniform};
 use thread_profiler::profile_scope;
 
 use crate::{
-    camera::{ActiveCamera, Camera},
+    bundle::Target,
+    camera::{ActiveCamera, Camera, CameraView},
     pod::{self, IntoPod},
+    render_texture::{self, RenderTexture},
     resources::AmbientColor,
 };
 
@@ -56,6 +58,27 @@ impl CameraGatherer {
         }
     }
 
+    /// Collect the cameras to render this frame with their `CameraView` and render target, in
+    /// render order. See [`render_texture::target_views`] for how the cameras are selected.
+    #[must_use]
+    pub fn gather_views(world: &World, resources: &Resources) -> Vec<(Entity, CameraView, Target)> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("gather_views");
+
+        let active_camera = resources.get::<ActiveCamera>().and_then(|r| r.entity);
+        render_texture::target_views(
+            active_camera,
+            <(
+                Entity,
+                Read<Camera>,
+                Option<&CameraView>,
+                Option<&RenderTexture>,
+            )>::query()
+            .iter(world)
+            .map(|(e, _, view, texture)| (*e, view, texture.map(RenderTexture::target))),
+        )
+    }
+
     /// Collect `ActiveCamera` and `Camera` instances from the provided resource storage and selects
     /// the appropriate camera to use for projection, and returns the camera position and extracted
     /// projection matrix.
@@ -66,23 +89,14 @@ impl CameraGatherer {
         #[cfg(feature = "profiler")]
         profile_scope!("gather_cameras");
 
-        let defcam = Camera::standard_2d(1.0, 1.0);
-        let identity = Transform::default();
-
-        let camera_entity = Self::gather_camera_entity(world, resources);
-
-        let camera =
-            camera_entity.and_then(|e| world.entry_ref(e).unwrap().into_component::<Camera>().ok());
-        let camera = camera.unwrap_or(&defcam);
+        Self::gather_camera(world, Self::gather_camera_entity(world, resources))
+    }
 
-        let transform = camera_entity.and_then(|e| {
-            world
-                .entry_ref(e)
-                .unwrap()
-                .into_component::<Transform>()
-                .ok()
-        });
-        let transform = transform.unwrap_or(&identity);
+    /// Returns the camera position and projection matrix of `camera_entity`, or of a default 2D
+    /// camera if there is none.
+    #[must_use]
+    pub fn gather_camera(world: &World, camera_entity: Option<Entity>) -> Self {
+        let (camera, transform) = Self::camera_and_transform(world, camera_entity);
 
         let camera_position =
             convert::<_, Vector3<f32>>(transform.global_matrix().column(3).xyz()).into_pod();
@@ -106,6 +120,43 @@ impl CameraGatherer {
             projview,
         }
     }
+
+    /// Returns the `Camera` of `camera_entity` and its view matrix, or those of a default 2D
+    /// camera if there is none.
+    #[must_use]
+    pub fn gather_camera_matrices(
+        world: &World,
+        camera_entity: Option<Entity>,
+    ) -> (Camera, Matrix4<f32>) {
+        let (camera, transform) = Self::camera_and_transform(world, camera_entity);
+        (
+            camera,
+            convert::<_, Matrix4<f32>>(transform.global_view_matrix()),
+        )
+    }
+
+    fn camera_and_transform(world: &World, camera_entity: Option<Entity>) -> (Camera, Transform) {
+        let camera = camera_entity.and_then(|e| {
+            world
+                .entry_ref(e)
+                .ok()?
+                .into_component::<Camera>()
+                .ok()
+                .cloned()
+        });
+        let transform = camera_entity.and_then(|e| {
+            world
+                .entry_ref(e)
+                .ok()?
+                .into_component::<Transform>()
+                .ok()
+                .cloned()
+        });
+        (
+            camera.unwrap_or_else(|| Camera::standard_2d(1.0, 1.0)),
+            transform.unwrap_or_default(),
+        )
+    }
 }
 
 /// If an `AmbientColor` exists in the resources, return it - otherwise return pure white.
@@ -67,6 +67,16 @@ where
         })
     }
 
+    /// Creates an empty `DynamicUniform` with the same `DescriptorSetLayout`, whose sets can be
+    /// bound to the same pipelines.
+    #[must_use]
+    pub fn with_same_layout(&self) -> Self {
+        Self {
+            layout: self.layout.clone(),
+            per_image: Vec::new(),
+        }
+    }
+
     /// Returns the `DescriptSetLayout` for this set.
     #[must_use]
     pub fn raw_layout(&self) -> &B::DescriptorSetLayout {
@@ -9,6 +9,7 @@ use amethyst_core::num::PrimInt;
 use derivative::Derivative;
 use glsl_layout::Uniform;
 use rendy::{
+    command::RenderPassEncoder,
     factory::Factory,
     graph::render::PrepareResult,
     hal::{self, buffer::Usage, format, pso},
@@ -20,7 +21,10 @@ use smallvec::SmallVec;
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
 
-use crate::types::{Backend, Texture};
+use crate::{
+    camera::Viewport,
+    types::{Backend, Texture},
+};
 
 /// Helper function to clone ranges.
 #[inline]
@@ -328,6 +332,33 @@ impl<'a, T: PrimInt, I: ExactSizeIterator> ExactSizeIterator for TapCountIterato
 
 impl<'a, T: PrimInt, I: FusedIterator> FusedIterator for TapCountIterator<'a, T, I> {}
 
+/// Restricts drawing to `viewport` of a framebuffer of the given size, for pipelines built with
+/// [`PipelineDescBuilder::with_dynamic_viewport`](crate::pipeline::PipelineDescBuilder::with_dynamic_viewport).
+pub fn set_viewport<B: Backend>(
+    encoder: &mut RenderPassEncoder<'_, B>,
+    viewport: &Viewport,
+    framebuffer_width: u32,
+    framebuffer_height: u32,
+) {
+    let (x, y, w, h) = viewport.to_pixels(framebuffer_width, framebuffer_height);
+    let rect = pso::Rect {
+        x: x as i16,
+        y: y as i16,
+        w: w as i16,
+        h: h as i16,
+    };
+    unsafe {
+        encoder.set_viewports(
+            0,
+            &[pso::Viewport {
+                rect,
+                depth: 0.0..1.0,
+            }],
+        );
+        encoder.set_scissors(0, &[rect]);
+    }
+}
+
 /// Helper structure for tracking indexed changes for per-image draw call recording.
 #[derive(Debug, Clone, Copy, Derivative)]
 #[derivative(Default)]
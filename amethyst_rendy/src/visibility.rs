@@ -2,29 +2,95 @@
 use std::cmp::Ordering;
 
 use amethyst_core::{
-    ecs::{component, systems::ParallelRunnable, Entity, IntoQuery, System, SystemBuilder},
+    ecs::{
+        systems::ParallelRunnable, world::EntryRef, Entity, EntityStore, IntoQuery, System,
+        SystemBuilder,
+    },
     math::{convert, distance_squared, Matrix4, Point3, Vector4},
     transform::Transform,
-    Hidden, HiddenPropagate,
+    Time,
 };
+use fnv::FnvHashMap;
 use indexmap::IndexSet;
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
 
 use crate::{
-    camera::{ActiveCamera, Camera},
+    bundle::Target,
+    camera::{ActiveCamera, Camera, CameraView, RenderLayers, Viewport},
+    lod::{LodSelection, MeshLod},
+    render_texture::{self, RenderTexture},
+    spatial::SpatialIndex,
     transparent::Transparent,
 };
 
-/// Resource for controlling what entities should be rendered, and whether to draw them ordered or
-/// not, which is useful for transparent surfaces.
+/// Resource for controlling what entities should be rendered by each camera, and whether to draw
+/// them ordered or not, which is useful for transparent surfaces.
 #[derive(Default, Debug)]
 pub struct Visibility {
+    /// Entities visible to each rendered camera, in render order.
+    pub views: Vec<ViewVisibility>,
+}
+
+/// Entities visible to one of the rendered cameras.
+#[derive(Debug, Clone)]
+pub struct ViewVisibility {
+    /// Camera entity
+    pub camera: Entity,
+    /// Render target the camera draws to
+    pub target: Target,
+    /// Part of the render target the camera draws to
+    pub viewport: Viewport,
     /// Visible entities that can be drawn in any order
     pub visible_unordered: IndexSet<Entity>,
     /// Visible entities that need to be drawn in the given order
     pub visible_ordered: Vec<Entity>,
+    /// Level of detail selected for the visible entities with a [`MeshLod`]
+    pub lods: FnvHashMap<Entity, LodSelection>,
+}
+
+/// Sets `views` to empty lists for `cameras`, keeping the allocated lists.
+pub(crate) fn reset_views(
+    views: &mut Vec<ViewVisibility>,
+    cameras: &[(Entity, CameraView, Target)],
+) {
+    views.truncate(cameras.len());
+    for (i, (camera, view, target)) in cameras.iter().enumerate() {
+        if let Some(visibility) = views.get_mut(i) {
+            visibility.camera = *camera;
+            visibility.target = *target;
+            visibility.viewport = view.viewport;
+            visibility.visible_unordered.clear();
+            visibility.visible_ordered.clear();
+            visibility.lods.clear();
+        } else {
+            views.push(ViewVisibility {
+                camera: *camera,
+                target: *target,
+                viewport: view.viewport,
+                visible_unordered: IndexSet::default(),
+                visible_ordered: Vec::new(),
+                lods: FnvHashMap::default(),
+            });
+        }
+    }
+}
+
+/// Returns the views of `views` drawn to `target`, see [`Target::camera_target`].
+pub(crate) fn views_for_target<'a>(
+    views: &'a [ViewVisibility],
+    target: Target,
+) -> Vec<&'a ViewVisibility> {
+    let target = target.camera_target();
+    views.iter().filter(|view| view.target == target).collect()
+}
+
+/// Returns the render layers of the entity, see [`RenderLayers`].
+pub(crate) fn entity_layers(entry: &EntryRef<'_>) -> RenderLayers {
+    entry
+        .get_component::<RenderLayers>()
+        .map_or(RenderLayers::DEFAULT, |layers| *layers)
 }
 
 /// Defines a object's bounding sphere used by frustum culling.
@@ -70,15 +136,25 @@ struct Internals {
     camera_distance: f32,
 }
 
-/// Determine what entities are visible to the camera, and which are not. Will also sort transparent
-/// entities back to front based on distance from camera.
+/// Determine what entities are visible to each rendered camera, and which are not. Will also sort
+/// transparent entities back to front based on distance from camera.
+///
+/// The rendered cameras are the `ActiveCamera`, the cameras with a [`CameraView`] and the cameras
+/// with a [`RenderTexture`], each of which only sees the entities on one of its [`RenderLayers`].
+///
+/// Entities are culled with the [`SpatialIndex`], which must be up to date with the `Transform`
+/// of the current frame, so this should run after the `SpatialIndexSystem` and before rendering
+/// occurs.
 ///
-/// Note that this should run after `Transform` has been updated for the current frame, and
-/// before rendering occurs.
+/// The level of detail of the visible entities with a [`MeshLod`] is selected for each camera,
+/// those beyond the last level are culled.
 #[derive(Default, Debug)]
 pub struct VisibilitySortingSystem {
     centroids: Vec<Internals>,
     transparent: Vec<Internals>,
+    /// Selections of the current and previous frame by camera and entity.
+    lods: FnvHashMap<(Entity, Entity), LodSelection>,
+    previous_lods: FnvHashMap<(Entity, Entity), LodSelection>,
 }
 
 impl System for VisibilitySortingSystem {
@@ -86,106 +162,122 @@ impl System for VisibilitySortingSystem {
         Box::new(
             SystemBuilder::new("VisibilitySortingSystem")
                 .read_resource::<ActiveCamera>()
+                .read_resource::<SpatialIndex>()
+                .read_resource::<Time>()
                 .write_resource::<Visibility>()
-                .with_query(<(&Camera, &Transform)>::query())
-                .with_query(<(Entity, &Camera, &Transform)>::query())
-                .with_query(
-                    <(
-                        Entity,
-                        &Transform,
-                        Option<&Transparent>,
-                        Option<&BoundingSphere>,
-                    )>::query()
-                    .filter(!component::<Hidden>() & !component::<HiddenPropagate>()),
-                )
+                .read_component::<Camera>()
+                .read_component::<Transform>()
+                .read_component::<Transparent>()
+                .read_component::<RenderLayers>()
+                .read_component::<MeshLod>()
+                .with_query(<(
+                    Entity,
+                    &Camera,
+                    Option<&CameraView>,
+                    Option<&RenderTexture>,
+                )>::query())
                 .build(
                     move |commands,
                           world,
-                          (active_camera, visibility),
-                          (camera_query1, camera_query2, entity_query)| {
+                          (active_camera, index, time, visibility),
+                          camera_query| {
                         #[cfg(feature = "profiler")]
                         profile_scope!("visibility_sorting_system");
 
-                        visibility.visible_unordered.clear();
-                        visibility.visible_ordered.clear();
-                        self.transparent.clear();
-                        self.centroids.clear();
+                        let cameras = render_texture::target_views(
+                            active_camera.entity,
+                            camera_query.iter(world).map(|(entity, _, view, texture)| {
+                                (*entity, view, texture.map(RenderTexture::target))
+                            }),
+                        );
+                        reset_views(&mut visibility.views, &cameras);
 
                         let origin = Point3::origin();
+                        let delta_seconds = time.delta_real_time().as_secs_f32();
+                        std::mem::swap(&mut self.lods, &mut self.previous_lods);
+                        self.lods.clear();
 
-                        let (camera, camera_transform) = match active_camera.entity.map_or_else(
-                            || camera_query1.iter(world).next(),
-                            |e| {
-                                camera_query2
-                                    .iter(world)
-                                    .find(|(camera_entity, _, _)| **camera_entity == e)
-                                    .map(|(_entity, camera, camera_transform)| {
-                                        (camera, camera_transform)
-                                    })
-                            },
-                        ) {
-                            Some(r) => r,
-                            None => return,
-                        };
-
-                        let camera_centroid =
-                            camera_transform.global_matrix().transform_point(&origin);
-                        let frustum = Frustum::new(
-                            convert::<_, Matrix4<f32>>(camera.matrix)
-                                * camera_transform.global_matrix().try_inverse().unwrap(),
-                        );
+                        for (view_visibility, (camera_entity, camera_view, _)) in
+                            visibility.views.iter_mut().zip(&cameras)
+                        {
+                            self.transparent.clear();
+                            self.centroids.clear();
 
-                        self.centroids.extend(
-                            entity_query
-                                .iter(world)
-                                .map(|(entity, transform, transparent, sphere)| {
-                                    let pos = sphere.map_or(origin, |s| s.center);
-                                    let matrix = transform.global_matrix();
-                                    (
-                                        *entity,
-                                        transparent.is_some(),
-                                        matrix.transform_point(&pos),
-                                        sphere.map_or(1.0, |s| s.radius)
-                                            * matrix[(0, 0)]
-                                                .max(matrix[(1, 1)])
-                                                .max(matrix[(2, 2)]),
-                                    )
-                                })
-                                .filter(|(_, _, centroid, radius)| {
-                                    frustum.check_sphere(centroid, *radius)
-                                })
-                                .map(|(entity, transparent, centroid, _)| {
-                                    Internals {
-                                        entity,
-                                        transparent,
-                                        centroid,
-                                        camera_distance: distance_squared(
-                                            &centroid,
-                                            &camera_centroid,
-                                        ),
+                            let camera_entry = match world.entry_ref(*camera_entity) {
+                                Ok(entry) => entry,
+                                Err(_) => continue,
+                            };
+                            let (camera, camera_transform) = match (
+                                camera_entry.get_component::<Camera>(),
+                                camera_entry.get_component::<Transform>(),
+                            ) {
+                                (Ok(camera), Ok(transform)) => (camera, transform),
+                                _ => continue,
+                            };
+
+                            let camera_centroid =
+                                camera_transform.global_matrix().transform_point(&origin);
+                            let projection = convert::<_, Matrix4<f32>>(camera.matrix);
+                            let frustum = Frustum::new(
+                                projection
+                                    * camera_transform.global_matrix().try_inverse().unwrap(),
+                            );
+
+                            let centroids = &mut self.centroids;
+                            let (lods, previous_lods) = (&mut self.lods, &self.previous_lods);
+                            let view_lods = &mut view_visibility.lods;
+                            index.query_frustum(&frustum, |entity, sphere| {
+                                let entry = match world.entry_ref(entity) {
+                                    Ok(entry) => entry,
+                                    Err(_) => return,
+                                };
+                                if !camera_view.layers.intersects(entity_layers(&entry)) {
+                                    return;
+                                }
+                                if let Ok(lod) = entry.get_component::<MeshLod>() {
+                                    let key = (*camera_entity, entity);
+                                    let selection = lod.select(
+                                        lod.metric_value(sphere, &camera_centroid, &projection),
+                                        previous_lods.get(&key),
+                                        delta_seconds,
+                                    );
+                                    lods.insert(key, selection);
+                                    if selection.is_hidden() {
+                                        return;
                                     }
-                                }),
-                        );
+                                    view_lods.insert(entity, selection);
+                                }
+                                centroids.push(Internals {
+                                    entity,
+                                    transparent: entry.get_component::<Transparent>().is_ok(),
+                                    centroid: sphere.center,
+                                    camera_distance: distance_squared(
+                                        &sphere.center,
+                                        &camera_centroid,
+                                    ),
+                                });
+                            });
 
-                        self.transparent
-                            .extend(self.centroids.iter().filter(|c| c.transparent).cloned());
+                            self.transparent
+                                .extend(self.centroids.iter().filter(|c| c.transparent).cloned());
 
-                        self.transparent.sort_by(|a, b| {
-                            b.camera_distance
-                                .partial_cmp(&a.camera_distance)
-                                .unwrap_or(Ordering::Equal)
-                        });
+                            self.transparent.sort_by(|a, b| {
+                                b.camera_distance
+                                    .partial_cmp(&a.camera_distance)
+                                    .unwrap_or(Ordering::Equal)
+                            });
 
-                        visibility.visible_unordered.extend(
-                            self.centroids
-                                .iter()
-                                .filter(|c| !c.transparent)
-                                .map(|c| c.entity),
-                        );
+                            view_visibility.visible_unordered.extend(
+                                self.centroids
+                                    .iter()
+                                    .filter(|c| !c.transparent)
+                                    .map(|c| c.entity),
+                            );
 
-                        visibility
-                            .visible_ordered
-                            .extend(self.transparent.iter().map(|c| c.entity));
+                            view_visibility
+                                .visible_ordered
+                                .extend(self.transparent.iter().map(|c| c.entity));
+                        }
                     },
                 ),
         )
@@ -18,6 +18,8 @@
     clippy::pub_enum_variant_names
 )]
 
+#[cfg(feature = "locale")]
+pub use self::localized::{LocalizedArg, LocalizedText, LocalizedTextSystem, LocalizedUiBundle};
 pub use self::{
     blink::*,
     bundle::{AudioUiBundle, UiBundle},
@@ -59,6 +61,8 @@ mod glyphs;
 mod image;
 mod label;
 mod layout;
+#[cfg(feature = "locale")]
+mod localized;
 mod pass;
 mod resize;
 mod selection;
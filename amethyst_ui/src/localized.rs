@@ -0,0 +1,217 @@
+//! Module for the `LocalizedText` component and the `LocalizedTextSystem`.
+
+use std::collections::HashMap;
+
+use amethyst_assets::prefab::{legion_prefab, register_component_type, serde_diff, SerdeDiff};
+use amethyst_core::ecs::{
+    DispatcherBuilder, Entity, IntoQuery, ParallelRunnable, Read, Resources, System, SystemBuilder,
+    SystemBundle, World, Write,
+};
+use amethyst_error::Error;
+use amethyst_locale::{FluentArgs, FluentValue, Localization};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+use type_uuid::TypeUuid;
+
+use crate::UiText;
+
+/// Value of an argument of a [`LocalizedText`].
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum LocalizedArg {
+    /// Text inserted as is.
+    String(String),
+    /// A number, formatted and matched against plural categories by Fluent.
+    Number(f64),
+}
+
+impl From<String> for LocalizedArg {
+    fn from(value: String) -> Self {
+        LocalizedArg::String(value)
+    }
+}
+
+impl From<&str> for LocalizedArg {
+    fn from(value: &str) -> Self {
+        LocalizedArg::String(value.to_string())
+    }
+}
+
+macro_rules! impl_number_arg {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for LocalizedArg {
+                fn from(value: $ty) -> Self {
+                    LocalizedArg::Number(f64::from(value))
+                }
+            }
+        )*
+    };
+}
+
+impl_number_arg!(f64, f32, i32, u32, i16, u16, i8, u8);
+
+/// Sets the text of the `UiText` on the same entity to a Fluent message of the
+/// [`Localization`] resource.
+///
+/// The text is resolved again when the message id or arguments change, when the requested
+/// languages change and when a locale is reloaded, so switching the language updates every
+/// visible text at once. Texts whose message is missing in all languages show the message id.
+///
+/// Fluent puts invisible Unicode isolation marks around arguments, which most fonts draw as
+/// boxes, so UI games usually turn them off with `Localization::set_use_isolating`.
+///
+/// # Example
+///
+/// ```
+/// use amethyst::ui::LocalizedText;
+///
+/// let score = LocalizedText::new("score")
+///     .with_arg("player", "Ferris")
+///     .with_arg("points", 42);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, SerdeDiff, TypeUuid)]
+#[uuid = "3b2f6a8e-0c4d-4e57-9a61-7d2f5c8b1e43"]
+#[serde(default)]
+pub struct LocalizedText {
+    /// Id of the Fluent message.
+    pub id: String,
+    /// Arguments of the message, by name.
+    #[serde_diff(opaque)]
+    pub args: Vec<(String, LocalizedArg)>,
+}
+
+register_component_type!(LocalizedText);
+
+impl LocalizedText {
+    /// Creates a text showing the message `id` without arguments.
+    pub fn new<S: Into<String>>(id: S) -> Self {
+        LocalizedText {
+            id: id.into(),
+            args: Vec::new(),
+        }
+    }
+
+    /// Sets the argument `name` of the message.
+    #[must_use]
+    pub fn with_arg<S, V>(mut self, name: S, value: V) -> Self
+    where
+        S: Into<String>,
+        V: Into<LocalizedArg>,
+    {
+        self.set_arg(name, value);
+        self
+    }
+
+    /// Sets the argument `name` of the message, replacing its previous value.
+    pub fn set_arg<S, V>(&mut self, name: S, value: V)
+    where
+        S: Into<String>,
+        V: Into<LocalizedArg>,
+    {
+        let name = name.into();
+        let value = value.into();
+        match self.args.iter_mut().find(|(arg, _)| *arg == name) {
+            Some((_, old)) => *old = value,
+            None => self.args.push((name, value)),
+        }
+    }
+
+    /// Formats the message in the current language.
+    #[must_use]
+    pub fn resolve(&self, localization: &Localization) -> String {
+        if self.args.is_empty() {
+            return localization.format_or_id(&self.id, None);
+        }
+        let mut args = FluentArgs::new();
+        for (name, value) in &self.args {
+            match value {
+                LocalizedArg::String(value) => args.set(name.as_str(), value.as_str()),
+                LocalizedArg::Number(value) => args.set(name.as_str(), FluentValue::from(*value)),
+            }
+        }
+        localization.format_or_id(&self.id, Some(&args))
+    }
+}
+
+/// System writing the resolved [`LocalizedText`] messages into `UiText::text`.
+#[derive(Debug)]
+pub struct LocalizedTextSystem;
+
+impl System for LocalizedTextSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        // What each entity shows, with the localization revision and the frame it was checked.
+        let mut resolved: HashMap<Entity, (u64, u64, LocalizedText)> = HashMap::new();
+        let mut frame = 0_u64;
+
+        Box::new(
+            SystemBuilder::new("LocalizedTextSystem")
+                .read_resource::<Localization>()
+                .with_query(<(Entity, Read<LocalizedText>, Write<UiText>)>::query())
+                .build(move |_, world, localization, query| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("localized_text_system");
+
+                    frame += 1;
+                    let revision = localization.revision();
+                    let mut count = 0;
+                    query.for_each_mut(world, |(entity, localized, text)| {
+                        count += 1;
+                        if let Some((checked, seen, previous)) = resolved.get_mut(entity) {
+                            *checked = frame;
+                            if *seen == revision && *previous == *localized {
+                                return;
+                            }
+                        }
+                        text.text = localized.resolve(localization);
+                        resolved.insert(*entity, (frame, revision, localized.clone()));
+                    });
+
+                    // Forget removed entities.
+                    if resolved.len() > count {
+                        resolved.retain(|_, (checked, _, _)| *checked == frame);
+                    }
+                }),
+        )
+    }
+}
+
+/// Adds the [`LocalizedTextSystem`].
+///
+/// Requires the `UiBundle` and the `LocalizationBundle`.
+#[derive(Debug, Default)]
+pub struct LocalizedUiBundle;
+
+impl SystemBundle for LocalizedUiBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        _resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        builder.add_system(LocalizedTextSystem);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_arg_replaces_previous_value() {
+        let mut text = LocalizedText::new("score").with_arg("points", 1);
+        text.set_arg("points", 2);
+        text.set_arg("player", "Ferris");
+        assert_eq!(
+            text.args,
+            vec![
+                ("points".to_string(), LocalizedArg::Number(2.0)),
+                (
+                    "player".to_string(),
+                    LocalizedArg::String("Ferris".to_string())
+                ),
+            ]
+        );
+    }
+}
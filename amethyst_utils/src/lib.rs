@@ -24,3 +24,4 @@ pub mod ortho_camera;
 pub mod removal;
 pub mod tag;
 pub mod time_destroy;
+pub mod timer;
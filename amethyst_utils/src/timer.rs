@@ -0,0 +1,379 @@
+//! Timers and cooldowns driven by a named clock from `amethyst_core::clock::Clocks`.
+//!
+//! Both components default to the game clock, so they stop while gameplay is paused and follow
+//! its time scale. Point them at `REAL_CLOCK` for UI timers which should keep running. Add the
+//! [`TimerBundle`] to advance them every frame.
+
+use std::collections::HashSet;
+
+use amethyst_core::{
+    clock::{Clocks, GAME_CLOCK},
+    ecs::{
+        DispatcherBuilder, IntoQuery, ParallelRunnable, Resources, SystemBuilder, SystemBundle,
+        World, Write,
+    },
+};
+use amethyst_error::Error;
+use log::warn;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+fn game_clock() -> String {
+    GAME_CLOCK.to_string()
+}
+
+/// Counts up to `duration` seconds of the chosen clock, optionally restarting afterwards.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Timer {
+    /// Name of the clock driving this timer.
+    #[serde(default = "game_clock")]
+    pub clock: String,
+    /// The time in seconds after which the timer finishes.
+    pub duration: f64,
+    /// Whether the timer starts over once it finished.
+    #[serde(default)]
+    pub repeat: bool,
+    #[serde(skip)]
+    elapsed: f64,
+    #[serde(skip)]
+    finished_this_frame: u32,
+}
+
+impl Timer {
+    /// Creates a one-shot timer driven by the game clock.
+    #[must_use]
+    pub fn new(duration: f64) -> Self {
+        Timer {
+            clock: game_clock(),
+            duration,
+            repeat: false,
+            elapsed: 0.0,
+            finished_this_frame: 0,
+        }
+    }
+
+    /// Creates a timer driven by the game clock which restarts every time it finishes.
+    #[must_use]
+    pub fn repeating(duration: f64) -> Self {
+        Timer {
+            repeat: true,
+            ..Timer::new(duration)
+        }
+    }
+
+    /// Drives this timer with the clock called `clock` instead.
+    #[must_use]
+    pub fn with_clock<S: Into<String>>(mut self, clock: S) -> Self {
+        self.clock = clock.into();
+        self
+    }
+
+    /// Returns the seconds elapsed since the timer was (re)started.
+    #[must_use]
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    /// Returns the seconds left until the timer finishes.
+    #[must_use]
+    pub fn remaining(&self) -> f64 {
+        (self.duration - self.elapsed).max(0.0)
+    }
+
+    /// Returns `true` if a one-shot timer has run out.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        !self.repeat && self.elapsed >= self.duration
+    }
+
+    /// Returns how often the timer finished during the last frame. Repeating timers with a short
+    /// duration can finish several times per frame.
+    #[must_use]
+    pub fn times_finished(&self) -> u32 {
+        self.finished_this_frame
+    }
+
+    /// Returns `true` if the timer finished during the last frame.
+    #[must_use]
+    pub fn just_finished(&self) -> bool {
+        self.finished_this_frame > 0
+    }
+
+    /// Restarts the timer.
+    pub fn reset(&mut self) {
+        self.elapsed = 0.0;
+        self.finished_this_frame = 0;
+    }
+
+    fn tick(&mut self, delta: f64) {
+        self.finished_this_frame = 0;
+        if self.is_finished() {
+            return;
+        }
+
+        self.elapsed += delta;
+        if self.elapsed < self.duration {
+            return;
+        }
+
+        if self.repeat && self.duration > 0.0 {
+            while self.elapsed >= self.duration {
+                self.elapsed -= self.duration;
+                self.finished_this_frame += 1;
+            }
+        } else {
+            self.elapsed = self.duration;
+            self.finished_this_frame = 1;
+        }
+    }
+}
+
+/// Limits how often an action can be performed, measured in seconds of the chosen clock.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cooldown {
+    /// Name of the clock driving this cooldown.
+    #[serde(default = "game_clock")]
+    pub clock: String,
+    /// The time in seconds which has to pass between two triggers.
+    pub duration: f64,
+    #[serde(skip)]
+    remaining: f64,
+}
+
+impl Cooldown {
+    /// Creates a ready cooldown driven by the game clock.
+    #[must_use]
+    pub fn new(duration: f64) -> Self {
+        Cooldown {
+            clock: game_clock(),
+            duration,
+            remaining: 0.0,
+        }
+    }
+
+    /// Drives this cooldown with the clock called `clock` instead.
+    #[must_use]
+    pub fn with_clock<S: Into<String>>(mut self, clock: S) -> Self {
+        self.clock = clock.into();
+        self
+    }
+
+    /// Returns `true` if the action can be performed.
+    #[must_use]
+    pub fn is_ready(&self) -> bool {
+        self.remaining <= 0.0
+    }
+
+    /// Returns the seconds left until the action can be performed again.
+    #[must_use]
+    pub fn remaining(&self) -> f64 {
+        self.remaining.max(0.0)
+    }
+
+    /// Starts the cooldown if it is ready. Returns whether the action may be performed.
+    pub fn trigger(&mut self) -> bool {
+        if self.is_ready() {
+            self.remaining = self.duration;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Makes the cooldown ready immediately.
+    pub fn reset(&mut self) {
+        self.remaining = 0.0;
+    }
+
+    fn tick(&mut self, delta: f64) {
+        if !self.is_ready() {
+            self.remaining -= delta;
+        }
+    }
+}
+
+/// Warns about a missing clock the first time `name` is seen, instead of once per frame.
+fn warn_unknown_clock(warned: &mut HashSet<String>, kind: &str, name: &str) {
+    if !warned.contains(name) {
+        warn!("{} is driven by unknown clock `{}`", kind, name);
+        warned.insert(name.to_string());
+    }
+}
+
+/// The system in charge of advancing `Timer` components by the delta time of their clock.
+#[must_use]
+pub fn build_timer_system() -> impl ParallelRunnable {
+    let mut unknown_clocks = HashSet::new();
+    SystemBuilder::new("timer_system")
+        .read_resource::<Clocks>()
+        .with_query(<Write<Timer>>::query())
+        .build(move |_, subworld, clocks, timer_query| {
+            #[cfg(feature = "profiler")]
+            profile_scope!("timer_system");
+
+            for timer in timer_query.iter_mut(subworld) {
+                if let Some(clock) = clocks.get(&timer.clock) {
+                    timer.tick(clock.delta_time().as_secs_f64());
+                } else {
+                    warn_unknown_clock(&mut unknown_clocks, "Timer", &timer.clock);
+                }
+            }
+        })
+}
+
+/// The system in charge of advancing `Cooldown` components by the delta time of their clock.
+#[must_use]
+pub fn build_cooldown_system() -> impl ParallelRunnable {
+    let mut unknown_clocks = HashSet::new();
+    SystemBuilder::new("cooldown_system")
+        .read_resource::<Clocks>()
+        .with_query(<Write<Cooldown>>::query())
+        .build(move |_, subworld, clocks, cooldown_query| {
+            #[cfg(feature = "profiler")]
+            profile_scope!("cooldown_system");
+
+            for cooldown in cooldown_query.iter_mut(subworld) {
+                if cooldown.is_ready() {
+                    continue;
+                }
+                if let Some(clock) = clocks.get(&cooldown.clock) {
+                    cooldown.tick(clock.delta_time().as_secs_f64());
+                } else {
+                    warn_unknown_clock(&mut unknown_clocks, "Cooldown", &cooldown.clock);
+                }
+            }
+        })
+}
+
+/// Adds the systems advancing [`Timer`] and [`Cooldown`] components.
+///
+/// Each component follows the clock named by its `clock` field in the [`Clocks`] resource, which
+/// the `Application` advances at the start of every frame. The resource is inserted if missing.
+#[derive(Debug, Default)]
+pub struct TimerBundle;
+
+impl SystemBundle for TimerBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        if !resources.contains::<Clocks>() {
+            resources.insert(Clocks::default());
+        }
+        builder
+            .add_system(build_timer_system)
+            .add_system(build_cooldown_system);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use amethyst_core::{
+        clock::REAL_CLOCK,
+        ecs::{DispatcherBuilder, Entity, Resources, World},
+    };
+
+    use super::*;
+
+    fn run_frame(world: &mut World, resources: &mut Resources, delta: Duration) {
+        resources.get_mut::<Clocks>().unwrap().advance(delta);
+        DispatcherBuilder::default()
+            .add_bundle(TimerBundle)
+            .build(world, resources)
+            .unwrap()
+            .execute(world, resources);
+    }
+
+    fn timer(world: &mut World, entity: Entity) -> Timer {
+        world
+            .entry(entity)
+            .unwrap()
+            .get_component::<Timer>()
+            .unwrap()
+            .clone()
+    }
+
+    fn cooldown(world: &mut World, entity: Entity) -> Cooldown {
+        world
+            .entry(entity)
+            .unwrap()
+            .get_component::<Cooldown>()
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn timer_stops_while_clock_is_paused() {
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        resources.insert(Clocks::default());
+        let game = world.push((Timer::new(1.0),));
+        let real = world.push((Timer::new(1.0).with_clock(REAL_CLOCK),));
+
+        resources.get_mut::<Clocks>().unwrap().game_mut().pause();
+        run_frame(&mut world, &mut resources, Duration::from_secs(2));
+        assert!(timer(&mut world, game).elapsed() < f64::EPSILON);
+        assert!(timer(&mut world, real).just_finished());
+
+        resources.get_mut::<Clocks>().unwrap().game_mut().resume();
+        run_frame(&mut world, &mut resources, Duration::from_secs(1));
+        assert!(timer(&mut world, game).just_finished());
+        assert!(timer(&mut world, game).is_finished());
+    }
+
+    #[test]
+    fn timer_and_cooldown_follow_time_scale() {
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        let mut clocks = Clocks::default();
+        clocks.game_mut().set_time_scale(0.5);
+        resources.insert(clocks);
+        let mut triggered = Cooldown::new(1.0);
+        assert!(triggered.trigger());
+        let entity = world.push((Timer::new(1.0), triggered));
+
+        run_frame(&mut world, &mut resources, Duration::from_secs(1));
+        assert!((timer(&mut world, entity).remaining() - 0.5).abs() < f64::EPSILON);
+        assert!(!timer(&mut world, entity).just_finished());
+        assert!((cooldown(&mut world, entity).remaining() - 0.5).abs() < f64::EPSILON);
+
+        run_frame(&mut world, &mut resources, Duration::from_secs(1));
+        assert!(timer(&mut world, entity).just_finished());
+        assert!(cooldown(&mut world, entity).is_ready());
+    }
+
+    #[test]
+    fn repeating_timer_restarts() {
+        let mut timer = Timer::repeating(0.25);
+        timer.tick(0.6);
+        assert_eq!(timer.times_finished(), 2);
+        assert!((timer.elapsed() - 0.1).abs() < 1e-9);
+        assert!(!timer.is_finished());
+
+        timer.tick(0.1);
+        assert!(!timer.just_finished());
+        timer.tick(0.1);
+        assert_eq!(timer.times_finished(), 1);
+    }
+
+    #[test]
+    fn cooldown_blocks_until_elapsed() {
+        let mut cooldown = Cooldown::new(1.0);
+        assert!(cooldown.trigger());
+        assert!(!cooldown.trigger());
+
+        cooldown.tick(0.75);
+        assert!(!cooldown.is_ready());
+        cooldown.tick(0.25);
+        assert!(cooldown.trigger());
+
+        cooldown.reset();
+        assert!(cooldown.is_ready());
+    }
+}
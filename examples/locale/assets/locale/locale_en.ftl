@@ -1,2 +1,3 @@
-hello = Hello, world!
+hello = Hello, { $name }!
 bye = See you later!
+credits = Made with Amethyst
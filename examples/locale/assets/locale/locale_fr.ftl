@@ -1,2 +1,2 @@
-hello = Bonjour!
-bye = Au revoir!
+hello = Bonjour, { $name } !
+bye = Au revoir !
@@ -1,7 +1,8 @@
-//! Example showing how to load a Locale file as an Asset using the Loader.
+//! Example showing how to load Locale files as Assets and format their messages in the
+//! preferred language, falling back to English.
 
 use amethyst::{
-    assets::{AssetStorage, DefaultLoader, Handle, Loader, LoaderBundle},
+    assets::{DefaultLoader, LoaderBundle},
     ecs::*,
     locale::*,
     prelude::*,
@@ -10,53 +11,36 @@ use amethyst::{
     Error,
 };
 
-struct Example {
-    handle_en: Option<Handle<Locale>>,
-    handle_fr: Option<Handle<Locale>>,
-}
-
-impl Example {
-    pub fn new() -> Self {
-        Example {
-            handle_en: None,
-            handle_fr: None,
-        }
-    }
-}
+struct Example;
 
 impl SimpleState for Example {
     fn on_start(&mut self, data: StateData<'_, GameData>) {
         let loader = data.resources.get::<DefaultLoader>().unwrap();
-        self.handle_en = Some(loader.load("locale/locale_en.ftl"));
-        self.handle_fr = Some(loader.load("locale/locale_fr.ftl"));
+        let mut localization = data.resources.get_mut::<Localization>().unwrap();
+        // The language of each file is taken from its name.
+        localization.load(&*loader, "locale/locale_en.ftl");
+        localization.load(&*loader, "locale/locale_fr.ftl");
     }
 
     fn update(&mut self, data: &mut StateData<'_, GameData>) -> SimpleTrans {
-        let _loader = data.resources.get::<DefaultLoader>().unwrap();
-
-        // Check if the locale has been loaded.
-        let store = data.resources.get::<AssetStorage<Locale>>().unwrap();
+        let mut localization = data.resources.get_mut::<Localization>().unwrap();
 
-        for h in [self.handle_en.as_ref(), self.handle_fr.as_ref()].iter() {
-            if let Some(locale) = h.and_then(|h| store.get(h)) {
-                let bundle = &locale.bundle;
-                let msg_hello = bundle
-                    .get_message("hello")
-                    .expect("Failed to load message for hello");
-                let msg_bye = bundle
-                    .get_message("bye")
-                    .expect("Failed to load message for bye");
-                let hello_value = msg_hello.value.expect("Hello message has no value");
-                let bye_value = msg_bye.value.expect("Bye message has no value");
+        // Wait until both locales have been loaded.
+        if localization.available_languages().count() < 2 {
+            return Trans::None;
+        }
 
-                let mut errors = vec![];
-                println!("{}", bundle.format_pattern(hello_value, None, &mut errors));
-                println!("{}", bundle.format_pattern(bye_value, None, &mut errors));
-                assert_eq!(errors.len(), 0);
-            }
+        let mut args = FluentArgs::new();
+        args.set("name", "Ferris");
+        for language in &[langid!("en-US"), langid!("fr-FR")] {
+            localization.set_requested_languages(vec![language.clone()]);
+            println!("{}", localization.format_or_id("hello", Some(&args)));
+            println!("{}", localization.format_or_id("bye", None));
+            // Only in English, French falls back to it.
+            println!("{}", localization.format_or_id("credits", None));
         }
 
-        Trans::None
+        Trans::Quit
     }
 }
 
@@ -70,9 +54,10 @@ fn main() -> Result<(), Error> {
 
     builder
         .add_bundle(LoaderBundle)
+        .add_bundle(LocalizationBundle::new(langid!("en")))
         .add_bundle(RenderingBundle::<DefaultBackend>::new());
 
-    let game = Application::new(assets_dir, Example::new(), builder)?;
+    let game = Application::new(assets_dir, Example, builder)?;
     game.run();
     Ok(())
 }
@@ -11,12 +11,14 @@ use amethyst::{
         Named,
     },
     ecs::{
-        DispatcherBuilder, Entity, IntoQuery, ParallelRunnable, Resources, System, SystemBuilder,
+        DispatcherBuilder, Entity, EntityStore, IntoQuery, ParallelRunnable, Resources, System,
+        SystemBuilder,
     },
     input::{InputBundle, InputHandler},
     prelude::World,
     renderer::{
         camera::{ActiveCamera, Camera},
+        picking::pick_sprites,
         plugins::{RenderFlat2D, RenderToWindow},
         rendy::hal::command::ClearColor,
         sprite::{SpriteRender, SpriteSheet, Sprites},
@@ -36,13 +38,14 @@ impl System for MouseRaycastSystem {
         Box::new(
             SystemBuilder::new("MouseRaycastSystem")
                 .with_query(<(&Camera, &Transform)>::query())
-                .with_query(<(&SpriteRender, &Transform, &Named)>::query())
+                .with_query(<(Entity, &SpriteRender, &Transform)>::query())
                 .with_query(<(&UiTransform, &mut UiText)>::query())
                 .read_resource::<InputHandler>()
                 .read_resource::<ActiveCamera>()
                 .read_resource::<ScreenDimensions>()
                 .read_resource::<AssetStorage<SpriteSheet>>()
                 .read_resource::<AssetStorage<Sprites>>()
+                .read_component::<Named>()
                 .build(
                     |_,
                      world,
@@ -78,39 +81,26 @@ impl System for MouseRaycastSystem {
                                     }
                                 }
 
-                                // Find any sprites which the mouse is currently inside
-                                let mut found_name = None;
-                                let (left, mut right) = world.split_for_query(sprite_query);
-                                for (sprite, transform, name) in sprite_query.iter(&left) {
-                                    let sprite_sheet =
-                                        sprite_sheets.get(&sprite.sprite_sheet).unwrap();
-                                    let sprites =
-                                        sprites_storage.get(&sprite_sheet.sprites).unwrap();
-                                    let sprite = &sprites.build_sprites()[sprite.sprite_number];
-                                    let (min_x, max_x, min_y, max_y) = {
-                                        // Sprites are centered on a coordinate, so we build out a bbox for the sprite coordinate
-                                        // and dimensions
-                                        // Notice we ignore z-axis for this example.
-                                        (
-                                            transform.translation().x - (sprite.width * 0.5),
-                                            transform.translation().x + (sprite.width * 0.5),
-                                            transform.translation().y - (sprite.height * 0.5),
-                                            transform.translation().y + (sprite.height * 0.5),
-                                        )
-                                    };
-                                    if mouse_world_position.x > min_x
-                                        && mouse_world_position.x < max_x
-                                        && mouse_world_position.y > min_y
-                                        && mouse_world_position.y < max_y
-                                    {
-                                        found_name = Some(&name.0);
-                                    }
-                                }
+                                // Find the nearest sprite which the mouse is currently inside
+                                let hits = pick_sprites(
+                                    sprite_query.iter(&*world).map(
+                                        |(entity, sprite, transform)| (*entity, sprite, transform),
+                                    ),
+                                    sprite_sheets,
+                                    sprites_storage,
+                                    &ray,
+                                    f32::INFINITY,
+                                );
+                                let found_name = hits.first().and_then(|hit| {
+                                    world.entry_ref(hit.entity).ok().and_then(|entry| {
+                                        entry.get_component::<Named>().ok().cloned()
+                                    })
+                                });
 
-                                for (transform, text) in ui_texts.iter_mut(&mut right) {
+                                for (transform, text) in ui_texts.iter_mut(world) {
                                     if transform.id == "under_mouse" {
                                         if let Some(name) = found_name {
-                                            text.text = format!("{}", name);
+                                            text.text = name.0.to_string();
                                         } else {
                                             text.text = "".to_string();
                                         }
@@ -0,0 +1,46 @@
+//! Validates prefab files, reporting problems with their file and line.
+
+use std::path::PathBuf;
+
+use amethyst::{
+    assets::prefab::{
+        legion_prefab, register_component_type, serde_diff, validate_prefabs,
+        ComponentRegistryBuilder, SerdeDiff,
+    },
+    utils::application_root_dir,
+    Error,
+};
+use serde::{Deserialize, Serialize};
+use type_uuid::TypeUuid;
+
+/// The component used by the prefab example, it has to be registered to be validated.
+#[derive(TypeUuid, Serialize, Deserialize, SerdeDiff, Clone, Default, Debug)]
+#[uuid = "f5780013-bae4-49f0-ac0e-a108ff52fec0"]
+struct Position2D {
+    position: Vec<f32>,
+}
+
+register_component_type!(Position2D);
+
+fn main() -> Result<(), Error> {
+    let mut dirs: Vec<PathBuf> = std::env::args_os().skip(1).map(PathBuf::from).collect();
+    if dirs.is_empty() {
+        dirs.push(application_root_dir()?.join("../prefab/assets"));
+    }
+
+    let registry = ComponentRegistryBuilder::default()
+        .auto_register_components()
+        .build();
+
+    let diagnostics = validate_prefabs(&dirs, &registry);
+    for diagnostic in &diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+    if diagnostics.is_empty() {
+        println!("All prefabs are valid");
+        Ok(())
+    } else {
+        eprintln!("{} problem(s) found", diagnostics.len());
+        std::process::exit(1);
+    }
+}
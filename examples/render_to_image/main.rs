@@ -0,0 +1,89 @@
+//! Renders frames without a window and compares them with a golden image.
+
+use std::path::PathBuf;
+
+use amethyst::{
+    assets::LoaderBundle,
+    core::transform::TransformBundle,
+    prelude::*,
+    renderer::{
+        golden::{compare_golden, Tolerance},
+        plugins::RenderFlat2D,
+        rendy::hal::command::ClearColor,
+        types::DefaultBackend,
+        CapturedImage, RenderToImage, RenderingBundle,
+    },
+    utils::application_root_dir,
+};
+use log::{error, info};
+
+/// Frame whose image is compared, the first frames may still be setting up the render graph.
+const COMPARED_FRAME: u64 = 5;
+
+/// Frames after which the example gives up waiting for a captured image.
+const MAX_FRAMES: u64 = 100;
+
+struct CompareGolden {
+    golden: PathBuf,
+    frames: u64,
+}
+
+impl SimpleState for CompareGolden {
+    fn update(&mut self, data: &mut StateData<'_, GameData>) -> SimpleTrans {
+        self.frames += 1;
+        let captured = data.resources.get::<CapturedImage>().unwrap();
+        match captured.frame() {
+            Some(frame) if frame >= COMPARED_FRAME => {}
+            _ if self.frames < MAX_FRAMES => return Trans::None,
+            _ => {
+                error!("No image was captured after {} frames", self.frames);
+                std::process::exit(1);
+            }
+        }
+
+        match compare_golden(&captured, &self.golden, Tolerance::default()) {
+            Ok(()) => {
+                info!(
+                    "Frame {:?} matches {}",
+                    captured.frame(),
+                    self.golden.display()
+                );
+                Trans::Quit
+            }
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+fn main() -> amethyst::Result<()> {
+    amethyst::start_logger(Default::default());
+
+    let app_root = application_root_dir()?;
+    let assets_dir = app_root.join("assets");
+
+    let mut dispatcher = DispatcherBuilder::default();
+    dispatcher
+        .add_bundle(LoaderBundle)
+        .add_bundle(TransformBundle)
+        .add_bundle(
+            RenderingBundle::<DefaultBackend>::new()
+                // Draws into an image downloaded to the `CapturedImage` resource every frame,
+                // instead of a window.
+                .with_plugin(RenderToImage::new(64, 48).with_clear(ClearColor {
+                    float32: [0.0, 1.0, 0.0, 1.0],
+                }))
+                .with_plugin(RenderFlat2D::default()),
+        );
+
+    let state = CompareGolden {
+        golden: app_root.join("golden/clear.png"),
+        frames: 0,
+    };
+    let game = Application::new(assets_dir, state, dispatcher)?;
+    game.run();
+
+    Ok(())
+}
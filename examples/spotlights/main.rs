@@ -138,6 +138,7 @@ mod example_utils {
             range: 4.0,
             smoothness: 0.0,
             direction: Vector3::new(0.0, 0.0, 1.0),
+            shadows: None,
         };
 
         let mut spotlight_1_transform = Transform::default();
@@ -150,6 +151,7 @@ mod example_utils {
             range: 4.0,
             smoothness: 0.0,
             direction: Vector3::new(0.0, 0.0, 1.0),
+            shadows: None,
         };
 
         let mut spotlight_2_transform = Transform::default();
@@ -162,6 +164,7 @@ mod example_utils {
             range: 4.0,
             smoothness: 0.0,
             direction: Vector3::new(0.0, 0.0, 1.0),
+            shadows: None,
         };
 
         let mut spotlight_3_transform = Transform::default();
@@ -174,6 +177,7 @@ mod example_utils {
             range: 10.0,
             smoothness: 0.8,
             direction: Vector3::new(1.0, -0.4, 0.4),
+            shadows: None,
         };
         let mut spotlight_4_transform = Transform::default();
         spotlight_4_transform.set_translation_xyz(-5.0, 2., -1.5);
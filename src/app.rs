@@ -21,8 +21,9 @@ use crate::{
     assets::{DefaultLoader, Source},
     core::{
         frame_limiter::{FrameLimiter, FrameRateLimitConfig, FrameRateLimitStrategy},
+        logger,
         shrev::{EventChannel, ReaderId},
-        ArcThreadPool, EventReader, Stopwatch, Time,
+        ArcThreadPool, Clocks, EventReader, Stopwatch, Time,
     },
     ecs::{Resource, Resources, World},
     error::Error,
@@ -236,6 +237,8 @@ where
 
         self.resources.get_mut::<Stopwatch>().unwrap().start();
 
+        // Both the game clock and `Time` start with a time scale of `1.0`.
+        let mut game_time_scale = 1.0;
         while self.states.is_running() {
             self.advance_frame();
             {
@@ -246,8 +249,18 @@ where
             {
                 let mut stopwatch = self.resources.get_mut::<Stopwatch>().unwrap();
                 let elapsed = stopwatch.elapsed();
+                let mut clocks = self.resources.get_mut::<Clocks>().unwrap();
+                clocks.advance(elapsed);
                 let mut time = self.resources.get_mut::<Time>().unwrap();
+                // `Time` follows the game clock whenever its scale changes, its real time stays
+                // unscaled. In between, a scale set with `Time::set_time_scale` is kept.
+                let time_scale = clocks.game().effective_time_scale();
+                if (time_scale - game_time_scale).abs() > f32::EPSILON {
+                    time.set_time_scale(time_scale);
+                    game_time_scale = time_scale;
+                }
                 time.advance_frame(elapsed);
+                logger::set_frame_number(time.frame_number());
                 stopwatch.stop();
                 stopwatch.restart();
             }
@@ -535,6 +548,10 @@ where
         resources.insert(FrameLimiter::default());
         resources.insert(Stopwatch::default());
         resources.insert(Time::default());
+        resources.insert(Clocks::default());
+        if let Some(log_buffer) = logger::log_buffer() {
+            resources.insert(log_buffer);
+        }
 
         let asset_dirs = vec![path.as_ref().to_path_buf()];
 
@@ -0,0 +1,315 @@
+//! Reloads configuration files when they change on disk.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{
+    config::Config,
+    core::{
+        ecs::{DispatcherBuilder, Resources, SystemBundle, World},
+        frame_limiter::{FrameLimiter, FrameRateLimitConfig},
+        shrev::EventChannel,
+    },
+    error::Error,
+    LoggerConfig,
+};
+
+/// Published into an `EventChannel<ConfigChanged<T>>` when a watched configuration file of type
+/// `T` has changed and could be parsed.
+#[derive(Debug, Clone)]
+pub struct ConfigChanged<T> {
+    /// Path of the changed file.
+    pub path: PathBuf,
+    /// The new configuration.
+    pub config: T,
+}
+
+type ReloadFn = fn(&Path, &mut Resources);
+
+#[derive(Debug)]
+struct WatchedFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    reload: ReloadFn,
+}
+
+/// Watches configuration files and reloads them with [`Config::load`] when they are modified.
+///
+/// Every reloaded configuration is written into the `EventChannel<ConfigChanged<T>>` of its
+/// type. Files which fail to parse are logged and the previous configuration stays in effect.
+/// Files are polled every second by default, see [`ConfigWatcher::set_interval`].
+///
+/// The watcher is inserted and updated by the [`ConfigWatcherBundle`]. More files can be added
+/// at runtime with [`ConfigWatcher::watch`].
+#[derive(Debug)]
+pub struct ConfigWatcher {
+    files: Vec<WatchedFile>,
+    interval: Duration,
+    last_poll: Option<Instant>,
+}
+
+impl Default for ConfigWatcher {
+    fn default() -> Self {
+        ConfigWatcher {
+            files: Vec::new(),
+            interval: Duration::from_secs(1),
+            last_poll: None,
+        }
+    }
+}
+
+impl ConfigWatcher {
+    /// Reloads `path` as a `T` when it changes. The `EventChannel<ConfigChanged<T>>` is inserted
+    /// when the file changes for the first time if it does not exist yet, so systems reading it
+    /// should be added with [`ConfigWatcherBundle::with_config`] instead.
+    pub fn watch<T, P>(&mut self, path: P)
+    where
+        T: Config + Send + Sync + 'static,
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+        self.files.push(WatchedFile {
+            modified: modified(&path),
+            path,
+            reload: reload::<T>,
+        });
+    }
+
+    /// Sets how often the files are checked for changes.
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Returns the files modified since the last poll, at most once per interval.
+    fn poll(&mut self) -> Vec<(PathBuf, ReloadFn)> {
+        let now = Instant::now();
+        if self
+            .last_poll
+            .map_or(false, |last| now.duration_since(last) < self.interval)
+        {
+            return Vec::new();
+        }
+        self.last_poll = Some(now);
+
+        let mut changed = Vec::new();
+        for file in &mut self.files {
+            let modified = modified(&file.path);
+            if modified.is_some() && modified != file.modified {
+                file.modified = modified;
+                changed.push((file.path.clone(), file.reload));
+            }
+        }
+        changed
+    }
+
+    /// Reloads the changed files.
+    pub fn update(resources: &mut Resources) {
+        let changed = match resources.get_mut::<ConfigWatcher>() {
+            Some(mut watcher) => watcher.poll(),
+            None => return,
+        };
+        for (path, reload) in changed {
+            log::info!("Reloading configuration {}", path.display());
+            reload(&path, resources);
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+fn reload<T: Config + Send + Sync + 'static>(path: &Path, resources: &mut Resources) {
+    let config = match T::load(path) {
+        Ok(config) => config,
+        Err(e) => {
+            log::error!("Failed to reload configuration {}: {}", path.display(), e);
+            return;
+        }
+    };
+    insert_channel::<T>(resources);
+    resources
+        .get_mut::<EventChannel<ConfigChanged<T>>>()
+        .expect("Config channel was just inserted")
+        .single_write(ConfigChanged {
+            path: path.to_path_buf(),
+            config,
+        });
+}
+
+/// Inserts the [`ConfigWatcher`] and reloads watched configuration files once per frame.
+///
+/// Changes to the frame limiter and logger configurations can be applied automatically, other
+/// configuration types are applied by the systems reading their `ConfigChanged` events.
+///
+/// # Example
+///
+/// ```no_run
+/// use amethyst::{ecs::DispatcherBuilder, window::DisplayConfig, ConfigWatcherBundle};
+///
+/// let mut game_data = DispatcherBuilder::default();
+/// game_data.add_bundle(
+///     ConfigWatcherBundle::new()
+///         .with_frame_limit_config("config/frame_limit.ron")
+///         .with_logger_config("config/logger.ron")
+///         .with_config::<DisplayConfig, _>("config/display.ron"),
+/// );
+/// ```
+#[derive(Debug, Default)]
+pub struct ConfigWatcherBundle {
+    watcher: ConfigWatcher,
+    channels: Vec<fn(&mut Resources)>,
+    frame_limit: bool,
+    logger: bool,
+}
+
+impl ConfigWatcherBundle {
+    /// Creates a bundle without any watched files.
+    #[must_use]
+    pub fn new() -> Self {
+        ConfigWatcherBundle::default()
+    }
+
+    /// Watches `path` and inserts the `EventChannel<ConfigChanged<T>>` for its changes.
+    #[must_use]
+    pub fn with_config<T, P>(mut self, path: P) -> Self
+    where
+        T: Config + Send + Sync + 'static,
+        P: Into<PathBuf>,
+    {
+        self.watcher.watch::<T, _>(path);
+        self.channels.push(insert_channel::<T>);
+        self
+    }
+
+    /// Watches a [`FrameRateLimitConfig`] file and applies it to the [`FrameLimiter`].
+    #[must_use]
+    pub fn with_frame_limit_config<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.frame_limit = true;
+        self.with_config::<FrameRateLimitConfig, _>(path)
+    }
+
+    /// Watches a [`LoggerConfig`] file and applies its `level_filter` as the maximum log level.
+    ///
+    /// Levels more verbose than the one the logger was started with stay filtered, and the other
+    /// settings only take effect on the next start.
+    #[must_use]
+    pub fn with_logger_config<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.logger = true;
+        self.with_config::<LoggerConfig, _>(path)
+    }
+
+    /// Sets how often the files are checked for changes, every second by default.
+    #[must_use]
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.watcher.set_interval(interval);
+        self
+    }
+}
+
+fn insert_channel<T: Send + Sync + 'static>(resources: &mut Resources) {
+    if !resources.contains::<EventChannel<ConfigChanged<T>>>() {
+        resources.insert(EventChannel::<ConfigChanged<T>>::new());
+    }
+}
+
+impl SystemBundle for ConfigWatcherBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        for insert in &self.channels {
+            insert(resources);
+        }
+        resources.insert(std::mem::take(&mut self.watcher));
+
+        builder.add_thread_local_fn(|_, resources| ConfigWatcher::update(resources));
+
+        if self.frame_limit {
+            let mut reader = resources
+                .get_mut::<EventChannel<ConfigChanged<FrameRateLimitConfig>>>()
+                .expect("Frame limit config channel was just inserted")
+                .register_reader();
+            builder.add_thread_local_fn(move |_, resources| {
+                let channel = resources
+                    .get::<EventChannel<ConfigChanged<FrameRateLimitConfig>>>()
+                    .expect("Frame limit config channel was removed");
+                if let Some(changed) = channel.read(&mut reader).last() {
+                    if let Some(mut limiter) = resources.get_mut::<FrameLimiter>() {
+                        limiter.set_rate(changed.config.strategy.clone(), changed.config.fps);
+                    }
+                }
+            });
+        }
+
+        if self.logger {
+            let mut reader = resources
+                .get_mut::<EventChannel<ConfigChanged<LoggerConfig>>>()
+                .expect("Logger config channel was just inserted")
+                .register_reader();
+            builder.add_thread_local_fn(move |_, resources| {
+                let channel = resources
+                    .get::<EventChannel<ConfigChanged<LoggerConfig>>>()
+                    .expect("Logger config channel was removed");
+                if let Some(changed) = channel.read(&mut reader).last() {
+                    log::set_max_level(changed.config.level_filter);
+                }
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+
+    #[test]
+    fn changed_files_are_reloaded_into_the_event_channel() {
+        let path = env::temp_dir().join("amethyst_config_watcher_frame_limit.ron");
+        fs::write(&path, "(strategy: Unlimited, fps: 60)").unwrap();
+
+        let mut resources = Resources::default();
+        let mut watcher = ConfigWatcher::default();
+        watcher.set_interval(Duration::from_secs(0));
+        watcher.watch::<FrameRateLimitConfig, _>(&path);
+        resources.insert(watcher);
+        insert_channel::<FrameRateLimitConfig>(&mut resources);
+        let mut reader = resources
+            .get_mut::<EventChannel<ConfigChanged<FrameRateLimitConfig>>>()
+            .unwrap()
+            .register_reader();
+
+        ConfigWatcher::update(&mut resources);
+        assert_eq!(
+            resources
+                .get::<EventChannel<ConfigChanged<FrameRateLimitConfig>>>()
+                .unwrap()
+                .read(&mut reader)
+                .count(),
+            0
+        );
+
+        fs::write(&path, "(strategy: Unlimited, fps: 30)").unwrap();
+        // Don't rely on the modification time changing on file systems with a coarse resolution.
+        let later = SystemTime::now() + Duration::from_secs(5);
+        resources.get_mut::<ConfigWatcher>().unwrap().files[0].modified = Some(later);
+
+        ConfigWatcher::update(&mut resources);
+        let channel = resources
+            .get::<EventChannel<ConfigChanged<FrameRateLimitConfig>>>()
+            .unwrap();
+        let events: Vec<_> = channel.read(&mut reader).collect();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].config.fps, 30);
+        assert_eq!(events[0].path, path);
+    }
+}
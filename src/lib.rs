@@ -82,6 +82,8 @@ pub use amethyst_assets as assets;
 #[cfg(feature = "audio")]
 pub use amethyst_audio as audio;
 pub use amethyst_config as config;
+#[cfg(feature = "console")]
+pub use amethyst_console as console;
 pub use amethyst_controls as controls;
 pub use amethyst_core as core;
 pub use amethyst_derive as derive;
@@ -106,6 +108,7 @@ pub use winit;
 
 pub use self::{
     app::{Application, ApplicationBuilder, CoreApplication},
+    config_watcher::{ConfigChanged, ConfigWatcher, ConfigWatcherBundle},
     core::{
         ecs,
         logger::{start_logger, LevelFilter as LogLevelFilter, Logger, LoggerConfig, StdoutLog},
@@ -125,6 +128,7 @@ pub use crate::derive::*;
 pub mod prelude;
 
 mod app;
+mod config_watcher;
 mod game_data;
 mod state;
 mod state_event;